@@ -1,36 +1,164 @@
 use axum::Json;
 use axum::response::{IntoResponse, Response};
-use http::StatusCode;
-use serde::{Deserialize, Serialize};
+use http::{HeaderValue, StatusCode, header};
+use pecan_core::errors::{CoreExecutionError, CoreServiceError};
 use thiserror::Error;
 
-#[derive(Error, Debug, PartialEq, Eq, Serialize, Deserialize)]
+use crate::api::middleware::request_id::current_request_id;
+use crate::application::problem_store::ProblemStoreError;
+use crate::domain::models::error::{ErrorDetail, ErrorResponse, FieldError};
+
+/// Seconds a client is asked to wait before retrying when the service is saturated.
+pub const SERVICE_BUSY_RETRY_AFTER_SECS: u64 = 1;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum APIError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Request validation failed")]
+    ValidationFailed(Vec<FieldError>),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
     #[error("Not supported language: {0}")]
     NotSupportedLanguage(String),
-    #[error("Compile error: {0}")]
-    CompileError(String),
-    #[error("Runtime error: {0}")]
-    RuntimeError(String),
-    #[error("Time limit exceeded: {0}")]
-    TimeLimitExceeded(String),
-    #[error("Memory limit exceeded: {0}")]
-    MemoryLimitExceeded(String),
+    #[error("Rate limit exceeded: {message}")]
+    RateLimited { message: String, retry_after: u64 },
     #[error("Allocating task error: {0}")]
     AllocatingTaskError(String),
     #[error("Internal error: {0}")]
     InternalError(String),
     #[error("Service busy: {0}")]
     ServiceBusy(String),
+    #[error("Session timed out: {0}")]
+    SessionTimeout(String),
+}
+
+impl APIError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            APIError::InvalidRequest(_) | APIError::ValidationFailed(_) => StatusCode::BAD_REQUEST,
+            APIError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            APIError::Forbidden(_) => StatusCode::FORBIDDEN,
+            APIError::NotFound(_) => StatusCode::NOT_FOUND,
+            APIError::Conflict(_) => StatusCode::CONFLICT,
+            APIError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            APIError::NotSupportedLanguage(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            APIError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            APIError::ServiceBusy(_) => StatusCode::SERVICE_UNAVAILABLE,
+            APIError::SessionTimeout(_) => StatusCode::REQUEST_TIMEOUT,
+            APIError::AllocatingTaskError(_) | APIError::InternalError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Stable, machine-readable error code exposed as `error.code`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            APIError::InvalidRequest(_) => "invalid_request",
+            APIError::ValidationFailed(_) => "validation_failed",
+            APIError::Unauthorized(_) => "unauthorized",
+            APIError::Forbidden(_) => "forbidden",
+            APIError::NotFound(_) => "not_found",
+            APIError::Conflict(_) => "conflict",
+            APIError::PayloadTooLarge(_) => "payload_too_large",
+            APIError::NotSupportedLanguage(_) => "not_supported_language",
+            APIError::RateLimited { .. } => "rate_limited",
+            APIError::AllocatingTaskError(_) => "allocating_task_error",
+            APIError::InternalError(_) => "internal_error",
+            APIError::ServiceBusy(_) => "service_busy",
+            APIError::SessionTimeout(_) => "session_timeout",
+        }
+    }
+
+    fn retry_after(&self) -> Option<u64> {
+        match self {
+            APIError::RateLimited { retry_after, .. } => Some(*retry_after),
+            APIError::ServiceBusy(_) => Some(SERVICE_BUSY_RETRY_AFTER_SECS),
+            _ => None,
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            APIError::ValidationFailed(fields) => serde_json::to_value(fields).ok(),
+            _ => None,
+        }
+    }
+
+    /// Body of the error response, for errors reported outside a response
+    /// such as the final event of a stream.
+    pub fn to_body(&self, request_id: Option<String>) -> ErrorResponse {
+        ErrorResponse {
+            error: ErrorDetail {
+                code: self.code().to_string(),
+                message: self.to_string(),
+                details: self.details(),
+                request_id,
+            },
+        }
+    }
+}
+
+impl From<CoreExecutionError> for APIError {
+    fn from(error: CoreExecutionError) -> Self {
+        match error {
+            CoreExecutionError::NotSupportedLanguage(msg) => APIError::NotSupportedLanguage(msg),
+            CoreExecutionError::ServiceBusy(msg) => APIError::ServiceBusy(msg),
+            CoreExecutionError::AllocatingTaskError(msg) => APIError::AllocatingTaskError(msg),
+            e => APIError::InternalError(e.to_string()),
+        }
+    }
+}
+
+impl From<CoreServiceError> for APIError {
+    fn from(error: CoreServiceError) -> Self {
+        match error {
+            CoreServiceError::SandboxNotFound(id) => APIError::NotFound(format!("sandbox {}", id)),
+            CoreServiceError::SandboxBusy(id) => {
+                APIError::Conflict(format!("sandbox {} is running", id))
+            }
+            e => APIError::InternalError(e.to_string()),
+        }
+    }
 }
 
 impl IntoResponse for APIError {
     fn into_response(self) -> Response {
-        let status = match &self {
-            APIError::ServiceBusy(_) => StatusCode::SERVICE_UNAVAILABLE,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        };
-        (status, Json(self)).into_response()
+        let status = self.status_code();
+        let retry_after = self.retry_after();
+        let body = self.to_body(current_request_id());
+
+        let mut response = (status, Json(body)).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        if status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+impl From<ProblemStoreError> for APIError {
+    fn from(error: ProblemStoreError) -> Self {
+        match error {
+            ProblemStoreError::InvalidId => APIError::InvalidRequest(error.to_string()),
+            ProblemStoreError::NotFound(_) => APIError::NotFound(error.to_string()),
+            ProblemStoreError::Io(_) => APIError::InternalError(error.to_string()),
+        }
     }
 }
 
@@ -38,23 +166,66 @@ impl IntoResponse for APIError {
 mod tests {
     use axum::body::to_bytes;
     use axum::response::IntoResponse;
-    use http::StatusCode;
+    use http::{StatusCode, header};
 
     use super::APIError;
+    use crate::domain::models::error::{ErrorResponse, FieldError};
+
+    async fn decode(error: APIError) -> (StatusCode, Option<String>, ErrorResponse) {
+        let response = error.into_response();
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("response body read");
+        let decoded: ErrorResponse = serde_json::from_slice(&body).expect("json decode");
+        (status, retry_after, decoded)
+    }
 
     #[tokio::test]
     async fn into_response_returns_json_error_body() {
-        let error = APIError::NotSupportedLanguage("brain".to_string());
+        let (status, retry_after, body) =
+            decode(APIError::NotSupportedLanguage("brain".to_string())).await;
 
-        let response = error.into_response();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(retry_after, None);
+        assert_eq!(body.error.code, "not_supported_language");
+        assert_eq!(body.error.message, "Not supported language: brain");
+        assert_eq!(body.error.details, None);
+    }
 
-        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    #[tokio::test]
+    async fn validation_errors_carry_field_details() {
+        let fields = vec![FieldError {
+            field: "time_limit".to_string(),
+            message: "must be positive".to_string(),
+        }];
+        let (status, _, body) = decode(APIError::ValidationFailed(fields.clone())).await;
 
-        let body = to_bytes(response.into_body(), usize::MAX)
-            .await
-            .expect("response body read");
-        let decoded: APIError = serde_json::from_slice(&body).expect("json decode");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.error.code, "validation_failed");
+        assert_eq!(
+            body.error.details,
+            Some(serde_json::to_value(fields).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn saturation_errors_set_retry_after() {
+        let (status, retry_after, _) = decode(APIError::ServiceBusy("busy".to_string())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(retry_after.as_deref(), Some("1"));
 
-        assert_eq!(decoded, APIError::NotSupportedLanguage("brain".to_string()));
+        let (status, retry_after, body) = decode(APIError::RateLimited {
+            message: "slow down".to_string(),
+            retry_after: 7,
+        })
+        .await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(retry_after.as_deref(), Some("7"));
+        assert_eq!(body.error.code, "rate_limited");
     }
 }
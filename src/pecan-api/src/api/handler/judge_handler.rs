@@ -1,25 +1,109 @@
+use axum::Extension;
 use axum::Json;
 use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::{Stream, StreamExt};
+use http::{StatusCode, header};
+use uuid::Uuid;
 
 use crate::api::error::APIError;
-use crate::application::service::judge_service;
+use crate::api::extract::{ApiJson, ApiPath};
+use crate::application::auth::ApiKeyIdentity;
+use crate::application::quota::ClientId;
+use crate::application::service::{batch_service, function_service, judge_service};
 use crate::application::state::SharedState;
+use crate::domain::models::batch::{BatchCreatedResponse, BatchProgressResponse, BatchRequest};
+use crate::domain::models::function::{FunctionJudgeRequest, FunctionJudgeResponse};
 use crate::domain::models::judge::{
     JudgeAsyncRequest, JudgeAsyncResponse, JudgeRequest, JudgeResponse,
 };
 
 pub async fn judge_single(
     State(state): State<SharedState>,
-    Json(request): Json<JudgeRequest>,
+    Extension(client): Extension<ClientId>,
+    ApiJson(request): ApiJson<JudgeRequest>,
 ) -> Result<Json<JudgeResponse>, APIError> {
-    let response = judge_service::judge(request, &state).await?;
+    let response = judge_service::judge(request, &client, &state).await?;
     Ok(Json(response))
 }
 
+/// Run a submission and stream its progress as server-sent events.
+pub async fn judge_single_stream(
+    State(state): State<SharedState>,
+    Extension(client): Extension<ClientId>,
+    ApiJson(request): ApiJson<JudgeRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, APIError> {
+    let events = judge_service::judge_stream(request, &client, &state)?;
+    let events = events.map(|event| Event::default().event(event.name()).json_data(&event));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
 pub async fn judge_single_async(
     State(state): State<SharedState>,
-    Json(request): Json<JudgeAsyncRequest>,
+    Extension(client): Extension<ClientId>,
+    ApiJson(request): ApiJson<JudgeAsyncRequest>,
 ) -> Result<Json<JudgeAsyncResponse>, APIError> {
-    let response = judge_service::judge_async(request, &state).await?;
+    let response = judge_service::judge_async(request, &client, &state).await?;
     Ok(Json(response))
 }
+
+/// Judge a submission by calling its function on each case.
+pub async fn judge_function(
+    State(state): State<SharedState>,
+    Extension(client): Extension<ClientId>,
+    ApiJson(request): ApiJson<FunctionJudgeRequest>,
+) -> Result<Json<FunctionJudgeResponse>, APIError> {
+    let response = function_service::judge(request, &client, &state).await?;
+    Ok(Json(response))
+}
+
+pub async fn create_batch(
+    State(state): State<SharedState>,
+    Extension(client): Extension<ClientId>,
+    identity: Option<Extension<ApiKeyIdentity>>,
+    ApiJson(request): ApiJson<BatchRequest>,
+) -> Result<(StatusCode, Json<BatchCreatedResponse>), APIError> {
+    let identity = identity.map(|Extension(identity)| identity);
+    let response = batch_service::create_batch(request, &client, identity.as_ref(), &state).await?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+pub async fn get_batch(
+    State(state): State<SharedState>,
+    identity: Option<Extension<ApiKeyIdentity>>,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<Json<BatchProgressResponse>, APIError> {
+    let identity = identity.map(|Extension(identity)| identity);
+    let response = batch_service::get_batch(id, identity.as_ref(), &state)?;
+    Ok(Json(response))
+}
+
+/// Finished items as JSON lines, in completion order.
+pub async fn get_batch_results(
+    State(state): State<SharedState>,
+    identity: Option<Extension<ApiKeyIdentity>>,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<impl IntoResponse, APIError> {
+    let identity = identity.map(|Extension(identity)| identity);
+    let results = batch_service::get_batch_results(id, identity.as_ref(), &state)?;
+
+    let mut body = String::new();
+    for result in &results {
+        let line =
+            serde_json::to_string(result).map_err(|e| APIError::InternalError(e.to_string()))?;
+        body.push_str(&line);
+        body.push('\n');
+    }
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body))
+}
+
+pub async fn cancel_batch(
+    State(state): State<SharedState>,
+    identity: Option<Extension<ApiKeyIdentity>>,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<(StatusCode, Json<BatchProgressResponse>), APIError> {
+    let identity = identity.map(|Extension(identity)| identity);
+    let response = batch_service::cancel_batch(id, identity.as_ref(), &state)?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
@@ -0,0 +1,14 @@
+use axum::Json;
+use axum::extract::State;
+
+use crate::api::error::APIError;
+use crate::application::service::language_service;
+use crate::application::state::SharedState;
+use crate::domain::models::language::LanguageListResponse;
+
+pub async fn list_languages(
+    State(state): State<SharedState>,
+) -> Result<Json<LanguageListResponse>, APIError> {
+    let response = language_service::list_languages(&state).await?;
+    Ok(Json(response))
+}
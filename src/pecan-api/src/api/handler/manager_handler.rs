@@ -1,10 +1,16 @@
 use axum::Json;
 use axum::extract::State;
+use http::StatusCode;
+use uuid::Uuid;
 
 use crate::api::error::APIError;
+use crate::api::extract::{ApiJson, ApiPath};
 use crate::application::service::manager_service;
 use crate::application::state::SharedState;
-use crate::domain::models::manager::SandboxStatusResponse;
+use crate::domain::models::manager::{
+    DrainRequest, DrainResponse, ResizePoolRequest, SandboxListResponse, SandboxStatusResponse,
+    SelfTestResponse,
+};
 
 pub async fn get_sandbox_status(
     State(state): State<SharedState>,
@@ -12,3 +18,46 @@ pub async fn get_sandbox_status(
     let response = manager_service::get_sandbox_status(&state).await?;
     Ok(Json(response))
 }
+
+pub async fn list_sandboxes(
+    State(state): State<SharedState>,
+) -> Result<Json<SandboxListResponse>, APIError> {
+    let response = manager_service::list_sandboxes(&state).await?;
+    Ok(Json(response))
+}
+
+pub async fn resize_pool(
+    State(state): State<SharedState>,
+    ApiJson(request): ApiJson<ResizePoolRequest>,
+) -> Result<Json<SandboxStatusResponse>, APIError> {
+    let response = manager_service::resize_pool(request, &state).await?;
+    Ok(Json(response))
+}
+
+pub async fn recycle_sandbox(
+    State(state): State<SharedState>,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<StatusCode, APIError> {
+    manager_service::recycle_sandbox(id, &state).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn get_drain(State(state): State<SharedState>) -> Result<Json<DrainResponse>, APIError> {
+    let response = manager_service::get_drain(&state).await?;
+    Ok(Json(response))
+}
+
+pub async fn set_drain(
+    State(state): State<SharedState>,
+    ApiJson(request): ApiJson<DrainRequest>,
+) -> Result<Json<DrainResponse>, APIError> {
+    let response = manager_service::set_drain(request, &state).await?;
+    Ok(Json(response))
+}
+
+pub async fn run_self_test(
+    State(state): State<SharedState>,
+) -> Result<Json<SelfTestResponse>, APIError> {
+    let response = manager_service::run_self_test(&state).await?;
+    Ok(Json(response))
+}
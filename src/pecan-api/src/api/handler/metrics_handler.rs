@@ -0,0 +1,24 @@
+use axum::extract::State;
+use axum::response::IntoResponse;
+use http::header;
+
+use crate::api::error::APIError;
+use crate::application::state::SharedState;
+use crate::application::telemetry;
+
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub async fn get_metrics(State(state): State<SharedState>) -> Result<impl IntoResponse, APIError> {
+    let handle = state
+        .metrics
+        .as_ref()
+        .ok_or_else(|| APIError::NotFound("Metrics are disabled".to_string()))?;
+
+    telemetry::update_gauges(&state.service).await;
+    handle.run_upkeep();
+
+    Ok((
+        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        handle.render(),
+    ))
+}
@@ -1,3 +1,7 @@
 pub mod judge_handler;
+pub mod language_handler;
 pub mod manager_handler;
+pub mod metrics_handler;
+pub mod problem_handler;
+pub mod session_handler;
 pub mod webhook_handler;
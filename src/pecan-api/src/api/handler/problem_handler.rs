@@ -0,0 +1,65 @@
+use axum::Extension;
+use axum::Json;
+use axum::extract::State;
+use http::StatusCode;
+
+use crate::api::error::APIError;
+use crate::api::extract::{ApiJson, ApiPath};
+use crate::application::quota::ClientId;
+use crate::application::service::problem_service;
+use crate::application::state::SharedState;
+use crate::domain::models::problem::{
+    ProblemImportRequest, ProblemJudgeRequest, ProblemJudgeResponse, ProblemResponse,
+    ProblemUploadRequest,
+};
+
+pub async fn upload_problem(
+    State(state): State<SharedState>,
+    ApiPath(id): ApiPath<String>,
+    ApiJson(request): ApiJson<ProblemUploadRequest>,
+) -> Result<(StatusCode, Json<ProblemResponse>), APIError> {
+    let response = problem_service::upload(id, request, &state).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+pub async fn import_problem(
+    State(state): State<SharedState>,
+    ApiPath(id): ApiPath<String>,
+    ApiJson(request): ApiJson<ProblemImportRequest>,
+) -> Result<(StatusCode, Json<ProblemResponse>), APIError> {
+    let response = problem_service::import(id, request, &state).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+pub async fn get_problem(
+    State(state): State<SharedState>,
+    ApiPath(id): ApiPath<String>,
+) -> Result<Json<ProblemResponse>, APIError> {
+    let response = problem_service::get(&id, None, &state).await?;
+    Ok(Json(response))
+}
+
+pub async fn get_problem_version(
+    State(state): State<SharedState>,
+    ApiPath((id, version)): ApiPath<(String, u32)>,
+) -> Result<Json<ProblemResponse>, APIError> {
+    let response = problem_service::get(&id, Some(version), &state).await?;
+    Ok(Json(response))
+}
+
+pub async fn delete_problem(
+    State(state): State<SharedState>,
+    ApiPath(id): ApiPath<String>,
+) -> Result<StatusCode, APIError> {
+    problem_service::delete(&id, &state).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn judge_problem(
+    State(state): State<SharedState>,
+    Extension(client): Extension<ClientId>,
+    ApiJson(request): ApiJson<ProblemJudgeRequest>,
+) -> Result<Json<ProblemJudgeResponse>, APIError> {
+    let response = problem_service::judge(request, &client, &state).await?;
+    Ok(Json(response))
+}
@@ -0,0 +1,143 @@
+use axum::Extension;
+use axum::extract::State;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::response::Response;
+use futures::StreamExt;
+use tokio::time::{Instant, sleep_until};
+use tracing::Instrument;
+
+use crate::api::error::APIError;
+use crate::api::middleware::request_id::{current_request_id, with_request_id};
+use crate::application::quota::{ClientId, InflightPermit};
+use crate::application::service::session_service;
+use crate::application::state::SharedState;
+use crate::domain::models::judge::JudgeStreamEvent;
+use crate::domain::models::session::{SessionMessage, SessionStartRequest};
+
+/// Open an interactive session over WebSocket. The execution is reserved
+/// before the upgrade, so quota and drain errors are regular HTTP errors.
+pub async fn open_session(
+    State(state): State<SharedState>,
+    Extension(client): Extension<ClientId>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, APIError> {
+    let inflight = session_service::admit_session(&client, &state)?;
+    // the socket is served after the handler returned
+    let request_id = current_request_id();
+    let span = tracing::info_span!("session", client = %client);
+    Ok(upgrade.on_upgrade(move |socket| {
+        with_request_id(request_id, serve_session(socket, inflight, state)).instrument(span)
+    }))
+}
+
+async fn serve_session(mut socket: WebSocket, inflight: InflightPermit, state: SharedState) {
+    if let Err(e) = run_session(&mut socket, inflight, &state).await {
+        tracing::info!(error = %e, "session ended with an error");
+        let _ = send_event(
+            &mut socket,
+            &JudgeStreamEvent::Error(e.to_body(current_request_id())),
+        )
+        .await;
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+async fn send_event(socket: &mut WebSocket, event: &JudgeStreamEvent) -> Result<(), axum::Error> {
+    let frame = serde_json::json!({ "event": event.name(), "data": event });
+    socket.send(Message::Text(frame.to_string().into())).await
+}
+
+/// Next text message of the client, `None` once it closed the socket.
+async fn next_message(socket: &mut WebSocket) -> Option<Result<String, APIError>> {
+    loop {
+        match socket.recv().await? {
+            Ok(Message::Text(text)) => return Some(Ok(text.to_string())),
+            Ok(Message::Binary(_)) => {
+                return Some(Err(APIError::InvalidRequest(
+                    "session messages must be JSON text".to_string(),
+                )));
+            }
+            Ok(Message::Ping(_) | Message::Pong(_)) => continue,
+            Ok(Message::Close(_)) | Err(_) => return None,
+        }
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(text: &str) -> Result<T, APIError> {
+    serde_json::from_str(text).map_err(|e| APIError::InvalidRequest(e.to_string()))
+}
+
+/// Run the session until the program exits, the client leaves or a session
+/// timeout expires. Returning drops the program's run, which kills it and
+/// releases its sandbox.
+async fn run_session(
+    socket: &mut WebSocket,
+    inflight: InflightPermit,
+    state: &SharedState,
+) -> Result<(), APIError> {
+    let config = &state.config.session;
+    let idle_timeout = config.idle_timeout();
+    let deadline = Instant::now() + config.max_duration();
+    let idle = || APIError::SessionTimeout(format!("idle for {} seconds", idle_timeout.as_secs()));
+
+    let start = tokio::select! {
+        message = next_message(socket) => match message {
+            Some(text) => parse::<SessionStartRequest>(&text?)?,
+            None => return Ok(()),
+        },
+        _ = sleep_until(Instant::now() + idle_timeout) => return Err(idle()),
+    };
+    let (input, events) = session_service::start_session(start, inflight, state)?;
+    let mut input = Some(input);
+    let mut input_bytes = 0;
+    let max_input_bytes = state.config.limits.max_stdin_size;
+    tokio::pin!(events);
+
+    let mut idle_at = Instant::now() + idle_timeout;
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                let Some(event) = event else {
+                    return Ok(());
+                };
+                let last = matches!(event, JudgeStreamEvent::Exit(_) | JudgeStreamEvent::Error(_));
+                if send_event(socket, &event).await.is_err() || last {
+                    return Ok(());
+                }
+            }
+            message = next_message(socket) => {
+                let Some(text) = message else {
+                    return Ok(());
+                };
+                match parse::<SessionMessage>(&text?)? {
+                    SessionMessage::Stdin { data, data_b64 } => {
+                        let data = match data_b64 {
+                            Some(data) => data.0,
+                            None => data.into_bytes(),
+                        };
+                        input_bytes += data.len();
+                        if input_bytes > max_input_bytes {
+                            return Err(APIError::PayloadTooLarge(format!(
+                                "session input exceeds {} bytes",
+                                max_input_bytes
+                            )));
+                        }
+                        if let Some(input) = &input {
+                            // fails only once the program exited
+                            let _ = input.send(data);
+                        }
+                    }
+                    SessionMessage::CloseStdin => input = None,
+                }
+            }
+            _ = sleep_until(idle_at) => return Err(idle()),
+            _ = sleep_until(deadline) => {
+                return Err(APIError::SessionTimeout(format!(
+                    "open for {} seconds",
+                    config.max_duration().as_secs()
+                )));
+            }
+        }
+        idle_at = Instant::now() + idle_timeout;
+    }
+}
@@ -1,61 +1,100 @@
-use pecan_core::code_execution::{AsyncCodeExecutionResult, CodeExecutionStatus};
-use reqwest::Client;
+use std::sync::Arc;
+
+use pecan_core::code_execution::AsyncCodeExecutionResult;
+use reqwest::{Client, RequestBuilder};
 use tokio::select;
 use tokio::sync::mpsc::Receiver;
+use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 use crate::api::error::APIError;
-use crate::domain::models::judge::{JudgeAsyncWebhookResponse, JudgeResponse, JudgeStatus};
+use crate::api::middleware::request_id::X_REQUEST_ID;
+use crate::application::quota::ClientQuotas;
+use crate::application::service::judge_service::verdict;
+use crate::application::telemetry::record_webhook_delivery;
+use crate::domain::models::batch::BatchProgressResponse;
+use crate::domain::models::judge::JudgeAsyncWebhookResponse;
+
+/// Send a prepared webhook request and record its outcome.
+async fn post(webhook_request: RequestBuilder) -> Result<(), APIError> {
+    let response = webhook_request.send().await.map_err(|e| {
+        record_webhook_delivery("failed");
+        APIError::InternalError(e.to_string())
+    })?;
+
+    if !response.status().is_success() {
+        record_webhook_delivery("rejected");
+        return Err(APIError::InternalError(format!(
+            "webhook responded with {}",
+            response.status()
+        )));
+    }
+    record_webhook_delivery("delivered");
+    Ok(())
+}
 
 async fn send_webhook_request(
     client: &Client,
     res: AsyncCodeExecutionResult,
 ) -> Result<(), APIError> {
-    match res.result {
-        Some(r) => {
-            let status = match r.status {
-                CodeExecutionStatus::Success => {
-                    if r.stdout == res.desired_stdout {
-                        JudgeStatus::Accepted
-                    } else {
-                        JudgeStatus::WrongAnswer
-                    }
-                }
-                CodeExecutionStatus::CompileError => JudgeStatus::CompileError,
-                CodeExecutionStatus::RuntimeError => JudgeStatus::RuntimeError,
-                CodeExecutionStatus::TimeLimitExceeded => JudgeStatus::TimeLimitExceeded,
-                CodeExecutionStatus::MemoryLimitExceeded => JudgeStatus::MemoryLimitExceeded,
-                CodeExecutionStatus::InternalError => JudgeStatus::InternalError,
-            };
-
-            let body = JudgeAsyncWebhookResponse {
-                request_id: res.request_id,
-                res: JudgeResponse {
-                    code: status.clone().into_status_code(),
-                    status,
-                    stdout: r.stdout,
-                    stderr: r.stderr,
-                    time: r.time,
-                    memory: r.memory,
-                },
-            };
-
-            let _res = client
-                .post(res.webhook_url)
-                .json(&body)
-                .send()
-                .await
-                .map_err(|e| APIError::InternalError(e.to_string()));
-
-            Ok(())
-        }
+    let body = JudgeAsyncWebhookResponse {
+        request_id: res.request_id,
+        res: verdict(
+            res.language,
+            res.result,
+            &res.desired_stdout,
+            res.judged_file.as_deref(),
+        ),
+    };
+
+    let mut webhook_request = client.post(res.webhook_url).json(&body);
+    if let Some(origin_request_id) = &res.origin_request_id {
+        webhook_request = webhook_request.header(X_REQUEST_ID, origin_request_id);
+    }
+    post(webhook_request).await
+}
+
+/// Report a finished batch to its webhook.
+pub async fn send_batch_webhook(
+    webhook_url: &str,
+    origin_request_id: Option<&str>,
+    body: &BatchProgressResponse,
+) -> Result<(), APIError> {
+    let client = Client::builder()
+        .connect_timeout(std::time::Duration::from_secs(2))
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .map_err(|e| APIError::InternalError(e.to_string()))?;
+
+    let mut webhook_request = client.post(webhook_url).json(body);
+    if let Some(origin_request_id) = origin_request_id {
+        webhook_request = webhook_request.header(X_REQUEST_ID, origin_request_id);
+    }
+    post(webhook_request).await
+}
 
-        None => Err(APIError::InternalError("".to_string())),
+async fn deliver(client: Client, quotas: Arc<ClientQuotas>, msg: AsyncCodeExecutionResult) {
+    let request_id = msg.request_id;
+    quotas.release_queued(request_id);
+    let span = tracing::info_span!(parent: &msg.span, "webhook");
+    async {
+        tracing::debug!("sending webhook request");
+        match send_webhook_request(&client, msg).await {
+            Ok(_) => tracing::debug!("webhook delivered"),
+            Err(e) => tracing::error!(job_id = %request_id, error = %e, "webhook delivery failed"),
+        }
     }
+    .instrument(span)
+    .await;
 }
 
+/// Deliver results, up to `concurrency` at once, until `cancel` fires; then
+/// flush whatever is already buffered in the channel before returning.
 pub async fn webhook_handler_loop(
     mut rx: Receiver<AsyncCodeExecutionResult>,
+    quotas: Arc<ClientQuotas>,
+    concurrency: usize,
     cancel: CancellationToken,
 ) {
     tracing::info!("Webhook handler loop started");
@@ -69,31 +108,128 @@ pub async fn webhook_handler_loop(
         })
         .unwrap();
 
+    let mut deliveries = JoinSet::new();
+    let mut flushing = false;
+    let mut flushed = 0;
     loop {
+        // a slow endpoint holds up one slot rather than every other webhook
+        if deliveries.len() >= concurrency.max(1) {
+            deliveries.join_next().await;
+            continue;
+        }
         select! {
-            _ = cancel.cancelled() => {
-                tracing::debug!("Webhook handler loop cancelled");
-                break;
+            _ = cancel.cancelled(), if !flushing => {
+                rx.close();
+                flushing = true;
             }
             msg = rx.recv() => {
-                match msg {
-                    Some(msg) => {
-                        let request_id = msg.request_id;
-                        tracing::debug!(
-                            request_id = %request_id,
-                            "sending webhook request"
-                        );
-                        match send_webhook_request(&client, msg).await {
-                            Ok(_) => tracing::debug!(request_id = %request_id, "webhook delivered"),
-                            Err(e) => tracing::error!(request_id = %request_id, error = %e, "webhook delivery failed"),
-                        }
-                    }
-                    None => {
-                        tracing::debug!("Webhook channel closed, exiting loop");
-                        break;
-                    }
+                let Some(msg) = msg else {
+                    break;
+                };
+                flushed += usize::from(flushing);
+                deliveries.spawn(deliver(client.clone(), Arc::clone(&quotas), msg));
+            }
+        }
+    }
+    while deliveries.join_next().await.is_some() {}
+
+    if flushing {
+        tracing::debug!(flushed, "Webhook handler loop cancelled");
+    } else {
+        tracing::debug!("Webhook channel closed, exiting loop");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use pecan_core::code_execution::{
+        AsyncCodeExecutionResult, CodeExecutionResult, CodeExecutionStatus,
+    };
+    use pecan_core::toolchains::Language;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tokio_util::sync::CancellationToken;
+    use uuid::Uuid;
+
+    use super::webhook_handler_loop;
+    use crate::application::config::RateLimitConfig;
+    use crate::application::quota::ClientQuotas;
+
+    fn result(webhook_url: String) -> AsyncCodeExecutionResult {
+        AsyncCodeExecutionResult {
+            request_id: Uuid::new_v4(),
+            origin_request_id: None,
+            span: tracing::Span::none(),
+            language: Language::from("python3"),
+            webhook_url,
+            send_failed_count: 0,
+            desired_stdout: b"3\n".to_vec(),
+            judged_file: None,
+            result: CodeExecutionResult {
+                status: CodeExecutionStatus::Success,
+                stdout: b"3\n".to_vec(),
+                stderr: Vec::new(),
+                time: 0.1,
+                memory: 1024.0,
+                compile_time: 0.0,
+                output_files: Vec::new(),
+                exit_code: Some(0),
+                cached: false,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_endpoints_are_delivered_to_concurrently_up_to_the_bound() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let delivered = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let (active, peak, delivered) = (active.clone(), peak.clone(), delivered.clone());
+            async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let (active, peak, delivered) =
+                        (active.clone(), peak.clone(), delivered.clone());
+                    tokio::spawn(async move {
+                        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        let mut request = [0; 4096];
+                        let _ = stream.read(&mut request).await;
+                        tokio::time::sleep(Duration::from_millis(300)).await;
+                        active.fetch_sub(1, Ordering::SeqCst);
+                        delivered.fetch_add(1, Ordering::SeqCst);
+                        let _ = stream
+                            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                            .await;
+                    });
                 }
             }
+        });
+
+        let quotas = Arc::new(ClientQuotas::new(RateLimitConfig {
+            requests_per_sec: 0.0,
+            burst: 0.0,
+            max_inflight_per_client: 0,
+            max_queued_per_client: 0,
+        }));
+        let (tx, rx) = mpsc::channel(8);
+        for _ in 0..5 {
+            tx.send(result(url.clone())).await.unwrap();
         }
+        // results still buffered at shutdown are flushed the same way
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        webhook_handler_loop(rx, quotas, 2, cancel).await;
+
+        assert_eq!(delivered.load(Ordering::SeqCst), 5);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
     }
 }
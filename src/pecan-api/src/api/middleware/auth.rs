@@ -0,0 +1,62 @@
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use http::{Method, header};
+use tracing::Instrument;
+
+use crate::api::error::APIError;
+use crate::application::auth::Scope;
+use crate::application::state::SharedState;
+
+pub const X_API_KEY: &str = "x-api-key";
+
+/// Read the key from `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+fn extract_api_key(request: &Request) -> Option<&str> {
+    let headers = request.headers();
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| headers.get(X_API_KEY).and_then(|value| value.to_str().ok()))
+        .map(str::trim)
+}
+
+/// Reject requests without a key holding `scope`. Pass-through when no key file is configured,
+/// except for requests that change the pool or the problems, which need `ADMIN_UNAUTHENTICATED`.
+pub async fn auth_middleware(
+    State((state, scope)): State<(SharedState, Scope)>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, APIError> {
+    let Some(store) = &state.api_keys else {
+        let mutating = !matches!(*request.method(), Method::GET | Method::HEAD);
+        if mutating
+            && matches!(scope, Scope::Admin | Scope::Problems)
+            && !state.config.auth.admin_unauthenticated
+        {
+            return Err(APIError::Forbidden(
+                "this route needs API keys; set API_KEYS_FILE, or ADMIN_UNAUTHENTICATED=true"
+                    .to_string(),
+            ));
+        }
+        return Ok(next.run(request).await);
+    };
+
+    let key = extract_api_key(&request)
+        .ok_or_else(|| APIError::Unauthorized("Missing API key".to_string()))?;
+    let identity = store
+        .authenticate(key)
+        .ok_or_else(|| APIError::Unauthorized("Invalid API key".to_string()))?;
+
+    if !identity.has_scope(scope) {
+        tracing::warn!(key_id = %identity.id, scope = scope.as_str(), "API key lacks scope");
+        return Err(APIError::Forbidden(format!(
+            "API key lacks the '{}' scope",
+            scope.as_str()
+        )));
+    }
+
+    let span = tracing::info_span!("api_key", key_id = %identity.id);
+    request.extensions_mut().insert(identity);
+    Ok(next.run(request).instrument(span).await)
+}
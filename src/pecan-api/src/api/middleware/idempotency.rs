@@ -0,0 +1,90 @@
+use axum::body::{Body, to_bytes};
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use http::HeaderValue;
+use http::header::CONTENT_TYPE;
+
+use crate::api::error::APIError;
+use crate::api::middleware::rate_limit::client_id;
+use crate::application::idempotency::{Lookup, MAX_KEY_LENGTH, StoredResponse, fingerprint};
+use crate::application::state::SharedState;
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::new(Body::from(stored.body));
+    *response.status_mut() = stored.status;
+    let headers = response.headers_mut();
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|c| HeaderValue::from_str(&c).ok())
+    {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+    headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+/// Answer a request carrying an `Idempotency-Key` that was seen before with
+/// the response of the first one, instead of submitting the work again.
+/// Only successful responses are kept, so failed attempts can be retried.
+/// Must run after `auth_middleware` and before `rate_limit_middleware`, so
+/// that replays are not charged to the client's rate limit.
+pub async fn idempotency_middleware(
+    State((state, body_limit)): State<(SharedState, usize)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, APIError> {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|k| !k.is_empty() && k.len() <= MAX_KEY_LENGTH)
+        .ok_or_else(|| {
+            APIError::InvalidRequest(format!(
+                "Idempotency-Key must be 1 to {} ASCII characters",
+                MAX_KEY_LENGTH
+            ))
+        })?
+        .to_string();
+    let client = client_id(&request);
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, body_limit).await.map_err(|_| {
+        APIError::PayloadTooLarge(format!("request body exceeds {} bytes", body_limit))
+    })?;
+    let fingerprint = fingerprint(parts.method.as_str(), parts.uri.path(), &body);
+
+    let claim = match state.idempotency.begin(&client, &key, fingerprint)? {
+        Lookup::Replay(stored) => {
+            tracing::info!(client = %client, "replaying idempotent request");
+            return Ok(replay(stored));
+        }
+        Lookup::Claimed(claim) => claim,
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if !response.status().is_success() {
+        // dropping the claim frees the key for a retry
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| APIError::InternalError(format!("failed to read response: {}", e)))?;
+    claim.complete(StoredResponse {
+        status: parts.status,
+        content_type: parts
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|c| c.to_str().ok())
+            .map(str::to_string),
+        body: body.to_vec(),
+    });
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
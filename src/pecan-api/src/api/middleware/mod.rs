@@ -0,0 +1,4 @@
+pub mod auth;
+pub mod idempotency;
+pub mod rate_limit;
+pub mod request_id;
@@ -0,0 +1,47 @@
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use http::HeaderValue;
+
+use crate::api::error::APIError;
+use crate::application::auth::ApiKeyIdentity;
+use crate::application::quota::ClientId;
+use crate::application::state::SharedState;
+
+pub const X_RATELIMIT_LIMIT: &str = "x-ratelimit-limit";
+pub const X_RATELIMIT_REMAINING: &str = "x-ratelimit-remaining";
+
+/// The API key id when the request was authenticated, otherwise the remote address.
+pub fn client_id(request: &Request) -> ClientId {
+    if let Some(identity) = request.extensions().get::<ApiKeyIdentity>() {
+        return ClientId::ApiKey(identity.id.clone());
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| ClientId::Ip(addr.ip().to_string()))
+        .unwrap_or_else(|| ClientId::Ip("unknown".to_string()))
+}
+
+/// Apply the per-client token bucket and expose the caller's `ClientId` to the handler.
+/// Must run after `auth_middleware` so that API key identities are known.
+pub async fn rate_limit_middleware(
+    State(state): State<SharedState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, APIError> {
+    let client = client_id(&request);
+    let status = state.quotas.check_rate(&client)?;
+
+    request.extensions_mut().insert(client);
+    let mut response = next.run(request).await;
+
+    if let Some(status) = status {
+        let headers = response.headers_mut();
+        headers.insert(X_RATELIMIT_LIMIT, HeaderValue::from(status.limit));
+        headers.insert(X_RATELIMIT_REMAINING, HeaderValue::from(status.remaining));
+    }
+    Ok(response)
+}
@@ -0,0 +1,84 @@
+use std::time::Instant;
+
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use http::HeaderValue;
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const X_REQUEST_ID: &str = "x-request-id";
+/// longest client-provided request id that is accepted as-is
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    /// request id of the HTTP request being handled by the current task
+    static REQUEST_ID: String;
+}
+
+/// Request id attached to the request extensions by `request_id_middleware`.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Returns the request id of the HTTP request being handled, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Run `future` with `request_id` as the current request id, for work of a
+/// request that continues after its handler returned.
+pub async fn with_request_id<F: Future>(request_id: Option<String>, future: F) -> F::Output {
+    match request_id {
+        Some(request_id) => REQUEST_ID.scope(request_id, future).await,
+        None => future.await,
+    }
+}
+
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Accept the caller's `X-Request-Id` or generate one, expose it to the
+/// handler and echo it back in the response headers. Everything the request
+/// does runs inside a `request` span carrying the id.
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(String::from)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    request
+        .extensions_mut()
+        .insert(RequestId(request_id.clone()));
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+    let started = Instant::now();
+
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(request))
+        .instrument(span.clone())
+        .await;
+
+    span.in_scope(|| {
+        tracing::info!(
+            status = response.status().as_u16(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            "request completed"
+        )
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(X_REQUEST_ID, value);
+    }
+    response
+}
@@ -1,4 +1,6 @@
 pub mod error;
+pub mod extract;
 pub mod handler;
+pub mod middleware;
 pub mod routes;
 pub mod server;
@@ -1,14 +1,120 @@
+use std::sync::Arc;
+
 use axum::Router;
-use axum::routing::post;
+use axum::extract::DefaultBodyLimit;
+use axum::middleware::from_fn_with_state;
+use axum::routing::{get, post};
 
-use crate::api::handler::judge_handler;
+use crate::api::handler::{judge_handler, problem_handler, session_handler};
+use crate::api::middleware::auth::auth_middleware;
+use crate::api::middleware::idempotency::idempotency_middleware;
+use crate::api::middleware::rate_limit::rate_limit_middleware;
+use crate::application::auth::Scope;
 use crate::application::state::SharedState;
 
-pub fn routes() -> Router<SharedState> {
+pub fn routes(state: &SharedState) -> Router<SharedState> {
+    let body_limit = state.config.limits.max_body_size();
+    let batch_body_limit = state.config.batch.max_body_size;
+
     Router::new()
-        .route("/judge-single", post(judge_handler::judge_single))
+        .route(
+            "/judge-single",
+            post(judge_handler::judge_single)
+                .route_layer(from_fn_with_state(Arc::clone(state), rate_limit_middleware))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), body_limit),
+                    idempotency_middleware,
+                ))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), Scope::Judge),
+                    auth_middleware,
+                )),
+        )
+        .route(
+            "/judge-single-stream",
+            post(judge_handler::judge_single_stream)
+                .route_layer(from_fn_with_state(Arc::clone(state), rate_limit_middleware))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), Scope::Judge),
+                    auth_middleware,
+                )),
+        )
+        .route(
+            "/session",
+            get(session_handler::open_session)
+                .route_layer(from_fn_with_state(Arc::clone(state), rate_limit_middleware))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), Scope::Judge),
+                    auth_middleware,
+                )),
+        )
         .route(
             "/judge-single-async",
-            post(judge_handler::judge_single_async),
+            post(judge_handler::judge_single_async)
+                .route_layer(from_fn_with_state(Arc::clone(state), rate_limit_middleware))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), body_limit),
+                    idempotency_middleware,
+                ))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), Scope::JudgeAsync),
+                    auth_middleware,
+                )),
+        )
+        .route(
+            "/problem",
+            post(problem_handler::judge_problem)
+                .route_layer(from_fn_with_state(Arc::clone(state), rate_limit_middleware))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), body_limit),
+                    idempotency_middleware,
+                ))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), Scope::Judge),
+                    auth_middleware,
+                )),
+        )
+        .route(
+            "/function",
+            post(judge_handler::judge_function)
+                .route_layer(from_fn_with_state(Arc::clone(state), rate_limit_middleware))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), body_limit),
+                    idempotency_middleware,
+                ))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), Scope::Judge),
+                    auth_middleware,
+                )),
+        )
+        .route(
+            "/batch",
+            post(judge_handler::create_batch)
+                .layer(DefaultBodyLimit::max(batch_body_limit))
+                .route_layer(from_fn_with_state(Arc::clone(state), rate_limit_middleware))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), batch_body_limit),
+                    idempotency_middleware,
+                ))
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), Scope::JudgeAsync),
+                    auth_middleware,
+                )),
+        )
+        .route(
+            "/batch/{id}",
+            get(judge_handler::get_batch)
+                .delete(judge_handler::cancel_batch)
+                .route_layer(from_fn_with_state(
+                    (Arc::clone(state), Scope::JudgeAsync),
+                    auth_middleware,
+                )),
+        )
+        .route(
+            "/batch/{id}/results",
+            get(judge_handler::get_batch_results).route_layer(from_fn_with_state(
+                (Arc::clone(state), Scope::JudgeAsync),
+                auth_middleware,
+            )),
         )
 }
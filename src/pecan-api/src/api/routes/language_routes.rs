@@ -0,0 +1,9 @@
+use axum::Router;
+use axum::routing::get;
+
+use crate::api::handler::language_handler;
+use crate::application::state::SharedState;
+
+pub fn routes() -> Router<SharedState> {
+    Router::new().route("/", get(language_handler::list_languages))
+}
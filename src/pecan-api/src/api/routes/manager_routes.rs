@@ -1,9 +1,30 @@
+use std::sync::Arc;
+
 use axum::Router;
-use axum::routing::get;
+use axum::middleware::from_fn_with_state;
+use axum::routing::{get, post, put};
 
 use crate::api::handler::manager_handler;
+use crate::api::middleware::auth::auth_middleware;
+use crate::application::auth::Scope;
 use crate::application::state::SharedState;
 
-pub fn routes() -> Router<SharedState> {
-    Router::new().route("/sandbox-status", get(manager_handler::get_sandbox_status))
+pub fn routes(state: &SharedState) -> Router<SharedState> {
+    Router::new()
+        .route("/sandbox-status", get(manager_handler::get_sandbox_status))
+        .route("/sandboxes", get(manager_handler::list_sandboxes))
+        .route(
+            "/sandboxes/{id}/recycle",
+            post(manager_handler::recycle_sandbox),
+        )
+        .route("/pool", put(manager_handler::resize_pool))
+        .route(
+            "/drain",
+            get(manager_handler::get_drain).put(manager_handler::set_drain),
+        )
+        .route("/selftest", post(manager_handler::run_self_test))
+        .route_layer(from_fn_with_state(
+            (Arc::clone(state), Scope::Admin),
+            auth_middleware,
+        ))
 }
@@ -1,2 +1,4 @@
 pub mod judge_routes;
+pub mod language_routes;
 pub mod manager_routes;
+pub mod problem_routes;
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::DefaultBodyLimit;
+use axum::middleware::from_fn_with_state;
+use axum::routing::{get, post};
+
+use crate::api::handler::problem_handler;
+use crate::api::middleware::auth::auth_middleware;
+use crate::application::auth::Scope;
+use crate::application::state::SharedState;
+
+pub fn routes(state: &SharedState) -> Router<SharedState> {
+    Router::new()
+        .route(
+            "/{id}",
+            post(problem_handler::upload_problem)
+                .layer(DefaultBodyLimit::max(state.config.problems.max_body_size()))
+                .get(problem_handler::get_problem)
+                .delete(problem_handler::delete_problem),
+        )
+        .route("/{id}/import", post(problem_handler::import_problem))
+        .route(
+            "/{id}/versions/{version}",
+            get(problem_handler::get_problem_version),
+        )
+        .route_layer(from_fn_with_state(
+            (Arc::clone(state), Scope::Problems),
+            auth_middleware,
+        ))
+}
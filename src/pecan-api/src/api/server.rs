@@ -1,29 +1,82 @@
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::Router;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::middleware;
 use axum::response::IntoResponse;
 use axum::routing::get;
+use axum::{Json, Router};
+use http::{HeaderName, HeaderValue, Method, StatusCode, header};
 use pecan_core::code_execution::AsyncCodeExecutionResult;
 use tokio::net::TcpListener;
 use tokio::sync::mpsc::Receiver;
+use tokio::time::{Instant, timeout_at};
 use tokio_util::sync::CancellationToken;
-use tower_http::cors::{Any, CorsLayer};
-
-use crate::api::handler::webhook_handler;
-use crate::api::routes::{judge_routes, manager_routes};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::api::error::APIError;
+use crate::api::handler::{metrics_handler, webhook_handler};
+use crate::api::middleware::auth::{X_API_KEY, auth_middleware};
+use crate::api::middleware::idempotency::IDEMPOTENCY_KEY;
+use crate::api::middleware::request_id::request_id_middleware;
+use crate::api::routes::{judge_routes, language_routes, manager_routes, problem_routes};
+use crate::application::auth::Scope;
+use crate::application::config::ServerConfig;
+use crate::application::service::language_service;
 use crate::application::state::SharedState;
+use crate::domain::models::health::HealthStatus;
+
+/// Any origin may make simple requests; only the configured origins may also
+/// send the headers and methods that need a preflight.
+fn cors_layer(config: &ServerConfig) -> CorsLayer {
+    if config.cors_allowed_origins.is_empty() {
+        return CorsLayer::new().allow_origin(Any);
+    }
+    let origins: Vec<HeaderValue> = config
+        .cors_allowed_origins
+        .iter()
+        .filter_map(|origin| match HeaderValue::from_str(origin) {
+            Ok(origin) => Some(origin),
+            Err(_) => {
+                tracing::warn!(origin = %origin, "ignoring invalid CORS origin");
+                None
+            }
+        })
+        .collect();
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            HeaderName::from_static(X_API_KEY),
+            HeaderName::from_static(IDEMPOTENCY_KEY),
+        ])
+}
 
 pub async fn start(state: SharedState, webhook_rx: Receiver<AsyncCodeExecutionResult>) {
-    let cors_layer = CorsLayer::new().allow_origin(Any);
+    let cors_layer = cors_layer(&state.config.server);
 
     let router = Router::new()
         .route("/v1/health", get(health_handler))
         .route("/v1/version", get(version_handler))
-        .nest("/v1/judge", judge_routes::routes())
-        .nest("/v1/manager", manager_routes::routes())
+        .route(
+            "/metrics",
+            get(metrics_handler::get_metrics).route_layer(middleware::from_fn_with_state(
+                (Arc::clone(&state), Scope::Admin),
+                auth_middleware,
+            )),
+        )
+        .nest("/v1/judge", judge_routes::routes(&state))
+        .nest("/v1/languages", language_routes::routes())
+        .nest("/v1/manager", manager_routes::routes(&state))
+        .nest("/v1/problems", problem_routes::routes(&state))
+        .fallback(fallback_handler)
         .with_state(Arc::clone(&state))
+        .layer(DefaultBodyLimit::max(state.config.limits.max_body_size()))
+        .layer(middleware::from_fn(request_id_middleware))
         .layer(cors_layer);
 
     let addr = SocketAddr::from_str(&format!(
@@ -42,39 +95,111 @@ pub async fn start(state: SharedState, webhook_rx: Receiver<AsyncCodeExecutionRe
         }
     };
 
-    let shutdown_signal = async {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Failed to install CTRL+C signal handler");
-        tracing::info!("\nShutdown signal received, starting graceful shutdown...");
-    };
-
     let cancel_token = CancellationToken::new();
+    let shutdown_token = CancellationToken::new();
 
-    let task_loop_token = cancel_token.child_token();
+    // stopped separately so that the last results still reach their webhooks
+    let task_loop_token = CancellationToken::new();
     let task_loop_service = Arc::clone(&state.service);
 
-    let webhook_token = cancel_token.child_token();
+    let webhook_token = CancellationToken::new();
+    let webhook_quotas = Arc::clone(&state.quotas);
+    let webhook_concurrency = state.config.service.async_workers.max(1) as usize;
 
     tracing::info!("Spawning background services...");
-    tokio::spawn(async move {
-        webhook_handler::webhook_handler_loop(webhook_rx, webhook_token).await;
+    let webhook_handle = tokio::spawn({
+        let webhook_token = webhook_token.clone();
+        async move {
+            webhook_handler::webhook_handler_loop(
+                webhook_rx,
+                webhook_quotas,
+                webhook_concurrency,
+                webhook_token,
+            )
+            .await;
+        }
     });
 
-    tokio::spawn(async move {
-        task_loop_service.run_task_loop(task_loop_token).await;
+    let task_loop_handle = tokio::spawn({
+        let task_loop_token = task_loop_token.clone();
+        async move {
+            task_loop_service.run_task_loop(task_loop_token).await;
+        }
     });
 
-    let server = axum::serve(listener, router).with_graceful_shutdown(async {
-        shutdown_signal.await;
+    if let Some(api_keys) = &state.api_keys {
+        let api_keys = Arc::clone(api_keys);
+        let reload_token = cancel_token.child_token();
+        let reload_interval = Duration::from_secs(state.config.auth.reload_interval_secs);
+        tokio::spawn(async move {
+            api_keys
+                .run_reload_loop(reload_interval, reload_token)
+                .await;
+        });
+    }
+
+    tokio::spawn({
+        let state = Arc::clone(&state);
+        let shutdown_token = shutdown_token.clone();
+        async move {
+            shutdown_signal().await;
+            tracing::info!("Shutdown signal received, starting graceful shutdown...");
+            state.service.set_draining(true);
+            shutdown_token.cancel();
+
+            state.batches.cancel_all();
+            let aborted = state.service.abort_queued().await;
+            if aborted > 0 {
+                tracing::info!(aborted, "Aborted queued jobs");
+            }
+        }
     });
 
-    if let Err(e) = server.await {
-        tracing::error!("Server error: {}", e);
-    }
+    let server = axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_token.clone().cancelled_owned());
+    let mut server = std::pin::pin!(server.into_future());
+
+    let grace_period = state.config.server.shutdown_grace_period();
+    let deadline = tokio::select! {
+        res = &mut server => {
+            if let Err(e) = res {
+                tracing::error!("Server error: {}", e);
+            }
+            Instant::now() + grace_period
+        }
+        _ = shutdown_token.cancelled() => {
+            let deadline = Instant::now() + grace_period;
+            match timeout_at(deadline, &mut server).await {
+                Ok(Err(e)) => tracing::error!("Server error: {}", e),
+                Ok(Ok(())) => {}
+                Err(_) => tracing::warn!("Grace period elapsed with requests still in flight"),
+            }
+            deadline
+        }
+    };
 
     cancel_token.cancel();
 
+    // the queue is closed by now, so the workers only finish the tasks they hold
+    task_loop_token.cancel();
+    if timeout_at(deadline, task_loop_handle).await.is_err() {
+        tracing::warn!("Grace period elapsed with async jobs still running");
+    }
+    if timeout_at(deadline, state.batches.wait()).await.is_err() {
+        tracing::warn!("Grace period elapsed with batches still running");
+    }
+    if !state.service.wait_until_idle(deadline.into_std()).await {
+        tracing::warn!("Grace period elapsed with executions still running");
+    }
+
+    webhook_token.cancel();
+    if timeout_at(deadline, webhook_handle).await.is_err() {
+        tracing::warn!("Grace period elapsed with webhooks still pending");
+    }
+
     tracing::info!("Cleaning up resources...");
     if let Err(e) = state.service.shutdown().await {
         tracing::error!("Error during cleanup: {}", e);
@@ -83,10 +208,45 @@ pub async fn start(state: SharedState, webhook_rx: Receiver<AsyncCodeExecutionRe
     }
 }
 
-pub async fn health_handler() -> impl IntoResponse {
-    "OK"
+/// Resolves on Ctrl-C, or on SIGTERM where available.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install CTRL+C signal handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+pub async fn health_handler(State(state): State<SharedState>) -> impl IntoResponse {
+    let health = language_service::get_health(&state).await;
+    // lets load balancers take a draining node out of rotation
+    let status = match health.status {
+        HealthStatus::Draining => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+    (status, Json(health))
 }
 
 pub async fn version_handler() -> impl IntoResponse {
     format!("v{}", env!("CARGO_PKG_VERSION"))
 }
+
+pub async fn fallback_handler() -> APIError {
+    APIError::NotFound("No such endpoint".to_string())
+}
@@ -1,23 +1,106 @@
+use std::path::Path;
 use std::sync::Arc;
 
+use pecan_core::service::ServiceSpec;
+
 use crate::api::server;
+use crate::application::auth::ApiKeyStore;
+use crate::application::batch::BatchStore;
 use crate::application::config;
+use crate::application::idempotency::IdempotencyStore;
+use crate::application::problem_store::ProblemStore;
+use crate::application::quota::ClientQuotas;
 use crate::application::state::AppState;
+use crate::application::telemetry;
 
 pub async fn run() {
     let config = config::load_config();
 
-    let (service, webhook_rx) = pecan_core::init(
-        config.service.max_queue_size,
-        config.service.max_concurrent_executions,
-        config.service.max_queue_size as usize,
-    )
+    // installed before the service starts so that prewarm failures are counted
+    let metrics = config.server.metrics_enabled.then(|| {
+        telemetry::install_recorder()
+            .unwrap_or_else(|e| panic!("Failed to install metrics recorder: {}", e))
+    });
+
+    let (service, webhook_rx) = pecan_core::init(ServiceSpec {
+        enable_bg_worker_loop: true,
+        max_queue_size: config.service.max_queue_size,
+        max_concurrent_executions: config.service.max_concurrent_executions,
+        webhook_buffer_size: config.service.max_queue_size as usize,
+        async_workers: config.service.async_workers,
+        priority_shares: config.service.priority_shares,
+        autoscale: config.service.autoscale.policy(),
+        result_cache: config.service.result_cache.policy(),
+        artifact_cache: config.service.artifact_cache.policy(),
+    })
     .await
     .unwrap();
 
+    if config.service.self_test_on_startup {
+        tracing::info!("Running toolchain self-test...");
+        let report = service.run_self_test().await;
+        for result in &report.results {
+            if result.passed {
+                tracing::info!(
+                    language = result.language.as_str(),
+                    compile_time = result.compile_time,
+                    run_time = result.run_time,
+                    "toolchain self-test passed"
+                );
+            } else {
+                tracing::warn!(
+                    language = result.language.as_str(),
+                    reason = result.message.as_deref().unwrap_or_default(),
+                    "toolchain self-test failed, language marked unavailable"
+                );
+            }
+        }
+    }
+
+    let api_keys = config.auth.api_keys_file.as_ref().map(|path| {
+        let store = ApiKeyStore::load(Path::new(path))
+            .unwrap_or_else(|e| panic!("Failed to load API keys: {}", e));
+        tracing::info!("Loaded API keys from {}", path);
+        Arc::new(store)
+    });
+    if api_keys.is_none() {
+        if config.auth.admin_unauthenticated {
+            tracing::warn!("API_KEYS_FILE is not set, authentication is disabled");
+        } else {
+            tracing::warn!(
+                "API_KEYS_FILE is not set, authentication is disabled except for the routes that change the pool or the problems"
+            );
+        }
+    }
+
+    let quotas = Arc::new(ClientQuotas::new(config.rate_limit.clone()));
+
+    let batches = BatchStore::new(config.batch.retention());
+    let idempotency = Arc::new(IdempotencyStore::new(
+        config.idempotency.retention(),
+        config.idempotency.max_keys_per_client,
+        config.idempotency.max_bytes,
+    ));
+    let problems = match &config.problems.store_dir {
+        Some(dir) => {
+            let store = ProblemStore::open(dir.into())
+                .await
+                .unwrap_or_else(|e| panic!("Failed to open problem store: {}", e));
+            tracing::info!("Serving problems from {}", dir);
+            Some(Arc::new(store))
+        }
+        None => None,
+    };
+
     let shared_state = Arc::new(AppState {
         config,
         service: Arc::new(service),
+        api_keys,
+        quotas,
+        batches,
+        idempotency,
+        problems,
+        metrics,
     });
 
     server::start(shared_state, webhook_rx).await;
@@ -0,0 +1,242 @@
+//! API key store backed by a JSON file of hashed keys.
+//!
+//! Keys are never stored in plain text: the file holds the hex-encoded
+//! SHA-256 digest of each key, and incoming keys are hashed before lookup.
+//!
+//! ```json
+//! {
+//!   "keys": [
+//!     { "id": "backend", "key_hash": "9f86d08...", "scopes": ["judge", "judge:async"] }
+//!   ]
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "judge")]
+    Judge,
+    #[serde(rename = "judge:async")]
+    JudgeAsync,
+    /// upload, import and delete problem packages
+    #[serde(rename = "problems")]
+    Problems,
+    /// implies every other scope
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Judge => "judge",
+            Scope::JudgeAsync => "judge:async",
+            Scope::Problems => "problems",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+/// Identity of an authenticated caller, attached to the request extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyIdentity {
+    pub id: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl ApiKeyIdentity {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiKeyEntry {
+    id: String,
+    key_hash: String,
+    scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiKeyFile {
+    keys: Vec<ApiKeyEntry>,
+}
+
+/// Hex-encoded SHA-256 digest of `key`, the format stored in the key file.
+pub fn hash_api_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn parse_key_file(content: &str) -> Result<HashMap<String, ApiKeyIdentity>, String> {
+    let file: ApiKeyFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+
+    let mut keys = HashMap::with_capacity(file.keys.len());
+    for entry in file.keys {
+        let key_hash = entry.key_hash.to_ascii_lowercase();
+        if key_hash.len() != 64 || !key_hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("key {} has an invalid key_hash", entry.id));
+        }
+        let identity = ApiKeyIdentity {
+            id: entry.id,
+            scopes: entry.scopes,
+        };
+        if let Some(previous) = keys.insert(key_hash, identity) {
+            return Err(format!("key {} has a duplicate key_hash", previous.id));
+        }
+    }
+    Ok(keys)
+}
+
+pub struct ApiKeyStore {
+    path: PathBuf,
+    /// key hash -> identity
+    keys: RwLock<HashMap<String, ApiKeyIdentity>>,
+    modified: RwLock<Option<SystemTime>>,
+}
+
+impl ApiKeyStore {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let store = Self {
+            path: path.to_path_buf(),
+            keys: RwLock::new(HashMap::new()),
+            modified: RwLock::new(None),
+        };
+        store.reload()?;
+        Ok(store)
+    }
+
+    /// Re-read the key file. On error the previously loaded keys stay active.
+    pub fn reload(&self) -> Result<usize, String> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        let content = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("failed to read {}: {}", self.path.display(), e))?;
+        let keys = parse_key_file(&content)?;
+        let count = keys.len();
+
+        if let Ok(mut current) = self.keys.write() {
+            *current = keys;
+        }
+        if let Ok(mut current) = self.modified.write() {
+            *current = modified;
+        }
+        Ok(count)
+    }
+
+    /// Reload only if the file's modification time changed since the last load.
+    pub fn reload_if_changed(&self) -> Result<Option<usize>, String> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        let unchanged = self
+            .modified
+            .read()
+            .map(|current| *current == modified)
+            .unwrap_or(false);
+        if unchanged {
+            return Ok(None);
+        }
+        self.reload().map(Some)
+    }
+
+    /// Reload the key file when it changes on disk or when SIGHUP is received.
+    pub async fn run_reload_loop(&self, interval: Duration, cancel: CancellationToken) {
+        #[cfg(unix)]
+        let mut hangup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+
+        loop {
+            #[cfg(unix)]
+            let hangup_received = async {
+                match hangup.as_mut() {
+                    Some(signal) => signal.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let hangup_received = std::future::pending::<Option<()>>();
+
+            let result = tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = hangup_received => self.reload().map(Some),
+                _ = sleep(interval) => self.reload_if_changed(),
+            };
+
+            match result {
+                Ok(Some(count)) => tracing::info!(keys = count, "API keys reloaded"),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to reload API keys, keeping previous keys")
+                }
+            }
+        }
+    }
+
+    pub fn authenticate(&self, key: &str) -> Option<ApiKeyIdentity> {
+        let key_hash = hash_api_key(key);
+        self.keys
+            .read()
+            .ok()
+            .and_then(|keys| keys.get(&key_hash).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scope, hash_api_key, parse_key_file};
+
+    #[test]
+    fn hash_is_hex_sha256() {
+        assert_eq!(
+            hash_api_key("test"),
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        );
+    }
+
+    #[test]
+    fn parses_scopes_and_looks_up_by_hash() {
+        let content = format!(
+            r#"{{"keys": [
+                {{"id": "backend", "key_hash": "{}", "scopes": ["judge", "judge:async"]}},
+                {{"id": "ops", "key_hash": "{}", "scopes": ["admin"]}}
+            ]}}"#,
+            hash_api_key("backend-key"),
+            hash_api_key("ops-key"),
+        );
+        let keys = parse_key_file(&content).expect("valid key file");
+
+        let backend = &keys[&hash_api_key("backend-key")];
+        assert_eq!(backend.id, "backend");
+        assert!(backend.has_scope(Scope::JudgeAsync));
+        assert!(!backend.has_scope(Scope::Admin));
+
+        let ops = &keys[&hash_api_key("ops-key")];
+        assert!(ops.has_scope(Scope::Judge));
+        assert!(ops.has_scope(Scope::Admin));
+    }
+
+    #[test]
+    fn rejects_malformed_hashes_and_unknown_scopes() {
+        assert!(
+            parse_key_file(r#"{"keys": [{"id": "a", "key_hash": "abc", "scopes": []}]}"#).is_err()
+        );
+        let content = format!(
+            r#"{{"keys": [{{"id": "a", "key_hash": "{}", "scopes": ["root"]}}]}}"#,
+            hash_api_key("a")
+        );
+        assert!(parse_key_file(&content).is_err());
+    }
+}
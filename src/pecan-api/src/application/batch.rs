@@ -0,0 +1,217 @@
+//! In-memory registry of batch submissions and their progress.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use uuid::Uuid;
+
+use crate::domain::models::batch::{BatchItemResult, BatchProgressResponse, BatchStatus};
+
+pub struct Batch {
+    pub id: Uuid,
+    /// API key that created the batch, `None` when authentication is disabled
+    pub owner: Option<String>,
+    pub total: usize,
+    cancel: CancellationToken,
+    state: Mutex<BatchState>,
+}
+
+#[derive(Default)]
+struct BatchState {
+    running: usize,
+    done: usize,
+    cancelled: usize,
+    /// in completion order
+    results: Vec<BatchItemResult>,
+    finished_at: Option<Instant>,
+}
+
+impl Batch {
+    fn lock(&self) -> MutexGuard<'_, BatchState> {
+        // counters stay meaningful after a panic elsewhere, so keep using them
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Skip the items that have not started yet; running items still finish.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub fn start_item(&self) {
+        self.lock().running += 1;
+    }
+
+    pub fn finish_item(&self, result: BatchItemResult) {
+        let mut state = self.lock();
+        state.running -= 1;
+        state.done += 1;
+        state.results.push(result);
+    }
+
+    pub fn skip_item(&self) {
+        self.lock().cancelled += 1;
+    }
+
+    pub fn finish(&self) {
+        self.lock().finished_at = Some(Instant::now());
+    }
+
+    pub fn progress(&self) -> BatchProgressResponse {
+        let state = self.lock();
+        let status = match (state.finished_at, self.is_cancelled()) {
+            (None, _) => BatchStatus::Running,
+            (Some(_), true) => BatchStatus::Cancelled,
+            (Some(_), false) => BatchStatus::Completed,
+        };
+        BatchProgressResponse {
+            batch_id: self.id,
+            status,
+            total: self.total,
+            queued: self.total - state.running - state.done - state.cancelled,
+            running: state.running,
+            done: state.done,
+            cancelled: state.cancelled,
+        }
+    }
+
+    /// Results of the items finished so far.
+    pub fn results(&self) -> Vec<BatchItemResult> {
+        self.lock().results.clone()
+    }
+
+    fn is_expired(&self, now: Instant, retention: Duration) -> bool {
+        self.lock()
+            .finished_at
+            .is_some_and(|finished_at| now.duration_since(finished_at) > retention)
+    }
+}
+
+pub struct BatchStore {
+    batches: Mutex<HashMap<Uuid, Arc<Batch>>>,
+    /// how long finished batches stay retrievable
+    retention: Duration,
+    /// parent of every batch's token, cancelled on shutdown
+    cancel: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl BatchStore {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            batches: Mutex::new(HashMap::new()),
+            retention,
+            cancel: CancellationToken::new(),
+            tasks: TaskTracker::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Uuid, Arc<Batch>>> {
+        self.batches.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a new batch; finished batches past their retention are dropped.
+    pub fn insert(&self, id: Uuid, owner: Option<String>, total: usize) -> Arc<Batch> {
+        let batch = Arc::new(Batch {
+            id,
+            owner,
+            total,
+            cancel: self.cancel.child_token(),
+            state: Mutex::new(BatchState::default()),
+        });
+
+        let now = Instant::now();
+        let mut batches = self.lock();
+        batches.retain(|_, batch| !batch.is_expired(now, self.retention));
+        batches.insert(id, Arc::clone(&batch));
+        batch
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<Arc<Batch>> {
+        self.lock().get(&id).cloned()
+    }
+
+    /// Run a batch in the background; `wait` waits for it.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.spawn(task);
+    }
+
+    /// Cancel every batch; their running items still finish.
+    pub fn cancel_all(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Wait until every batch has finished and sent its webhook.
+    pub async fn wait(&self) {
+        self.tasks.close();
+        self.tasks.wait().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::BatchStore;
+    use crate::domain::models::batch::{BatchItemResult, BatchStatus};
+
+    fn item(submission: usize) -> BatchItemResult {
+        BatchItemResult {
+            submission,
+            test_case: None,
+            result: None,
+            error: Some("failed".to_string()),
+        }
+    }
+
+    #[test]
+    fn progress_tracks_items_and_cancellation() {
+        let store = BatchStore::new(Duration::from_secs(60));
+        let batch = store.insert(Uuid::new_v4(), None, 3);
+
+        batch.start_item();
+        let progress = batch.progress();
+        assert_eq!(progress.status, BatchStatus::Running);
+        assert_eq!((progress.queued, progress.running), (2, 1));
+
+        batch.finish_item(item(0));
+        batch.cancel();
+        batch.skip_item();
+        batch.skip_item();
+        batch.finish();
+
+        let progress = batch.progress();
+        assert_eq!(progress.status, BatchStatus::Cancelled);
+        assert_eq!(
+            (progress.queued, progress.done, progress.cancelled),
+            (0, 1, 2)
+        );
+        assert_eq!(batch.results(), vec![item(0)]);
+    }
+
+    #[test]
+    fn finished_batches_expire_after_retention() {
+        let store = BatchStore::new(Duration::ZERO);
+        let finished = store.insert(Uuid::new_v4(), None, 0);
+        finished.finish();
+        let running = store.insert(Uuid::new_v4(), None, 1);
+
+        std::thread::sleep(Duration::from_millis(5));
+        store.insert(Uuid::new_v4(), None, 1);
+
+        assert!(store.get(finished.id).is_none());
+        assert!(store.get(running.id).is_some());
+    }
+}
@@ -0,0 +1,396 @@
+//! Checking of an output against the expected answer, by a built-in
+//! comparator or from the run of a checker program.
+//!
+//! Checker programs follow testlib: they are run as `checker input.txt
+//! output.txt answer.txt` and report through their exit code.
+//! - 0 accepts the output, and 1 or 2 reject it.
+//! - 7 gives the points written after `points` at the start of the message,
+//!   as testlib's `quitp` does.
+//! - 50 + n gives n hundredths of the points, as testlib's `_pc(n)` does.
+//! - 3, or any other outcome, is a failure of the checker itself.
+//!
+//! The message is what the checker wrote to stderr.
+//!
+//! Values returned by a function are compared structurally instead.
+
+use pecan_core::code_execution::{CodeExecutionResult, CodeExecutionStatus};
+use pecan_core::harness::ValueType;
+use serde_json::Value;
+
+use crate::domain::models::judge::JudgeStatus;
+use crate::domain::models::problem::Comparator;
+
+/// Arguments of a checker program, naming its input files.
+pub const CHECKER_ARGS: [&str; 3] = ["input.txt", "output.txt", "answer.txt"];
+
+/// Bytes of a checker message kept in the response.
+const MAX_MESSAGE_SIZE: usize = 1024;
+
+/// Outcome of checking an output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub status: JudgeStatus,
+    /// between 0 and 1
+    pub score: f64,
+    pub message: Option<String>,
+}
+
+impl Check {
+    pub fn scored(score: f64, message: Option<String>) -> Self {
+        let status = if score >= 1.0 {
+            JudgeStatus::Accepted
+        } else if score > 0.0 {
+            JudgeStatus::PartiallyCorrect
+        } else {
+            JudgeStatus::WrongAnswer
+        };
+        Self {
+            status,
+            score,
+            message,
+        }
+    }
+
+    /// The checker could not judge the output, which is none of the
+    /// submission's fault.
+    pub fn failed(message: String) -> Self {
+        Self {
+            status: JudgeStatus::InternalError,
+            score: 0.0,
+            message: Some(format!("checker failed: {}", message)),
+        }
+    }
+}
+
+pub fn compare(comparator: &Comparator, output: &[u8], answer: &[u8]) -> Check {
+    match comparator {
+        Comparator::Exact => Check::scored(if output == answer { 1.0 } else { 0.0 }, None),
+        Comparator::Tokens => {
+            compare_tokens(
+                output,
+                answer,
+                |found, expected| {
+                    if found == expected { 1.0 } else { 0.0 }
+                },
+            )
+        }
+        Comparator::Float { tolerance, partial } => {
+            compare_tokens(output, answer, |found, expected| {
+                let (Some(found), Some(expected)) = (number(found), number(expected)) else {
+                    return if found == expected { 1.0 } else { 0.0 };
+                };
+                if within(found, expected, *tolerance) {
+                    return 1.0;
+                }
+                partial
+                    .iter()
+                    .filter(|credit| within(found, expected, credit.tolerance))
+                    .map(|credit| credit.score)
+                    .fold(0.0, f64::max)
+            })
+        }
+    }
+}
+
+/// `found` is within an absolute or relative error of `tolerance`.
+fn within(found: f64, expected: f64, tolerance: f64) -> bool {
+    let error = (found - expected).abs();
+    error <= tolerance || error <= tolerance * expected.abs()
+}
+
+fn number(token: &[u8]) -> Option<f64> {
+    let value: f64 = std::str::from_utf8(token).ok()?.parse().ok()?;
+    value.is_finite().then_some(value)
+}
+
+/// Compare the whitespace-separated tokens of `output` and `answer` pairwise;
+/// the score is the lowest of them and the message names the first token
+/// that fell short.
+fn compare_tokens(output: &[u8], answer: &[u8], score: impl Fn(&[u8], &[u8]) -> f64) -> Check {
+    let tokens = |text: &[u8]| -> Vec<Vec<u8>> {
+        text.split(u8::is_ascii_whitespace)
+            .filter(|token| !token.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect()
+    };
+    let (found, expected) = (tokens(output), tokens(answer));
+    if found.len() != expected.len() {
+        return Check::scored(
+            0.0,
+            Some(format!(
+                "expected {} tokens, found {}",
+                expected.len(),
+                found.len()
+            )),
+        );
+    }
+
+    let mut lowest = 1.0;
+    let mut message = None;
+    for (i, (found, expected)) in found.iter().zip(&expected).enumerate() {
+        let token_score = score(found, expected);
+        if token_score < 1.0 && message.is_none() {
+            message = Some(format!(
+                "token {} differs: expected `{}`, found `{}`",
+                i + 1,
+                String::from_utf8_lossy(expected),
+                String::from_utf8_lossy(found)
+            ));
+        }
+        lowest = f64::min(lowest, token_score);
+    }
+    Check::scored(lowest, message)
+}
+
+/// Compare the value a function returned with the expected one, both of
+/// type `value_type`; doubles may differ by `tolerance`. The message gives
+/// the position of the first difference within the lists.
+pub fn compare_values(
+    value_type: &ValueType,
+    returned: &Value,
+    expected: &Value,
+    tolerance: f64,
+) -> Check {
+    match difference(
+        value_type,
+        returned,
+        expected,
+        tolerance,
+        &mut String::new(),
+    ) {
+        Some(difference) => Check::scored(0.0, message(difference.as_bytes())),
+        None => Check::scored(1.0, None),
+    }
+}
+
+fn difference(
+    value_type: &ValueType,
+    returned: &Value,
+    expected: &Value,
+    tolerance: f64,
+    path: &mut String,
+) -> Option<String> {
+    let at = |path: &str| {
+        if path.is_empty() {
+            String::new()
+        } else {
+            format!("at {}: ", path)
+        }
+    };
+    match (value_type, returned, expected) {
+        (ValueType::Double, Value::Number(found), Value::Number(wanted))
+            if within(
+                found.as_f64().unwrap_or(f64::NAN),
+                wanted.as_f64().unwrap_or(f64::NAN),
+                tolerance,
+            ) =>
+        {
+            None
+        }
+        (ValueType::List(inner), Value::Array(found), Value::Array(wanted)) => {
+            if found.len() != wanted.len() {
+                return Some(format!(
+                    "{}expected {} elements, found {}",
+                    at(path),
+                    wanted.len(),
+                    found.len()
+                ));
+            }
+            found
+                .iter()
+                .zip(wanted)
+                .enumerate()
+                .find_map(|(i, (found, wanted))| {
+                    let len = path.len();
+                    path.push_str(&format!("[{}]", i));
+                    let difference = difference(inner, found, wanted, tolerance, path);
+                    path.truncate(len);
+                    difference
+                })
+        }
+        _ if returned == expected => None,
+        _ => Some(format!(
+            "{}expected {}, found {}",
+            at(path),
+            expected,
+            returned
+        )),
+    }
+}
+
+/// Check reported by the run of a checker program.
+pub fn checker_check(result: &CodeExecutionResult) -> Check {
+    let message = message(&result.stderr);
+    let exit_code = match result.status {
+        CodeExecutionStatus::Success => 0,
+        CodeExecutionStatus::RuntimeError => match result.exit_code {
+            Some(exit_code) => exit_code,
+            None => return Check::failed("killed by a signal".to_string()),
+        },
+        CodeExecutionStatus::CompileError => {
+            return Check::failed(format!("compile error: {}", message.unwrap_or_default()));
+        }
+        status => return Check::failed(format!("{:?}", status)),
+    };
+
+    let score = match exit_code {
+        0 => 1.0,
+        1 | 2 => 0.0,
+        7 => {
+            let points = message
+                .as_deref()
+                .unwrap_or_default()
+                .trim_start_matches("points")
+                .split_whitespace()
+                .next()
+                .and_then(|points| points.parse::<f64>().ok());
+            match points {
+                Some(points) if (0.0..=1.0).contains(&points) => points,
+                _ => return Check::failed("points must be between 0 and 1".to_string()),
+            }
+        }
+        50..=150 => (exit_code - 50) as f64 / 100.0,
+        _ => {
+            return Check::failed(format!(
+                "exited with status {}: {}",
+                exit_code,
+                message.unwrap_or_default()
+            ));
+        }
+    };
+    Check::scored(score, message)
+}
+
+fn message(stderr: &[u8]) -> Option<String> {
+    let message = String::from_utf8_lossy(stderr);
+    let mut message = message.trim();
+    if message.len() > MAX_MESSAGE_SIZE {
+        let mut end = MAX_MESSAGE_SIZE;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message = &message[..end];
+    }
+    (!message.is_empty()).then(|| message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use pecan_core::code_execution::{CodeExecutionResult, CodeExecutionStatus};
+    use serde_json::json;
+
+    use super::{checker_check, compare, compare_values};
+    use crate::domain::models::judge::JudgeStatus;
+    use crate::domain::models::problem::{Comparator, PartialCredit};
+
+    #[test]
+    fn comparators_score_outputs() {
+        let check = |comparator: &Comparator, output: &str| {
+            compare(comparator, output.as_bytes(), b"3.0 10\n")
+        };
+        assert_eq!(check(&Comparator::Exact, "3.0 10").score, 0.0);
+        assert_eq!(check(&Comparator::Tokens, "3.0\n10").score, 1.0);
+
+        let float = Comparator::Float {
+            tolerance: 1e-6,
+            partial: vec![PartialCredit {
+                tolerance: 0.05,
+                score: 0.5,
+            }],
+        };
+        assert_eq!(
+            check(&float, "3.0000001 10.0").status,
+            JudgeStatus::Accepted
+        );
+        let close = check(&float, "3.1 10");
+        assert_eq!(
+            (close.status, close.score),
+            (JudgeStatus::PartiallyCorrect, 0.5)
+        );
+        assert_eq!(
+            close.message.as_deref(),
+            Some("token 1 differs: expected `3.0`, found `3.1`")
+        );
+        assert_eq!(check(&float, "4 10").score, 0.0);
+        assert_eq!(
+            check(&float, "3").message.as_deref(),
+            Some("expected 2 tokens, found 1")
+        );
+    }
+
+    #[test]
+    fn returned_values_are_compared_structurally() {
+        let grid = "list<list<double>>".parse().unwrap();
+        let expected = json!([[1.0, 2], [3.5]]);
+        let check = |returned| compare_values(&grid, &returned, &expected, 1e-6);
+
+        assert_eq!(
+            check(json!([[1.0000001, 2.0], [3.5]])).status,
+            JudgeStatus::Accepted
+        );
+        assert_eq!(
+            check(json!([[1.0, 2.0], [3.6]])).message.as_deref(),
+            Some("at [1][0]: expected 3.5, found 3.6")
+        );
+        assert_eq!(
+            check(json!([[1.0, 2.0]])).message.as_deref(),
+            Some("expected 2 elements, found 1")
+        );
+        let words = "list<string>".parse().unwrap();
+        let check = compare_values(&words, &json!(["a", "c"]), &json!(["a", "b"]), 0.0);
+        assert_eq!(
+            (check.status, check.message.as_deref()),
+            (
+                JudgeStatus::WrongAnswer,
+                Some("at [1]: expected \"b\", found \"c\"")
+            )
+        );
+    }
+
+    #[test]
+    fn checker_exit_codes_follow_testlib() {
+        let run = |status, exit_code, stderr: &str| CodeExecutionResult {
+            status,
+            stdout: Vec::new(),
+            stderr: stderr.into(),
+            time: 0.0,
+            memory: 0.0,
+            compile_time: 0.0,
+            output_files: Vec::new(),
+            exit_code,
+            cached: false,
+        };
+        let error =
+            |exit_code, stderr| run(CodeExecutionStatus::RuntimeError, Some(exit_code), stderr);
+
+        let ok = checker_check(&run(CodeExecutionStatus::Success, Some(0), "ok 1 number\n"));
+        assert_eq!(
+            (ok.status, ok.message.as_deref()),
+            (JudgeStatus::Accepted, Some("ok 1 number"))
+        );
+        assert_eq!(
+            checker_check(&error(1, "wrong answer")).status,
+            JudgeStatus::WrongAnswer
+        );
+
+        let points = checker_check(&error(7, "points 0.25 close enough"));
+        assert_eq!(
+            (points.status, points.score),
+            (JudgeStatus::PartiallyCorrect, 0.25)
+        );
+        assert_eq!(
+            checker_check(&error(7, "points 5")).status,
+            JudgeStatus::InternalError
+        );
+        assert_eq!(checker_check(&error(80, "")).score, 0.3);
+
+        let failed = checker_check(&error(3, "bad answer file"));
+        assert_eq!(failed.status, JudgeStatus::InternalError);
+        assert_eq!(
+            failed.message.as_deref(),
+            Some("checker failed: exited with status 3: bad answer file")
+        );
+        let killed = run(CodeExecutionStatus::TimeLimitExceeded, None, "");
+        assert_eq!(checker_check(&killed).status, JudgeStatus::InternalError);
+    }
+}
@@ -1,16 +1,41 @@
 use std::env;
 use std::str::FromStr;
+use std::time::Duration;
+
+use pecan_core::cache::ResultCachePolicy;
+use pecan_core::{ArtifactCachePolicy, AutoscalePolicy, PriorityShares};
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub server: ServerConfig,
     pub service: ServiceConfig,
+    pub limits: LimitsConfig,
+    pub auth: AuthConfig,
+    pub rate_limit: RateLimitConfig,
+    pub batch: BatchConfig,
+    pub idempotency: IdempotencyConfig,
+    pub session: SessionConfig,
+    pub problems: ProblemConfig,
 }
 
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub port: u16,
     pub host: String,
+    /// serve Prometheus metrics at `/metrics`
+    pub metrics_enabled: bool,
+    /// browser origins allowed to send keys and JSON bodies; when empty any
+    /// origin may make simple requests only
+    pub cors_allowed_origins: Vec<String>,
+    /// how long shutdown waits for in-flight requests, running executions and
+    /// pending webhooks before tearing the sandboxes down
+    pub shutdown_grace_period_secs: u64,
+}
+
+impl ServerConfig {
+    pub fn shutdown_grace_period(&self) -> Duration {
+        Duration::from_secs(self.shutdown_grace_period_secs)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -18,27 +43,380 @@ pub struct ServiceConfig {
     pub enable_bg_worker_loop: bool,
     pub max_queue_size: u32,
     pub max_concurrent_executions: u32,
+    /// async jobs executed at the same time
+    pub async_workers: u32,
+    /// how long a queued job waits for a sandbox before failing, in seconds;
+    /// zero waits as long as it takes
+    pub async_queue_timeout_secs: u64,
+    /// share of the sandbox pool reserved to each priority class
+    pub priority_shares: PriorityShares,
+    pub self_test_on_startup: bool,
+    pub autoscale: AutoscaleConfig,
+    pub result_cache: ResultCacheConfig,
+    pub artifact_cache: ArtifactCacheConfig,
+}
+
+impl ServiceConfig {
+    /// Bound on the wait of a queued job for a sandbox, independent of its
+    /// time limit since lower priority classes wait by design.
+    pub fn async_queue_timeout(&self) -> Option<f64> {
+        (self.async_queue_timeout_secs > 0).then_some(self.async_queue_timeout_secs as f64)
+    }
+}
+
+/// Reuse of compiled programs across submissions; disabled by default.
+#[derive(Debug, Clone)]
+pub struct ArtifactCacheConfig {
+    pub enabled: bool,
+    pub dir: String,
+    /// bound on the total size of the stored programs, in bytes
+    pub max_bytes: u64,
+}
+
+impl ArtifactCacheConfig {
+    pub fn policy(&self) -> Option<ArtifactCachePolicy> {
+        self.enabled.then(|| ArtifactCachePolicy {
+            dir: self.dir.clone().into(),
+            max_bytes: self.max_bytes,
+        })
+    }
+}
+
+/// Reuse of results for identical submissions; disabled by default.
+#[derive(Debug, Clone)]
+pub struct ResultCacheConfig {
+    pub enabled: bool,
+    /// how long a cached result is served, in seconds
+    pub ttl_secs: u64,
+    /// bound on the total size of the cached results, in bytes
+    pub max_bytes: usize,
+    /// keep results on disk under this directory instead of in memory
+    pub dir: Option<String>,
+}
+
+impl ResultCacheConfig {
+    pub fn policy(&self) -> Option<ResultCachePolicy> {
+        self.enabled.then(|| ResultCachePolicy {
+            ttl: Duration::from_secs(self.ttl_secs),
+            max_bytes: self.max_bytes,
+            dir: self.dir.as_ref().map(Into::into),
+        })
+    }
+}
+
+/// Sandbox pool autoscaling; the pool stays at `max_concurrent_executions` when disabled.
+#[derive(Debug, Clone)]
+pub struct AutoscaleConfig {
+    pub enabled: bool,
+    pub min_sandboxes: usize,
+    pub max_sandboxes: usize,
+    pub step: usize,
+    /// grow when a submission waited this long for a sandbox
+    pub scale_up_wait_ms: u64,
+    /// grow when this many submissions are waiting for a sandbox
+    pub scale_up_waiting: usize,
+    /// shrink idle sandboxes after this many seconds without pressure
+    pub scale_down_after_secs: u64,
+}
+
+impl AutoscaleConfig {
+    pub fn policy(&self) -> Option<AutoscalePolicy> {
+        self.enabled.then(|| AutoscalePolicy {
+            min_sandboxes: self.min_sandboxes,
+            max_sandboxes: self.max_sandboxes,
+            step: self.step,
+            scale_up_wait: Duration::from_millis(self.scale_up_wait_ms),
+            scale_up_waiting: self.scale_up_waiting,
+            scale_down_after: Duration::from_secs(self.scale_down_after_secs),
+        })
+    }
+}
+
+/// What to do with a time or memory limit outside the configured range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// reject the request with a field-level validation error
+    Reject,
+    /// silently clamp the value into the configured range
+    Clamp,
+}
+
+impl FromStr for LimitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(LimitPolicy::Reject),
+            "clamp" => Ok(LimitPolicy::Clamp),
+            _ => Err(format!("unknown limit policy: {}", s)),
+        }
+    }
+}
+
+/// Server-side bounds of judge requests. Sizes are in bytes.
+#[derive(Debug, Clone)]
+pub struct LimitsConfig {
+    pub policy: LimitPolicy,
+    /// time limit in seconds
+    pub min_time_limit: f64,
+    pub max_time_limit: f64,
+    /// memory limit in kilobytes
+    pub min_memory_limit: f64,
+    pub max_memory_limit: f64,
+    pub max_source_size: usize,
+    pub max_stdin_size: usize,
+    pub max_expected_output_size: usize,
+    /// files a project submission may contain
+    pub max_project_files: usize,
+    /// total size of a project's files, after unpacking an archive
+    pub max_project_size: usize,
+    /// directory levels a project path may have
+    pub max_project_depth: usize,
+    /// output files a request may declare
+    pub max_output_files: usize,
+    /// size cap of one output file, also its default
+    pub max_output_file_size: u64,
+    /// test cases a function judge request may contain
+    pub max_function_cases: usize,
+}
+
+impl LimitsConfig {
+    /// Largest request body that can still hold a submission within the limits.
+    pub fn max_body_size(&self) -> usize {
+        // JSON escaping may double the payload (base64 only adds a third),
+        // plus room for the remaining fields
+        let source_size = self.max_source_size.max(self.max_project_size);
+        (source_size + self.max_stdin_size + self.max_expected_output_size) * 2 + 64 * 1024
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// JSON file of hashed API keys; authentication is disabled when unset
+    pub api_keys_file: Option<String>,
+    /// how often the key file is checked for changes, in seconds
+    pub reload_interval_secs: u64,
+    /// serve the mutating admin and problem routes without a key file
+    pub admin_unauthenticated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    /// executions a single batch may expand to
+    pub max_items: usize,
+    /// request body limit of the batch endpoint, in bytes
+    pub max_body_size: usize,
+    /// how long finished batches stay retrievable, in seconds
+    pub retention_secs: u64,
+}
+
+impl BatchConfig {
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(self.retention_secs)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyConfig {
+    /// how long an `Idempotency-Key` is remembered, in seconds
+    pub retention_secs: u64,
+    /// keys remembered per client; the oldest is forgotten past this
+    pub max_keys_per_client: usize,
+    /// total size of remembered keys and responses; the oldest key of any
+    /// client is forgotten past this
+    pub max_bytes: usize,
+}
+
+impl IdempotencyConfig {
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(self.retention_secs)
+    }
+}
+
+/// Bounds of interactive sessions, in seconds.
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// a session ends once neither the client nor the program did anything
+    /// for this long
+    pub idle_timeout_secs: u64,
+    /// a session ends this long after it started, whatever it is doing
+    pub max_duration_secs: u64,
+}
+
+impl SessionConfig {
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+
+    pub fn max_duration(&self) -> Duration {
+        Duration::from_secs(self.max_duration_secs)
+    }
+}
+
+/// Stored problem packages; disabled unless a store directory is set.
+#[derive(Debug, Clone)]
+pub struct ProblemConfig {
+    pub store_dir: Option<String>,
+    /// packages can be imported from directories below this one
+    pub import_dir: Option<String>,
+    /// tests a package may contain
+    pub max_tests: usize,
+    /// total size of a package's files, in bytes
+    pub max_size: usize,
+    /// time limit in seconds of a checker run
+    pub checker_time_limit: f64,
+    /// memory limit in kilobytes of a checker run
+    pub checker_memory_limit: f64,
+}
+
+impl ProblemConfig {
+    /// Request body limit of uploads, which may carry a whole package.
+    pub fn max_body_size(&self) -> usize {
+        // as for judge requests, JSON escaping may double the payload
+        self.max_size * 2 + 64 * 1024
+    }
+
+    /// Files a package may contain; Polygon packages carry statements,
+    /// solutions and generators next to the tests.
+    pub fn max_files(&self) -> usize {
+        self.max_tests * 2 + 256
+    }
+}
+
+/// Per-client limits; zero disables the corresponding check.
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// token bucket refill rate of judge submissions per second
+    pub requests_per_sec: f64,
+    /// token bucket capacity
+    pub burst: f64,
+    /// concurrent synchronous executions per client
+    pub max_inflight_per_client: usize,
+    /// async jobs per client that are queued or running
+    pub max_queued_per_client: usize,
 }
 
 pub fn load_config() -> Config {
-    let config = Config {
+    let max_concurrent_executions = get_env_or_default("MAX_CONCURRENT_EXECUTIONS", 20);
+
+    Config {
         server: ServerConfig {
             port: get_env_or_default("PORT", 8080),
             host: get_env_or_default("HOST", String::from("0.0.0.0")),
+            metrics_enabled: get_env_or_default("METRICS_ENABLED", true),
+            cors_allowed_origins: env::var("CORS_ALLOWED_ORIGINS")
+                .map(|origins| {
+                    origins
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|origin| !origin.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            shutdown_grace_period_secs: get_env_or_default("SHUTDOWN_GRACE_PERIOD", 30),
         },
         service: ServiceConfig {
             enable_bg_worker_loop: get_env_or_default("ENABLE_BG_WORKER_LOOP", true),
             max_queue_size: get_env_or_default("MAX_QUEUE_SIZE", 100),
-            max_concurrent_executions: get_env_or_default("MAX_CONCURRENT_EXECUTIONS", 20),
+            max_concurrent_executions,
+            async_workers: get_env_or_default("ASYNC_WORKERS", max_concurrent_executions),
+            async_queue_timeout_secs: get_env_or_default("ASYNC_QUEUE_TIMEOUT", 600),
+            priority_shares: PriorityShares {
+                realtime: get_env_or_default("PRIORITY_RESERVED_REALTIME", 0.2),
+                normal: get_env_or_default("PRIORITY_RESERVED_NORMAL", 0.2),
+                bulk: get_env_or_default("PRIORITY_RESERVED_BULK", 0.1),
+            },
+            self_test_on_startup: get_env_or_default("SELF_TEST_ON_STARTUP", true),
+            autoscale: AutoscaleConfig {
+                enabled: get_env_or_default("AUTOSCALE_ENABLED", false),
+                min_sandboxes: get_env_or_default(
+                    "AUTOSCALE_MIN_SANDBOXES",
+                    max_concurrent_executions as usize,
+                ),
+                max_sandboxes: get_env_or_default(
+                    "AUTOSCALE_MAX_SANDBOXES",
+                    get_env_or_default("MAX_PREWARMED_SANDBOXES", 1000),
+                ),
+                step: get_env_or_default("AUTOSCALE_STEP", 4),
+                scale_up_wait_ms: get_env_or_default("AUTOSCALE_SCALE_UP_WAIT_MS", 200),
+                scale_up_waiting: get_env_or_default("AUTOSCALE_SCALE_UP_WAITING", 1),
+                scale_down_after_secs: get_env_or_default("AUTOSCALE_SCALE_DOWN_AFTER", 300),
+            },
+            result_cache: ResultCacheConfig {
+                enabled: get_env_or_default("RESULT_CACHE_ENABLED", false),
+                ttl_secs: get_env_or_default("RESULT_CACHE_TTL", 3600),
+                max_bytes: get_env_or_default("RESULT_CACHE_MAX_BYTES", 256 * 1024 * 1024),
+                dir: env::var("RESULT_CACHE_DIR").ok(),
+            },
+            artifact_cache: ArtifactCacheConfig {
+                enabled: get_env_or_default("ARTIFACT_CACHE_ENABLED", false),
+                dir: get_env_or_default(
+                    "ARTIFACT_CACHE_DIR",
+                    String::from("/var/cache/pecan/artifacts"),
+                ),
+                max_bytes: get_env_or_default("ARTIFACT_CACHE_MAX_BYTES", 1024 * 1024 * 1024),
+            },
+        },
+        limits: LimitsConfig {
+            policy: get_env_or_default("LIMIT_POLICY", LimitPolicy::Reject),
+            min_time_limit: get_env_or_default("MIN_TIME_LIMIT", 0.1),
+            max_time_limit: get_env_or_default("MAX_TIME_LIMIT", 20.0),
+            min_memory_limit: get_env_or_default("MIN_MEMORY_LIMIT", 8192.0),
+            max_memory_limit: get_env_or_default("MAX_MEMORY_LIMIT", 2097152.0),
+            max_source_size: get_env_or_default("MAX_SOURCE_SIZE", 128 * 1024),
+            max_stdin_size: get_env_or_default("MAX_STDIN_SIZE", 8 * 1024 * 1024),
+            max_expected_output_size: get_env_or_default(
+                "MAX_EXPECTED_OUTPUT_SIZE",
+                8 * 1024 * 1024,
+            ),
+            max_project_files: get_env_or_default("MAX_PROJECT_FILES", 64),
+            max_project_size: get_env_or_default("MAX_PROJECT_SIZE", 1024 * 1024),
+            max_project_depth: get_env_or_default("MAX_PROJECT_DEPTH", 8),
+            max_output_files: get_env_or_default("MAX_OUTPUT_FILES", 8),
+            max_output_file_size: get_env_or_default("MAX_OUTPUT_FILE_SIZE", 1024 * 1024),
+            max_function_cases: get_env_or_default("MAX_FUNCTION_CASES", 100),
+        },
+        auth: AuthConfig {
+            api_keys_file: env::var("API_KEYS_FILE").ok(),
+            reload_interval_secs: get_env_or_default("API_KEYS_RELOAD_INTERVAL", 10),
+            admin_unauthenticated: get_env_or_default("ADMIN_UNAUTHENTICATED", false),
+        },
+        rate_limit: RateLimitConfig {
+            requests_per_sec: get_env_or_default("RATE_LIMIT_PER_SEC", 0.0),
+            burst: get_env_or_default("RATE_LIMIT_BURST", 20.0),
+            max_inflight_per_client: get_env_or_default("MAX_INFLIGHT_PER_CLIENT", 0),
+            max_queued_per_client: get_env_or_default("MAX_QUEUED_PER_CLIENT", 0),
         },
-    };
-    config
+        batch: BatchConfig {
+            max_items: get_env_or_default("MAX_BATCH_ITEMS", 10_000),
+            max_body_size: get_env_or_default("MAX_BATCH_BODY_SIZE", 64 * 1024 * 1024),
+            retention_secs: get_env_or_default("BATCH_RETENTION", 3600),
+        },
+        idempotency: IdempotencyConfig {
+            retention_secs: get_env_or_default("IDEMPOTENCY_RETENTION", 86400),
+            max_keys_per_client: get_env_or_default("IDEMPOTENCY_MAX_KEYS_PER_CLIENT", 10_000),
+            max_bytes: get_env_or_default("IDEMPOTENCY_MAX_BYTES", 64 * 1024 * 1024),
+        },
+        session: SessionConfig {
+            idle_timeout_secs: get_env_or_default("SESSION_IDLE_TIMEOUT", 60),
+            max_duration_secs: get_env_or_default("SESSION_MAX_DURATION", 300),
+        },
+        problems: ProblemConfig {
+            store_dir: env::var("PROBLEM_STORE_DIR").ok(),
+            import_dir: env::var("PROBLEM_IMPORT_DIR").ok(),
+            max_tests: get_env_or_default("MAX_PROBLEM_TESTS", 1000),
+            max_size: get_env_or_default("MAX_PROBLEM_SIZE", 256 * 1024 * 1024),
+            checker_time_limit: get_env_or_default("CHECKER_TIME_LIMIT", 10.0),
+            checker_memory_limit: get_env_or_default("CHECKER_MEMORY_LIMIT", 262144.0),
+        },
+    }
 }
 
 #[inline]
 fn get_env_or_default<T: FromStr>(key: &str, default: T) -> T {
     if let Ok(v) = env::var(key) {
-        return v.parse().unwrap_or_else(|_| default);
+        return v.parse().unwrap_or(default);
     }
     default
 }
@@ -0,0 +1,420 @@
+//! Replay of submissions retried with the same `Idempotency-Key`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use http::StatusCode;
+use sha2::{Digest, Sha256};
+
+use crate::api::error::APIError;
+use crate::application::quota::ClientId;
+
+/// Longest accepted `Idempotency-Key`.
+pub const MAX_KEY_LENGTH: usize = 255;
+
+pub type Fingerprint = [u8; 32];
+
+/// Identify a request by its target and body, so that a reused key with a
+/// different payload can be told apart from a genuine retry.
+pub fn fingerprint(method: &str, path: &str, body: &[u8]) -> Fingerprint {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update([0]);
+    hasher.update(path.as_bytes());
+    hasher.update([0]);
+    hasher.update(body);
+    hasher.finalize().into()
+}
+
+/// Response kept for replay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    pub status: StatusCode,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+pub enum Lookup {
+    /// first use of the key; the caller must `complete` or drop the claim
+    Claimed(IdempotencyClaim),
+    Replay(StoredResponse),
+}
+
+enum EntryState {
+    InProgress,
+    Done(StoredResponse),
+}
+
+struct Entry {
+    fingerprint: Fingerprint,
+    created_at: Instant,
+    /// insertion order, which is also the order of expiry
+    seq: u64,
+    /// bytes counted against the global cap: the key, then its response
+    size: usize,
+    state: EntryState,
+}
+
+type EntryKey = (ClientId, String);
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<EntryKey, Entry>,
+    by_age: BTreeMap<u64, EntryKey>,
+    /// keys of each client by age, to evict its oldest one past the cap
+    by_client: HashMap<ClientId, BTreeMap<u64, String>>,
+    next_seq: u64,
+    /// sum of the sizes of all entries
+    size: usize,
+}
+
+impl Entries {
+    fn insert(&mut self, key: EntryKey, fingerprint: Fingerprint, now: Instant) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let size = key.1.len();
+        self.size += size;
+        self.by_age.insert(seq, key.clone());
+        self.by_client
+            .entry(key.0.clone())
+            .or_default()
+            .insert(seq, key.1.clone());
+        self.by_key.insert(
+            key,
+            Entry {
+                fingerprint,
+                created_at: now,
+                seq,
+                size,
+                state: EntryState::InProgress,
+            },
+        );
+        seq
+    }
+
+    /// Forget `key` if it is still the entry inserted as `seq`.
+    fn remove(&mut self, key: &EntryKey, seq: u64) {
+        if self.by_key.get(key).is_none_or(|entry| entry.seq != seq) {
+            return;
+        }
+        if let Some(entry) = self.by_key.remove(key) {
+            self.size -= entry.size;
+        }
+        self.by_age.remove(&seq);
+        if let Some(keys) = self.by_client.get_mut(&key.0) {
+            keys.remove(&seq);
+            if keys.is_empty() {
+                self.by_client.remove(&key.0);
+            }
+        }
+    }
+
+    fn remove_expired(&mut self, now: Instant, retention: Duration) {
+        while let Some((&seq, key)) = self.by_age.first_key_value() {
+            let expired = self
+                .by_key
+                .get(key)
+                .is_none_or(|entry| now.duration_since(entry.created_at) > retention);
+            if !expired {
+                break;
+            }
+            let key = key.clone();
+            self.remove(&key, seq);
+        }
+    }
+
+    /// Make room for one more key of `client`, evicting its oldest ones.
+    fn evict_over(&mut self, client: &ClientId, max_keys: usize) {
+        while let Some((seq, key)) = self
+            .by_client
+            .get(client)
+            .filter(|keys| keys.len() >= max_keys.max(1))
+            .and_then(|keys| keys.first_key_value())
+            .map(|(&seq, key)| (seq, key.clone()))
+        {
+            self.remove(&(client.clone(), key), seq);
+        }
+    }
+
+    /// Evict the oldest keys of any client until at most `max_bytes` are kept.
+    fn evict_bytes(&mut self, max_bytes: usize) {
+        while self.size > max_bytes {
+            let Some((&seq, key)) = self.by_age.first_key_value() else {
+                break;
+            };
+            let key = key.clone();
+            self.remove(&key, seq);
+        }
+    }
+}
+
+pub struct IdempotencyStore {
+    entries: Mutex<Entries>,
+    retention: Duration,
+    max_keys_per_client: usize,
+    max_bytes: usize,
+}
+
+/// Reservation of a key while its request runs. Dropping it without
+/// `complete` forgets the key, so a failed or abandoned request can be retried.
+pub struct IdempotencyClaim {
+    store: Arc<IdempotencyStore>,
+    key: Option<(EntryKey, u64)>,
+}
+
+impl IdempotencyClaim {
+    pub fn complete(mut self, response: StoredResponse) {
+        let Some((key, seq)) = self.key.take() else {
+            return;
+        };
+        let mut entries = self.store.lock();
+        let Some(entry) = entries.by_key.get_mut(&key) else {
+            return;
+        };
+        // the key may have been evicted and claimed again meanwhile
+        if entry.seq != seq {
+            return;
+        }
+        let size = response_size(&response);
+        if entry.size + size > self.store.max_bytes {
+            // it could never be kept, so a retry runs the request again
+            entries.remove(&key, seq);
+            return;
+        }
+        entry.state = EntryState::Done(response);
+        entry.size += size;
+        entries.size += size;
+        entries.evict_bytes(self.store.max_bytes);
+    }
+}
+
+impl Drop for IdempotencyClaim {
+    fn drop(&mut self) {
+        if let Some((key, seq)) = self.key.take() {
+            self.store.lock().remove(&key, seq);
+        }
+    }
+}
+
+fn response_size(response: &StoredResponse) -> usize {
+    response.body.len() + response.content_type.as_ref().map_or(0, String::len)
+}
+
+impl IdempotencyStore {
+    /// Remember keys for `retention`, at most `max_keys_per_client` keys of
+    /// one client and at most `max_bytes` of keys and responses in all, the
+    /// oldest being forgotten first.
+    pub fn new(retention: Duration, max_keys_per_client: usize, max_bytes: usize) -> Self {
+        Self {
+            entries: Mutex::new(Entries::default()),
+            retention,
+            max_keys_per_client,
+            max_bytes,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Claim `key` for a new request, or return the response of the request
+    /// that used it before.
+    pub fn begin(
+        self: &Arc<Self>,
+        client: &ClientId,
+        key: &str,
+        fingerprint: Fingerprint,
+    ) -> Result<Lookup, APIError> {
+        let now = Instant::now();
+        let entry_key = (client.clone(), key.to_string());
+
+        let mut entries = self.lock();
+        entries.remove_expired(now, self.retention);
+
+        if let Some(entry) = entries.by_key.get(&entry_key) {
+            if entry.fingerprint != fingerprint {
+                return Err(APIError::Conflict(
+                    "Idempotency-Key was already used with a different request".to_string(),
+                ));
+            }
+            return match &entry.state {
+                EntryState::InProgress => Err(APIError::Conflict(
+                    "A request with this Idempotency-Key is still in progress".to_string(),
+                )),
+                EntryState::Done(response) => Ok(Lookup::Replay(response.clone())),
+            };
+        }
+
+        entries.evict_over(client, self.max_keys_per_client);
+        let seq = entries.insert(entry_key.clone(), fingerprint, now);
+        entries.evict_bytes(self.max_bytes);
+        Ok(Lookup::Claimed(IdempotencyClaim {
+            store: Arc::clone(self),
+            key: Some((entry_key, seq)),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use http::StatusCode;
+
+    use super::{IdempotencyStore, Lookup, StoredResponse, fingerprint};
+    use crate::api::error::APIError;
+    use crate::application::quota::ClientId;
+
+    fn response() -> StoredResponse {
+        StoredResponse {
+            status: StatusCode::OK,
+            content_type: Some("application/json".to_string()),
+            body: b"{\"request_id\":\"x\"}".to_vec(),
+        }
+    }
+
+    #[test]
+    fn completed_requests_are_replayed_and_conflicts_rejected() {
+        let store = Arc::new(IdempotencyStore::new(Duration::from_secs(60), 100, 1 << 20));
+        let client = ClientId::Ip("127.0.0.1".to_string());
+        let body = fingerprint("POST", "/v1/judge/judge-single-async", b"{}");
+
+        let Ok(Lookup::Claimed(claim)) = store.begin(&client, "k1", body) else {
+            panic!("first use must claim the key");
+        };
+        assert!(matches!(
+            store.begin(&client, "k1", body),
+            Err(APIError::Conflict(_))
+        ));
+        claim.complete(response());
+
+        assert!(matches!(
+            store.begin(&client, "k1", body),
+            Ok(Lookup::Replay(stored)) if stored == response()
+        ));
+
+        let other = fingerprint("POST", "/v1/judge/judge-single-async", b"{\"a\":1}");
+        assert!(matches!(
+            store.begin(&client, "k1", other),
+            Err(APIError::Conflict(_))
+        ));
+        // keys are scoped per client
+        assert!(matches!(
+            store.begin(&ClientId::Ip("10.0.0.1".to_string()), "k1", other),
+            Ok(Lookup::Claimed(_))
+        ));
+    }
+
+    #[test]
+    fn dropped_claims_release_the_key() {
+        let store = Arc::new(IdempotencyStore::new(Duration::from_secs(60), 100, 1 << 20));
+        let client = ClientId::ApiKey("ci".to_string());
+        let body = fingerprint("POST", "/v1/judge/judge-single", b"{}");
+
+        drop(store.begin(&client, "k1", body));
+
+        assert!(matches!(
+            store.begin(&client, "k1", body),
+            Ok(Lookup::Claimed(_))
+        ));
+    }
+
+    #[test]
+    fn oldest_keys_of_a_client_are_evicted_past_the_cap() {
+        let store = Arc::new(IdempotencyStore::new(Duration::from_secs(60), 2, 1 << 20));
+        let client = ClientId::ApiKey("ci".to_string());
+        let other = ClientId::ApiKey("other".to_string());
+        let body = fingerprint("POST", "/v1/judge/judge-single", b"{}");
+
+        for key in ["k1", "k2", "k3"] {
+            let Ok(Lookup::Claimed(claim)) = store.begin(&client, key, body) else {
+                panic!("first use must claim the key");
+            };
+            claim.complete(response());
+        }
+        let Ok(Lookup::Claimed(claim)) = store.begin(&other, "k1", body) else {
+            panic!("other clients keep their own keys");
+        };
+        claim.complete(response());
+
+        assert!(matches!(
+            store.begin(&client, "k3", body),
+            Ok(Lookup::Replay(_))
+        ));
+        assert!(matches!(
+            store.begin(&other, "k1", body),
+            Ok(Lookup::Replay(_))
+        ));
+        assert!(matches!(
+            store.begin(&client, "k1", body),
+            Ok(Lookup::Claimed(_))
+        ));
+    }
+
+    #[test]
+    fn oldest_keys_of_any_client_are_evicted_past_the_byte_cap() {
+        // room for the responses of two keys
+        let size = "k1".len() + super::response_size(&response());
+        let store = Arc::new(IdempotencyStore::new(
+            Duration::from_secs(60),
+            100,
+            2 * size,
+        ));
+        let body = fingerprint("POST", "/v1/judge/judge-single", b"{}");
+        let clients: Vec<_> = ["10.0.0.1", "10.0.0.2", "10.0.0.3"]
+            .into_iter()
+            .map(|ip| ClientId::Ip(ip.to_string()))
+            .collect();
+
+        for client in &clients {
+            let Ok(Lookup::Claimed(claim)) = store.begin(client, "k1", body) else {
+                panic!("first use must claim the key");
+            };
+            claim.complete(response());
+        }
+        assert_eq!(store.lock().size, 2 * size);
+        assert!(matches!(
+            store.begin(&clients[2], "k1", body),
+            Ok(Lookup::Replay(_))
+        ));
+        assert!(matches!(
+            store.begin(&clients[0], "k1", body),
+            Ok(Lookup::Claimed(_))
+        ));
+
+        // a response larger than the cap is not kept at all
+        let store = Arc::new(IdempotencyStore::new(
+            Duration::from_secs(60),
+            100,
+            size - 1,
+        ));
+        let Ok(Lookup::Claimed(claim)) = store.begin(&clients[0], "k1", body) else {
+            panic!("first use must claim the key");
+        };
+        claim.complete(response());
+        assert_eq!(store.lock().size, 0);
+        assert!(matches!(
+            store.begin(&clients[0], "k1", body),
+            Ok(Lookup::Claimed(_))
+        ));
+    }
+
+    #[test]
+    fn expired_keys_are_forgotten() {
+        let store = Arc::new(IdempotencyStore::new(Duration::ZERO, 100, 1 << 20));
+        let client = ClientId::ApiKey("ci".to_string());
+        let body = fingerprint("POST", "/v1/judge/judge-single", b"{}");
+
+        let Ok(Lookup::Claimed(claim)) = store.begin(&client, "k1", body) else {
+            panic!("first use must claim the key");
+        };
+        claim.complete(response());
+        std::thread::sleep(Duration::from_millis(5));
+
+        let again = store.begin(&client, "k1", body);
+        assert!(matches!(again, Ok(Lookup::Claimed(_))));
+        assert_eq!(store.lock().by_age.len(), 1);
+    }
+}
@@ -1,4 +1,15 @@
 pub mod app;
+pub mod auth;
+pub mod batch;
+pub mod checker;
 pub mod config;
+pub mod idempotency;
+pub mod problem_package;
+pub mod problem_store;
+pub mod project;
+pub mod quota;
+pub mod scoring;
 pub mod service;
 pub mod state;
+pub mod telemetry;
+pub mod validation;
@@ -0,0 +1,512 @@
+//! Reading of problem packages in the formats accepted on upload and import.
+//!
+//! A package is a tree of files in one of two layouts:
+//! - Polygon-style, described by a `problem.xml` whose `tests` testset gives
+//!   the limits, the number of tests and the paths of their input and answer
+//!   files, along with the checker and interactor sources. Its test groups
+//!   become subtasks.
+//! - A `tests/` directory of `NN.in` files and their `NN.out` (or `NN.ans`)
+//!   answers, with an optional `problem.json` holding `time_limit`,
+//!   `memory_limit`, the `path` and `language` of a `checker` and an
+//!   `interactor`, a built-in `comparator` and `subtasks`.
+//!
+//! Headers next to a checker or interactor source, such as `testlib.h`, are
+//! kept as its support files.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::domain::models::problem::{Comparator, Subtask, SubtaskScoring};
+
+/// Files of a package by their `/`-separated relative path.
+pub type PackageFiles = BTreeMap<String, Vec<u8>>;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PackageError {
+    #[error("{0} is missing")]
+    MissingFile(String),
+    #[error("{0} is malformed: {1}")]
+    Malformed(String, String),
+    #[error("has no tests")]
+    NoTests,
+    #[error("{0} has no matching answer file")]
+    MissingAnswer(String),
+    #[error("language of {0} is not supported: {1}")]
+    UnknownLanguage(String, String),
+    #[error("must contain at most {0} files")]
+    TooManyFiles(usize),
+    #[error("must be at most {0} bytes")]
+    TooLarge(usize),
+    #[error("cannot be read: {0}")]
+    Unreadable(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageTest {
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageProgram {
+    pub language: String,
+    pub code: Vec<u8>,
+    /// files the source includes, by their path relative to it
+    pub files: PackageFiles,
+}
+
+/// Contents of a problem package, before it is validated and stored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Package {
+    pub time_limit: Option<f64>,
+    pub memory_limit: Option<f64>,
+    pub tests: Vec<PackageTest>,
+    pub checker: Option<PackageProgram>,
+    pub comparator: Option<Comparator>,
+    pub interactor: Option<PackageProgram>,
+    pub subtasks: Vec<Subtask>,
+}
+
+/// `problem.json` of the simple layout.
+#[derive(Deserialize)]
+struct SimpleManifest {
+    time_limit: Option<f64>,
+    memory_limit: Option<f64>,
+    checker: Option<SimpleProgram>,
+    comparator: Option<Comparator>,
+    interactor: Option<SimpleProgram>,
+    #[serde(default)]
+    subtasks: Vec<Subtask>,
+}
+
+#[derive(Deserialize)]
+struct SimpleProgram {
+    path: String,
+    language: String,
+}
+
+/// Read a package laid out in either format. A single directory wrapping the
+/// whole package, as archivers often produce, is looked through.
+pub fn read_package(mut files: PackageFiles) -> Result<Package, PackageError> {
+    if !files.contains_key("problem.xml") && !files.keys().any(|path| path.starts_with("tests/")) {
+        files = strip_common_root(files);
+    }
+    match files.get("problem.xml") {
+        Some(xml) => read_polygon(xml, &files),
+        None => read_simple(&files),
+    }
+}
+
+fn strip_common_root(files: PackageFiles) -> PackageFiles {
+    let root = match files.keys().next().and_then(|path| path.split_once('/')) {
+        Some((root, _)) => format!("{}/", root),
+        None => return files,
+    };
+    if !files.keys().all(|path| path.starts_with(&root)) {
+        return files;
+    }
+    files
+        .into_iter()
+        .map(|(path, content)| (path[root.len()..].to_string(), content))
+        .collect()
+}
+
+fn take(files: &PackageFiles, path: &str) -> Result<Vec<u8>, PackageError> {
+    files
+        .get(path)
+        .cloned()
+        .ok_or_else(|| PackageError::MissingFile(path.to_string()))
+}
+
+/// Program whose source is at `path`, along with the headers beside it.
+fn take_program(
+    files: &PackageFiles,
+    path: &str,
+    language: &str,
+) -> Result<PackageProgram, PackageError> {
+    let dir = path.rfind('/').map_or("", |end| &path[..=end]);
+    let headers = files
+        .iter()
+        .filter_map(|(other, content)| {
+            let name = other.strip_prefix(dir)?;
+            let header = !name.contains('/') && (name.ends_with(".h") || name.ends_with(".hpp"));
+            header.then(|| (name.to_string(), content.clone()))
+        })
+        .collect();
+    Ok(PackageProgram {
+        language: language.to_string(),
+        code: take(files, path)?,
+        files: headers,
+    })
+}
+
+fn read_polygon(xml: &[u8], files: &PackageFiles) -> Result<Package, PackageError> {
+    let malformed = |e: &dyn std::fmt::Display| {
+        PackageError::Malformed("problem.xml".to_string(), e.to_string())
+    };
+    let xml = std::str::from_utf8(xml).map_err(|e| malformed(&e))?;
+    let document = roxmltree::Document::parse(xml).map_err(|e| malformed(&e))?;
+    let testset = document
+        .descendants()
+        .find(|node| node.has_tag_name("testset") && node.attribute("name") == Some("tests"))
+        .ok_or_else(|| malformed(&"no tests testset"))?;
+    let text = |name: &str| {
+        testset
+            .children()
+            .find(|node| node.has_tag_name(name))
+            .and_then(|node| node.text())
+            .map(str::trim)
+    };
+    let number = |name: &str| -> Result<Option<f64>, PackageError> {
+        text(name)
+            .map(|value| value.parse::<f64>().map_err(|e| malformed(&e)))
+            .transpose()
+    };
+
+    let count = number("test-count")?.unwrap_or_default() as usize;
+    let input_pattern =
+        text("input-path-pattern").ok_or_else(|| malformed(&"no input-path-pattern"))?;
+    let answer_pattern =
+        text("answer-path-pattern").ok_or_else(|| malformed(&"no answer-path-pattern"))?;
+    // a missing file ends an overstated count before it is allocated
+    let mut tests = Vec::new();
+    for i in 1..=count {
+        tests.push(PackageTest {
+            input: take(files, &format_path(input_pattern, i))?,
+            output: take(files, &format_path(answer_pattern, i))?,
+        });
+    }
+    if tests.is_empty() {
+        return Err(PackageError::NoTests);
+    }
+
+    let program = |asset: &str| -> Result<Option<PackageProgram>, PackageError> {
+        let Some(source) = document
+            .descendants()
+            .find(|node| node.has_tag_name(asset))
+            .and_then(|node| node.children().find(|node| node.has_tag_name("source")))
+        else {
+            return Ok(None);
+        };
+        let path = source
+            .attribute("path")
+            .ok_or_else(|| malformed(&format!("{} source has no path", asset)))?;
+        let kind = source.attribute("type").unwrap_or_default();
+        let language = polygon_language(kind)
+            .ok_or_else(|| PackageError::UnknownLanguage(path.to_string(), kind.to_string()))?;
+        take_program(files, path, language).map(Some)
+    };
+
+    Ok(Package {
+        // Polygon gives milliseconds and bytes
+        time_limit: number("time-limit")?.map(|ms| ms / 1000.0),
+        memory_limit: number("memory-limit")?.map(|bytes| bytes / 1024.0),
+        tests,
+        checker: program("checker")?,
+        comparator: None,
+        interactor: program("interactor")?,
+        subtasks: polygon_subtasks(testset).map_err(|e| malformed(&e))?,
+    })
+}
+
+/// Subtasks from the groups of a Polygon testset. Groups scored as a whole
+/// become `min` subtasks; groups scored per test become `sum` subtasks when
+/// their tests are worth the same, and `proportional` ones otherwise.
+fn polygon_subtasks(testset: roxmltree::Node) -> Result<Vec<Subtask>, String> {
+    let Some(groups) = testset.children().find(|node| node.has_tag_name("groups")) else {
+        return Ok(Vec::new());
+    };
+    let points = |node: roxmltree::Node| -> Option<f64> {
+        node.attribute("points")
+            .and_then(|points| points.parse().ok())
+    };
+    let tests: Vec<(Option<&str>, f64)> = testset
+        .children()
+        .find(|node| node.has_tag_name("tests"))
+        .map(|tests| {
+            tests
+                .children()
+                .filter(|node| node.has_tag_name("test"))
+                .map(|test| (test.attribute("group"), points(test).unwrap_or(0.0)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut names = Vec::new();
+    let mut subtasks = Vec::new();
+    for group in groups.children().filter(|node| node.has_tag_name("group")) {
+        let name = group.attribute("name").ok_or("a group has no name")?;
+        let members: Vec<usize> = (0..tests.len())
+            .filter(|&i| tests[i].0 == Some(name))
+            .collect();
+        let test_points: Vec<f64> = members.iter().map(|&i| tests[i].1).collect();
+        let depends_on = group
+            .descendants()
+            .filter(|node| node.has_tag_name("dependency"))
+            .map(|dependency| {
+                let required = dependency.attribute("group").unwrap_or_default();
+                names
+                    .iter()
+                    .position(|name| *name == required)
+                    .ok_or_else(|| {
+                        format!(
+                            "group {} depends on {}, which is not listed before it",
+                            name, required
+                        )
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        let (scoring, points) = match group.attribute("points-policy") {
+            Some("each-test") => match test_points.first() {
+                Some(&first) if test_points.iter().all(|&p| p == first) => {
+                    (SubtaskScoring::Sum, first)
+                }
+                _ => (SubtaskScoring::Proportional, test_points.iter().sum()),
+            },
+            _ => (
+                SubtaskScoring::Min,
+                points(group).unwrap_or_else(|| test_points.iter().sum()),
+            ),
+        };
+        names.push(name);
+        subtasks.push(Subtask {
+            name: Some(name.to_string()),
+            points,
+            scoring,
+            tests: members,
+            depends_on,
+        });
+    }
+    Ok(subtasks)
+}
+
+/// Substitute the test number into a Polygon path pattern such as `tests/%02d`.
+fn format_path(pattern: &str, number: usize) -> String {
+    let Some(start) = pattern.find('%') else {
+        return pattern.to_string();
+    };
+    let Some(end) = pattern[start..].find('d').map(|end| start + end) else {
+        return pattern.to_string();
+    };
+    let width: usize = pattern[start + 1..end].parse().unwrap_or(0);
+    format!(
+        "{}{:0width$}{}",
+        &pattern[..start],
+        number,
+        &pattern[end + 1..],
+        width = width
+    )
+}
+
+/// Language of a Polygon source type such as `cpp.g++17` or `python.3`.
+fn polygon_language(kind: &str) -> Option<&'static str> {
+    let family = kind.split('.').next().unwrap_or_default();
+    match family {
+        "c" => Some("c"),
+        "cpp" => Some("cpp"),
+        "java" | "java8" | "java11" | "java21" => Some("java"),
+        "kotlin" => Some("kotlin"),
+        "go" => Some("go"),
+        "rust" => Some("rust"),
+        "python" | "py" => Some("python"),
+        "js" | "node" => Some("node"),
+        _ => None,
+    }
+}
+
+fn read_simple(files: &PackageFiles) -> Result<Package, PackageError> {
+    let manifest = match files.get("problem.json") {
+        Some(json) => Some(
+            serde_json::from_slice::<SimpleManifest>(json)
+                .map_err(|e| PackageError::Malformed("problem.json".to_string(), e.to_string()))?,
+        ),
+        None => None,
+    };
+
+    let mut inputs: Vec<(&str, &str)> = files
+        .keys()
+        .filter_map(|path| {
+            let stem = path.strip_prefix("tests/")?.strip_suffix(".in")?;
+            (!stem.contains('/')).then_some((path.as_str(), stem))
+        })
+        .collect();
+    // numbered tests in numeric order, so that `10` follows `9`
+    inputs.sort_by_key(|(_, stem)| (stem.parse::<u64>().map_or((1, 0), |n| (0, n)), *stem));
+    let mut tests = Vec::with_capacity(inputs.len());
+    for (path, stem) in inputs {
+        let output = ["out", "ans"]
+            .iter()
+            .find_map(|ext| files.get(&format!("tests/{}.{}", stem, ext)))
+            .ok_or_else(|| PackageError::MissingAnswer(path.to_string()))?;
+        tests.push(PackageTest {
+            input: files[path].clone(),
+            output: output.clone(),
+        });
+    }
+    if tests.is_empty() {
+        return Err(PackageError::NoTests);
+    }
+
+    let program =
+        |program: Option<&SimpleProgram>| -> Result<Option<PackageProgram>, PackageError> {
+            program
+                .map(|program| take_program(files, &program.path, &program.language))
+                .transpose()
+        };
+    Ok(Package {
+        time_limit: manifest.as_ref().and_then(|m| m.time_limit),
+        memory_limit: manifest.as_ref().and_then(|m| m.memory_limit),
+        tests,
+        checker: program(manifest.as_ref().and_then(|m| m.checker.as_ref()))?,
+        comparator: manifest.as_ref().and_then(|m| m.comparator.clone()),
+        interactor: program(manifest.as_ref().and_then(|m| m.interactor.as_ref()))?,
+        subtasks: manifest.map(|m| m.subtasks).unwrap_or_default(),
+    })
+}
+
+/// Read the regular files below `dir`, at most `max_files` of `max_size`
+/// bytes in total. Links and special files are skipped.
+pub async fn read_dir(
+    dir: &Path,
+    max_files: usize,
+    max_size: usize,
+) -> Result<PackageFiles, PackageError> {
+    let unreadable = |e: std::io::Error| PackageError::Unreadable(e.to_string());
+    let mut files = PackageFiles::new();
+    let mut size = 0;
+    let mut pending = vec![String::new()];
+    while let Some(prefix) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(dir.join(&prefix))
+            .await
+            .map_err(unreadable)?;
+        while let Some(entry) = entries.next_entry().await.map_err(unreadable)? {
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let path = format!("{}{}", prefix, name);
+            let file_type = entry.file_type().await.map_err(unreadable)?;
+            if file_type.is_dir() {
+                pending.push(format!("{}/", path));
+            } else if file_type.is_file() {
+                if files.len() == max_files {
+                    return Err(PackageError::TooManyFiles(max_files));
+                }
+                let len = entry.metadata().await.map_err(unreadable)?.len() as usize;
+                size += len;
+                if size > max_size {
+                    return Err(PackageError::TooLarge(max_size));
+                }
+                let content = tokio::fs::read(entry.path()).await.map_err(unreadable)?;
+                files.insert(path, content);
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PackageError, PackageFiles, format_path, read_package};
+    use crate::domain::models::problem::SubtaskScoring;
+
+    fn files(entries: &[(&str, &str)]) -> PackageFiles {
+        entries
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_numbered_tests_in_numeric_order() {
+        let package = read_package(files(&[
+            (
+                "sum/problem.json",
+                r#"{"time_limit": 2.0, "checker": {"path": "check.cpp", "language": "cpp"}}"#,
+            ),
+            ("sum/check.cpp", "#include \"testlib.h\"\nint main() {}"),
+            ("sum/testlib.h", "#pragma once"),
+            ("sum/tests/10.in", "10"),
+            ("sum/tests/10.ans", "20"),
+            ("sum/tests/9.in", "9"),
+            ("sum/tests/9.out", "18"),
+        ]))
+        .unwrap();
+        assert_eq!(package.time_limit, Some(2.0));
+        assert_eq!(package.memory_limit, None);
+        let inputs: Vec<_> = package.tests.iter().map(|t| t.input.as_slice()).collect();
+        assert_eq!(inputs, [b"9".as_slice(), b"10"]);
+        let checker = package.checker.unwrap();
+        assert_eq!(checker.language, "cpp");
+        assert_eq!(checker.files.keys().collect::<Vec<_>>(), ["testlib.h"]);
+
+        assert_eq!(
+            read_package(files(&[("tests/1.in", "1")])),
+            Err(PackageError::MissingAnswer("tests/1.in".to_string()))
+        );
+    }
+
+    #[test]
+    fn reads_polygon_packages() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<problem revision="3" short-name="aplusb">
+  <judging input-file="" output-file="">
+    <testset name="tests">
+      <time-limit>1500</time-limit>
+      <memory-limit>268435456</memory-limit>
+      <test-count>2</test-count>
+      <input-path-pattern>tests/%02d</input-path-pattern>
+      <answer-path-pattern>tests/%02d.a</answer-path-pattern>
+      <tests>
+        <test method="manual" group="samples" points="0"/>
+        <test method="generated" group="all" points="5"/>
+      </tests>
+      <groups>
+        <group name="samples" points="0" points-policy="complete-group"/>
+        <group name="all" points-policy="each-test">
+          <dependencies>
+            <dependency group="samples"/>
+          </dependencies>
+        </group>
+      </groups>
+    </testset>
+  </judging>
+  <assets>
+    <checker type="testlib">
+      <source path="files/check.cpp" type="cpp.g++17"/>
+    </checker>
+  </assets>
+</problem>"#;
+        let package = read_package(files(&[
+            ("problem.xml", xml),
+            ("files/check.cpp", "int main() {}"),
+            ("tests/01", "1 2"),
+            ("tests/01.a", "3"),
+            ("tests/02", "2 2"),
+            ("tests/02.a", "4"),
+        ]))
+        .unwrap();
+        assert_eq!(package.time_limit, Some(1.5));
+        assert_eq!(package.memory_limit, Some(262144.0));
+        assert_eq!(package.tests.len(), 2);
+        assert_eq!(package.tests[1].output, b"4");
+        assert_eq!(package.checker.unwrap().language, "cpp");
+        assert_eq!(package.interactor, None);
+        let subtasks: Vec<_> = package
+            .subtasks
+            .iter()
+            .map(|s| (s.scoring, s.points, s.tests.clone(), s.depends_on.clone()))
+            .collect();
+        assert_eq!(
+            subtasks,
+            [
+                (SubtaskScoring::Min, 0.0, vec![0], vec![]),
+                (SubtaskScoring::Sum, 5.0, vec![1], vec![0])
+            ]
+        );
+
+        assert_eq!(format_path("tests/%03d.a", 7), "tests/007.a");
+    }
+}
@@ -0,0 +1,346 @@
+//! Disk store of problem packages. Every upload becomes a new version that
+//! is never changed afterwards, laid out under `<dir>/<id>/<version>/` as
+//! `manifest.json`, `tests/NNN.in` and `tests/NNN.out`, and the `checker` and
+//! `interactor` sources when the package has them, with their support files
+//! below `checker.files/` and `interactor.files/`.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::application::problem_package::{
+    Package, PackageFiles, PackageProgram, PackageTest, read_dir,
+};
+use crate::domain::models::problem::{Comparator, Subtask};
+
+const MANIFEST_FILE: &str = "manifest.json";
+pub const CHECKER_FILE: &str = "checker";
+pub const INTERACTOR_FILE: &str = "interactor";
+const MAX_ID_LEN: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum ProblemStoreError {
+    #[error("problem id must be 1 to {MAX_ID_LEN} letters, digits, '-' or '_'")]
+    InvalidId,
+    #[error("problem {0}")]
+    NotFound(String),
+    #[error("problem store: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Description of one stored version; the test data stays on disk.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProblemManifest {
+    pub id: String,
+    pub version: u32,
+    /// time limit in seconds of every test
+    pub time_limit: f64,
+    /// memory limit in kilobytes of every test
+    pub memory_limit: f64,
+    /// number of tests
+    pub tests: usize,
+    /// language of the checker source
+    pub checker: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<Comparator>,
+    /// language of the interactor source
+    pub interactor: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtasks: Vec<Subtask>,
+    /// UNIX epoch seconds
+    pub created_at: u64,
+}
+
+pub struct ProblemStore {
+    dir: PathBuf,
+    /// serializes uploads so that concurrent ones get distinct versions
+    writing: Mutex<()>,
+}
+
+fn test_path(index: usize, ext: &str) -> String {
+    format!("tests/{:03}.{}", index + 1, ext)
+}
+
+impl ProblemStore {
+    pub async fn open(dir: PathBuf) -> io::Result<Self> {
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            dir,
+            writing: Mutex::new(()),
+        })
+    }
+
+    /// Directory of problem `id`; ids are restricted so that they cannot
+    /// name anything outside the store.
+    fn problem_dir(&self, id: &str) -> Result<PathBuf, ProblemStoreError> {
+        let valid = !id.is_empty()
+            && id.len() <= MAX_ID_LEN
+            && id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if !valid {
+            return Err(ProblemStoreError::InvalidId);
+        }
+        Ok(self.dir.join(id))
+    }
+
+    /// Store `package` as the next version of problem `id`.
+    pub async fn save(
+        &self,
+        id: &str,
+        package: &Package,
+        time_limit: f64,
+        memory_limit: f64,
+    ) -> Result<ProblemManifest, ProblemStoreError> {
+        let problem_dir = self.problem_dir(id)?;
+        let _writing = self.writing.lock().await;
+        let version = match self.versions(id).await {
+            Ok(versions) => versions.last().map_or(1, |last| last + 1),
+            Err(ProblemStoreError::NotFound(_)) => 1,
+            Err(e) => return Err(e),
+        };
+        let manifest = ProblemManifest {
+            id: id.to_string(),
+            version,
+            time_limit,
+            memory_limit,
+            tests: package.tests.len(),
+            checker: package.checker.as_ref().map(|p| p.language.clone()),
+            comparator: package.comparator.clone(),
+            interactor: package.interactor.as_ref().map(|p| p.language.clone()),
+            subtasks: package.subtasks.clone(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        };
+
+        // written aside and renamed, so readers never see a partial version
+        let staging = problem_dir.join(format!(".staging-{}", Uuid::new_v4()));
+        let written = write_version(&staging, &manifest, package).await;
+        let renamed = match written {
+            Ok(()) => tokio::fs::rename(&staging, problem_dir.join(version.to_string())).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = renamed {
+            let _ = tokio::fs::remove_dir_all(&staging).await;
+            return Err(e.into());
+        }
+        Ok(manifest)
+    }
+
+    /// Stored versions of problem `id`, oldest first.
+    pub async fn versions(&self, id: &str) -> Result<Vec<u32>, ProblemStoreError> {
+        let problem_dir = self.problem_dir(id)?;
+        let mut entries = match tokio::fs::read_dir(&problem_dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(ProblemStoreError::NotFound(id.to_string()));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut versions = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(version) = entry.file_name().to_str().and_then(|n| n.parse().ok()) {
+                versions.push(version);
+            }
+        }
+        if versions.is_empty() {
+            return Err(ProblemStoreError::NotFound(id.to_string()));
+        }
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    /// Manifest of `version` of problem `id`, or of its latest version.
+    pub async fn get(
+        &self,
+        id: &str,
+        version: Option<u32>,
+    ) -> Result<ProblemManifest, ProblemStoreError> {
+        let version = match version {
+            Some(version) => version,
+            None => *self.versions(id).await?.last().unwrap_or(&0),
+        };
+        let path = self
+            .problem_dir(id)?
+            .join(version.to_string())
+            .join(MANIFEST_FILE);
+        let manifest = match tokio::fs::read(&path).await {
+            Ok(manifest) => manifest,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(ProblemStoreError::NotFound(format!(
+                    "{} version {}",
+                    id, version
+                )));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_slice(&manifest)
+            .map_err(|e| ProblemStoreError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+
+    fn version_dir(&self, manifest: &ProblemManifest) -> Result<PathBuf, ProblemStoreError> {
+        Ok(self
+            .problem_dir(&manifest.id)?
+            .join(manifest.version.to_string()))
+    }
+
+    /// Input and expected output of test `index`, counted from zero.
+    pub async fn read_test(
+        &self,
+        manifest: &ProblemManifest,
+        index: usize,
+    ) -> Result<PackageTest, ProblemStoreError> {
+        let dir = self.version_dir(manifest)?;
+        Ok(PackageTest {
+            input: tokio::fs::read(dir.join(test_path(index, "in"))).await?,
+            output: tokio::fs::read(dir.join(test_path(index, "out"))).await?,
+        })
+    }
+
+    /// Source and support files of the program stored as `name`, one of
+    /// [`CHECKER_FILE`] and [`INTERACTOR_FILE`], in `language`.
+    pub async fn read_program(
+        &self,
+        manifest: &ProblemManifest,
+        name: &str,
+        language: &str,
+    ) -> Result<PackageProgram, ProblemStoreError> {
+        let dir = self.version_dir(manifest)?;
+        let support_dir = dir.join(format!("{}.files", name));
+        let files = if tokio::fs::try_exists(&support_dir).await? {
+            // the package was validated when stored, so no limit applies
+            read_dir(&support_dir, usize::MAX, usize::MAX)
+                .await
+                .map_err(io::Error::other)?
+        } else {
+            PackageFiles::new()
+        };
+        Ok(PackageProgram {
+            language: language.to_string(),
+            code: tokio::fs::read(dir.join(name)).await?,
+            files,
+        })
+    }
+
+    /// Delete every version of problem `id`.
+    pub async fn remove(&self, id: &str) -> Result<(), ProblemStoreError> {
+        let problem_dir = self.problem_dir(id)?;
+        let _writing = self.writing.lock().await;
+        match tokio::fs::remove_dir_all(&problem_dir).await {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Err(ProblemStoreError::NotFound(id.to_string()))
+            }
+            removed => removed.map_err(Into::into),
+        }
+    }
+}
+
+async fn write_version(
+    dir: &Path,
+    manifest: &ProblemManifest,
+    package: &Package,
+) -> io::Result<()> {
+    tokio::fs::create_dir_all(dir.join("tests")).await?;
+    for (i, test) in package.tests.iter().enumerate() {
+        tokio::fs::write(dir.join(test_path(i, "in")), &test.input).await?;
+        tokio::fs::write(dir.join(test_path(i, "out")), &test.output).await?;
+    }
+    for (name, program) in [
+        (CHECKER_FILE, &package.checker),
+        (INTERACTOR_FILE, &package.interactor),
+    ] {
+        let Some(program) = program else {
+            continue;
+        };
+        tokio::fs::write(dir.join(name), &program.code).await?;
+        let support_dir = dir.join(format!("{}.files", name));
+        for (path, content) in &program.files {
+            // paths were validated to stay below the program's directory
+            let path = support_dir.join(path);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(path, content).await?;
+        }
+    }
+    let manifest = serde_json::to_vec_pretty(manifest).map_err(io::Error::other)?;
+    tokio::fs::write(dir.join(MANIFEST_FILE), manifest).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use super::{CHECKER_FILE, ProblemStore, ProblemStoreError};
+    use crate::application::problem_package::{Package, PackageProgram, PackageTest};
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("pecan-problems-{}", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn uploads_become_new_versions() {
+        let dir = temp_dir();
+        let store = ProblemStore::open(dir.clone()).await.unwrap();
+        let package = |output: &[u8]| Package {
+            tests: vec![PackageTest {
+                input: b"1 2\n".to_vec(),
+                output: output.to_vec(),
+            }],
+            ..Package::default()
+        };
+
+        let checker = PackageProgram {
+            language: "cpp".to_string(),
+            code: b"#include \"lib/testlib.h\"".to_vec(),
+            files: [("lib/testlib.h".to_string(), b"#pragma once".to_vec())].into(),
+        };
+        let with_checker = Package {
+            checker: Some(checker.clone()),
+            ..package(b"3\n")
+        };
+        store
+            .save("aplusb", &with_checker, 1.0, 65536.0)
+            .await
+            .unwrap();
+        let second = store
+            .save("aplusb", &package(b"3"), 2.0, 65536.0)
+            .await
+            .unwrap();
+        assert_eq!(second.version, 2);
+        assert_eq!(store.versions("aplusb").await.unwrap(), [1, 2]);
+
+        let latest = store.get("aplusb", None).await.unwrap();
+        assert_eq!(latest, second);
+        let first = store.get("aplusb", Some(1)).await.unwrap();
+        assert_eq!(first.time_limit, 1.0);
+        assert_eq!(store.read_test(&first, 0).await.unwrap().output, b"3\n");
+        let stored = store.read_program(&first, CHECKER_FILE, "cpp").await;
+        assert_eq!(stored.unwrap(), checker);
+
+        assert!(matches!(
+            store.get("aplusb", Some(3)).await,
+            Err(ProblemStoreError::NotFound(_))
+        ));
+        assert!(matches!(
+            store.get("../aplusb", None).await,
+            Err(ProblemStoreError::InvalidId)
+        ));
+
+        store.remove("aplusb").await.unwrap();
+        assert!(matches!(
+            store.versions("aplusb").await,
+            Err(ProblemStoreError::NotFound(_))
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
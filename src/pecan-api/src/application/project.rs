@@ -0,0 +1,197 @@
+//! Unpacking of project archives into individual files.
+
+use std::io::{Cursor, Read};
+
+use flate2::read::GzDecoder;
+use thiserror::Error;
+
+use crate::domain::models::bytes::Base64Bytes;
+use crate::domain::models::project::{ArchiveFormat, ProjectArchive, ProjectFile};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ArchiveError {
+    #[error("is not a valid {0} archive: {1}")]
+    Malformed(&'static str, String),
+    #[error("{0} is not a regular file or directory")]
+    UnsupportedEntry(String),
+    #[error("must contain at most {0} files")]
+    TooManyFiles(usize),
+    #[error("must unpack to at most {0} bytes")]
+    TooLarge(usize),
+}
+
+impl ArchiveFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+/// Files unpacked so far, bounded while reading so that an archive cannot
+/// expand past the limits.
+struct Unpacked {
+    format: &'static str,
+    files: Vec<ProjectFile>,
+    size: usize,
+    max_files: usize,
+    max_size: usize,
+}
+
+impl Unpacked {
+    fn add(&mut self, path: String, reader: impl Read) -> Result<(), ArchiveError> {
+        if self.files.len() == self.max_files {
+            return Err(ArchiveError::TooManyFiles(self.max_files));
+        }
+        let remaining = self.max_size - self.size;
+        let mut content = Vec::new();
+        reader
+            .take(remaining as u64 + 1)
+            .read_to_end(&mut content)
+            .map_err(|e| self.malformed(e))?;
+        if content.len() > remaining {
+            return Err(ArchiveError::TooLarge(self.max_size));
+        }
+        self.size += content.len();
+        // archivers commonly prefix member names with `./`
+        let path = path.strip_prefix("./").unwrap_or(&path).to_string();
+        self.files.push(ProjectFile {
+            path,
+            content: String::new(),
+            content_b64: Some(Base64Bytes(content)),
+        });
+        Ok(())
+    }
+
+    fn malformed(&self, error: impl std::fmt::Display) -> ArchiveError {
+        ArchiveError::Malformed(self.format, error.to_string())
+    }
+}
+
+/// Unpack `archive` into at most `max_files` files of `max_size` bytes in total.
+/// Directories are implied by the paths of their files and not returned.
+pub fn unpack(
+    archive: &ProjectArchive,
+    max_files: usize,
+    max_size: usize,
+) -> Result<Vec<ProjectFile>, ArchiveError> {
+    let data = &archive.data.0;
+    let mut unpacked = Unpacked {
+        format: archive.format.as_str(),
+        files: Vec::new(),
+        size: 0,
+        max_files,
+        max_size,
+    };
+    match archive.format {
+        ArchiveFormat::Tar => unpack_tar(&data[..], &mut unpacked)?,
+        ArchiveFormat::TarGz => unpack_tar(GzDecoder::new(&data[..]), &mut unpacked)?,
+        ArchiveFormat::Zip => {
+            let mut zip =
+                zip::ZipArchive::new(Cursor::new(&data[..])).map_err(|e| unpacked.malformed(e))?;
+            for i in 0..zip.len() {
+                let entry = zip.by_index(i).map_err(|e| unpacked.malformed(e))?;
+                if entry.is_dir() {
+                    continue;
+                }
+                if !entry.is_file() {
+                    return Err(ArchiveError::UnsupportedEntry(entry.name().to_string()));
+                }
+                let path = entry.name().to_string();
+                unpacked.add(path, entry)?;
+            }
+        }
+    }
+    Ok(unpacked.files)
+}
+
+fn unpack_tar(reader: impl Read, unpacked: &mut Unpacked) -> Result<(), ArchiveError> {
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries().map_err(|e| unpacked.malformed(e))? {
+        let entry = entry.map_err(|e| unpacked.malformed(e))?;
+        let path = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            continue;
+        }
+        if !entry_type.is_file() {
+            return Err(ArchiveError::UnsupportedEntry(path));
+        }
+        unpacked.add(path, entry)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    use super::{ArchiveError, unpack};
+    use crate::domain::models::bytes::Base64Bytes;
+    use crate::domain::models::project::{ArchiveFormat, ProjectArchive};
+
+    fn tar(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn archive(format: ArchiveFormat, data: &[u8]) -> ProjectArchive {
+        ProjectArchive {
+            format,
+            data: Base64Bytes(data.to_vec()),
+        }
+    }
+
+    #[test]
+    fn unpacks_tar_gz_and_zip_archives() {
+        let files = [("main.c", "int main() {}"), ("./lib/util.h", "")];
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&tar(&files)).unwrap();
+        let unpacked =
+            unpack(&archive(ArchiveFormat::TarGz, &gz.finish().unwrap()), 8, 64).unwrap();
+        let paths: Vec<_> = unpacked.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["main.c", "lib/util.h"]);
+        assert_eq!(
+            unpacked[0].content_b64,
+            Some(Base64Bytes(b"int main() {}".to_vec()))
+        );
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("lib/", options).unwrap();
+        zip.start_file("lib/util.h", options).unwrap();
+        zip.write_all(b"#pragma once").unwrap();
+        let data = zip.finish().unwrap().into_inner();
+        let unpacked = unpack(&archive(ArchiveFormat::Zip, &data), 8, 64).unwrap();
+        assert_eq!(unpacked.len(), 1);
+        assert_eq!(unpacked[0].path, "lib/util.h");
+    }
+
+    #[test]
+    fn stops_at_the_limits() {
+        let data = tar(&[("a", "12345678"), ("b", "12345678")]);
+        assert_eq!(
+            unpack(&archive(ArchiveFormat::Tar, &data), 1, 64),
+            Err(ArchiveError::TooManyFiles(1))
+        );
+        assert_eq!(
+            unpack(&archive(ArchiveFormat::Tar, &data), 8, 12),
+            Err(ArchiveError::TooLarge(12))
+        );
+    }
+}
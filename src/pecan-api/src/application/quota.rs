@@ -0,0 +1,333 @@
+//! Per-client submission rate limits and concurrency quotas.
+//!
+//! Clients are identified by API key id when authentication is enabled,
+//! otherwise by remote IP address. Every check happens before work enters
+//! `Service::execute` / `Service::execute_async`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::api::error::APIError;
+use crate::application::config::RateLimitConfig;
+
+/// Buckets above this count are swept for idle (fully refilled) entries.
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClientId {
+    ApiKey(String),
+    Ip(String),
+}
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientId::ApiKey(id) => write!(f, "key:{}", id),
+            ClientId::Ip(ip) => write!(f, "ip:{}", ip),
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Outcome of a successful rate limit check, used for the response headers.
+pub struct RateLimitStatus {
+    pub limit: u64,
+    pub remaining: u64,
+}
+
+#[derive(Default)]
+struct QuotaState {
+    buckets: HashMap<ClientId, TokenBucket>,
+    inflight: HashMap<ClientId, usize>,
+    queued: HashMap<ClientId, usize>,
+    /// owner of every async job that has not been reported back yet
+    queued_jobs: HashMap<Uuid, ClientId>,
+}
+
+fn decrement(counts: &mut HashMap<ClientId, usize>, client: &ClientId) {
+    if let Some(count) = counts.get_mut(client) {
+        *count = count.saturating_sub(1);
+        if *count == 0 {
+            counts.remove(client);
+        }
+    }
+}
+
+pub struct ClientQuotas {
+    config: RateLimitConfig,
+    state: Mutex<QuotaState>,
+    /// woken whenever an in-flight slot is released
+    inflight_released: Notify,
+}
+
+impl ClientQuotas {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(QuotaState::default()),
+            inflight_released: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, QuotaState>, APIError> {
+        self.state
+            .lock()
+            .map_err(|_| APIError::InternalError("quota state poisoned".to_string()))
+    }
+
+    /// Take one token from the client's bucket. `None` when rate limiting is disabled.
+    pub fn check_rate(&self, client: &ClientId) -> Result<Option<RateLimitStatus>, APIError> {
+        let rate = self.config.requests_per_sec;
+        if rate <= 0.0 {
+            return Ok(None);
+        }
+        let burst = self.config.burst.max(1.0);
+        let now = Instant::now();
+
+        let mut state = self.lock()?;
+        if state.buckets.len() > MAX_TRACKED_BUCKETS {
+            state.buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.refilled_at).as_secs_f64() * rate < burst
+            });
+        }
+
+        let bucket = state.buckets.entry(client.clone()).or_insert(TokenBucket {
+            tokens: burst,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens < 1.0 {
+            let retry_after = ((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64;
+            return Err(APIError::RateLimited {
+                message: format!("Too many submissions from {}", client),
+                retry_after,
+            });
+        }
+        bucket.tokens -= 1.0;
+
+        Ok(Some(RateLimitStatus {
+            limit: burst as u64,
+            remaining: bucket.tokens.floor() as u64,
+        }))
+    }
+
+    /// Take `cost` more tokens from the client's bucket after a request that
+    /// already passed `check_rate`. The bucket may go into debt, so that a
+    /// request standing for many submissions delays the client's next ones.
+    pub fn charge_rate(&self, client: &ClientId, cost: f64) -> Result<(), APIError> {
+        if self.config.requests_per_sec <= 0.0 || cost <= 0.0 {
+            return Ok(());
+        }
+        if let Some(bucket) = self.lock()?.buckets.get_mut(client) {
+            bucket.tokens -= cost;
+        }
+        Ok(())
+    }
+
+    /// Reserve an in-flight synchronous execution slot, released when the permit drops.
+    pub fn acquire_inflight(
+        self: &Arc<Self>,
+        client: &ClientId,
+    ) -> Result<InflightPermit, APIError> {
+        let max = self.config.max_inflight_per_client;
+        let mut state = self.lock()?;
+        let count = state.inflight.entry(client.clone()).or_insert(0);
+        if max > 0 && *count >= max {
+            return Err(APIError::RateLimited {
+                message: format!("At most {} concurrent executions per client", max),
+                retry_after: 1,
+            });
+        }
+        *count += 1;
+
+        Ok(InflightPermit {
+            quotas: Arc::clone(self),
+            client: client.clone(),
+        })
+    }
+
+    /// `acquire_inflight` that waits for a slot of the client to be released
+    /// instead of failing, for work the client submitted in bulk.
+    pub async fn wait_inflight(
+        self: &Arc<Self>,
+        client: &ClientId,
+    ) -> Result<InflightPermit, APIError> {
+        loop {
+            let released = self.inflight_released.notified();
+            tokio::pin!(released);
+            // registered before the check so that a release in between is not missed
+            released.as_mut().enable();
+            match self.acquire_inflight(client) {
+                Err(APIError::RateLimited { .. }) => released.await,
+                permit => return permit,
+            }
+        }
+    }
+
+    /// Reserve a queued async job slot for `request_id` until `release_queued` is called.
+    pub fn acquire_queued(&self, client: &ClientId, request_id: Uuid) -> Result<(), APIError> {
+        let max = self.config.max_queued_per_client;
+        let mut state = self.lock()?;
+        let count = state.queued.entry(client.clone()).or_insert(0);
+        if max > 0 && *count >= max {
+            return Err(APIError::RateLimited {
+                message: format!("At most {} queued jobs per client", max),
+                retry_after: 1,
+            });
+        }
+        *count += 1;
+        state.queued_jobs.insert(request_id, client.clone());
+        Ok(())
+    }
+
+    /// Release the slot of an async job once its result was reported or enqueueing failed.
+    pub fn release_queued(&self, request_id: Uuid) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(client) = state.queued_jobs.remove(&request_id) {
+                decrement(&mut state.queued, &client);
+            }
+        }
+    }
+
+    fn release_inflight(&self, client: &ClientId) {
+        if let Ok(mut state) = self.state.lock() {
+            decrement(&mut state.inflight, client);
+        }
+        self.inflight_released.notify_waiters();
+    }
+}
+
+/// RAII slot of a running synchronous execution.
+pub struct InflightPermit {
+    quotas: Arc<ClientQuotas>,
+    client: ClientId,
+}
+
+impl Drop for InflightPermit {
+    fn drop(&mut self) {
+        self.quotas.release_inflight(&self.client);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use uuid::Uuid;
+
+    use super::{ClientId, ClientQuotas};
+    use crate::api::error::APIError;
+    use crate::application::config::RateLimitConfig;
+
+    fn quotas(
+        requests_per_sec: f64,
+        burst: f64,
+        inflight: usize,
+        queued: usize,
+    ) -> Arc<ClientQuotas> {
+        Arc::new(ClientQuotas::new(RateLimitConfig {
+            requests_per_sec,
+            burst,
+            max_inflight_per_client: inflight,
+            max_queued_per_client: queued,
+        }))
+    }
+
+    #[test]
+    fn token_bucket_allows_burst_then_limits() {
+        let quotas = quotas(0.5, 2.0, 0, 0);
+        let client = ClientId::Ip("10.0.0.1".to_string());
+
+        assert_eq!(quotas.check_rate(&client).unwrap().unwrap().remaining, 1);
+        assert_eq!(quotas.check_rate(&client).unwrap().unwrap().remaining, 0);
+        match quotas.check_rate(&client) {
+            Err(APIError::RateLimited { retry_after, .. }) => assert_eq!(retry_after, 2),
+            other => panic!("expected rate limit, got {:?}", other.err()),
+        }
+
+        // buckets are independent per client
+        let other = ClientId::ApiKey("backend".to_string());
+        assert!(quotas.check_rate(&other).is_ok());
+    }
+
+    #[test]
+    fn zero_rate_disables_rate_limiting() {
+        let quotas = quotas(0.0, 1.0, 0, 0);
+        let client = ClientId::Ip("10.0.0.1".to_string());
+        for _ in 0..100 {
+            assert!(quotas.check_rate(&client).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn inflight_permit_is_released_on_drop() {
+        let quotas = quotas(0.0, 1.0, 1, 0);
+        let client = ClientId::ApiKey("backend".to_string());
+
+        let permit = quotas.acquire_inflight(&client).expect("first slot");
+        assert!(quotas.acquire_inflight(&client).is_err());
+        drop(permit);
+        assert!(quotas.acquire_inflight(&client).is_ok());
+    }
+
+    #[test]
+    fn charged_requests_put_the_bucket_into_debt() {
+        let quotas = quotas(1.0, 2.0, 0, 0);
+        let client = ClientId::Ip("10.0.0.1".to_string());
+
+        quotas.check_rate(&client).unwrap();
+        quotas.charge_rate(&client, 4.0).unwrap();
+        match quotas.check_rate(&client) {
+            Err(APIError::RateLimited { retry_after, .. }) => assert_eq!(retry_after, 4),
+            other => panic!("expected rate limit, got {:?}", other.err()),
+        }
+    }
+
+    #[tokio::test]
+    async fn waiting_for_an_inflight_slot_resumes_once_one_is_released() {
+        let quotas = quotas(0.0, 1.0, 1, 0);
+        let client = ClientId::ApiKey("backend".to_string());
+
+        let permit = quotas.acquire_inflight(&client).expect("first slot");
+        let waiting = tokio::spawn({
+            let (quotas, client) = (Arc::clone(&quotas), client.clone());
+            async move { quotas.wait_inflight(&client).await.map(|_| ()) }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        drop(permit);
+        waiting.await.unwrap().expect("slot once released");
+    }
+
+    #[test]
+    fn queued_slots_are_tracked_per_job() {
+        let quotas = quotas(0.0, 1.0, 0, 2);
+        let client = ClientId::ApiKey("backend".to_string());
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+
+        quotas.acquire_queued(&client, first).unwrap();
+        quotas.acquire_queued(&client, second).unwrap();
+        assert!(quotas.acquire_queued(&client, Uuid::new_v4()).is_err());
+
+        quotas.release_queued(first);
+        // releasing an unknown or already released job is a no-op
+        quotas.release_queued(first);
+        quotas.release_queued(Uuid::new_v4());
+
+        assert!(quotas.acquire_queued(&client, Uuid::new_v4()).is_ok());
+        assert!(quotas.acquire_queued(&client, Uuid::new_v4()).is_err());
+    }
+}
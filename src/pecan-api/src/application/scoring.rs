@@ -0,0 +1,110 @@
+//! Scores of subtasks from the verdicts of their tests.
+
+use crate::domain::models::judge::JudgeResponse;
+use crate::domain::models::problem::{Subtask, SubtaskResult, SubtaskScoring, SubtaskStatus};
+
+/// Score of one test between 0 and 1.
+pub fn test_score(result: &JudgeResponse) -> f64 {
+    result.score
+}
+
+pub fn max_score(subtask: &Subtask) -> f64 {
+    match subtask.scoring {
+        SubtaskScoring::Sum => subtask.points * subtask.tests.len() as f64,
+        SubtaskScoring::Min | SubtaskScoring::Proportional => subtask.points,
+    }
+}
+
+/// Whether every subtask `subtask` depends on got its full score; `results`
+/// holds the results of the earlier subtasks.
+pub fn prerequisites_met(subtask: &Subtask, results: &[SubtaskResult]) -> bool {
+    subtask.depends_on.iter().all(|&dependency| {
+        results.get(dependency).is_some_and(|result| {
+            result.status == SubtaskStatus::Scored && result.score >= result.max_score
+        })
+    })
+}
+
+/// Score subtask `index` from the scores of the tests that ran; a test that
+/// did not run scores nothing.
+pub fn score_subtask(index: usize, subtask: &Subtask, scores: &[Option<f64>]) -> SubtaskResult {
+    let test_scores = subtask
+        .tests
+        .iter()
+        .map(|&test| scores.get(test).copied().flatten().unwrap_or(0.0));
+    let score = match subtask.scoring {
+        SubtaskScoring::Min => subtask.points * test_scores.fold(1.0, f64::min),
+        SubtaskScoring::Sum => subtask.points * test_scores.sum::<f64>(),
+        SubtaskScoring::Proportional => {
+            subtask.points * test_scores.sum::<f64>() / subtask.tests.len().max(1) as f64
+        }
+    };
+    SubtaskResult {
+        subtask: index,
+        name: subtask.name.clone(),
+        status: SubtaskStatus::Scored,
+        score,
+        max_score: max_score(subtask),
+    }
+}
+
+pub fn skip_subtask(index: usize, subtask: &Subtask) -> SubtaskResult {
+    SubtaskResult {
+        subtask: index,
+        name: subtask.name.clone(),
+        status: SubtaskStatus::Skipped,
+        score: 0.0,
+        max_score: max_score(subtask),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prerequisites_met, score_subtask, skip_subtask};
+    use crate::domain::models::problem::{Subtask, SubtaskScoring, SubtaskStatus};
+
+    fn subtask(scoring: SubtaskScoring, depends_on: Vec<usize>) -> Subtask {
+        Subtask {
+            name: None,
+            points: 10.0,
+            scoring,
+            tests: vec![0, 1, 2, 3],
+            depends_on,
+        }
+    }
+
+    #[test]
+    fn scoring_rules_combine_test_scores() {
+        // the last test did not run
+        let scores = [Some(1.0), Some(0.0), Some(1.0), None];
+        let score = |scoring| score_subtask(0, &subtask(scoring, vec![]), &scores);
+        assert_eq!(score(SubtaskScoring::Min).score, 0.0);
+        assert_eq!(score(SubtaskScoring::Sum).score, 20.0);
+        assert_eq!(score(SubtaskScoring::Sum).max_score, 40.0);
+        assert_eq!(score(SubtaskScoring::Proportional).score, 5.0);
+
+        let passed = score_subtask(0, &subtask(SubtaskScoring::Min, vec![]), &[Some(1.0); 4]);
+        assert_eq!((passed.status, passed.score), (SubtaskStatus::Scored, 10.0));
+    }
+
+    #[test]
+    fn subtasks_need_the_full_score_of_their_prerequisites() {
+        let full = score_subtask(0, &subtask(SubtaskScoring::Min, vec![]), &[Some(1.0); 4]);
+        let partial = score_subtask(1, &subtask(SubtaskScoring::Sum, vec![]), &[Some(0.5); 4]);
+        let skipped = skip_subtask(2, &subtask(SubtaskScoring::Min, vec![1]));
+        let results = [full, partial, skipped];
+
+        assert!(prerequisites_met(
+            &subtask(SubtaskScoring::Min, vec![0]),
+            &results
+        ));
+        assert!(!prerequisites_met(
+            &subtask(SubtaskScoring::Min, vec![0, 1]),
+            &results
+        ));
+        assert!(!prerequisites_met(
+            &subtask(SubtaskScoring::Min, vec![2]),
+            &results
+        ));
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+use futures::stream;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::api::error::APIError;
+use crate::api::handler::webhook_handler::send_batch_webhook;
+use crate::api::middleware::request_id::current_request_id;
+use crate::application::auth::{ApiKeyIdentity, Scope};
+use crate::application::batch::Batch;
+use crate::application::quota::ClientId;
+use crate::application::service::judge_service::run_judge;
+use crate::application::state::SharedState;
+use crate::application::validation::validate_batch_request;
+use crate::domain::models::batch::{
+    BatchCreatedResponse, BatchItemResult, BatchProgressResponse, BatchRequest, BatchStatus,
+};
+use crate::domain::models::judge::{JudgeRequest, JudgeResponse};
+
+/// Accept a batch and start judging it in the background
+#[tracing::instrument(skip_all, fields(client = %client, batch_id))]
+pub async fn create_batch(
+    mut request: BatchRequest,
+    client: &ClientId,
+    identity: Option<&ApiKeyIdentity>,
+    state: &SharedState,
+) -> Result<BatchCreatedResponse, APIError> {
+    validate_batch_request(
+        &mut request,
+        &state.config.limits,
+        state.config.batch.max_items,
+    )?;
+    state.service.admit()?;
+
+    let batch_id = Uuid::new_v4();
+    tracing::Span::current().record("batch_id", tracing::field::display(batch_id));
+    // a batch takes one queued job slot, released once it finishes
+    state.quotas.acquire_queued(client, batch_id)?;
+
+    // with shared test cases every submission runs against every case
+    let items: Vec<(usize, Option<usize>)> = if request.test_cases.is_empty() {
+        (0..request.submissions.len()).map(|s| (s, None)).collect()
+    } else {
+        (0..request.submissions.len())
+            .flat_map(|s| (0..request.test_cases.len()).map(move |t| (s, Some(t))))
+            .collect()
+    };
+    let total = items.len();
+    // the request itself already paid for one item
+    state
+        .quotas
+        .charge_rate(client, total.saturating_sub(1) as f64)?;
+    let batch = state
+        .batches
+        .insert(batch_id, identity.map(|i| i.id.clone()), total);
+
+    let origin_request_id = current_request_id();
+    state.batches.spawn(
+        run_batch(
+            Arc::clone(state),
+            client.clone(),
+            batch,
+            request,
+            items,
+            origin_request_id,
+        )
+        .instrument(tracing::Span::current()),
+    );
+
+    Ok(BatchCreatedResponse { batch_id, total })
+}
+
+async fn run_batch(
+    state: SharedState,
+    client: ClientId,
+    batch: Arc<Batch>,
+    request: BatchRequest,
+    items: Vec<(usize, Option<usize>)>,
+    origin_request_id: Option<String>,
+) {
+    let concurrency = state.config.service.async_workers.max(1) as usize;
+    stream::iter(items)
+        .map(|(submission, test_case)| {
+            let (state, client, batch, request) = (&state, &client, &batch, &request);
+            async move {
+                // items count toward the client's concurrent executions, as
+                // if submitted one by one
+                let inflight = state.quotas.wait_inflight(client).await;
+                if batch.is_cancelled() {
+                    batch.skip_item();
+                    return;
+                }
+                batch.start_item();
+
+                let outcome = match inflight {
+                    Ok(_inflight) => judge_item(request, submission, test_case, state).await,
+                    Err(e) => Err(e),
+                };
+
+                let (result, error) = match outcome {
+                    Ok(result) => (Some(result), None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                batch.finish_item(BatchItemResult {
+                    submission,
+                    test_case,
+                    result,
+                    error,
+                });
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect::<()>()
+        .await;
+
+    batch.finish();
+    state.quotas.release_queued(batch.id);
+
+    let progress = batch.progress();
+    tracing::info!(status = ?progress.status, done = progress.done, "batch finished");
+    if let Some(webhook_url) = &request.webhook_url {
+        if let Err(e) =
+            send_batch_webhook(webhook_url, origin_request_id.as_deref(), &progress).await
+        {
+            tracing::error!(error = %e, "batch webhook delivery failed");
+        }
+    }
+}
+
+/// Judge one submission of a batch, against one of the shared test cases
+/// when there are some.
+async fn judge_item(
+    request: &BatchRequest,
+    submission: usize,
+    test_case: Option<usize>,
+    state: &SharedState,
+) -> Result<JudgeResponse, APIError> {
+    let code = &request.submissions[submission];
+    let (stdin, stdin_b64, desired_stdout, desired_stdout_b64) =
+        match test_case.map(|t| &request.test_cases[t]) {
+            Some(t) => (
+                &t.stdin,
+                &t.stdin_b64,
+                &t.desired_stdout,
+                &t.desired_stdout_b64,
+            ),
+            None => (
+                &code.stdin,
+                &code.stdin_b64,
+                &code.desired_stdout,
+                &code.desired_stdout_b64,
+            ),
+        };
+    // accepted before any drain started, so it runs to completion; like an
+    // async job it waits behind higher priority work
+    run_judge(
+        JudgeRequest {
+            code: code.code.clone(),
+            project: code.project.clone(),
+            language: code.language.clone(),
+            stdin: stdin.clone(),
+            stdin_b64: stdin_b64.clone(),
+            desired_stdout: desired_stdout.clone(),
+            desired_stdout_b64: desired_stdout_b64.clone(),
+            time_limit: request.time_limit,
+            memory_limit: request.memory_limit,
+            priority: request.priority,
+            output_files: request.output_files.clone(),
+            judged_file: request.judged_file.clone(),
+        },
+        state.config.service.async_queue_timeout(),
+        state,
+    )
+    .await
+}
+
+/// Look up a batch visible to the caller; batches of other API keys are
+/// only visible to admins.
+fn find_batch(
+    id: Uuid,
+    identity: Option<&ApiKeyIdentity>,
+    state: &SharedState,
+) -> Result<Arc<Batch>, APIError> {
+    let not_found = || APIError::NotFound(format!("batch {}", id));
+    let batch = state.batches.get(id).ok_or_else(not_found)?;
+    match (&batch.owner, identity) {
+        (Some(owner), Some(identity))
+            if *owner != identity.id && !identity.has_scope(Scope::Admin) =>
+        {
+            Err(not_found())
+        }
+        _ => Ok(batch),
+    }
+}
+
+pub fn get_batch(
+    id: Uuid,
+    identity: Option<&ApiKeyIdentity>,
+    state: &SharedState,
+) -> Result<BatchProgressResponse, APIError> {
+    Ok(find_batch(id, identity, state)?.progress())
+}
+
+pub fn get_batch_results(
+    id: Uuid,
+    identity: Option<&ApiKeyIdentity>,
+    state: &SharedState,
+) -> Result<Vec<BatchItemResult>, APIError> {
+    Ok(find_batch(id, identity, state)?.results())
+}
+
+/// Cancel the items that have not started; running items still finish.
+pub fn cancel_batch(
+    id: Uuid,
+    identity: Option<&ApiKeyIdentity>,
+    state: &SharedState,
+) -> Result<BatchProgressResponse, APIError> {
+    let batch = find_batch(id, identity, state)?;
+    if batch.progress().status == BatchStatus::Completed {
+        return Err(APIError::Conflict(format!(
+            "batch {} already completed",
+            id
+        )));
+    }
+    batch.cancel();
+    Ok(batch.progress())
+}
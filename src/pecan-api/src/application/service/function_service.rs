@@ -0,0 +1,146 @@
+use pecan_core::code_execution::{
+    CodeExecutionRequest, CodeExecutionResult, CodeExecutionStatus, OutputFileSpec, Project,
+};
+use pecan_core::harness::{self, HarnessError, RESULT_FILE, Signature};
+use pecan_core::toolchains::Language;
+use serde_json::Value;
+
+use crate::api::error::APIError;
+use crate::application::checker::{Check, compare_values};
+use crate::application::quota::ClientId;
+use crate::application::service::judge_service::{self, priority};
+use crate::application::state::SharedState;
+use crate::application::validation::validate_function_request;
+use crate::domain::models::error::FieldError;
+use crate::domain::models::function::{
+    FunctionCase, FunctionCaseResult, FunctionJudgeRequest, FunctionJudgeResponse,
+};
+use crate::domain::models::judge::JudgeStatus;
+
+/// The submission with the driver of its language, which may not support
+/// every type of the signature.
+fn driver_project(
+    request: &FunctionJudgeRequest,
+    signature: &Signature,
+) -> Result<Project, APIError> {
+    let language = Language::from(request.language.as_str());
+    harness::project(language, signature, &request.code).map_err(|e| match e {
+        HarnessError::UnsupportedLanguage(_) => {
+            APIError::NotSupportedLanguage(request.language.clone())
+        }
+        e => APIError::ValidationFailed(vec![FieldError {
+            field: "signature".to_string(),
+            message: e.to_string(),
+        }]),
+    })
+}
+
+/// Check the value the driver wrote to the result file; the file is taken
+/// out of the result, so only the files the submission wrote remain.
+fn check_return(
+    result: &mut CodeExecutionResult,
+    signature: &Signature,
+    case: &FunctionCase,
+    tolerance: f64,
+) -> (Check, Option<Value>) {
+    let file = result
+        .output_files
+        .iter()
+        .position(|file| file.name == RESULT_FILE)
+        .map(|index| result.output_files.remove(index));
+    let wrong = |message: &str| Check::scored(0.0, Some(message.to_string()));
+    let content = match file {
+        Some(file) if file.truncated => return (wrong("return value is too large"), None),
+        Some(file) => file.content,
+        None => None,
+    };
+    let Some(content) = content else {
+        return (wrong("no return value"), None);
+    };
+    match harness::decode(&content, &signature.returns) {
+        Ok(returned) => {
+            let check = compare_values(&signature.returns, &returned, &case.expected, tolerance);
+            (check, Some(returned))
+        }
+        Err(e) => (wrong(&format!("unreadable return value: {}", e)), None),
+    }
+}
+
+/// Judge a submission by calling its function on each case in turn, one run
+/// per case so that a crash fails only its own case. The return value is
+/// compared as a value of the signature's type.
+#[tracing::instrument(skip_all, fields(language = %request.language, client = %client))]
+pub async fn judge(
+    mut request: FunctionJudgeRequest,
+    client: &ClientId,
+    state: &SharedState,
+) -> Result<FunctionJudgeResponse, APIError> {
+    let (signature, inputs) = validate_function_request(&mut request, &state.config.limits)?;
+    let project = driver_project(&request, &signature)?;
+    let language = Language::from(request.language.as_str());
+    let _inflight = state.quotas.acquire_inflight(client)?;
+    state.service.admit()?;
+
+    let mut status = JudgeStatus::Accepted;
+    let mut cases = Vec::with_capacity(request.cases.len());
+    for (index, (case, input)) in request.cases.iter().zip(inputs).enumerate() {
+        // boxed, as an execution's future is too large to nest in this one's
+        let mut result = Box::pin(state.service.execute_admitted(CodeExecutionRequest {
+            language,
+            code: String::new(),
+            project: Some(project.clone()),
+            input,
+            timeout: request.time_limit,
+            memory_limit: request.memory_limit,
+            priority: priority(request.priority),
+            queue_timeout: Some(request.time_limit),
+            output_files: vec![OutputFileSpec {
+                name: RESULT_FILE.to_string(),
+                max_size: state.config.limits.max_output_file_size,
+            }],
+            args: Vec::new(),
+            input_files: Vec::new(),
+            bypass_artifact_cache: false,
+        }))
+        .await?;
+        let (check, returned) = match result.status {
+            CodeExecutionStatus::Success => {
+                let (check, returned) =
+                    check_return(&mut result, &signature, case, request.tolerance);
+                (Some(check), returned)
+            }
+            _ => {
+                result.output_files.clear();
+                (None, None)
+            }
+        };
+        let response = judge_service::response(language, result, check);
+
+        let failed = response.status != JudgeStatus::Accepted;
+        if failed && status == JudgeStatus::Accepted {
+            status = response.status.clone();
+        }
+        cases.push(FunctionCaseResult {
+            case: index,
+            result: response,
+            returned,
+        });
+        if failed && request.stop_on_failure {
+            break;
+        }
+    }
+
+    Ok(FunctionJudgeResponse {
+        code: status.clone().into_status_code(),
+        status,
+        time: cases
+            .iter()
+            .map(|case| case.result.time)
+            .fold(0.0, f64::max),
+        memory: cases
+            .iter()
+            .map(|case| case.result.memory)
+            .fold(0.0, f64::max),
+        cases,
+    })
+}
@@ -1,81 +1,496 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::{Stream, stream};
 use pecan_core::code_execution::{
-    CodeExecutionRequest, CodeExecutionRequestLazy, CodeExecutionStatus,
+    CodeExecutionRequest, CodeExecutionRequestLazy, CodeExecutionResult, CodeExecutionStatus,
+    OutputFileSpec, Project, ProjectFile,
 };
-use pecan_core::errors::CoreExecutionError;
+use pecan_core::toolchains::Language;
+use pecan_core::{ExecutionEvent, ExecutionPhase, OutputChunk, OutputStream, Priority};
+use tokio::sync::mpsc;
+use tracing::Instrument;
 use uuid::Uuid;
 
 use crate::api::error::APIError;
+use crate::api::middleware::request_id::current_request_id;
+use crate::application::checker::Check;
+use crate::application::quota::ClientId;
 use crate::application::state::SharedState;
+use crate::application::telemetry::record_verdict;
+use crate::application::validation::{validate_judge_async_request, validate_judge_request};
+use crate::domain::models::bytes::{payload, text_or_bytes};
 use crate::domain::models::judge::{
-    JudgeAsyncRequest, JudgeAsyncResponse, JudgeRequest, JudgeResponse, JudgeStatus,
+    JudgeAsyncRequest, JudgeAsyncResponse, JudgeOutputEvent, JudgeOutputStream, JudgePhase,
+    JudgePhaseEvent, JudgePriority, JudgeRequest, JudgeResponse, JudgeStatus, JudgeStreamEvent,
 };
+use crate::domain::models::output_file::{OutputFileRequest, OutputFileResponse};
+use crate::domain::models::project::ProjectRequest;
 
-/// Process single judge request and returns judge response
-pub async fn judge(request: JudgeRequest, state: &SharedState) -> Result<JudgeResponse, APIError> {
-    let service = &state.service;
-    let result = service
-        .execute(CodeExecutionRequest {
-            language: request.language.as_str().into(),
-            code: request.code,
-            input: request.stdin,
-            timeout: request.time_limit,
-            memory_limit: request.memory_limit,
+/// Events of a streamed run buffered ahead of a slow client; once full the
+/// program waits on its output.
+pub const STREAM_EVENT_BUFFER: usize = 64;
+
+pub fn priority(priority: JudgePriority) -> Priority {
+    match priority {
+        JudgePriority::Realtime => Priority::Realtime,
+        JudgePriority::Normal => Priority::Normal,
+        JudgePriority::Bulk => Priority::Bulk,
+    }
+}
+
+/// The files of a validated project; validation already unpacked any archive.
+pub fn project(project: &ProjectRequest) -> Project {
+    Project {
+        files: project
+            .files
+            .iter()
+            .map(|file| ProjectFile {
+                path: file.path.clone(),
+                content: payload(&file.content, file.content_b64.as_ref()).to_vec(),
+            })
+            .collect(),
+        entry_point: project.entry_point.clone(),
+    }
+}
+
+/// The output files of a validated request; validation already filled in
+/// the default size caps.
+pub fn output_files(files: &[OutputFileRequest]) -> Vec<OutputFileSpec> {
+    files
+        .iter()
+        .map(|file| OutputFileSpec {
+            name: file.name.clone(),
+            max_size: file.max_size.unwrap_or_default(),
         })
-        .await
-        .map_err(|e| match e {
-            CoreExecutionError::ServiceBusy(msg) => APIError::ServiceBusy(msg),
-            e => APIError::InternalError(e.to_string()),
-        })?;
-
-    let status = match result.status {
-        CodeExecutionStatus::Success => {
-            if result.stdout == request.desired_stdout {
-                JudgeStatus::Accepted
-            } else {
-                JudgeStatus::WrongAnswer
-            }
+        .collect()
+}
+
+/// Process single judge request and returns judge response
+#[tracing::instrument(skip_all, fields(language = %request.language, client = %client))]
+pub async fn judge(
+    mut request: JudgeRequest,
+    client: &ClientId,
+    state: &SharedState,
+) -> Result<JudgeResponse, APIError> {
+    validate_judge_request(&mut request, &state.config.limits)?;
+    let _inflight = state.quotas.acquire_inflight(client)?;
+    state.service.admit()?;
+
+    let queue_timeout = Some(request.time_limit);
+    run_judge(request, queue_timeout, state).await
+}
+
+fn execution_request(request: &JudgeRequest) -> CodeExecutionRequest {
+    CodeExecutionRequest {
+        language: request.language.as_str().into(),
+        code: request.code.clone(),
+        project: request.project.as_ref().map(project),
+        input: payload(&request.stdin, request.stdin_b64.as_ref()).to_vec(),
+        timeout: request.time_limit,
+        memory_limit: request.memory_limit,
+        priority: priority(request.priority),
+        queue_timeout: Some(request.time_limit),
+        output_files: output_files(&request.output_files),
+        args: Vec::new(),
+        input_files: Vec::new(),
+        bypass_artifact_cache: false,
+    }
+}
+
+/// The output a judge compares: stdout, or with `judged_file` the output file
+/// of that name. A missing or truncated file has no output.
+pub fn judged_output<'a>(
+    result: &'a CodeExecutionResult,
+    judged_file: Option<&str>,
+) -> Option<&'a [u8]> {
+    match judged_file {
+        Some(name) => result
+            .output_files
+            .iter()
+            .find(|file| file.name == name && !file.truncated)
+            .and_then(|file| file.content.as_deref()),
+        None => Some(result.stdout.as_slice()),
+    }
+}
+
+/// Response to a run, judged by `check` when the program succeeded; a run
+/// that succeeded unchecked is an internal error. Records the verdict.
+pub fn response(
+    language: Language,
+    result: CodeExecutionResult,
+    check: Option<Check>,
+) -> JudgeResponse {
+    let (status, score, checker_message) = match (result.status, check) {
+        (CodeExecutionStatus::Success, Some(check)) => (check.status, check.score, check.message),
+        (CodeExecutionStatus::Success, None) => (JudgeStatus::InternalError, 0.0, None),
+        (CodeExecutionStatus::CompileError, _) => (JudgeStatus::CompileError, 0.0, None),
+        (CodeExecutionStatus::RuntimeError, _) => (JudgeStatus::RuntimeError, 0.0, None),
+        (CodeExecutionStatus::TimeLimitExceeded, _) => (JudgeStatus::TimeLimitExceeded, 0.0, None),
+        (CodeExecutionStatus::MemoryLimitExceeded, _) => {
+            (JudgeStatus::MemoryLimitExceeded, 0.0, None)
         }
-        CodeExecutionStatus::CompileError => JudgeStatus::CompileError,
-        CodeExecutionStatus::RuntimeError => JudgeStatus::RuntimeError,
-        CodeExecutionStatus::TimeLimitExceeded => JudgeStatus::TimeLimitExceeded,
-        CodeExecutionStatus::MemoryLimitExceeded => JudgeStatus::MemoryLimitExceeded,
-        CodeExecutionStatus::InternalError => JudgeStatus::InternalError,
+        (CodeExecutionStatus::InternalError, _) => (JudgeStatus::InternalError, 0.0, None),
+        (CodeExecutionStatus::Aborted, _) => (JudgeStatus::Aborted, 0.0, None),
     };
+    record_verdict(language, &status);
 
-    Ok(JudgeResponse {
+    let (stdout, stdout_b64) = text_or_bytes(result.stdout);
+    let (stderr, stderr_b64) = text_or_bytes(result.stderr);
+    JudgeResponse {
         code: status.clone().into_status_code(),
         status,
-        stdout: result.stdout,
-        stderr: result.stderr,
+        stdout,
+        stderr,
+        binary_output: stdout_b64.is_some() || stderr_b64.is_some(),
+        stdout_b64,
+        stderr_b64,
         time: result.time,
         memory: result.memory,
-    })
+        score,
+        checker_message,
+        output_files: result
+            .output_files
+            .into_iter()
+            .map(|file| {
+                let written = file.content.is_some();
+                let (content, content_b64) = text_or_bytes(file.content.unwrap_or_default());
+                OutputFileResponse {
+                    name: file.name,
+                    written,
+                    content,
+                    content_b64,
+                    truncated: file.truncated,
+                }
+            })
+            .collect(),
+        cached: result.cached,
+    }
+}
+
+/// Compare the result byte for byte with the expected output and record the
+/// verdict. With `judged_file` the output file of that name is compared
+/// instead of stdout; a missing or truncated file is a wrong answer.
+pub fn verdict(
+    language: Language,
+    result: CodeExecutionResult,
+    desired_stdout: &[u8],
+    judged_file: Option<&str>,
+) -> JudgeResponse {
+    let accepted = judged_output(&result, judged_file) == Some(desired_stdout);
+    let check = Check::scored(if accepted { 1.0 } else { 0.0 }, None);
+    response(language, result, Some(check))
+}
+
+/// Execute an already validated and admitted request and compute its verdict,
+/// waiting up to `queue_timeout` seconds for a sandbox.
+pub async fn run_judge(
+    request: JudgeRequest,
+    queue_timeout: Option<f64>,
+    state: &SharedState,
+) -> Result<JudgeResponse, APIError> {
+    let mut execution = execution_request(&request);
+    execution.queue_timeout = queue_timeout;
+    let language = execution.language;
+    let result = state.service.execute_admitted(execution).await?;
+    let desired_stdout = payload(&request.desired_stdout, request.desired_stdout_b64.as_ref());
+    Ok(verdict(
+        language,
+        result,
+        desired_stdout,
+        request.judged_file.as_deref(),
+    ))
+}
+
+fn stream_event(event: ExecutionEvent) -> JudgeStreamEvent {
+    let output = |chunk: OutputChunk| {
+        let (data, data_b64) = text_or_bytes(chunk.data);
+        JudgeOutputEvent {
+            stream: match chunk.stream {
+                OutputStream::Stdout => JudgeOutputStream::Stdout,
+                OutputStream::Stderr => JudgeOutputStream::Stderr,
+            },
+            data,
+            data_b64,
+        }
+    };
+    match event {
+        ExecutionEvent::Phase(phase) => JudgeStreamEvent::Phase(JudgePhaseEvent {
+            phase: match phase {
+                ExecutionPhase::Queued => JudgePhase::Queued,
+                ExecutionPhase::Compiling => JudgePhase::Compiling,
+                ExecutionPhase::Running => JudgePhase::Running,
+            },
+        }),
+        ExecutionEvent::CompileOutput(chunk) => JudgeStreamEvent::CompileOutput(output(chunk)),
+        ExecutionEvent::Output(chunk) => JudgeStreamEvent::Output(output(chunk)),
+    }
+}
+
+enum StreamState<F> {
+    Running(Pin<Box<F>>, mpsc::Receiver<ExecutionEvent>),
+    /// the run finished, forward what is still buffered before its outcome
+    Draining(JudgeStreamEvent, mpsc::Receiver<ExecutionEvent>),
+    Done,
+}
+
+/// Forward the events of `run` as they arrive, then its outcome. Dropping the
+/// stream drops `run`, which kills the program and releases its sandbox.
+pub fn event_stream<F>(
+    run: F,
+    events: mpsc::Receiver<ExecutionEvent>,
+) -> impl Stream<Item = JudgeStreamEvent> + Send + 'static
+where
+    F: Future<Output = JudgeStreamEvent> + Send + 'static,
+{
+    stream::unfold(
+        StreamState::Running(Box::pin(run), events),
+        |state| async move {
+            match state {
+                StreamState::Running(mut run, mut events) => {
+                    tokio::select! {
+                        biased;
+                        Some(event) = events.recv() => {
+                            Some((stream_event(event), StreamState::Running(run, events)))
+                        }
+                        outcome = &mut run => match events.recv().await {
+                            Some(event) => {
+                                Some((stream_event(event), StreamState::Draining(outcome, events)))
+                            }
+                            None => Some((outcome, StreamState::Done)),
+                        },
+                    }
+                }
+                StreamState::Draining(outcome, mut events) => match events.recv().await {
+                    Some(event) => {
+                        Some((stream_event(event), StreamState::Draining(outcome, events)))
+                    }
+                    None => Some((outcome, StreamState::Done)),
+                },
+                StreamState::Done => None,
+            }
+        },
+    )
+}
+
+/// Validate and admit a judge request whose progress and output are streamed
+/// as they are produced, ending with the verdict. Errors past admission are
+/// reported as the final event.
+#[tracing::instrument(skip_all, fields(language = %request.language, client = %client))]
+pub fn judge_stream(
+    mut request: JudgeRequest,
+    client: &ClientId,
+    state: &SharedState,
+) -> Result<impl Stream<Item = JudgeStreamEvent> + Send + use<>, APIError> {
+    validate_judge_request(&mut request, &state.config.limits)?;
+    let inflight = state.quotas.acquire_inflight(client)?;
+    state.service.admit()?;
+
+    // the stream outlives the handler, along with its request id and span
+    let request_id = current_request_id();
+    let (sender, receiver) = mpsc::channel(STREAM_EVENT_BUFFER);
+    let state = Arc::clone(state);
+    let run = async move {
+        let _inflight = inflight;
+        let execution = execution_request(&request);
+        let language = execution.language;
+        // the sender is dropped with this future, which ends the draining
+        match state.service.execute_streaming(execution, &sender).await {
+            Ok(result) => {
+                let desired_stdout =
+                    payload(&request.desired_stdout, request.desired_stdout_b64.as_ref());
+                JudgeStreamEvent::Result(verdict(
+                    language,
+                    result,
+                    desired_stdout,
+                    request.judged_file.as_deref(),
+                ))
+            }
+            Err(e) => JudgeStreamEvent::Error(APIError::from(e).to_body(request_id)),
+        }
+    }
+    .instrument(tracing::Span::current());
+
+    Ok(event_stream(run, receiver))
 }
 
 /// Enqueue new judge request for lazy execution (later returned by webhook)
+#[tracing::instrument(skip_all, fields(language = %request.language, client = %client, job_id))]
 pub async fn judge_async(
-    request: JudgeAsyncRequest,
+    mut request: JudgeAsyncRequest,
+    client: &ClientId,
     state: &SharedState,
 ) -> Result<JudgeAsyncResponse, APIError> {
+    validate_judge_async_request(&mut request, &state.config.limits)?;
+
     let service = &state.service;
     let request_id = Uuid::new_v4();
+    tracing::Span::current().record("job_id", tracing::field::display(request_id));
+    // released by the webhook handler once the result is reported
+    state.quotas.acquire_queued(client, request_id)?;
 
     service
         .execute_async(CodeExecutionRequestLazy {
             request_id,
+            origin_request_id: current_request_id(),
+            span: tracing::Span::current(),
+            enqueued_at: Instant::now(),
             webhook_url: request.webhook_url,
             send_failed_count: 0,
-            desired_stdout: request.desired_stdout,
+            desired_stdout: payload(&request.desired_stdout, request.desired_stdout_b64.as_ref())
+                .to_vec(),
+            judged_file: request.judged_file,
             req: CodeExecutionRequest {
                 language: request.language.as_str().into(),
                 code: request.code,
-                input: request.stdin,
+                project: request.project.as_ref().map(project),
+                input: payload(&request.stdin, request.stdin_b64.as_ref()).to_vec(),
                 timeout: request.time_limit,
                 memory_limit: request.memory_limit,
+                priority: priority(request.priority),
+                queue_timeout: state.config.service.async_queue_timeout(),
+                output_files: output_files(&request.output_files),
+                args: Vec::new(),
+                input_files: Vec::new(),
+                bypass_artifact_cache: false,
             },
         })
         .await
-        .map_err(|e| APIError::InternalError(e.to_string()))?;
+        .inspect_err(|_| state.quotas.release_queued(request_id))?;
 
     Ok(JudgeAsyncResponse { request_id })
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use pecan_core::code_execution::{CodeExecutionResult, CodeExecutionStatus, OutputFile};
+    use pecan_core::toolchains::Language;
+    use pecan_core::{ExecutionEvent, ExecutionPhase, OutputChunk, OutputStream};
+    use tokio::sync::mpsc;
+
+    use super::{event_stream, verdict};
+    use crate::domain::models::bytes::Base64Bytes;
+    use crate::domain::models::error::{ErrorDetail, ErrorResponse};
+    use crate::domain::models::judge::{JudgeStatus, JudgeStreamEvent};
+
+    #[test]
+    fn output_is_compared_byte_for_byte() {
+        let result = |stdout: &[u8]| CodeExecutionResult {
+            status: CodeExecutionStatus::Success,
+            stdout: stdout.to_vec(),
+            stderr: Vec::new(),
+            time: 0.0,
+            memory: 0.0,
+            compile_time: 0.0,
+            output_files: Vec::new(),
+            exit_code: Some(0),
+            cached: false,
+        };
+
+        let response = verdict(Language::C, result(b"\x00\xff\n"), b"\x00\xff\n", None);
+        assert_eq!(response.status, JudgeStatus::Accepted);
+        assert!(response.binary_output);
+        assert_eq!(response.stdout, "\0\u{FFFD}\n");
+        assert_eq!(
+            response.stdout_b64,
+            Some(Base64Bytes(b"\x00\xff\n".to_vec()))
+        );
+        assert_eq!(response.stderr_b64, None);
+
+        // the lossy text of both is the same, the bytes are not
+        let response = verdict(Language::C, result(b"\xfe"), b"\xff", None);
+        assert_eq!(response.status, JudgeStatus::WrongAnswer);
+
+        let response = verdict(Language::C, result(b"ok\n"), b"ok\n", None);
+        assert!(!response.binary_output);
+        assert_eq!(response.stdout_b64, None);
+    }
+
+    #[test]
+    fn judged_file_is_compared_instead_of_stdout() {
+        let file = |name: &str, content: Option<&[u8]>, truncated| OutputFile {
+            name: name.to_string(),
+            content: content.map(<[u8]>::to_vec),
+            truncated,
+        };
+        let result = |output_files| CodeExecutionResult {
+            status: CodeExecutionStatus::Success,
+            stdout: b"log\n".to_vec(),
+            stderr: Vec::new(),
+            time: 0.0,
+            memory: 0.0,
+            compile_time: 0.0,
+            output_files,
+            exit_code: Some(0),
+            cached: false,
+        };
+        let judge = |output_files| {
+            verdict(Language::C, result(output_files), b"42\n", Some("out.txt")).status
+        };
+
+        assert_eq!(
+            judge(vec![file("out.txt", Some(b"42\n"), false)]),
+            JudgeStatus::Accepted
+        );
+        assert_eq!(
+            judge(vec![file("out.txt", None, false)]),
+            JudgeStatus::WrongAnswer
+        );
+        assert_eq!(
+            judge(vec![file("out.txt", Some(b"42\n"), true)]),
+            JudgeStatus::WrongAnswer
+        );
+
+        let response = verdict(
+            Language::C,
+            result(vec![file("plot.png", Some(b"\x89PNG"), false)]),
+            b"log\n",
+            None,
+        );
+        assert_eq!(response.status, JudgeStatus::Accepted);
+        let plot = &response.output_files[0];
+        assert!(plot.written);
+        assert_eq!(plot.content_b64, Some(Base64Bytes(b"\x89PNG".to_vec())));
+    }
+
+    #[tokio::test]
+    async fn buffered_events_are_forwarded_before_the_outcome() {
+        let (sender, receiver) = mpsc::channel(8);
+        let outcome = JudgeStreamEvent::Error(ErrorResponse {
+            error: ErrorDetail {
+                code: "internal_error".to_string(),
+                message: "failed".to_string(),
+                details: None,
+                request_id: None,
+            },
+        });
+        let expected = outcome.clone();
+        let run = async move {
+            sender
+                .send(ExecutionEvent::Phase(ExecutionPhase::Running))
+                .await
+                .unwrap();
+            // left in the channel when the run completes
+            sender
+                .send(ExecutionEvent::Output(OutputChunk {
+                    stream: OutputStream::Stdout,
+                    data: b"42\n".to_vec(),
+                }))
+                .await
+                .unwrap();
+            outcome
+        };
+
+        let events: Vec<_> = event_stream(run, receiver)
+            .map(|event| (event.name(), event))
+            .collect()
+            .await;
+        assert_eq!(
+            events.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            ["phase", "output", "error"]
+        );
+        assert_eq!(events[2].1, expected);
+    }
+}
@@ -0,0 +1,77 @@
+use pecan_core::selftest::LanguageSelfTestResult;
+use pecan_core::toolchains::{Language, language_toolchain};
+
+use crate::api::error::APIError;
+use crate::application::state::SharedState;
+use crate::domain::models::health::{HealthResponse, HealthStatus};
+use crate::domain::models::language::{
+    LanguageListResponse, LanguageResponse, LanguageSelfTestResponse,
+};
+
+impl From<&LanguageSelfTestResult> for LanguageSelfTestResponse {
+    fn from(result: &LanguageSelfTestResult) -> Self {
+        Self {
+            language: result.language.as_str().to_string(),
+            passed: result.passed,
+            compile_time: result.compile_time,
+            run_time: result.run_time,
+            message: result.message.clone(),
+        }
+    }
+}
+
+/// list every registered toolchain along with its latest self-test result
+pub async fn list_languages(state: &SharedState) -> Result<LanguageListResponse, APIError> {
+    let report = state.service.self_test_report();
+
+    let languages = Language::SUPPORTED
+        .into_iter()
+        .filter_map(|language| {
+            let toolchain = language_toolchain(language)?;
+            let self_test = report.as_ref().and_then(|report| {
+                report
+                    .results
+                    .iter()
+                    .find(|result| result.language == language)
+                    .map(LanguageSelfTestResponse::from)
+            });
+
+            Some(LanguageResponse {
+                identifier: toolchain.identifier,
+                name: toolchain.name,
+                version: toolchain.version,
+                // languages are assumed available until a self-test proves otherwise
+                available: self_test.as_ref().map(|r| r.passed).unwrap_or(true),
+                self_test,
+            })
+        })
+        .collect();
+
+    Ok(LanguageListResponse { languages })
+}
+
+/// report `Degraded` when the latest self-test marked any toolchain unavailable
+pub async fn get_health(state: &SharedState) -> HealthResponse {
+    let unavailable_languages: Vec<String> = state
+        .service
+        .self_test_report()
+        .map(|report| {
+            report
+                .unavailable_languages()
+                .into_iter()
+                .map(|language| language.as_str().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    HealthResponse {
+        status: if state.service.is_draining() {
+            HealthStatus::Draining
+        } else if unavailable_languages.is_empty() {
+            HealthStatus::Ok
+        } else {
+            HealthStatus::Degraded
+        },
+        unavailable_languages,
+    }
+}
@@ -1,6 +1,29 @@
+use pecan_core::{SandboxInfo, SandboxStatus};
+use uuid::Uuid;
+
 use crate::api::error::APIError;
 use crate::application::state::SharedState;
-use crate::domain::models::manager::SandboxStatusResponse;
+use crate::domain::models::language::LanguageSelfTestResponse;
+use crate::domain::models::manager::{
+    DrainRequest, DrainResponse, ResizePoolRequest, SandboxListResponse, SandboxResponse,
+    SandboxState, SandboxStatusResponse, SelfTestResponse,
+};
+
+impl From<SandboxInfo> for SandboxResponse {
+    fn from(info: SandboxInfo) -> Self {
+        SandboxResponse {
+            id: info.id,
+            status: match info.status {
+                SandboxStatus::Idle => SandboxState::Idle,
+                SandboxStatus::Running => SandboxState::Running,
+                SandboxStatus::Error => SandboxState::Error,
+            },
+            running_since: info.running_since,
+            box_id: info.box_id,
+            path: info.path.to_string_lossy().to_string(),
+        }
+    }
+}
 
 /// get detailed sandbox service status
 pub async fn get_sandbox_status(state: &SharedState) -> Result<SandboxStatusResponse, APIError> {
@@ -17,3 +40,72 @@ pub async fn get_sandbox_status(state: &SharedState) -> Result<SandboxStatusResp
         error_sandboxes: error_sandboxes_count,
     })
 }
+
+/// list every sandbox of the pool
+pub async fn list_sandboxes(state: &SharedState) -> Result<SandboxListResponse, APIError> {
+    Ok(SandboxListResponse {
+        sandboxes: state
+            .service
+            .list_sandboxes()
+            .into_iter()
+            .map(SandboxResponse::from)
+            .collect(),
+    })
+}
+
+/// resize the sandbox pool and return the resulting status
+pub async fn resize_pool(
+    request: ResizePoolRequest,
+    state: &SharedState,
+) -> Result<SandboxStatusResponse, APIError> {
+    let size = state.service.resize_pool(request.size).await?;
+    tracing::info!(
+        requested = request.size,
+        size,
+        "sandbox pool resized by operator"
+    );
+    get_sandbox_status(state).await
+}
+
+/// replace an idle or failed sandbox with a fresh one
+pub async fn recycle_sandbox(id: Uuid, state: &SharedState) -> Result<(), APIError> {
+    state.service.recycle_sandbox(id)?;
+    Ok(())
+}
+
+pub async fn get_drain(state: &SharedState) -> Result<DrainResponse, APIError> {
+    let service = &state.service;
+    Ok(DrainResponse {
+        draining: service.is_draining(),
+        running_sandboxes: service.get_running_sandboxes_count().await,
+        queued_tasks: service.get_queue_depth(),
+    })
+}
+
+/// enter or leave drain mode
+pub async fn set_drain(
+    request: DrainRequest,
+    state: &SharedState,
+) -> Result<DrainResponse, APIError> {
+    state.service.set_draining(request.draining);
+    tracing::warn!(
+        draining = request.draining,
+        "drain mode changed by operator"
+    );
+    get_drain(state).await
+}
+
+/// run the toolchain self-test on demand and return the fresh report
+pub async fn run_self_test(state: &SharedState) -> Result<SelfTestResponse, APIError> {
+    let report = state.service.run_self_test().await;
+
+    Ok(SelfTestResponse {
+        degraded: report.is_degraded(),
+        finished_at: report.finished_at,
+        results: report
+            .results
+            .iter()
+            .map(LanguageSelfTestResponse::from)
+            .collect(),
+    })
+}
@@ -1,2 +1,7 @@
+pub mod batch_service;
+pub mod function_service;
 pub mod judge_service;
+pub mod language_service;
 pub mod manager_service;
+pub mod problem_service;
+pub mod session_service;
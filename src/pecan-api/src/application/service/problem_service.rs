@@ -0,0 +1,459 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use pecan_core::code_execution::{CodeExecutionRequest, CodeExecutionStatus, Project, ProjectFile};
+use pecan_core::toolchains::{Language, language_toolchain};
+
+use crate::api::error::APIError;
+use crate::application::checker::{CHECKER_ARGS, Check, checker_check, compare};
+use crate::application::problem_package::{
+    Package, PackageError, PackageFiles, PackageProgram, PackageTest, read_dir, read_package,
+};
+use crate::application::problem_store::{CHECKER_FILE, ProblemManifest, ProblemStore};
+use crate::application::project::unpack;
+use crate::application::quota::ClientId;
+use crate::application::scoring::{prerequisites_met, score_subtask, skip_subtask, test_score};
+use crate::application::service::judge_service::{self, priority, project};
+use crate::application::state::SharedState;
+use crate::application::validation::{
+    validate_package, validate_problem_judge_request, validate_problem_upload,
+};
+use crate::domain::models::bytes::payload;
+use crate::domain::models::error::FieldError;
+use crate::domain::models::judge::JudgeStatus;
+use crate::domain::models::problem::{
+    Comparator, ProblemImportRequest, ProblemJudgeRequest, ProblemJudgeResponse, ProblemProgram,
+    ProblemResponse, ProblemTestResult, ProblemUploadRequest,
+};
+
+fn store(state: &SharedState) -> Result<&Arc<ProblemStore>, APIError> {
+    state
+        .problems
+        .as_ref()
+        .ok_or_else(|| APIError::NotFound("problem store is not enabled".to_string()))
+}
+
+fn package_error(field: &str, error: PackageError) -> APIError {
+    APIError::ValidationFailed(vec![FieldError {
+        field: field.to_string(),
+        message: error.to_string(),
+    }])
+}
+
+fn program(program: ProblemProgram) -> PackageProgram {
+    PackageProgram {
+        language: program.language,
+        code: program.code.into_bytes(),
+        files: program
+            .files
+            .iter()
+            .map(|file| {
+                let content = payload(&file.content, file.content_b64.as_ref()).to_vec();
+                (file.path.clone(), content)
+            })
+            .collect(),
+    }
+}
+
+async fn response(
+    manifest: ProblemManifest,
+    store: &ProblemStore,
+) -> Result<ProblemResponse, APIError> {
+    let versions = store.versions(&manifest.id).await?;
+    Ok(ProblemResponse {
+        id: manifest.id,
+        version: manifest.version,
+        time_limit: manifest.time_limit,
+        memory_limit: manifest.memory_limit,
+        tests: manifest.tests,
+        checker: manifest.checker,
+        comparator: manifest.comparator,
+        interactor: manifest.interactor,
+        subtasks: manifest.subtasks,
+        created_at: manifest.created_at,
+        versions,
+    })
+}
+
+/// Validate `package` and store it as the next version of problem `id`.
+async fn save(
+    id: &str,
+    package: Package,
+    state: &SharedState,
+) -> Result<ProblemResponse, APIError> {
+    let store = store(state)?;
+    let (time_limit, memory_limit) =
+        validate_package(&package, &state.config.limits, &state.config.problems)?;
+    let manifest = store.save(id, &package, time_limit, memory_limit).await?;
+    tracing::info!(
+        problem = id,
+        version = manifest.version,
+        tests = manifest.tests,
+        "Stored problem"
+    );
+    response(manifest, store).await
+}
+
+/// Store a new version of problem `id` from its inline tests or archive.
+pub async fn upload(
+    id: String,
+    request: ProblemUploadRequest,
+    state: &SharedState,
+) -> Result<ProblemResponse, APIError> {
+    store(state)?;
+    validate_problem_upload(&request, &state.config.limits)?;
+
+    let problems = &state.config.problems;
+    let mut package = match &request.archive {
+        Some(archive) => {
+            let files = unpack(archive, problems.max_files(), problems.max_size).map_err(|e| {
+                APIError::ValidationFailed(vec![FieldError {
+                    field: "archive".to_string(),
+                    message: e.to_string(),
+                }])
+            })?;
+            let files: PackageFiles = files
+                .into_iter()
+                .map(|file| {
+                    let content = payload(&file.content, file.content_b64.as_ref()).to_vec();
+                    (file.path, content)
+                })
+                .collect();
+            read_package(files).map_err(|e| package_error("archive", e))?
+        }
+        None => Package {
+            tests: request
+                .tests
+                .iter()
+                .map(|test| PackageTest {
+                    input: payload(&test.stdin, test.stdin_b64.as_ref()).to_vec(),
+                    output: payload(&test.desired_stdout, test.desired_stdout_b64.as_ref())
+                        .to_vec(),
+                })
+                .collect(),
+            ..Package::default()
+        },
+    };
+    package.time_limit = request.time_limit.or(package.time_limit);
+    package.memory_limit = request.memory_limit.or(package.memory_limit);
+    // a checker or comparator of the request replaces either of the archive
+    if request.checker.is_some() || request.comparator.is_some() {
+        package.checker = request.checker.map(program);
+        package.comparator = request.comparator;
+    }
+    if let Some(interactor) = request.interactor {
+        package.interactor = Some(program(interactor));
+    }
+    if !request.subtasks.is_empty() {
+        package.subtasks = request.subtasks;
+    }
+    save(&id, package, state).await
+}
+
+/// `path` below `import_dir`; relative paths that climb out of it are refused
+/// up front and links that lead out of it once resolved.
+async fn import_path(import_dir: &str, path: &str) -> Result<PathBuf, APIError> {
+    let invalid =
+        || APIError::InvalidRequest(format!("{} is not below the import directory", path));
+    let relative = Path::new(path);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(invalid());
+    }
+    let root = tokio::fs::canonicalize(import_dir)
+        .await
+        .map_err(|e| APIError::InternalError(format!("import directory: {}", e)))?;
+    let dir = match tokio::fs::canonicalize(root.join(relative)).await {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(APIError::NotFound(format!("{} does not exist", path)));
+        }
+        Err(e) => return Err(APIError::InternalError(e.to_string())),
+    };
+    if !dir.starts_with(&root) {
+        return Err(invalid());
+    }
+    Ok(dir)
+}
+
+/// Store a new version of problem `id` from a package directory on the server.
+pub async fn import(
+    id: String,
+    request: ProblemImportRequest,
+    state: &SharedState,
+) -> Result<ProblemResponse, APIError> {
+    store(state)?;
+    let problems = &state.config.problems;
+    let Some(import_dir) = &problems.import_dir else {
+        return Err(APIError::NotFound(
+            "problem import is not enabled".to_string(),
+        ));
+    };
+    let dir = import_path(import_dir, &request.path).await?;
+    let files = read_dir(&dir, problems.max_files(), problems.max_size)
+        .await
+        .map_err(|e| package_error("path", e))?;
+    let package = read_package(files).map_err(|e| package_error("path", e))?;
+    save(&id, package, state).await
+}
+
+pub async fn get(
+    id: &str,
+    version: Option<u32>,
+    state: &SharedState,
+) -> Result<ProblemResponse, APIError> {
+    let store = store(state)?;
+    let manifest = store.get(id, version).await?;
+    response(manifest, store).await
+}
+
+pub async fn delete(id: &str, state: &SharedState) -> Result<(), APIError> {
+    store(state)?.remove(id).await?;
+    tracing::info!(problem = id, "Deleted problem");
+    Ok(())
+}
+
+/// Tests of one submission, each run at most once however many subtasks
+/// share it.
+struct TestRun<'a> {
+    request: &'a ProblemJudgeRequest,
+    manifest: &'a ProblemManifest,
+    store: &'a ProblemStore,
+    state: &'a SharedState,
+    /// language and project of the checker program
+    checker: Option<(Language, Project)>,
+    /// score of every test that ran
+    scores: Vec<Option<f64>>,
+    results: Vec<ProblemTestResult>,
+    status: JudgeStatus,
+    /// a test failed with `stop_on_failure` set
+    stopped: bool,
+}
+
+impl TestRun<'_> {
+    async fn run(&mut self, index: usize) -> Result<(), APIError> {
+        if self.stopped || self.scores[index].is_some() {
+            return Ok(());
+        }
+        let test = self.store.read_test(self.manifest, index).await?;
+        let language = Language::from(self.request.language.as_str());
+        // boxed, as an execution's future is too large to nest in this one's
+        let result = Box::pin(self.state.service.execute_admitted(CodeExecutionRequest {
+            language,
+            code: self.request.code.clone(),
+            project: self.request.project.as_ref().map(project),
+            input: test.input.clone(),
+            timeout: self.manifest.time_limit,
+            memory_limit: self.manifest.memory_limit,
+            priority: priority(self.request.priority),
+            queue_timeout: Some(self.manifest.time_limit),
+            output_files: Vec::new(),
+            args: Vec::new(),
+            input_files: Vec::new(),
+            bypass_artifact_cache: false,
+        }))
+        .await?;
+        let check = match result.status {
+            CodeExecutionStatus::Success => Some(self.check(test, &result.stdout).await?),
+            _ => None,
+        };
+        let response = judge_service::response(language, result, check);
+
+        let failed = response.status != JudgeStatus::Accepted;
+        if failed && self.status == JudgeStatus::Accepted {
+            self.status = response.status.clone();
+        }
+        self.stopped = failed && self.request.stop_on_failure;
+        self.scores[index] = Some(test_score(&response));
+        self.results.push(ProblemTestResult {
+            test: index,
+            result: response,
+        });
+        Ok(())
+    }
+
+    /// Check `output` against the answer of `test` with the problem's checker
+    /// program or comparator.
+    async fn check(&self, test: PackageTest, output: &[u8]) -> Result<Check, APIError> {
+        let Some((language, checker)) = &self.checker else {
+            let comparator = self.manifest.comparator.as_ref();
+            return Ok(compare(
+                comparator.unwrap_or(&Comparator::Exact),
+                output,
+                &test.output,
+            ));
+        };
+        let problems = &self.state.config.problems;
+        let file = |path: &str, content: Vec<u8>| ProjectFile {
+            path: path.to_string(),
+            content,
+        };
+        let [input, output_path, answer] = CHECKER_ARGS;
+        let result = Box::pin(self.state.service.execute_admitted(CodeExecutionRequest {
+            language: *language,
+            code: String::new(),
+            project: Some(checker.clone()),
+            input: Vec::new(),
+            timeout: problems.checker_time_limit,
+            memory_limit: problems.checker_memory_limit,
+            priority: priority(self.request.priority),
+            queue_timeout: Some(problems.checker_time_limit),
+            output_files: Vec::new(),
+            args: CHECKER_ARGS.map(String::from).to_vec(),
+            input_files: vec![
+                file(input, test.input),
+                file(output_path, output.to_vec()),
+                file(answer, test.output),
+            ],
+            bypass_artifact_cache: false,
+        }))
+        .await?;
+        Ok(checker_check(&result))
+    }
+}
+
+/// The checker program of `manifest` as a project, its source placed at the
+/// entry point of its language.
+async fn checker_project(
+    manifest: &ProblemManifest,
+    store: &ProblemStore,
+) -> Result<Option<(Language, Project)>, APIError> {
+    let Some(language_name) = &manifest.checker else {
+        return Ok(None);
+    };
+    let language = Language::from(language_name.as_str());
+    let toolchain = language_toolchain(language).ok_or_else(|| {
+        APIError::InternalError(format!(
+            "checker language {} is not supported",
+            language_name
+        ))
+    })?;
+    let checker = store
+        .read_program(manifest, CHECKER_FILE, language_name)
+        .await?;
+    let mut files = vec![ProjectFile {
+        path: toolchain.project_option.entry_point,
+        content: checker.code,
+    }];
+    files.extend(
+        checker
+            .files
+            .into_iter()
+            .map(|(path, content)| ProjectFile { path, content }),
+    );
+    Ok(Some((
+        language,
+        Project {
+            files,
+            entry_point: None,
+        },
+    )))
+}
+
+/// Judge a submission against the tests of a stored problem, one after the
+/// other; test data is read from the store as each test starts. Subtasks run
+/// in order, and those whose prerequisites fell short are skipped along with
+/// the tests no other subtask needs.
+#[tracing::instrument(skip_all, fields(problem = %request.problem_id, language = %request.language, client = %client))]
+pub async fn judge(
+    mut request: ProblemJudgeRequest,
+    client: &ClientId,
+    state: &SharedState,
+) -> Result<ProblemJudgeResponse, APIError> {
+    let store = store(state)?;
+    validate_problem_judge_request(&mut request, &state.config.limits)?;
+    let manifest = store.get(&request.problem_id, request.version).await?;
+    if manifest.interactor.is_some() {
+        return Err(APIError::Conflict(format!(
+            "problem {} needs an interactor, which cannot be run yet",
+            manifest.id
+        )));
+    }
+    let checker = checker_project(&manifest, store).await?;
+    let _inflight = state.quotas.acquire_inflight(client)?;
+    state.service.admit()?;
+
+    let mut run = TestRun {
+        request: &request,
+        manifest: &manifest,
+        store,
+        state,
+        checker,
+        scores: vec![None; manifest.tests],
+        results: Vec::with_capacity(manifest.tests),
+        status: JudgeStatus::Accepted,
+        stopped: false,
+    };
+    let mut subtasks = Vec::with_capacity(manifest.subtasks.len());
+    for (index, subtask) in manifest.subtasks.iter().enumerate() {
+        if !prerequisites_met(subtask, &subtasks) {
+            subtasks.push(skip_subtask(index, subtask));
+            continue;
+        }
+        for &test in &subtask.tests {
+            run.run(test).await?;
+        }
+        subtasks.push(score_subtask(index, subtask, &run.scores));
+    }
+    // tests outside every subtask still run for their verdicts
+    let grouped: HashSet<usize> = manifest
+        .subtasks
+        .iter()
+        .flat_map(|subtask| subtask.tests.iter().copied())
+        .collect();
+    for index in (0..manifest.tests).filter(|index| !grouped.contains(index)) {
+        run.run(index).await?;
+    }
+
+    let (status, tests) = (run.status, run.results);
+    let scored = !subtasks.is_empty();
+    Ok(ProblemJudgeResponse {
+        problem_id: manifest.id,
+        version: manifest.version,
+        code: status.clone().into_status_code(),
+        status,
+        time: tests
+            .iter()
+            .map(|test| test.result.time)
+            .fold(0.0, f64::max),
+        memory: tests
+            .iter()
+            .map(|test| test.result.memory)
+            .fold(0.0, f64::max),
+        tests,
+        score: scored.then(|| subtasks.iter().map(|subtask| subtask.score).sum()),
+        max_score: scored.then(|| subtasks.iter().map(|subtask| subtask.max_score).sum()),
+        subtasks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import_path;
+    use crate::api::error::APIError;
+
+    #[tokio::test]
+    async fn imports_stay_below_the_import_directory() {
+        let root = std::env::temp_dir().join(format!("pecan-import-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("sum")).unwrap();
+        std::os::unix::fs::symlink("/etc", root.join("escape")).unwrap();
+        let import_dir = root.to_str().unwrap();
+
+        let dir = import_path(import_dir, "./sum").await.unwrap();
+        assert!(dir.ends_with("sum"));
+        for path in ["../sum", "/etc", "escape"] {
+            assert!(matches!(
+                import_path(import_dir, path).await,
+                Err(APIError::InvalidRequest(_))
+            ));
+        }
+        assert!(matches!(
+            import_path(import_dir, "missing").await,
+            Err(APIError::NotFound(_))
+        ));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
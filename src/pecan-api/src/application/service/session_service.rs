@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use futures::Stream;
+use pecan_core::code_execution::{CodeExecutionRequest, CodeExecutionStatus, InteractiveInput};
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::api::error::APIError;
+use crate::api::middleware::request_id::current_request_id;
+use crate::application::quota::{ClientId, InflightPermit};
+use crate::application::service::judge_service::{
+    STREAM_EVENT_BUFFER, event_stream, priority, project,
+};
+use crate::application::state::SharedState;
+use crate::application::validation::validate_session_request;
+use crate::domain::models::judge::JudgeStreamEvent;
+use crate::domain::models::session::{SessionExit, SessionExitStatus, SessionStartRequest};
+
+/// Reserve an execution for a session about to be opened; the permit is held
+/// until the session ends.
+pub fn admit_session(client: &ClientId, state: &SharedState) -> Result<InflightPermit, APIError> {
+    let inflight = state.quotas.acquire_inflight(client)?;
+    state.service.admit()?;
+    Ok(inflight)
+}
+
+/// Start the program of a session. Returns the sender of its standard input
+/// and the stream of its progress and output, ending with its exit. Dropping
+/// the stream kills the program.
+#[tracing::instrument(skip_all, fields(language = %request.language))]
+pub fn start_session(
+    mut request: SessionStartRequest,
+    inflight: InflightPermit,
+    state: &SharedState,
+) -> Result<
+    (
+        mpsc::UnboundedSender<Vec<u8>>,
+        impl Stream<Item = JudgeStreamEvent> + Send + use<>,
+    ),
+    APIError,
+> {
+    validate_session_request(&mut request, &state.config.limits)?;
+
+    let request_id = current_request_id();
+    let (input, input_receiver) = mpsc::unbounded_channel();
+    let (sender, receiver) = mpsc::channel(STREAM_EVENT_BUFFER);
+    let wall_time_limit = state.config.session.max_duration().as_secs_f64();
+    let state = Arc::clone(state);
+    let run = async move {
+        let _inflight = inflight;
+        let execution = CodeExecutionRequest {
+            language: request.language.as_str().into(),
+            code: request.code,
+            project: request.project.as_ref().map(project),
+            input: Vec::new(),
+            timeout: request.time_limit,
+            memory_limit: request.memory_limit,
+            priority: priority(request.priority),
+            queue_timeout: Some(request.time_limit),
+            output_files: Vec::new(),
+            args: Vec::new(),
+            input_files: Vec::new(),
+            bypass_artifact_cache: false,
+        };
+        let interactive = InteractiveInput {
+            input: input_receiver,
+            wall_time_limit,
+        };
+        match state
+            .service
+            .execute_interactive(execution, &sender, interactive)
+            .await
+        {
+            Ok(result) => JudgeStreamEvent::Exit(SessionExit {
+                status: match result.status {
+                    CodeExecutionStatus::Success => SessionExitStatus::Success,
+                    CodeExecutionStatus::CompileError => SessionExitStatus::CompileError,
+                    CodeExecutionStatus::RuntimeError => SessionExitStatus::RuntimeError,
+                    CodeExecutionStatus::TimeLimitExceeded => SessionExitStatus::TimeLimitExceeded,
+                    CodeExecutionStatus::MemoryLimitExceeded => {
+                        SessionExitStatus::MemoryLimitExceeded
+                    }
+                    CodeExecutionStatus::InternalError => SessionExitStatus::InternalError,
+                    CodeExecutionStatus::Aborted => SessionExitStatus::Aborted,
+                },
+                time: result.time,
+                memory: result.memory,
+            }),
+            Err(e) => JudgeStreamEvent::Error(APIError::from(e).to_body(request_id)),
+        }
+    }
+    .instrument(tracing::Span::current());
+
+    Ok((input, event_stream(run, receiver)))
+}
@@ -1,10 +1,26 @@
 use std::sync::Arc;
 
+use metrics_exporter_prometheus::PrometheusHandle;
+
+use crate::application::auth::ApiKeyStore;
+use crate::application::batch::BatchStore;
 use crate::application::config::Config;
+use crate::application::idempotency::IdempotencyStore;
+use crate::application::problem_store::ProblemStore;
+use crate::application::quota::ClientQuotas;
 
 pub type SharedState = Arc<AppState>;
 
 pub struct AppState {
     pub config: Config,
     pub service: Arc<pecan_core::service::Service>,
+    /// `None` when authentication is disabled
+    pub api_keys: Option<Arc<ApiKeyStore>>,
+    pub quotas: Arc<ClientQuotas>,
+    pub batches: BatchStore,
+    pub idempotency: Arc<IdempotencyStore>,
+    /// `None` when no problem store directory is configured
+    pub problems: Option<Arc<ProblemStore>>,
+    /// `None` when metrics are disabled
+    pub metrics: Option<PrometheusHandle>,
 }
@@ -0,0 +1,219 @@
+//! Logging, tracing and Prometheus metrics of the API server.
+//!
+//! `pecan-sandbox` and `pecan-core` emit spans through `tracing` and values
+//! through the `metrics` facade; this module installs the subscriber and the
+//! recorder, adds the API-level metrics and refreshes the point-in-time
+//! gauges on every scrape.
+
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use pecan_core::Priority;
+use pecan_core::service::Service;
+use pecan_core::telemetry::{
+    EXECUTION_COMPILE_SECONDS, EXECUTION_MEMORY_KILOBYTES, EXECUTION_RUN_SECONDS,
+    EXECUTION_WAIT_SECONDS,
+};
+use pecan_core::toolchains::Language;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::domain::models::judge::JudgeStatus;
+
+/// judge verdicts, labelled by `language` and `status`
+pub const VERDICTS_TOTAL: &str = "pecan_verdicts_total";
+/// webhook deliveries, labelled by `outcome`
+pub const WEBHOOK_DELIVERIES_TOTAL: &str = "pecan_webhook_deliveries_total";
+/// sandboxes by `state` (`idle`, `running` or `error`)
+pub const SANDBOXES: &str = "pecan_sandboxes";
+/// async tasks waiting in the task queue, by priority
+pub const TASK_QUEUE_DEPTH: &str = "pecan_task_queue_depth";
+
+/// Keeps the OTLP exporter alive; pending spans are flushed on drop.
+pub struct TracingGuard {
+    #[cfg(feature = "otlp")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(tracer_provider) = self.tracer_provider.take() {
+            if let Err(e) = tracer_provider.shutdown() {
+                eprintln!("Failed to flush OTLP spans: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn build_tracer_provider()
+-> Result<opentelemetry_sdk::trace::SdkTracerProvider, opentelemetry_otlp::ExporterBuildError> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    // endpoint, headers and timeout are read from the standard OTEL_EXPORTER_OTLP_* variables
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_protocol(opentelemetry_otlp::Protocol::HttpBinary)
+        .build()?;
+    let service_name =
+        std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| env!("CARGO_PKG_NAME").to_string());
+
+    Ok(opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name(service_name)
+                .build(),
+        )
+        .build())
+}
+
+/// Install the global `tracing` subscriber. Spans are also exported over OTLP
+/// when built with the `otlp` feature and `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+pub fn init_tracing() -> TracingGuard {
+    let filter_layer = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::try_new("info").unwrap());
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .compact()
+        .with_target(false)
+        .with_file(true)
+        .with_line_number(true);
+    let registry = tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(fmt_layer);
+    let otlp_endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty());
+
+    #[cfg(feature = "otlp")]
+    {
+        use opentelemetry::trace::TracerProvider;
+
+        let tracer_provider = otlp_endpoint.map(|_| {
+            build_tracer_provider()
+                .unwrap_or_else(|e| panic!("Failed to build OTLP exporter: {}", e))
+        });
+        let otel_layer = tracer_provider.as_ref().map(|tracer_provider| {
+            tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("pecan-api"))
+        });
+        registry.with(otel_layer).init();
+        if tracer_provider.is_some() {
+            tracing::info!("Exporting traces over OTLP");
+        }
+
+        TracingGuard { tracer_provider }
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    {
+        registry.init();
+        if otlp_endpoint.is_some() {
+            tracing::warn!(
+                "OTEL_EXPORTER_OTLP_ENDPOINT is set but pecan-api was built without the `otlp` feature"
+            );
+        }
+
+        TracingGuard {}
+    }
+}
+
+const SECONDS_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 60.0,
+];
+const KILOBYTES_BUCKETS: &[f64] = &[
+    1024.0, 4096.0, 16384.0, 65536.0, 131072.0, 262144.0, 524288.0, 1048576.0, 2097152.0,
+];
+
+fn prometheus_builder() -> Result<PrometheusBuilder, BuildError> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(EXECUTION_COMPILE_SECONDS.to_string()),
+            SECONDS_BUCKETS,
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(EXECUTION_RUN_SECONDS.to_string()),
+            SECONDS_BUCKETS,
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(EXECUTION_WAIT_SECONDS.to_string()),
+            SECONDS_BUCKETS,
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(EXECUTION_MEMORY_KILOBYTES.to_string()),
+            KILOBYTES_BUCKETS,
+        )
+}
+
+/// Install the process-wide Prometheus recorder. Fails if one is already installed.
+pub fn install_recorder() -> Result<PrometheusHandle, BuildError> {
+    let handle = prometheus_builder()?.install_recorder()?;
+    describe_metrics();
+    Ok(handle)
+}
+
+fn describe_metrics() {
+    pecan_core::telemetry::describe_metrics();
+    metrics::describe_counter!(VERDICTS_TOTAL, "Judge verdicts");
+    metrics::describe_counter!(WEBHOOK_DELIVERIES_TOTAL, "Webhook delivery attempts");
+    metrics::describe_gauge!(SANDBOXES, "Sandboxes by state");
+    metrics::describe_gauge!(TASK_QUEUE_DEPTH, "Async tasks waiting in the task queue");
+}
+
+pub fn record_verdict(language: Language, status: &JudgeStatus) {
+    metrics::counter!(
+        VERDICTS_TOTAL,
+        "language" => language.as_str(),
+        "status" => status.as_str()
+    )
+    .increment(1);
+}
+
+pub fn record_webhook_delivery(outcome: &'static str) {
+    metrics::counter!(WEBHOOK_DELIVERIES_TOTAL, "outcome" => outcome).increment(1);
+}
+
+fn record_pool(idle: usize, running: usize, error: usize) {
+    metrics::gauge!(SANDBOXES, "state" => "idle").set(idle as f64);
+    metrics::gauge!(SANDBOXES, "state" => "running").set(running as f64);
+    metrics::gauge!(SANDBOXES, "state" => "error").set(error as f64);
+}
+
+/// Refresh the gauges that are sampled rather than updated on every event.
+pub async fn update_gauges(service: &Service) {
+    record_pool(
+        service.get_idle_sandboxes_count().await,
+        service.get_running_sandboxes_count().await,
+        service.get_error_sandboxes_count().await,
+    );
+    for priority in Priority::ALL {
+        metrics::gauge!(TASK_QUEUE_DEPTH, "priority" => priority.as_str())
+            .set(service.get_queue_depth_of(priority) as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pecan_core::toolchains::Language;
+
+    use super::{describe_metrics, prometheus_builder, record_pool, record_verdict};
+    use crate::domain::models::judge::JudgeStatus;
+
+    #[test]
+    fn verdicts_and_pool_gauges_are_rendered() {
+        let recorder = prometheus_builder().unwrap().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            describe_metrics();
+            record_verdict(Language::Python, &JudgeStatus::Accepted);
+            record_verdict(Language::Python, &JudgeStatus::Accepted);
+            record_pool(3, 1, 0);
+        });
+
+        let rendered = handle.render();
+        assert!(rendered.contains("# HELP pecan_verdicts_total Judge verdicts"));
+        assert!(
+            rendered.contains(r#"pecan_verdicts_total{language="python",status="accepted"} 2"#)
+        );
+        assert!(rendered.contains(r#"pecan_sandboxes{state="idle"} 3"#));
+        assert!(rendered.contains(r#"pecan_sandboxes{state="running"} 1"#));
+    }
+}
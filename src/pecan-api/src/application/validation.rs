@@ -0,0 +1,1323 @@
+//! Validation of judge requests against the server-side `LimitsConfig`.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use pecan_core::harness::{Parameter, Signature, ValueType, check_identifier, encode};
+use pecan_core::toolchains::{Language, language_toolchain};
+
+use crate::api::error::APIError;
+use crate::application::checker::CHECKER_ARGS;
+use crate::application::config::{LimitPolicy, LimitsConfig, ProblemConfig};
+use crate::application::problem_package::Package;
+use crate::application::project::unpack;
+use crate::domain::models::batch::BatchRequest;
+use crate::domain::models::bytes::{Base64Bytes, payload};
+use crate::domain::models::error::FieldError;
+use crate::domain::models::function::FunctionJudgeRequest;
+use crate::domain::models::judge::{JudgeAsyncRequest, JudgeRequest};
+use crate::domain::models::output_file::OutputFileRequest;
+use crate::domain::models::problem::{Comparator, ProblemJudgeRequest, ProblemUploadRequest};
+use crate::domain::models::project::ProjectRequest;
+use crate::domain::models::session::SessionStartRequest;
+
+/// Files the sandbox itself writes into the working directory.
+const RESERVED_PATHS: [&str; 1] = ["stdin.txt"];
+
+/// Collects field errors of one request.
+#[derive(Default)]
+struct Violations(Vec<FieldError>);
+
+impl Violations {
+    fn push(&mut self, field: &str, message: String) {
+        self.0.push(FieldError {
+            field: field.to_string(),
+            message,
+        });
+    }
+
+    fn into_result(self) -> Result<(), APIError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(APIError::ValidationFailed(self.0))
+        }
+    }
+}
+
+fn check_size(violations: &mut Violations, field: &str, value: &[u8], max: usize) {
+    if value.len() > max {
+        violations.push(
+            field,
+            format!("must be at most {} bytes, got {}", max, value.len()),
+        );
+    }
+}
+
+/// Check `value` against `[min, max]`, clamping it in place under `LimitPolicy::Clamp`.
+fn check_range(
+    violations: &mut Violations,
+    field: &str,
+    value: &mut f64,
+    min: f64,
+    max: f64,
+    policy: LimitPolicy,
+) {
+    if !value.is_finite() {
+        violations.push(field, "must be a finite number".to_string());
+        return;
+    }
+    if *value <= 0.0 {
+        violations.push(field, "must be positive".to_string());
+        return;
+    }
+    if (min..=max).contains(value) {
+        return;
+    }
+    match policy {
+        LimitPolicy::Clamp => *value = value.clamp(min, max),
+        LimitPolicy::Reject => {
+            violations.push(field, format!("must be between {} and {}", min, max))
+        }
+    }
+}
+
+/// Check that exactly one of `code` and `project` is given; an archive of
+/// the project is unpacked into its files in place.
+fn check_source(
+    violations: &mut Violations,
+    prefix: &str,
+    code: &str,
+    project: &mut Option<ProjectRequest>,
+    language: &str,
+    limits: &LimitsConfig,
+) {
+    let field = |name: &str| format!("{}{}", prefix, name);
+    match project {
+        None => {
+            if code.is_empty() {
+                violations.push(&field("code"), "must not be empty".to_string());
+            }
+            check_size(
+                violations,
+                &field("code"),
+                code.as_bytes(),
+                limits.max_source_size,
+            );
+        }
+        Some(_) if !code.is_empty() => violations.push(
+            &field("project"),
+            "must not be combined with code".to_string(),
+        ),
+        Some(project) => check_project(violations, &field("project"), project, language, limits),
+    }
+}
+
+fn check_project(
+    violations: &mut Violations,
+    field: &str,
+    project: &mut ProjectRequest,
+    language: &str,
+    limits: &LimitsConfig,
+) {
+    let field = |name: &str| format!("{}.{}", field, name);
+    if let Some(archive) = project.archive.take() {
+        if !project.files.is_empty() {
+            violations.push(
+                &field("archive"),
+                "must not be combined with files".to_string(),
+            );
+            return;
+        }
+        match unpack(&archive, limits.max_project_files, limits.max_project_size) {
+            Ok(files) => project.files = files,
+            Err(e) => {
+                violations.push(&field("archive"), e.to_string());
+                return;
+            }
+        }
+    }
+
+    if project.files.is_empty() {
+        violations.push(&field("files"), "must not be empty".to_string());
+        return;
+    }
+    if project.files.len() > limits.max_project_files {
+        violations.push(
+            &field("files"),
+            format!(
+                "must contain at most {} files, got {}",
+                limits.max_project_files,
+                project.files.len()
+            ),
+        );
+    }
+    let size: usize = project
+        .files
+        .iter()
+        .map(|file| payload(&file.content, file.content_b64.as_ref()).len())
+        .sum();
+    if size > limits.max_project_size {
+        violations.push(
+            &field("files"),
+            format!(
+                "must be at most {} bytes in total, got {}",
+                limits.max_project_size, size
+            ),
+        );
+    }
+
+    let mut paths = HashSet::new();
+    for (i, file) in project.files.iter().enumerate() {
+        let path_field = field(&format!("files[{}].path", i));
+        if file.content_b64.is_some() && !file.content.is_empty() {
+            violations.push(
+                &field(&format!("files[{}].content_b64", i)),
+                "must not be combined with content".to_string(),
+            );
+        }
+        if let Err(message) = check_path(&file.path, limits.max_project_depth) {
+            violations.push(&path_field, message);
+        } else if !paths.insert(file.path.as_str()) {
+            violations.push(&path_field, "must be unique".to_string());
+        }
+    }
+
+    let Some(toolchain) = language_toolchain(Language::from(language)) else {
+        return;
+    };
+    match &project.entry_point {
+        Some(entry_point) if !paths.contains(entry_point.as_str()) => violations.push(
+            &field("entry_point"),
+            "must be the path of one of the files".to_string(),
+        ),
+        None if !paths.contains(toolchain.project_option.entry_point.as_str()) => violations.push(
+            &field("entry_point"),
+            format!(
+                "must be given when there is no {}",
+                toolchain.project_option.entry_point
+            ),
+        ),
+        _ => {}
+    }
+
+    let project_option = &toolchain.project_option;
+    if project_option.single_directory {
+        let entry_point = project
+            .entry_point
+            .as_deref()
+            .unwrap_or(&project_option.entry_point);
+        for (i, file) in project.files.iter().enumerate() {
+            let is_source = project_option
+                .sources
+                .iter()
+                .any(|extension| file.path.ends_with(extension.as_str()));
+            if is_source && Path::new(&file.path).parent() != Path::new(entry_point).parent() {
+                violations.push(
+                    &field(&format!("files[{}].path", i)),
+                    format!(
+                        "must be in the directory of the entry point, as {} sources are built as one package",
+                        language
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Check that `path` stays inside the working directory: relative,
+/// `/`-separated, without `.` or `..` components and at most `max_depth`
+/// directories deep.
+fn check_path(path: &str, max_depth: usize) -> Result<(), String> {
+    if path.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if path.starts_with('/') {
+        return Err("must be relative".to_string());
+    }
+    if path.contains(['\\', '\0']) {
+        return Err("must use / as the only separator".to_string());
+    }
+    let components: Vec<&str> = path.split('/').collect();
+    if components
+        .iter()
+        .any(|component| component.is_empty() || *component == "." || *component == "..")
+    {
+        return Err("must not contain empty, . or .. components".to_string());
+    }
+    if components.len() - 1 > max_depth {
+        return Err(format!("must be at most {} directories deep", max_depth));
+    }
+    if RESERVED_PATHS.contains(&path) {
+        return Err(format!("{} is reserved", path));
+    }
+    Ok(())
+}
+
+/// Check the declared output files, capping each at the server's size limit
+/// when it gives none, and that `judged_file` is one of them.
+fn check_output_files(
+    violations: &mut Violations,
+    output_files: &mut [OutputFileRequest],
+    judged_file: Option<&str>,
+    limits: &LimitsConfig,
+) {
+    if output_files.len() > limits.max_output_files {
+        violations.push(
+            "output_files",
+            format!(
+                "must contain at most {} files, got {}",
+                limits.max_output_files,
+                output_files.len()
+            ),
+        );
+    }
+
+    let mut names = HashSet::new();
+    for (i, file) in output_files.iter_mut().enumerate() {
+        let field = |name: &str| format!("output_files[{}].{}", i, name);
+        if let Err(message) = check_path(&file.name, limits.max_project_depth) {
+            violations.push(&field("name"), message);
+        } else if !names.insert(file.name.as_str()) {
+            violations.push(&field("name"), "must be unique".to_string());
+        }
+        match file.max_size {
+            None => file.max_size = Some(limits.max_output_file_size),
+            Some(0) => violations.push(&field("max_size"), "must be positive".to_string()),
+            Some(max_size) if max_size > limits.max_output_file_size => violations.push(
+                &field("max_size"),
+                format!("must be at most {}", limits.max_output_file_size),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    if let Some(judged_file) = judged_file {
+        if !names.contains(judged_file) {
+            violations.push(
+                "judged_file",
+                "must be the name of one of the output_files".to_string(),
+            );
+        }
+    }
+}
+
+/// Check a field given either as text or as base64 under its `_b64` name.
+fn check_payload(
+    violations: &mut Violations,
+    field: &str,
+    text: &str,
+    encoded: Option<&Base64Bytes>,
+    max: usize,
+) {
+    let encoded_field = format!("{}_b64", field);
+    if encoded.is_some() && !text.is_empty() {
+        violations.push(
+            &encoded_field,
+            format!("must not be combined with {}", field),
+        );
+    }
+    let field = if encoded.is_some() {
+        &encoded_field
+    } else {
+        field
+    };
+    check_size(violations, field, payload(text, encoded), max);
+}
+
+fn check_limits(
+    violations: &mut Violations,
+    time_limit: &mut f64,
+    memory_limit: &mut f64,
+    limits: &LimitsConfig,
+) {
+    check_range(
+        violations,
+        "time_limit",
+        time_limit,
+        limits.min_time_limit,
+        limits.max_time_limit,
+        limits.policy,
+    );
+    check_range(
+        violations,
+        "memory_limit",
+        memory_limit,
+        limits.min_memory_limit,
+        limits.max_memory_limit,
+        limits.policy,
+    );
+}
+
+/// Validate `request` in place; limits may be clamped depending on the policy.
+pub fn validate_judge_request(
+    request: &mut JudgeRequest,
+    limits: &LimitsConfig,
+) -> Result<(), APIError> {
+    let mut violations = Violations::default();
+    check_source(
+        &mut violations,
+        "",
+        &request.code,
+        &mut request.project,
+        &request.language,
+        limits,
+    );
+    check_payload(
+        &mut violations,
+        "stdin",
+        &request.stdin,
+        request.stdin_b64.as_ref(),
+        limits.max_stdin_size,
+    );
+    check_payload(
+        &mut violations,
+        "desired_stdout",
+        &request.desired_stdout,
+        request.desired_stdout_b64.as_ref(),
+        limits.max_expected_output_size,
+    );
+    check_output_files(
+        &mut violations,
+        &mut request.output_files,
+        request.judged_file.as_deref(),
+        limits,
+    );
+    check_limits(
+        &mut violations,
+        &mut request.time_limit,
+        &mut request.memory_limit,
+        limits,
+    );
+    violations.into_result()
+}
+
+/// Validate `request` in place; limits may be clamped depending on the policy.
+pub fn validate_judge_async_request(
+    request: &mut JudgeAsyncRequest,
+    limits: &LimitsConfig,
+) -> Result<(), APIError> {
+    let mut violations = Violations::default();
+    check_source(
+        &mut violations,
+        "",
+        &request.code,
+        &mut request.project,
+        &request.language,
+        limits,
+    );
+    check_payload(
+        &mut violations,
+        "stdin",
+        &request.stdin,
+        request.stdin_b64.as_ref(),
+        limits.max_stdin_size,
+    );
+    check_payload(
+        &mut violations,
+        "desired_stdout",
+        &request.desired_stdout,
+        request.desired_stdout_b64.as_ref(),
+        limits.max_expected_output_size,
+    );
+    check_output_files(
+        &mut violations,
+        &mut request.output_files,
+        request.judged_file.as_deref(),
+        limits,
+    );
+    check_limits(
+        &mut violations,
+        &mut request.time_limit,
+        &mut request.memory_limit,
+        limits,
+    );
+    check_webhook_url(&mut violations, "webhook_url", &request.webhook_url);
+    violations.into_result()
+}
+
+/// Validate `request` in place; limits may be clamped depending on the policy.
+pub fn validate_session_request(
+    request: &mut SessionStartRequest,
+    limits: &LimitsConfig,
+) -> Result<(), APIError> {
+    let mut violations = Violations::default();
+    check_source(
+        &mut violations,
+        "",
+        &request.code,
+        &mut request.project,
+        &request.language,
+        limits,
+    );
+    check_limits(
+        &mut violations,
+        &mut request.time_limit,
+        &mut request.memory_limit,
+        limits,
+    );
+    violations.into_result()
+}
+
+fn check_webhook_url(violations: &mut Violations, field: &str, url: &str) {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        violations.push(field, "must be an http(s) URL".to_string());
+    }
+}
+
+/// Validate `request` in place; limits may be clamped depending on the policy.
+/// A batch may expand to at most `max_items` executions.
+pub fn validate_batch_request(
+    request: &mut BatchRequest,
+    limits: &LimitsConfig,
+    max_items: usize,
+) -> Result<(), APIError> {
+    let mut violations = Violations::default();
+    if request.submissions.is_empty() {
+        violations.push("submissions", "must not be empty".to_string());
+    }
+    let items = request.submissions.len() * request.test_cases.len().max(1);
+    if items > max_items {
+        violations.push(
+            "submissions",
+            format!(
+                "must expand to at most {} executions, got {}",
+                max_items, items
+            ),
+        );
+    }
+
+    let shared_cases = !request.test_cases.is_empty();
+    for (i, submission) in request.submissions.iter_mut().enumerate() {
+        let field = |name: &str| format!("submissions[{}].{}", i, name);
+        check_source(
+            &mut violations,
+            &field(""),
+            &submission.code,
+            &mut submission.project,
+            &submission.language,
+            limits,
+        );
+        if shared_cases {
+            continue;
+        }
+        check_payload(
+            &mut violations,
+            &field("stdin"),
+            &submission.stdin,
+            submission.stdin_b64.as_ref(),
+            limits.max_stdin_size,
+        );
+        check_payload(
+            &mut violations,
+            &field("desired_stdout"),
+            &submission.desired_stdout,
+            submission.desired_stdout_b64.as_ref(),
+            limits.max_expected_output_size,
+        );
+    }
+    for (i, case) in request.test_cases.iter().enumerate() {
+        let field = |name: &str| format!("test_cases[{}].{}", i, name);
+        check_payload(
+            &mut violations,
+            &field("stdin"),
+            &case.stdin,
+            case.stdin_b64.as_ref(),
+            limits.max_stdin_size,
+        );
+        check_payload(
+            &mut violations,
+            &field("desired_stdout"),
+            &case.desired_stdout,
+            case.desired_stdout_b64.as_ref(),
+            limits.max_expected_output_size,
+        );
+    }
+
+    check_output_files(
+        &mut violations,
+        &mut request.output_files,
+        request.judged_file.as_deref(),
+        limits,
+    );
+    check_range(
+        &mut violations,
+        "time_limit",
+        &mut request.time_limit,
+        limits.min_time_limit,
+        limits.max_time_limit,
+        limits.policy,
+    );
+    check_range(
+        &mut violations,
+        "memory_limit",
+        &mut request.memory_limit,
+        limits.min_memory_limit,
+        limits.max_memory_limit,
+        limits.policy,
+    );
+    if let Some(url) = &request.webhook_url {
+        check_webhook_url(&mut violations, "webhook_url", url);
+    }
+    violations.into_result()
+}
+
+/// Check the fields of an upload that are not part of the package itself.
+pub fn validate_problem_upload(
+    request: &ProblemUploadRequest,
+    limits: &LimitsConfig,
+) -> Result<(), APIError> {
+    let mut violations = Violations::default();
+    if request.archive.is_some() && !request.tests.is_empty() {
+        violations.push("archive", "must not be combined with tests".to_string());
+    }
+    for (i, test) in request.tests.iter().enumerate() {
+        let field = |name: &str| format!("tests[{}].{}", i, name);
+        check_payload(
+            &mut violations,
+            &field("stdin"),
+            &test.stdin,
+            test.stdin_b64.as_ref(),
+            limits.max_stdin_size,
+        );
+        check_payload(
+            &mut violations,
+            &field("desired_stdout"),
+            &test.desired_stdout,
+            test.desired_stdout_b64.as_ref(),
+            limits.max_expected_output_size,
+        );
+    }
+    violations.into_result()
+}
+
+/// Validate a package before it is stored and return its time and memory
+/// limits, which may be clamped depending on the policy.
+pub fn validate_package(
+    package: &Package,
+    limits: &LimitsConfig,
+    problems: &ProblemConfig,
+) -> Result<(f64, f64), APIError> {
+    let mut violations = Violations::default();
+    if package.tests.is_empty() {
+        violations.push("tests", "must not be empty".to_string());
+    }
+    if package.tests.len() > problems.max_tests {
+        violations.push(
+            "tests",
+            format!(
+                "must contain at most {} tests, got {}",
+                problems.max_tests,
+                package.tests.len()
+            ),
+        );
+    }
+    for (i, test) in package.tests.iter().enumerate() {
+        let field = |name: &str| format!("tests[{}].{}", i, name);
+        check_size(
+            &mut violations,
+            &field("stdin"),
+            &test.input,
+            limits.max_stdin_size,
+        );
+        check_size(
+            &mut violations,
+            &field("desired_stdout"),
+            &test.output,
+            limits.max_expected_output_size,
+        );
+    }
+
+    let mut limit = |field: &str, value: Option<f64>, min: f64, max: f64| match value {
+        Some(mut value) => {
+            check_range(&mut violations, field, &mut value, min, max, limits.policy);
+            value
+        }
+        None => {
+            violations.push(
+                field,
+                "must be given by the request or the package".to_string(),
+            );
+            0.0
+        }
+    };
+    let time_limit = limit(
+        "time_limit",
+        package.time_limit,
+        limits.min_time_limit,
+        limits.max_time_limit,
+    );
+    let memory_limit = limit(
+        "memory_limit",
+        package.memory_limit,
+        limits.min_memory_limit,
+        limits.max_memory_limit,
+    );
+
+    for (i, subtask) in package.subtasks.iter().enumerate() {
+        let field = |name: &str| format!("subtasks[{}].{}", i, name);
+        if !subtask.points.is_finite() || subtask.points < 0.0 {
+            violations.push(
+                &field("points"),
+                "must be a non-negative number".to_string(),
+            );
+        }
+        if subtask.tests.is_empty() {
+            violations.push(&field("tests"), "must not be empty".to_string());
+        }
+        if let Some(test) = subtask.tests.iter().find(|&&t| t >= package.tests.len()) {
+            violations.push(&field("tests"), format!("test {} does not exist", test));
+        }
+        if let Some(dependency) = subtask.depends_on.iter().find(|&&d| d >= i) {
+            violations.push(
+                &field("depends_on"),
+                format!("subtask {} is not an earlier subtask", dependency),
+            );
+        }
+    }
+
+    // nothing runs an interactor yet, so such a problem could never be judged
+    if package.interactor.is_some() {
+        violations.push(
+            "interactor",
+            "interactive problems are not supported".to_string(),
+        );
+    }
+
+    if let Some(program) = &package.checker {
+        let field = |name: &str| format!("checker.{}", name);
+        match language_toolchain(Language::from(program.language.as_str())) {
+            None => violations.push(
+                &field("language"),
+                format!("{} is not supported", program.language),
+            ),
+            Some(toolchain) => {
+                if program.files.len() > limits.max_project_files {
+                    violations.push(
+                        &field("files"),
+                        format!("must contain at most {} files", limits.max_project_files),
+                    );
+                }
+                // the source runs as the entry point, next to the files it checks
+                let entry_point = toolchain.project_option.entry_point.as_str();
+                for path in program.files.keys() {
+                    let path_field = field(&format!("files[{}]", path));
+                    if let Err(message) = check_path(path, limits.max_project_depth) {
+                        violations.push(&path_field, message);
+                    } else if path == entry_point || CHECKER_ARGS.contains(&path.as_str()) {
+                        violations.push(&path_field, format!("{} is reserved", path));
+                    }
+                }
+            }
+        }
+    }
+
+    match &package.comparator {
+        Some(_) if package.checker.is_some() => violations.push(
+            "comparator",
+            "must not be combined with a checker".to_string(),
+        ),
+        Some(Comparator::Float { tolerance, partial }) => {
+            let valid = |tolerance: f64| tolerance.is_finite() && tolerance >= 0.0;
+            if !valid(*tolerance) {
+                violations.push(
+                    "comparator.tolerance",
+                    "must be a non-negative number".to_string(),
+                );
+            }
+            for (i, credit) in partial.iter().enumerate() {
+                if !valid(credit.tolerance) {
+                    violations.push(
+                        &format!("comparator.partial[{}].tolerance", i),
+                        "must be a non-negative number".to_string(),
+                    );
+                }
+                if !(0.0..=1.0).contains(&credit.score) {
+                    violations.push(
+                        &format!("comparator.partial[{}].score", i),
+                        "must be between 0 and 1".to_string(),
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+    violations.into_result()?;
+    Ok((time_limit, memory_limit))
+}
+
+pub fn validate_problem_judge_request(
+    request: &mut ProblemJudgeRequest,
+    limits: &LimitsConfig,
+) -> Result<(), APIError> {
+    let mut violations = Violations::default();
+    check_source(
+        &mut violations,
+        "",
+        &request.code,
+        &mut request.project,
+        &request.language,
+        limits,
+    );
+    violations.into_result()
+}
+
+/// Parse the signature of a function judge request and check its cases
+/// against it; returns the signature and the encoded arguments of each case.
+/// The arguments of all cases together are held to the stdin limit and their
+/// expected values to the expected output limit.
+pub fn validate_function_request(
+    request: &mut FunctionJudgeRequest,
+    limits: &LimitsConfig,
+) -> Result<(Signature, Vec<Vec<u8>>), APIError> {
+    let mut violations = Violations::default();
+    if request.code.is_empty() {
+        violations.push("code", "must not be empty".to_string());
+    }
+    check_size(
+        &mut violations,
+        "code",
+        request.code.as_bytes(),
+        limits.max_source_size,
+    );
+    check_limits(
+        &mut violations,
+        &mut request.time_limit,
+        &mut request.memory_limit,
+        limits,
+    );
+    if !request.tolerance.is_finite() || request.tolerance < 0.0 {
+        violations.push("tolerance", "must be a non-negative number".to_string());
+    }
+
+    let signature = &request.signature;
+    if let Err(e) = check_identifier(&signature.name) {
+        violations.push("signature.name", e.to_string());
+    }
+    let mut names = HashSet::new();
+    let mut params = Vec::with_capacity(signature.params.len());
+    for (i, param) in signature.params.iter().enumerate() {
+        let field = |name: &str| format!("signature.params[{}].{}", i, name);
+        if let Err(e) = check_identifier(&param.name) {
+            violations.push(&field("name"), e.to_string());
+        } else if !names.insert(param.name.as_str()) || param.name == signature.name {
+            violations.push(&field("name"), format!("{} is already taken", param.name));
+        }
+        match param.value_type.parse() {
+            Ok(value_type) => params.push(Parameter {
+                name: param.name.clone(),
+                value_type,
+            }),
+            Err(e) => violations.push(&field("type"), e.to_string()),
+        }
+    }
+    let returns = match signature.returns.parse::<ValueType>() {
+        Ok(returns) => Some(returns),
+        Err(e) => {
+            violations.push("signature.returns", e.to_string());
+            None
+        }
+    };
+    // the cases cannot be checked without every type
+    let Some(returns) = returns.filter(|_| params.len() == signature.params.len()) else {
+        return Err(APIError::ValidationFailed(violations.0));
+    };
+
+    if request.cases.is_empty() {
+        violations.push("cases", "must not be empty".to_string());
+    } else if request.cases.len() > limits.max_function_cases {
+        violations.push(
+            "cases",
+            format!(
+                "must contain at most {} cases, got {}",
+                limits.max_function_cases,
+                request.cases.len()
+            ),
+        );
+    }
+    let mut inputs = Vec::with_capacity(request.cases.len());
+    let (mut input_size, mut expected_size) = (0, 0);
+    for (i, case) in request.cases.iter().enumerate() {
+        let field = |name: &str| format!("cases[{}].{}", i, name);
+        let mut input = Vec::new();
+        if case.args.len() != params.len() {
+            violations.push(
+                &field("args"),
+                format!(
+                    "expected {} arguments, found {}",
+                    params.len(),
+                    case.args.len()
+                ),
+            );
+        }
+        for (j, (arg, param)) in case.args.iter().zip(&params).enumerate() {
+            if let Err(e) = encode(arg, &param.value_type, &mut input) {
+                violations.push(&field(&format!("args[{}]", j)), e.to_string());
+            }
+        }
+        let mut expected = Vec::new();
+        if let Err(e) = encode(&case.expected, &returns, &mut expected) {
+            violations.push(&field("expected"), e.to_string());
+        }
+        input_size += input.len();
+        expected_size += expected.len();
+        inputs.push(input);
+    }
+    for (field, size, max) in [
+        ("args", input_size, limits.max_stdin_size),
+        ("expected", expected_size, limits.max_expected_output_size),
+    ] {
+        if size > max {
+            violations.push(
+                "cases",
+                format!(
+                    "{} must be at most {} bytes together, got {}",
+                    field, max, size
+                ),
+            );
+        }
+    }
+    violations.into_result()?;
+
+    let signature = Signature {
+        name: signature.name.clone(),
+        params,
+        returns,
+    };
+    Ok((signature, inputs))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        validate_batch_request, validate_function_request, validate_judge_request, validate_package,
+    };
+    use crate::api::error::APIError;
+    use crate::application::config::{LimitPolicy, LimitsConfig, ProblemConfig};
+    use crate::application::problem_package::{Package, PackageFiles, PackageProgram, PackageTest};
+    use crate::domain::models::batch::{BatchRequest, BatchSubmission, BatchTestCase};
+    use crate::domain::models::bytes::Base64Bytes;
+    use crate::domain::models::judge::{JudgePriority, JudgeRequest};
+    use crate::domain::models::output_file::OutputFileRequest;
+    use crate::domain::models::problem::{Comparator, PartialCredit, Subtask, SubtaskScoring};
+    use crate::domain::models::project::{ProjectFile, ProjectRequest};
+
+    fn limits(policy: LimitPolicy) -> LimitsConfig {
+        LimitsConfig {
+            policy,
+            min_time_limit: 0.1,
+            max_time_limit: 10.0,
+            min_memory_limit: 1024.0,
+            max_memory_limit: 262144.0,
+            max_source_size: 16,
+            max_stdin_size: 16,
+            max_expected_output_size: 16,
+            max_project_files: 3,
+            max_project_size: 32,
+            max_project_depth: 1,
+            max_output_files: 2,
+            max_output_file_size: 64,
+            max_function_cases: 2,
+        }
+    }
+
+    fn request(time_limit: f64, memory_limit: f64) -> JudgeRequest {
+        JudgeRequest {
+            code: "print(1)".to_string(),
+            project: None,
+            language: "python".to_string(),
+            stdin: String::new(),
+            stdin_b64: None,
+            desired_stdout: "1\n".to_string(),
+            desired_stdout_b64: None,
+            time_limit,
+            memory_limit,
+            priority: JudgePriority::Normal,
+            output_files: Vec::new(),
+            judged_file: None,
+        }
+    }
+
+    fn invalid_fields(result: Result<(), APIError>) -> Vec<String> {
+        match result {
+            Err(APIError::ValidationFailed(fields)) => {
+                fields.into_iter().map(|f| f.field).collect()
+            }
+            other => panic!("expected validation failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accepts_values_within_limits() {
+        let mut req = request(1.0, 65536.0);
+        assert!(validate_judge_request(&mut req, &limits(LimitPolicy::Reject)).is_ok());
+        assert_eq!(req.time_limit, 1.0);
+    }
+
+    #[test]
+    fn rejects_non_finite_and_negative_values_under_any_policy() {
+        for policy in [LimitPolicy::Reject, LimitPolicy::Clamp] {
+            let mut req = request(f64::NAN, -1.0);
+            let fields = invalid_fields(validate_judge_request(&mut req, &limits(policy)));
+            assert_eq!(fields, vec!["time_limit", "memory_limit"]);
+        }
+    }
+
+    #[test]
+    fn out_of_range_limits_follow_policy() {
+        let mut req = request(100000.0, 512.0);
+        let fields = invalid_fields(validate_judge_request(
+            &mut req,
+            &limits(LimitPolicy::Reject),
+        ));
+        assert_eq!(fields, vec!["time_limit", "memory_limit"]);
+
+        let mut req = request(100000.0, 512.0);
+        assert!(validate_judge_request(&mut req, &limits(LimitPolicy::Clamp)).is_ok());
+        assert_eq!(req.time_limit, 10.0);
+        assert_eq!(req.memory_limit, 1024.0);
+    }
+
+    #[test]
+    fn rejects_oversized_payload_fields() {
+        let mut req = request(1.0, 65536.0);
+        req.code = "x".repeat(17);
+        req.stdin = "y".repeat(17);
+        let fields = invalid_fields(validate_judge_request(
+            &mut req,
+            &limits(LimitPolicy::Clamp),
+        ));
+        assert_eq!(fields, vec!["code", "stdin"]);
+    }
+
+    #[test]
+    fn base64_payloads_are_checked_after_decoding() {
+        let mut req = request(1.0, 65536.0);
+        req.stdin_b64 = Some(Base64Bytes(vec![0xff; 16]));
+        req.desired_stdout = String::new();
+        req.desired_stdout_b64 = Some(Base64Bytes(vec![0xfe; 17]));
+        let fields = invalid_fields(validate_judge_request(
+            &mut req,
+            &limits(LimitPolicy::Reject),
+        ));
+        assert_eq!(fields, vec!["desired_stdout_b64"]);
+
+        req.desired_stdout = "1\n".to_string();
+        req.desired_stdout_b64 = Some(Base64Bytes(vec![0xfe]));
+        let fields = invalid_fields(validate_judge_request(
+            &mut req,
+            &limits(LimitPolicy::Reject),
+        ));
+        assert_eq!(fields, vec!["desired_stdout_b64"]);
+    }
+
+    #[test]
+    fn batch_errors_name_the_offending_item() {
+        let submission = |code: &str| BatchSubmission {
+            code: code.to_string(),
+            project: None,
+            language: "python".to_string(),
+            stdin: "z".repeat(17),
+            stdin_b64: None,
+            desired_stdout: String::new(),
+            desired_stdout_b64: None,
+        };
+        let mut req = BatchRequest {
+            submissions: vec![submission("print(1)"), submission("")],
+            test_cases: vec![BatchTestCase {
+                stdin: String::new(),
+                stdin_b64: None,
+                desired_stdout: "w".repeat(17),
+                desired_stdout_b64: None,
+            }],
+            time_limit: 1.0,
+            memory_limit: 65536.0,
+            priority: JudgePriority::Bulk,
+            webhook_url: Some("ftp://example.com".to_string()),
+            output_files: Vec::new(),
+            judged_file: None,
+        };
+        let fields = invalid_fields(validate_batch_request(
+            &mut req,
+            &limits(LimitPolicy::Reject),
+            1,
+        ));
+        // per-submission input is ignored when test cases are shared
+        assert_eq!(
+            fields,
+            vec![
+                "submissions",
+                "submissions[1].code",
+                "test_cases[0].desired_stdout",
+                "webhook_url"
+            ]
+        );
+    }
+
+    #[test]
+    fn project_paths_must_stay_inside_the_working_directory() {
+        let file = |path: &str| ProjectFile {
+            path: path.to_string(),
+            content: String::new(),
+            content_b64: None,
+        };
+        let mut req = request(1.0, 65536.0);
+        req.code = String::new();
+        req.project = Some(ProjectRequest {
+            files: vec![
+                file("main.py"),
+                file("../escape.py"),
+                file("/etc/passwd"),
+                file("a/b/deep.py"),
+                file("main.py"),
+            ],
+            archive: None,
+            entry_point: Some("run.py".to_string()),
+        });
+        let fields = invalid_fields(validate_judge_request(
+            &mut req,
+            &limits(LimitPolicy::Reject),
+        ));
+        assert_eq!(
+            fields,
+            vec![
+                "project.files",
+                "project.files[1].path",
+                "project.files[2].path",
+                "project.files[3].path",
+                "project.files[4].path",
+                "project.entry_point"
+            ]
+        );
+
+        // the default entry point of the language applies when none is given
+        req.project = Some(ProjectRequest {
+            files: vec![file("main.py"), file("lib/util.py")],
+            archive: None,
+            entry_point: None,
+        });
+        assert!(validate_judge_request(&mut req, &limits(LimitPolicy::Reject)).is_ok());
+
+        req.code = "print(1)".to_string();
+        let fields = invalid_fields(validate_judge_request(
+            &mut req,
+            &limits(LimitPolicy::Reject),
+        ));
+        assert_eq!(fields, vec!["project"]);
+    }
+
+    #[test]
+    fn go_sources_must_share_the_entry_point_directory() {
+        let file = |path: &str| ProjectFile {
+            path: path.to_string(),
+            content: String::new(),
+            content_b64: None,
+        };
+        let mut req = request(1.0, 65536.0);
+        req.code = String::new();
+        req.language = "go".to_string();
+        req.project = Some(ProjectRequest {
+            files: vec![file("main.go"), file("lib/x.go"), file("lib/data.txt")],
+            archive: None,
+            entry_point: None,
+        });
+        let fields = invalid_fields(validate_judge_request(
+            &mut req,
+            &limits(LimitPolicy::Reject),
+        ));
+        assert_eq!(fields, vec!["project.files[1].path"]);
+
+        req.project = Some(ProjectRequest {
+            files: vec![file("cmd/main.go"), file("cmd/x.go"), file("data.txt")],
+            archive: None,
+            entry_point: Some("cmd/main.go".to_string()),
+        });
+        assert!(validate_judge_request(&mut req, &limits(LimitPolicy::Reject)).is_ok());
+    }
+
+    #[test]
+    fn output_files_are_bounded_and_judged_file_declared() {
+        let file = |name: &str, max_size| OutputFileRequest {
+            name: name.to_string(),
+            max_size,
+        };
+        let mut req = request(1.0, 65536.0);
+        req.output_files = vec![file("out/result.txt", None), file("plot.png", Some(16))];
+        req.judged_file = Some("out/result.txt".to_string());
+        assert!(validate_judge_request(&mut req, &limits(LimitPolicy::Reject)).is_ok());
+        // the server's cap applies when none is given
+        assert_eq!(req.output_files[0].max_size, Some(64));
+
+        req.output_files = vec![
+            file("../result.txt", None),
+            file("plot.png", Some(65)),
+            file("plot.png", Some(0)),
+        ];
+        req.judged_file = Some("answer.txt".to_string());
+        let fields = invalid_fields(validate_judge_request(
+            &mut req,
+            &limits(LimitPolicy::Reject),
+        ));
+        assert_eq!(
+            fields,
+            vec![
+                "output_files",
+                "output_files[0].name",
+                "output_files[1].max_size",
+                "output_files[2].name",
+                "output_files[2].max_size",
+                "judged_file"
+            ]
+        );
+    }
+
+    #[test]
+    fn packages_need_limits_tests_and_known_languages() {
+        let problems = ProblemConfig {
+            store_dir: None,
+            import_dir: None,
+            max_tests: 2,
+            max_size: 1024,
+            checker_time_limit: 10.0,
+            checker_memory_limit: 262144.0,
+        };
+        let test = PackageTest {
+            input: b"1 2\n".to_vec(),
+            output: vec![b'3'; 17],
+        };
+        let package = Package {
+            time_limit: Some(100.0),
+            memory_limit: None,
+            tests: vec![test.clone(); 3],
+            checker: Some(PackageProgram {
+                language: "cobol".to_string(),
+                code: Vec::new(),
+                files: PackageFiles::new(),
+            }),
+            comparator: Some(Comparator::Tokens),
+            interactor: Some(PackageProgram {
+                language: "c".to_string(),
+                code: Vec::new(),
+                files: [("../testlib.h", ""), ("main.c", "")]
+                    .into_iter()
+                    .map(|(path, content)| (path.to_string(), content.into()))
+                    .collect(),
+            }),
+            subtasks: vec![Subtask {
+                name: None,
+                points: -1.0,
+                scoring: SubtaskScoring::Sum,
+                tests: vec![0, 3],
+                depends_on: vec![0],
+            }],
+        };
+        let fields = invalid_fields(
+            validate_package(&package, &limits(LimitPolicy::Clamp), &problems).map(|_| ()),
+        );
+        assert_eq!(
+            fields,
+            [
+                "tests",
+                "tests[0].desired_stdout",
+                "tests[1].desired_stdout",
+                "tests[2].desired_stdout",
+                "memory_limit",
+                "subtasks[0].points",
+                "subtasks[0].tests",
+                "subtasks[0].depends_on",
+                "interactor",
+                "checker.language",
+                "comparator"
+            ]
+        );
+
+        let package = Package {
+            memory_limit: Some(65536.0),
+            tests: vec![PackageTest {
+                output: b"3\n".to_vec(),
+                ..test
+            }],
+            checker: None,
+            interactor: None,
+            subtasks: Vec::new(),
+            ..package
+        };
+        let limits_config = limits(LimitPolicy::Clamp);
+        let limits = validate_package(&package, &limits_config, &problems);
+        assert_eq!(limits, Ok((10.0, 65536.0)));
+
+        let package = Package {
+            comparator: Some(Comparator::Float {
+                tolerance: f64::NAN,
+                partial: vec![PartialCredit {
+                    tolerance: 0.1,
+                    score: 1.5,
+                }],
+            }),
+            ..package
+        };
+        let fields =
+            invalid_fields(validate_package(&package, &limits_config, &problems).map(|_| ()));
+        assert_eq!(
+            fields,
+            ["comparator.tolerance", "comparator.partial[0].score"]
+        );
+    }
+
+    #[test]
+    fn function_cases_follow_the_signature() {
+        let request = |signature, cases| {
+            serde_json::from_value(json!({
+                "code": "int f;",
+                "language": "c",
+                "signature": signature,
+                "cases": cases,
+                "time_limit": 1.0,
+                "memory_limit": 65536.0,
+            }))
+            .unwrap()
+        };
+        let limits = limits(LimitPolicy::Reject);
+        let sum = json!({
+            "name": "sum",
+            "params": [{"name": "nums", "type": "list<int>"}],
+            "returns": "long",
+        });
+
+        let mut valid = request(sum.clone(), json!([{"args": [[1, 2]], "expected": 3}]));
+        let (signature, inputs) = validate_function_request(&mut valid, &limits).unwrap();
+        assert_eq!(signature.returns.to_string(), "long");
+        assert_eq!(inputs, [b"2 1 2 ".to_vec()]);
+
+        let mut invalid = request(
+            sum,
+            json!([
+                {"args": [[1, "2"]], "expected": 3},
+                {"args": [], "expected": 1.5},
+            ]),
+        );
+        let fields = invalid_fields(validate_function_request(&mut invalid, &limits).map(|_| ()));
+        assert_eq!(
+            fields,
+            ["cases[0].args[0]", "cases[1].args", "cases[1].expected"]
+        );
+
+        let names = json!({
+            "name": "main",
+            "params": [{"name": "a", "type": "int"}, {"name": "a", "type": "map"}],
+            "returns": "int",
+        });
+        let mut invalid = request(names, json!([]));
+        let fields = invalid_fields(validate_function_request(&mut invalid, &limits).map(|_| ()));
+        assert_eq!(
+            fields,
+            [
+                "signature.name",
+                "signature.params[1].name",
+                "signature.params[1].type"
+            ]
+        );
+    }
+}
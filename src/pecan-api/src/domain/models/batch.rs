@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::models::bytes::Base64Bytes;
+use crate::domain::models::judge::{JudgePriority, JudgeResponse};
+use crate::domain::models::output_file::OutputFileRequest;
+use crate::domain::models::project::ProjectRequest;
+
+fn default_batch_priority() -> JudgePriority {
+    JudgePriority::Bulk
+}
+
+/// Many submissions judged as one unit. With `test_cases`, every submission
+/// runs against every test case; otherwise each submission brings its own
+/// `stdin` and `desired_stdout`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchRequest {
+    pub submissions: Vec<BatchSubmission>,
+    #[serde(default)]
+    pub test_cases: Vec<BatchTestCase>,
+    pub time_limit: f64,
+    pub memory_limit: f64,
+    #[serde(default = "default_batch_priority")]
+    pub priority: JudgePriority,
+    /// notified once when the whole batch has finished
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// files the program writes, returned with each verdict
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_files: Vec<OutputFileRequest>,
+    /// output file compared with `desired_stdout` instead of the standard output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub judged_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchSubmission {
+    /// source of a single-file submission; empty when `project` is given
+    #[serde(default)]
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<ProjectRequest>,
+    pub language: String,
+    #[serde(default)]
+    pub stdin: String,
+    /// `stdin` as base64, for input that is not text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin_b64: Option<Base64Bytes>,
+    #[serde(default)]
+    pub desired_stdout: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desired_stdout_b64: Option<Base64Bytes>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchTestCase {
+    #[serde(default)]
+    pub stdin: String,
+    /// `stdin` as base64, for input that is not text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin_b64: Option<Base64Bytes>,
+    #[serde(default)]
+    pub desired_stdout: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desired_stdout_b64: Option<Base64Bytes>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchCreatedResponse {
+    pub batch_id: Uuid,
+    /// number of executions the batch expands to
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Running,
+    Completed,
+    Cancelled,
+}
+
+/// Progress of a batch; also the body of its completion webhook.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchProgressResponse {
+    pub batch_id: Uuid,
+    pub status: BatchStatus,
+    pub total: usize,
+    pub queued: usize,
+    pub running: usize,
+    pub done: usize,
+    /// items skipped because the batch was cancelled
+    pub cancelled: usize,
+}
+
+/// One line of the results stream.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchItemResult {
+    pub submission: usize,
+    /// index into `test_cases`, absent when the submission brought its own input
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_case: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<JudgeResponse>,
+    /// why the item produced no result
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
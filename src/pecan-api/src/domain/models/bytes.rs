@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// Bytes carried as a base64 string, for the `_b64` variants of text fields.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Base64Bytes(#[serde(with = "pecan_core::utils::serde_base64")] pub Vec<u8>);
+
+/// The bytes of a field given either as `text` or base64-encoded as `encoded`.
+pub fn payload<'a>(text: &'a str, encoded: Option<&'a Base64Bytes>) -> &'a [u8] {
+    match encoded {
+        Some(encoded) => &encoded.0,
+        None => text.as_bytes(),
+    }
+}
+
+/// `bytes` as text, along with the exact bytes when they are not valid UTF-8
+/// and the text is therefore lossy.
+pub fn text_or_bytes(bytes: Vec<u8>) -> (String, Option<Base64Bytes>) {
+    match String::from_utf8(bytes) {
+        Ok(text) => (text, None),
+        Err(e) => (
+            String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            Some(Base64Bytes(e.into_bytes())),
+        ),
+    }
+}
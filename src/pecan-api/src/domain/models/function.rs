@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::domain::models::judge::{JudgePriority, JudgeResponse, JudgeStatus, JudgeStatusCode};
+
+/// Submission judged by calling one of its functions on each case, through a
+/// driver generated for its language.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionJudgeRequest {
+    /// source defining the function, without a `main`
+    pub code: String,
+    pub language: String,
+    pub signature: FunctionSignature,
+    pub cases: Vec<FunctionCase>,
+    /// time limit in seconds of every case
+    pub time_limit: f64,
+    /// memory limit in kilobytes of every case
+    pub memory_limit: f64,
+    #[serde(default)]
+    pub priority: JudgePriority,
+    /// skip the remaining cases once one fails
+    #[serde(default)]
+    pub stop_on_failure: bool,
+    /// largest absolute or relative error of a correct `double`
+    #[serde(default = "default_tolerance")]
+    pub tolerance: f64,
+}
+
+fn default_tolerance() -> f64 {
+    1e-6
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionSignature {
+    pub name: String,
+    #[serde(default)]
+    pub params: Vec<FunctionParameter>,
+    /// type of the return value
+    pub returns: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionParameter {
+    pub name: String,
+    /// `int`, `long`, `double`, `bool`, `string` or `list<T>`
+    #[serde(rename = "type")]
+    pub value_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionCase {
+    /// one JSON value per parameter
+    pub args: Vec<Value>,
+    pub expected: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionJudgeResponse {
+    /// status of the first case that was not accepted, `Accepted` otherwise
+    pub code: JudgeStatusCode,
+    pub status: JudgeStatus,
+    /// results of the cases that ran, in order
+    pub cases: Vec<FunctionCaseResult>,
+    /// longest time and largest memory usage of a single case
+    pub time: f64,
+    pub memory: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionCaseResult {
+    /// index of the case, counted from zero
+    pub case: usize,
+    #[serde(flatten)]
+    pub result: JudgeResponse,
+    /// what the function returned, when it returned a value of its type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub returned: Option<Value>,
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    /// the server is up but some toolchains failed the self-test
+    Degraded,
+    /// the server rejects new submissions while finishing accepted work
+    Draining,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HealthResponse {
+    pub status: HealthStatus,
+    pub unavailable_languages: Vec<String>,
+}
@@ -1,6 +1,12 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::domain::models::bytes::Base64Bytes;
+use crate::domain::models::error::ErrorResponse;
+use crate::domain::models::output_file::{OutputFileRequest, OutputFileResponse};
+use crate::domain::models::project::ProjectRequest;
+use crate::domain::models::session::SessionExit;
+
 pub type JudgeStatusCode = u16;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -12,9 +18,26 @@ pub enum JudgeStatus {
     TimeLimitExceeded,
     MemoryLimitExceeded,
     InternalError,
+    Aborted,
+    /// a checker gave the output part of the points
+    PartiallyCorrect,
 }
 
 impl JudgeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JudgeStatus::Accepted => "accepted",
+            JudgeStatus::WrongAnswer => "wrong_answer",
+            JudgeStatus::CompileError => "compile_error",
+            JudgeStatus::RuntimeError => "runtime_error",
+            JudgeStatus::TimeLimitExceeded => "time_limit_exceeded",
+            JudgeStatus::MemoryLimitExceeded => "memory_limit_exceeded",
+            JudgeStatus::InternalError => "internal_error",
+            JudgeStatus::Aborted => "aborted",
+            JudgeStatus::PartiallyCorrect => "partially_correct",
+        }
+    }
+
     pub fn into_status_code(self) -> JudgeStatusCode {
         match self {
             JudgeStatus::Accepted => 0,
@@ -24,39 +47,110 @@ impl JudgeStatus {
             JudgeStatus::TimeLimitExceeded => 4,
             JudgeStatus::MemoryLimitExceeded => 5,
             JudgeStatus::InternalError => 6,
+            JudgeStatus::Aborted => 7,
+            JudgeStatus::PartiallyCorrect => 8,
         }
     }
 }
 
+/// Scheduling class of a submission; more urgent classes run first.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JudgePriority {
+    Realtime,
+    #[default]
+    Normal,
+    Bulk,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct JudgeRequest {
+    /// source of a single-file submission; empty when `project` is given
+    #[serde(default)]
     pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<ProjectRequest>,
     pub language: String,
+    #[serde(default)]
     pub stdin: String,
+    /// `stdin` as base64, for input that is not text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin_b64: Option<Base64Bytes>,
+    #[serde(default)]
     pub desired_stdout: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desired_stdout_b64: Option<Base64Bytes>,
     pub time_limit: f64,
     pub memory_limit: f64,
+    #[serde(default)]
+    pub priority: JudgePriority,
+    /// files the program writes, returned with the verdict
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_files: Vec<OutputFileRequest>,
+    /// output file compared with `desired_stdout` instead of the standard output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub judged_file: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct JudgeAsyncRequest {
+    /// source of a single-file submission; empty when `project` is given
+    #[serde(default)]
     pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<ProjectRequest>,
     pub language: String,
+    #[serde(default)]
     pub stdin: String,
+    /// `stdin` as base64, for input that is not text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin_b64: Option<Base64Bytes>,
+    #[serde(default)]
     pub desired_stdout: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desired_stdout_b64: Option<Base64Bytes>,
     pub time_limit: f64,
     pub memory_limit: f64,
     pub webhook_url: String,
+    #[serde(default)]
+    pub priority: JudgePriority,
+    /// files the program writes, returned with the verdict
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_files: Vec<OutputFileRequest>,
+    /// output file compared with `desired_stdout` instead of the standard output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub judged_file: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct JudgeResponse {
     pub code: JudgeStatusCode,
     pub status: JudgeStatus,
+    /// output as text; lossy when `binary_output` is set
     pub stdout: String,
     pub stderr: String,
+    /// exact output, only present when it is not valid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_b64: Option<Base64Bytes>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_b64: Option<Base64Bytes>,
+    /// stdout or stderr is not valid UTF-8
+    #[serde(default)]
+    pub binary_output: bool,
     pub time: f64,
     pub memory: f64,
+    /// share of the points between 0 and 1 the output earned
+    #[serde(default)]
+    pub score: f64,
+    /// explanation of the checker that judged the output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checker_message: Option<String>,
+    /// the requested output files in order; empty when the program did not run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_files: Vec<OutputFileResponse>,
+    /// the execution result was reused from an identical earlier submission
+    #[serde(default)]
+    pub cached: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -69,3 +163,63 @@ pub struct JudgeAsyncWebhookResponse {
     pub request_id: Uuid,
     pub res: JudgeResponse,
 }
+
+/// Stage reported by the `phase` event of a streamed judge request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JudgePhase {
+    Queued,
+    Compiling,
+    Running,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JudgeOutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JudgePhaseEvent {
+    pub phase: JudgePhase,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JudgeOutputEvent {
+    pub stream: JudgeOutputStream,
+    /// lossy when the chunk is not valid UTF-8
+    pub data: String,
+    /// exact chunk, only present when it is not valid UTF-8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_b64: Option<Base64Bytes>,
+}
+
+/// Event of a streamed judge request or an interactive session; serialized as
+/// the payload only, the kind is carried by the event name.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum JudgeStreamEvent {
+    Phase(JudgePhaseEvent),
+    CompileOutput(JudgeOutputEvent),
+    Output(JudgeOutputEvent),
+    /// final event of a completed run
+    Result(JudgeResponse),
+    /// final event of an interactive session whose program ended
+    Exit(SessionExit),
+    /// final event of a run that failed after the stream started
+    Error(ErrorResponse),
+}
+
+impl JudgeStreamEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JudgeStreamEvent::Phase(_) => "phase",
+            JudgeStreamEvent::CompileOutput(_) => "compile_output",
+            JudgeStreamEvent::Output(_) => "output",
+            JudgeStreamEvent::Result(_) => "result",
+            JudgeStreamEvent::Exit(_) => "exit",
+            JudgeStreamEvent::Error(_) => "error",
+        }
+    }
+}
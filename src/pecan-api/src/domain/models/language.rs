@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LanguageSelfTestResponse {
+    pub language: String,
+    pub passed: bool,
+    pub compile_time: f64,
+    pub run_time: f64,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LanguageResponse {
+    pub identifier: String,
+    pub name: String,
+    pub version: String,
+    /// false when the latest self-test failed for this toolchain
+    pub available: bool,
+    pub self_test: Option<LanguageSelfTestResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LanguageListResponse {
+    pub languages: Vec<LanguageResponse>,
+}
@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::models::language::LanguageSelfTestResponse;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SandboxStatusResponse {
@@ -7,3 +10,54 @@ pub struct SandboxStatusResponse {
     pub running_sandboxes: usize,
     pub error_sandboxes: usize,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxState {
+    Idle,
+    Running,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SandboxResponse {
+    pub id: Uuid,
+    pub status: SandboxState,
+    /// UNIX epoch seconds when the current run started
+    pub running_since: Option<u64>,
+    /// backend box identifier, e.g. the isolate box id
+    pub box_id: String,
+    /// backend working directory of the box
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SandboxListResponse {
+    pub sandboxes: Vec<SandboxResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResizePoolRequest {
+    /// desired number of sandboxes
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DrainRequest {
+    pub draining: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DrainResponse {
+    pub draining: bool,
+    pub running_sandboxes: usize,
+    pub queued_tasks: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SelfTestResponse {
+    pub degraded: bool,
+    /// UNIX epoch seconds when the self-test finished
+    pub finished_at: u64,
+    pub results: Vec<LanguageSelfTestResponse>,
+}
@@ -1,2 +1,12 @@
+pub mod batch;
+pub mod bytes;
+pub mod error;
+pub mod function;
+pub mod health;
 pub mod judge;
+pub mod language;
 pub mod manager;
+pub mod output_file;
+pub mod problem;
+pub mod project;
+pub mod session;
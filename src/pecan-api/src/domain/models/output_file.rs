@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::models::bytes::Base64Bytes;
+
+/// File the program is expected to write to its working directory.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutputFileRequest {
+    /// relative, `/`-separated path inside the working directory
+    pub name: String,
+    /// bytes returned at most; the server's `MAX_OUTPUT_FILE_SIZE` when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutputFileResponse {
+    pub name: String,
+    /// the program wrote the file
+    pub written: bool,
+    /// content as text; lossy when `content_b64` is present
+    pub content: String,
+    /// exact content, only present when it is not valid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_b64: Option<Base64Bytes>,
+    /// the file was larger than `max_size` and only its start is returned
+    #[serde(default)]
+    pub truncated: bool,
+}
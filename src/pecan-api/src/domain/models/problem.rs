@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::models::batch::BatchTestCase;
+use crate::domain::models::judge::{JudgePriority, JudgeResponse, JudgeStatus, JudgeStatusCode};
+use crate::domain::models::project::{ProjectArchive, ProjectFile, ProjectRequest};
+
+/// New version of a problem, given inline or as a package archive. Limits and
+/// programs given here take precedence over those of the archive.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProblemUploadRequest {
+    /// time limit in seconds of every test
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_limit: Option<f64>,
+    /// memory limit in kilobytes of every test
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tests: Vec<BatchTestCase>,
+    /// Polygon-style or `tests/NN.in` package, in place of `tests`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<ProjectArchive>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checker: Option<ProblemProgram>,
+    /// built-in comparison in place of a checker program
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<Comparator>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interactor: Option<ProblemProgram>,
+    /// replace the subtasks of the archive when not empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtasks: Vec<Subtask>,
+}
+
+/// How the scores of a subtask's tests, each between 0 and 1, add up.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtaskScoring {
+    /// `points` when every test passes, nothing otherwise
+    #[default]
+    Min,
+    /// `points` for each test that passes
+    Sum,
+    /// `points` shared evenly between the tests
+    Proportional,
+}
+
+/// Group of tests scored together.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Subtask {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub points: f64,
+    #[serde(default)]
+    pub scoring: SubtaskScoring,
+    /// indices of the tests, counted from zero
+    pub tests: Vec<usize>,
+    /// indices of earlier subtasks that must get their full score first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<usize>,
+}
+
+/// Import of a package directory on the server.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProblemImportRequest {
+    /// directory relative to `PROBLEM_IMPORT_DIR`
+    pub path: String,
+}
+
+/// Source of a checker or interactor.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProblemProgram {
+    pub language: String,
+    pub code: String,
+    /// files the source includes, such as `testlib.h`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<ProjectFile>,
+}
+
+/// Built-in comparison of an output with the expected answer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Comparator {
+    /// byte for byte
+    Exact,
+    /// the same whitespace-separated tokens
+    Tokens,
+    /// tokens, where numbers may differ by `tolerance`
+    Float {
+        /// largest absolute or relative error of a correct number
+        #[serde(default = "default_tolerance")]
+        tolerance: f64,
+        /// lower scores for numbers further off
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        partial: Vec<PartialCredit>,
+    },
+}
+
+fn default_tolerance() -> f64 {
+    1e-6
+}
+
+/// Score of a number within `tolerance` of the expected one but not within
+/// the comparator's own tolerance.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PartialCredit {
+    pub tolerance: f64,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProblemResponse {
+    pub id: String,
+    pub version: u32,
+    pub time_limit: f64,
+    pub memory_limit: f64,
+    /// number of tests
+    pub tests: usize,
+    /// language of the checker program
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checker: Option<String>,
+    /// built-in comparison, absent with a checker program or when outputs are
+    /// compared byte for byte
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<Comparator>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactor: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtasks: Vec<Subtask>,
+    /// UNIX epoch seconds
+    pub created_at: u64,
+    /// every stored version, oldest first
+    pub versions: Vec<u32>,
+}
+
+/// Submission judged against the tests of a stored problem.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProblemJudgeRequest {
+    pub problem_id: String,
+    /// latest version when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    /// source of a single-file submission; empty when `project` is given
+    #[serde(default)]
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<ProjectRequest>,
+    pub language: String,
+    #[serde(default)]
+    pub priority: JudgePriority,
+    /// skip the remaining tests once one fails
+    #[serde(default)]
+    pub stop_on_failure: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProblemJudgeResponse {
+    pub problem_id: String,
+    pub version: u32,
+    /// status of the first test that was not accepted, `Accepted` otherwise
+    pub code: JudgeStatusCode,
+    pub status: JudgeStatus,
+    /// results of the tests that ran, in the order they ran
+    pub tests: Vec<ProblemTestResult>,
+    /// longest time and largest memory usage of a single test
+    pub time: f64,
+    pub memory: f64,
+    /// sum of the subtask scores; absent for problems without subtasks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_score: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtasks: Vec<SubtaskResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProblemTestResult {
+    /// index of the test, counted from zero
+    pub test: usize,
+    #[serde(flatten)]
+    pub result: JudgeResponse,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtaskStatus {
+    Scored,
+    /// a prerequisite did not get its full score, so no test ran for it
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubtaskResult {
+    /// index of the subtask, counted from zero
+    pub subtask: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub status: SubtaskStatus,
+    pub score: f64,
+    pub max_score: f64,
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::models::bytes::Base64Bytes;
+
+/// A submission spread over several files, given either file by file or as
+/// one archive. Validation unpacks an archive into `files`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProjectRequest {
+    #[serde(default)]
+    pub files: Vec<ProjectFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<ProjectArchive>,
+    /// file the program starts from; the language's default file name when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_point: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProjectFile {
+    /// relative, `/`-separated path
+    pub path: String,
+    #[serde(default)]
+    pub content: String,
+    /// `content` as base64, for files that are not text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_b64: Option<Base64Bytes>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProjectArchive {
+    pub format: ArchiveFormat,
+    /// base64-encoded archive
+    pub data: Base64Bytes,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    #[serde(rename = "tar")]
+    Tar,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "zip")]
+    Zip,
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::models::bytes::Base64Bytes;
+use crate::domain::models::judge::JudgePriority;
+use crate::domain::models::project::ProjectRequest;
+
+/// First message of an interactive session: the program to run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionStartRequest {
+    /// source of a single-file submission; empty when `project` is given
+    #[serde(default)]
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<ProjectRequest>,
+    pub language: String,
+    pub time_limit: f64,
+    pub memory_limit: f64,
+    #[serde(default)]
+    pub priority: JudgePriority,
+}
+
+/// Message of the client once the session has started.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionMessage {
+    /// written to the program's standard input as is, `data_b64` decoded
+    Stdin {
+        #[serde(default)]
+        data: String,
+        #[serde(default)]
+        data_b64: Option<Base64Bytes>,
+    },
+    /// end of the program's standard input
+    CloseStdin,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionExitStatus {
+    Success,
+    CompileError,
+    RuntimeError,
+    TimeLimitExceeded,
+    MemoryLimitExceeded,
+    InternalError,
+    Aborted,
+}
+
+/// How the program of a session ended; its output was already streamed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionExit {
+    pub status: SessionExitStatus,
+    /// CPU time in seconds
+    pub time: f64,
+    /// memory usage in kilobytes
+    pub memory: f64,
+}
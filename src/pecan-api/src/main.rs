@@ -1,22 +1,11 @@
 use pecan_api::application;
+use pecan_api::application::telemetry::init_tracing;
 use pecan_core::SANDBOX_SOLUTION;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
 
 // API server entrypoint
 #[tokio::main]
 async fn main() {
-    let filter_layer = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::try_new("info").unwrap());
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .compact()
-        .with_target(false)
-        .with_file(true)
-        .with_line_number(true);
-    tracing_subscriber::registry()
-        .with(filter_layer)
-        .with(fmt_layer)
-        .init();
+    let _tracing = init_tracing();
 
     tracing::info!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
     tracing::info!("Sandbox solution: {}", SANDBOX_SOLUTION);
@@ -0,0 +1,428 @@
+//! Content-addressed cache of execution results.
+//!
+//! Results are keyed by everything that determines them: the language and its
+//! toolchain version and installed files, the source, the input and the limits. Only results that
+//! are expected to repeat are kept; see `is_cacheable`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use pecan_sandbox::fingerprint::path_fingerprint;
+use sha2::{Digest, Sha256};
+
+use crate::code_execution::{CodeExecutionRequest, CodeExecutionResult, CodeExecutionStatus};
+use crate::toolchains::language_toolchain;
+
+/// Runs that used at least this fraction of the time or memory limit may flip
+/// to a limit verdict on the next run, so they are not cached.
+const NEAR_LIMIT_RATIO: f64 = 0.8;
+
+/// Where cached results are kept and for how long.
+#[derive(Debug, Clone)]
+pub struct ResultCachePolicy {
+    pub ttl: Duration,
+    /// bound on the total size of the cached results, in bytes
+    pub max_bytes: usize,
+    /// keep results on disk under this directory instead of in memory
+    pub dir: Option<PathBuf>,
+}
+
+/// Part of every key, bumped when the stored form of a result changes so that
+/// entries written in an older form are never matched.
+const FORMAT_VERSION: &[u8] = b"3";
+
+pub type CacheKey = [u8; 32];
+
+/// Key of `request`, or `None` when its language has no toolchain.
+pub fn cache_key(request: &CodeExecutionRequest) -> Option<CacheKey> {
+    let toolchain = language_toolchain(request.language)?;
+
+    let mut hasher = Sha256::new();
+    for part in [
+        FORMAT_VERSION,
+        toolchain.identifier.as_bytes(),
+        toolchain.version.as_bytes(),
+        &Sha256::digest(request.code.as_bytes()),
+        &Sha256::digest(&request.input),
+        &request.timeout.to_le_bytes(),
+        &request.memory_limit.to_le_bytes(),
+    ] {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    // the version above is fixed in the code, while the toolchain may be
+    // upgraded in place
+    for path in toolchain.host_paths() {
+        let fingerprint = path_fingerprint(&path);
+        hasher.update((fingerprint.len() as u64).to_le_bytes());
+        hasher.update(fingerprint);
+    }
+    if let Some(project) = &request.project {
+        let entry_point = project.entry_point.as_deref().unwrap_or_default();
+        hasher.update((entry_point.len() as u64).to_le_bytes());
+        hasher.update(entry_point.as_bytes());
+        for file in &project.files {
+            hasher.update((file.path.len() as u64).to_le_bytes());
+            hasher.update(file.path.as_bytes());
+            hasher.update(Sha256::digest(&file.content));
+        }
+    }
+    for file in &request.output_files {
+        hasher.update((file.name.len() as u64).to_le_bytes());
+        hasher.update(file.name.as_bytes());
+        hasher.update(file.max_size.to_le_bytes());
+    }
+    // counted so that arguments and input files cannot pass for one another
+    hasher.update((request.args.len() as u64).to_le_bytes());
+    for arg in &request.args {
+        hasher.update((arg.len() as u64).to_le_bytes());
+        hasher.update(arg.as_bytes());
+    }
+    for file in &request.input_files {
+        hasher.update((file.path.len() as u64).to_le_bytes());
+        hasher.update(file.path.as_bytes());
+        hasher.update(Sha256::digest(&file.content));
+    }
+    Some(hasher.finalize().into())
+}
+
+/// Whether `result` of a run limited to `timeout` seconds and `memory_limit`
+/// would come out the same if the run were repeated. Failed runs are never
+/// cached, since a crash may depend on the host as much as on the program.
+pub fn is_cacheable(result: &CodeExecutionResult, timeout: f64, memory_limit: f64) -> bool {
+    result.status == CodeExecutionStatus::Success
+        && result.time < timeout * NEAR_LIMIT_RATIO
+        && result.memory < memory_limit * NEAR_LIMIT_RATIO
+}
+
+fn hex(key: &CacheKey) -> String {
+    key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(name: &str) -> Option<CacheKey> {
+    if name.len() != 64 {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(name.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(key)
+}
+
+struct Entry {
+    stored_at: SystemTime,
+    size: usize,
+    /// position in `Index::lru`
+    last_used: u64,
+    /// `None` when the result lives on disk
+    result: Option<CodeExecutionResult>,
+}
+
+#[derive(Default)]
+struct Index {
+    entries: HashMap<CacheKey, Entry>,
+    /// least recently used first
+    lru: BTreeMap<u64, CacheKey>,
+    bytes: usize,
+    tick: u64,
+}
+
+impl Index {
+    fn touch(&mut self, key: &CacheKey) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.lru.remove(&entry.last_used);
+            self.lru.insert(tick, *key);
+            entry.last_used = tick;
+        }
+    }
+
+    fn insert(&mut self, key: CacheKey, mut entry: Entry) {
+        self.remove(&key);
+        self.tick += 1;
+        entry.last_used = self.tick;
+        self.lru.insert(entry.last_used, key);
+        self.bytes += entry.size;
+        self.entries.insert(key, entry);
+    }
+
+    fn remove(&mut self, key: &CacheKey) -> bool {
+        let Some(entry) = self.entries.remove(key) else {
+            return false;
+        };
+        self.lru.remove(&entry.last_used);
+        self.bytes -= entry.size;
+        true
+    }
+
+    /// Drop least recently used entries until at most `max_bytes` remain.
+    fn evict(&mut self, max_bytes: usize) -> Vec<CacheKey> {
+        let mut evicted = Vec::new();
+        while self.bytes > max_bytes {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.bytes -= entry.size;
+            }
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+pub struct ResultCache {
+    policy: ResultCachePolicy,
+    index: Mutex<Index>,
+}
+
+impl ResultCache {
+    /// Create the cache; a disk cache picks up the results already in its
+    /// directory.
+    pub async fn open(policy: ResultCachePolicy) -> io::Result<Self> {
+        let mut index = Index::default();
+        if let Some(dir) = &policy.dir {
+            tokio::fs::create_dir_all(dir).await?;
+            let mut files = tokio::fs::read_dir(dir).await?;
+            while let Some(file) = files.next_entry().await? {
+                let Some(key) = file.file_name().to_str().and_then(parse_hex) else {
+                    continue;
+                };
+                let metadata = file.metadata().await?;
+                index.insert(
+                    key,
+                    Entry {
+                        stored_at: metadata.modified()?,
+                        size: metadata.len() as usize,
+                        last_used: 0,
+                        result: None,
+                    },
+                );
+            }
+        }
+
+        let cache = Self {
+            policy,
+            index: Mutex::new(index),
+        };
+        let evicted = cache.lock().evict(cache.policy.max_bytes);
+        cache.remove_files(&evicted).await;
+        Ok(cache)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Index> {
+        // the index stays consistent across a panic, so keep using it
+        self.index.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn path(dir: &Path, key: &CacheKey) -> PathBuf {
+        dir.join(hex(key))
+    }
+
+    async fn remove_files(&self, keys: &[CacheKey]) {
+        if let Some(dir) = &self.policy.dir {
+            for key in keys {
+                let _ = tokio::fs::remove_file(Self::path(dir, key)).await;
+            }
+        }
+    }
+
+    /// Number of cached results and their total size in bytes.
+    pub fn usage(&self) -> (usize, usize) {
+        let index = self.lock();
+        (index.entries.len(), index.bytes)
+    }
+
+    pub async fn get(&self, key: &CacheKey) -> Option<CodeExecutionResult> {
+        let cached = {
+            let mut index = self.lock();
+            let entry = index.entries.get(key)?;
+            let expired = entry
+                .stored_at
+                .elapsed()
+                .is_ok_and(|age| age > self.policy.ttl);
+            if expired {
+                index.remove(key);
+                None
+            } else {
+                let result = entry.result.clone();
+                index.touch(key);
+                Some(result)
+            }
+        };
+
+        let result = match (cached, &self.policy.dir) {
+            (None, _) => {
+                self.remove_files(&[*key]).await;
+                return None;
+            }
+            (Some(Some(result)), _) => Some(result),
+            (Some(None), Some(dir)) => tokio::fs::read(Self::path(dir, key))
+                .await
+                .ok()
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok()),
+            (Some(None), None) => None,
+        };
+        if result.is_none() {
+            // unreadable on disk, forget it
+            self.lock().remove(key);
+            self.remove_files(&[*key]).await;
+        }
+        result
+    }
+
+    pub async fn put(&self, key: CacheKey, result: &CodeExecutionResult) {
+        let (size, stored) = match &self.policy.dir {
+            Some(dir) => {
+                let Ok(bytes) = serde_json::to_vec(result) else {
+                    return;
+                };
+                // write aside and rename, so readers never see a partial file
+                let path = Self::path(dir, &key);
+                let partial = path.with_extension("partial");
+                if bytes.len() > self.policy.max_bytes
+                    || tokio::fs::write(&partial, &bytes).await.is_err()
+                    || tokio::fs::rename(&partial, &path).await.is_err()
+                {
+                    let _ = tokio::fs::remove_file(&partial).await;
+                    return;
+                }
+                (bytes.len(), None)
+            }
+            None => (
+                result.stdout.len()
+                    + result.stderr.len()
+                    + result
+                        .output_files
+                        .iter()
+                        .map(|file| file.name.len() + file.content.as_ref().map_or(0, Vec::len))
+                        .sum::<usize>()
+                    + size_of::<CodeExecutionResult>(),
+                Some(result.clone()),
+            ),
+        };
+        if size > self.policy.max_bytes {
+            return;
+        }
+
+        let evicted = {
+            let mut index = self.lock();
+            index.insert(
+                key,
+                Entry {
+                    stored_at: SystemTime::now(),
+                    size,
+                    last_used: 0,
+                    result: stored,
+                },
+            );
+            index.evict(self.policy.max_bytes)
+        };
+        self.remove_files(&evicted).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ResultCache, ResultCachePolicy, is_cacheable};
+    use crate::code_execution::{CodeExecutionResult, CodeExecutionStatus};
+
+    fn result(status: CodeExecutionStatus, stdout: &str, time: f64) -> CodeExecutionResult {
+        CodeExecutionResult {
+            status,
+            stdout: stdout.into(),
+            stderr: Vec::new(),
+            time,
+            memory: 1024.0,
+            compile_time: 0.0,
+            output_files: Vec::new(),
+            exit_code: Some(0),
+            cached: false,
+        }
+    }
+
+    fn policy(max_bytes: usize, dir: Option<std::path::PathBuf>) -> ResultCachePolicy {
+        ResultCachePolicy {
+            ttl: Duration::from_secs(60),
+            max_bytes,
+            dir,
+        }
+    }
+
+    #[test]
+    fn only_repeatable_results_are_cacheable() {
+        let memory_limit = 4096.0;
+        assert!(is_cacheable(
+            &result(CodeExecutionStatus::Success, "", 0.1),
+            1.0,
+            memory_limit
+        ));
+        assert!(!is_cacheable(
+            &result(CodeExecutionStatus::Success, "", 0.9),
+            1.0,
+            memory_limit
+        ));
+        // 1024 KB used of a 1200 KB limit
+        assert!(!is_cacheable(
+            &result(CodeExecutionStatus::Success, "", 0.1),
+            1.0,
+            1200.0
+        ));
+        for status in [
+            CodeExecutionStatus::RuntimeError,
+            CodeExecutionStatus::CompileError,
+            CodeExecutionStatus::TimeLimitExceeded,
+            CodeExecutionStatus::MemoryLimitExceeded,
+            CodeExecutionStatus::InternalError,
+        ] {
+            assert!(!is_cacheable(&result(status, "", 0.1), 1.0, memory_limit));
+        }
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_results_are_evicted() {
+        let one = result(CodeExecutionStatus::Success, "1", 0.1);
+        let entry_size = one.stdout.len() + size_of::<CodeExecutionResult>();
+        let cache = ResultCache::open(policy(entry_size * 2, None))
+            .await
+            .unwrap();
+
+        cache.put([1; 32], &one).await;
+        cache
+            .put([2; 32], &result(CodeExecutionStatus::Success, "2", 0.1))
+            .await;
+        assert!(cache.get(&[1; 32]).await.is_some());
+        cache
+            .put([3; 32], &result(CodeExecutionStatus::Success, "3", 0.1))
+            .await;
+
+        assert!(cache.get(&[2; 32]).await.is_none());
+        assert_eq!(cache.get(&[1; 32]).await.unwrap().stdout, b"1");
+        assert_eq!(cache.usage().0, 2);
+    }
+
+    #[tokio::test]
+    async fn disk_cache_survives_reopening() {
+        let dir = std::env::temp_dir().join(format!("pecan-cache-{}", uuid::Uuid::new_v4()));
+        let cache = ResultCache::open(policy(1 << 20, Some(dir.clone())))
+            .await
+            .unwrap();
+        cache
+            .put([7; 32], &result(CodeExecutionStatus::Success, "42\n", 0.1))
+            .await;
+        drop(cache);
+
+        let cache = ResultCache::open(policy(1 << 20, Some(dir.clone())))
+            .await
+            .unwrap();
+        assert_eq!(cache.get(&[7; 32]).await.unwrap().stdout, b"42\n");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
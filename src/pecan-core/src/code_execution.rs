@@ -1,30 +1,90 @@
 use std::sync::Arc;
+use std::time::Instant;
 
 use pecan_sandbox::errors::SandboxManagerError;
 use pecan_sandbox::manager::SandboxManager;
-use pecan_sandbox::sandbox::SandboxExecutionStatus;
+use pecan_sandbox::permits::Priority;
+use pecan_sandbox::sandbox::{
+    ExecutionEvents, ExecutionInput, SandboxAdditionalFileOptions, SandboxExecutionStatus,
+    SandboxOutputFileOptions,
+};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::errors::CoreExecutionError;
+use crate::telemetry::{self, EXECUTION_COMPILE_SECONDS};
 use crate::toolchains::Language;
 use crate::toolchains::sandbox_options::build_sandbox_execution_option;
 
+/// stderr of a compilation that ran out of time
+pub const COMPILE_TIMEOUT_MESSAGE: &str = "Compilation timed out";
+
 pub struct CodeExecutionRequest {
     pub language: Language,
     pub code: String,
-    pub input: String,
+    /// files to run instead of `code`
+    pub project: Option<Project>,
+    pub input: Vec<u8>,
     pub timeout: f64,
     pub memory_limit: f64,
+    pub priority: Priority,
+    /// seconds to wait for a free sandbox before giving up as busy; `None`
+    /// waits as long as it takes
+    pub queue_timeout: Option<f64>,
+    /// files the program writes, returned along with its output
+    pub output_files: Vec<OutputFileSpec>,
+    /// arguments passed to the program after the toolchain's own
+    pub args: Vec<String>,
+    /// files the program reads, added once it is compiled so that they do not
+    /// prevent reusing the compiled program
+    pub input_files: Vec<ProjectFile>,
+    /// compile even when the compiled program is stored, e.g. to check that
+    /// the compiler still works
+    pub bypass_artifact_cache: bool,
+}
+
+/// File the program is expected to write to its working directory.
+#[derive(Debug, Clone)]
+pub struct OutputFileSpec {
+    /// relative, `/`-separated path inside the working directory
+    pub name: String,
+    /// bytes returned at most
+    pub max_size: u64,
+}
+
+/// A submission spread over several files.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub files: Vec<ProjectFile>,
+    /// path of the file the program starts from; the toolchain's default
+    /// entry point when `None`
+    pub entry_point: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectFile {
+    /// relative, `/`-separated path inside the working directory
+    pub path: String,
+    pub content: Vec<u8>,
 }
 
 pub struct CodeExecutionRequestLazy {
     pub request_id: Uuid,
+    /// id of the API request that enqueued the task, echoed to the webhook
+    pub origin_request_id: Option<String>,
+    /// span of the enqueueing request; the task's execution is traced as its child
+    pub span: tracing::Span,
+    /// when the task was accepted, used to report its time in the queue
+    pub enqueued_at: Instant,
     pub webhook_url: String,
     pub send_failed_count: u32,
-    pub desired_stdout: String,
+    pub desired_stdout: Vec<u8>,
+    /// output file compared with `desired_stdout` instead of the standard output
+    pub judged_file: Option<String>,
     pub req: CodeExecutionRequest,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CodeExecutionStatus {
     Success,
     CompileError,
@@ -32,22 +92,61 @@ pub enum CodeExecutionStatus {
     InternalError,
     TimeLimitExceeded,
     MemoryLimitExceeded,
+    /// never ran: the service shut down while the task was still queued
+    Aborted,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CodeExecutionResult {
     pub status: CodeExecutionStatus,
-    pub stdout: String,
-    pub stderr: String,
+    #[serde(with = "crate::utils::serde_base64")]
+    pub stdout: Vec<u8>,
+    #[serde(with = "crate::utils::serde_base64")]
+    pub stderr: Vec<u8>,
     pub time: f64,
     pub memory: f64,
+    pub compile_time: f64,
+    /// the requested output files in order; empty when the program did not run
+    #[serde(default)]
+    pub output_files: Vec<OutputFile>,
+    /// exit status of a program that exited on its own
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// served from the result cache instead of being executed
+    #[serde(skip)]
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputFile {
+    pub name: String,
+    /// `None` when the program did not write the file
+    #[serde(with = "crate::utils::serde_base64::option")]
+    pub content: Option<Vec<u8>>,
+    /// only the first `max_size` bytes were kept
+    pub truncated: bool,
+}
+
+/// Standard input of an interactive execution, sent while the program runs.
+pub struct InteractiveInput {
+    pub input: ExecutionInput,
+    /// wall-clock bound of the whole run in seconds, replacing the one derived
+    /// from the time limit since the program mostly waits for its user
+    pub wall_time_limit: f64,
 }
 
 pub struct AsyncCodeExecutionResult {
     pub request_id: Uuid,
+    pub origin_request_id: Option<String>,
+    pub span: tracing::Span,
+    pub language: Language,
     pub webhook_url: String,
     pub send_failed_count: u32,
-    pub desired_stdout: String,
-    pub result: Option<CodeExecutionResult>,
+    pub desired_stdout: Vec<u8>,
+    /// output file compared with `desired_stdout` instead of the standard output
+    pub judged_file: Option<String>,
+    /// an `InternalError` result when the task failed to execute
+    pub result: CodeExecutionResult,
 }
 
 /// simply execute function provided by sandbox manager
@@ -55,16 +154,55 @@ pub async fn execute(
     sandbox_manager: &Arc<SandboxManager>,
     request: CodeExecutionRequest,
 ) -> Result<CodeExecutionResult, CoreExecutionError> {
-    let sandbox_execution_options = build_sandbox_execution_option(
+    execute_with_events(sandbox_manager, request, None, None).await
+}
+
+/// `execute` that reports progress and output to `events` while it runs, and
+/// reads the program's standard input from `interactive` when given.
+#[tracing::instrument(name = "execute", skip_all, fields(language = request.language.as_str()))]
+pub async fn execute_with_events(
+    sandbox_manager: &Arc<SandboxManager>,
+    request: CodeExecutionRequest,
+    events: Option<&ExecutionEvents>,
+    interactive: Option<InteractiveInput>,
+) -> Result<CodeExecutionResult, CoreExecutionError> {
+    let mut sandbox_execution_options = build_sandbox_execution_option(
         request.language,
         request.code,
+        request.project,
         request.input,
         request.timeout,
         request.memory_limit,
+        request.priority,
     )?;
+    sandbox_execution_options.output_files = request
+        .output_files
+        .iter()
+        .map(|file| SandboxOutputFileOptions {
+            file_name: file.name.clone(),
+            max_size: file.max_size,
+        })
+        .collect();
+    sandbox_execution_options.args.extend(request.args);
+    sandbox_execution_options.queue_timeout = request.queue_timeout;
+    sandbox_execution_options.bypass_artifact_cache = request.bypass_artifact_cache;
+    sandbox_execution_options.input_files = request
+        .input_files
+        .into_iter()
+        .map(|file| SandboxAdditionalFileOptions {
+            file_name: file.path,
+            file_content: file.content,
+        })
+        .collect();
+    let language = request.language.as_str();
+    let compiled = sandbox_execution_options.compile_options.is_some();
+    let input = interactive.map(|interactive| {
+        sandbox_execution_options.wall_time_limit = interactive.wall_time_limit;
+        interactive.input
+    });
 
     let result = match sandbox_manager
-        .execute_via_manager(&sandbox_execution_options)
+        .execute_with_events(&sandbox_execution_options, events, input)
         .await
     {
         Ok(result) => result,
@@ -75,17 +213,25 @@ pub async fn execute(
             ));
         }
         Err(SandboxManagerError::CompileTimeout) => {
+            metrics::histogram!(EXECUTION_COMPILE_SECONDS, "language" => language)
+                .record(sandbox_execution_options.compile_timeout);
             return Ok(CodeExecutionResult {
                 status: CodeExecutionStatus::CompileError,
-                stdout: String::new(),
-                stderr: "Compilation timed out".to_string(),
+                stdout: Vec::new(),
+                stderr: COMPILE_TIMEOUT_MESSAGE.into(),
                 time: 0.0,
                 memory: 0.0,
+                compile_time: sandbox_execution_options.compile_timeout,
+                output_files: Vec::new(),
+                exit_code: None,
+                cached: false,
             });
         }
         Err(e) => return Err(CoreExecutionError::InternalError(e.to_string())),
     };
 
+    telemetry::record_execution(request.language, request.priority, compiled, &result);
+
     Ok(CodeExecutionResult {
         status: match result.status {
             SandboxExecutionStatus::Success => CodeExecutionStatus::Success,
@@ -98,5 +244,17 @@ pub async fn execute(
         stderr: result.stderr,
         time: result.time,
         memory: result.memory,
+        compile_time: result.compile_time,
+        output_files: result
+            .output_files
+            .into_iter()
+            .map(|file| OutputFile {
+                name: file.file_name,
+                content: file.content,
+                truncated: file.truncated,
+            })
+            .collect(),
+        exit_code: result.exit_code,
+        cached: false,
     })
 }
@@ -8,6 +8,10 @@ pub enum CoreServiceError {
     InternalError(String),
     #[error("Task queue is full: {0}")]
     TaskQueueFull(String),
+    #[error("Sandbox not found: {0}")]
+    SandboxNotFound(String),
+    #[error("Sandbox is busy: {0}")]
+    SandboxBusy(String),
 }
 
 #[derive(Error, Debug)]
@@ -0,0 +1,213 @@
+//! C driver. Functions follow LeetCode's conventions: a list is passed as a
+//! pointer and its length, a list of lists also with the length of each row,
+//! and a returned list reports its length through a trailing
+//! `int *returnSize` (and `int **returnColumnSizes`) argument. Deeper lists
+//! are not supported.
+
+use std::fmt::Write;
+
+use super::{HarnessError, RESULT_FILE, Signature, ValueType};
+
+/// Headers a submission may rely on, as on LeetCode.
+const PRELUDE: &str = "\
+#include <ctype.h>
+#include <limits.h>
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+";
+
+const HELPERS: &str = r#"
+static char *pecan_in;
+static size_t pecan_len, pecan_pos;
+static FILE *pecan_out;
+
+static void pecan_read_input(void) {
+    size_t cap = 4096;
+    size_t n;
+    pecan_in = malloc(cap);
+    while ((n = fread(pecan_in + pecan_len, 1, cap - pecan_len - 1, stdin)) > 0) {
+        pecan_len += n;
+        if (pecan_len + 1 == cap) {
+            cap *= 2;
+            pecan_in = realloc(pecan_in, cap);
+        }
+    }
+    pecan_in[pecan_len] = '\0';
+}
+
+static void pecan_skip(void) {
+    while (pecan_pos < pecan_len && isspace((unsigned char)pecan_in[pecan_pos])) {
+        pecan_pos++;
+    }
+}
+
+static long long pecan_read_long(void) {
+    char *end;
+    long long value;
+    pecan_skip();
+    value = strtoll(pecan_in + pecan_pos, &end, 10);
+    pecan_pos = end - pecan_in;
+    return value;
+}
+
+static double pecan_read_double(void) {
+    char *end;
+    double value;
+    pecan_skip();
+    value = strtod(pecan_in + pecan_pos, &end);
+    pecan_pos = end - pecan_in;
+    return value;
+}
+
+static char *pecan_read_string(void) {
+    size_t len = (size_t)pecan_read_long();
+    char *value = malloc(len + 1);
+    pecan_pos++;
+    memcpy(value, pecan_in + pecan_pos, len);
+    value[len] = '\0';
+    pecan_pos += len;
+    return value;
+}
+
+static void pecan_write_long(long long value) {
+    fprintf(pecan_out, "%lld ", value);
+}
+
+static void pecan_write_double(double value) {
+    fprintf(pecan_out, "%.17g ", value);
+}
+
+static void pecan_write_string(const char *value) {
+    size_t len = strlen(value);
+    fprintf(pecan_out, "%zu ", len);
+    fwrite(value, 1, len, pecan_out);
+    fputc(' ', pecan_out);
+}
+"#;
+
+/// C type, read expression and write function of a scalar type.
+fn scalar(value_type: &ValueType) -> Option<(&'static str, &'static str, &'static str)> {
+    match value_type {
+        ValueType::Int => Some(("int", "(int)pecan_read_long()", "pecan_write_long")),
+        ValueType::Long => Some(("long long", "pecan_read_long()", "pecan_write_long")),
+        ValueType::Double => Some(("double", "pecan_read_double()", "pecan_write_double")),
+        ValueType::Bool => Some(("bool", "pecan_read_long() != 0", "pecan_write_long")),
+        ValueType::String => Some(("char*", "pecan_read_string()", "pecan_write_string")),
+        ValueType::List(_) => None,
+    }
+}
+
+/// A type as the scalar it holds and how many lists deep it is.
+fn shape(
+    value_type: &ValueType,
+) -> Result<(&'static str, &'static str, &'static str, usize), HarnessError> {
+    let mut depth = 0;
+    let mut inner = value_type;
+    while let ValueType::List(element) = inner {
+        inner = element;
+        depth += 1;
+    }
+    match scalar(inner) {
+        Some((c_type, read, write)) if depth <= 2 => Ok((c_type, read, write, depth)),
+        _ => Err(HarnessError::UnsupportedType {
+            language: "c".to_string(),
+            value_type: value_type.clone(),
+        }),
+    }
+}
+
+/// The submission between the headers and the driver; `#line` keeps the
+/// line numbers of its compile errors.
+pub fn source(signature: &Signature, code: &str) -> Result<String, HarnessError> {
+    let mut main = String::new();
+    let mut args = Vec::new();
+    for (i, param) in signature.params.iter().enumerate() {
+        let (c_type, read, _, depth) = shape(&param.value_type)?;
+        let arg = format!("pecan_arg{}", i);
+        match depth {
+            0 => {
+                let _ = writeln!(main, "    {} {} = {};", c_type, arg, read);
+                args.push(arg);
+            }
+            1 => {
+                let _ = writeln!(
+                    main,
+                    "    int {arg}_size = (int)pecan_read_long();\n    \
+                     {c_type} *{arg} = malloc(sizeof({c_type}) * ({arg}_size + 1));\n    \
+                     for (int i = 0; i < {arg}_size; i++) {arg}[i] = {read};"
+                );
+                args.push(format!("{arg}, {arg}_size"));
+            }
+            _ => {
+                let _ = writeln!(
+                    main,
+                    "    int {arg}_size = (int)pecan_read_long();\n    \
+                     {c_type} **{arg} = malloc(sizeof({c_type} *) * ({arg}_size + 1));\n    \
+                     int *{arg}_cols = malloc(sizeof(int) * ({arg}_size + 1));\n    \
+                     for (int i = 0; i < {arg}_size; i++) {{\n        \
+                     {arg}_cols[i] = (int)pecan_read_long();\n        \
+                     {arg}[i] = malloc(sizeof({c_type}) * ({arg}_cols[i] + 1));\n        \
+                     for (int j = 0; j < {arg}_cols[i]; j++) {arg}[i][j] = {read};\n    \
+                     }}"
+                );
+                args.push(format!("{arg}, {arg}_size, {arg}_cols"));
+            }
+        }
+    }
+
+    let (c_type, _, write, depth) = shape(&signature.returns)?;
+    let name = &signature.name;
+    match depth {
+        0 => {
+            let _ = writeln!(
+                main,
+                "    {c_type} pecan_result = {name}({});\n    \
+                 pecan_out = fopen(\"{RESULT_FILE}\", \"w\");\n    \
+                 {write}(pecan_result);",
+                args.join(", ")
+            );
+        }
+        1 => {
+            args.push("&pecan_size".to_string());
+            let _ = writeln!(
+                main,
+                "    int pecan_size = 0;\n    \
+                 {c_type} *pecan_result = {name}({});\n    \
+                 pecan_out = fopen(\"{RESULT_FILE}\", \"w\");\n    \
+                 pecan_write_long(pecan_size);\n    \
+                 for (int i = 0; i < pecan_size; i++) {write}(pecan_result[i]);",
+                args.join(", ")
+            );
+        }
+        _ => {
+            args.push("&pecan_size, &pecan_cols".to_string());
+            let _ = writeln!(
+                main,
+                "    int pecan_size = 0;\n    \
+                 int *pecan_cols = NULL;\n    \
+                 {c_type} **pecan_result = {name}({});\n    \
+                 pecan_out = fopen(\"{RESULT_FILE}\", \"w\");\n    \
+                 pecan_write_long(pecan_size);\n    \
+                 for (int i = 0; i < pecan_size; i++) {{\n        \
+                 pecan_write_long(pecan_cols[i]);\n        \
+                 for (int j = 0; j < pecan_cols[i]; j++) {write}(pecan_result[i][j]);\n    \
+                 }}",
+                args.join(", ")
+            );
+        }
+    }
+
+    Ok(format!(
+        "{PRELUDE}#line 1 \"solution.c\"\n{code}\n#line 1 \"driver.c\"\n{HELPERS}\n\
+         int main(void) {{\n    \
+         pecan_read_input();\n\
+         {main}    \
+         fclose(pecan_out);\n    \
+         return 0;\n\
+         }}\n"
+    ))
+}
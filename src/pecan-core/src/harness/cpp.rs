@@ -0,0 +1,148 @@
+//! C++ driver. Lists are `std::vector`s and strings `std::string`s; the
+//! function may take them by value or by reference.
+
+use std::fmt::Write;
+
+use super::{RESULT_FILE, Signature, ValueType};
+
+/// Headers and namespace a submission may rely on, as on LeetCode.
+const PRELUDE: &str = "\
+#include <algorithm>
+#include <cctype>
+#include <climits>
+#include <cmath>
+#include <cstdio>
+#include <cstdlib>
+#include <cstring>
+#include <fstream>
+#include <functional>
+#include <iostream>
+#include <iterator>
+#include <map>
+#include <numeric>
+#include <queue>
+#include <set>
+#include <stack>
+#include <string>
+#include <unordered_map>
+#include <unordered_set>
+#include <utility>
+#include <vector>
+using namespace std;
+";
+
+const HELPERS: &str = r#"
+static std::string pecan_in;
+static std::size_t pecan_pos = 0;
+static std::string pecan_out;
+
+static std::string pecan_token() {
+    while (pecan_pos < pecan_in.size() && std::isspace((unsigned char)pecan_in[pecan_pos])) {
+        pecan_pos++;
+    }
+    std::size_t start = pecan_pos;
+    while (pecan_pos < pecan_in.size() && !std::isspace((unsigned char)pecan_in[pecan_pos])) {
+        pecan_pos++;
+    }
+    return pecan_in.substr(start, pecan_pos - start);
+}
+
+static void pecan_read(int &value) { value = (int)std::stoll(pecan_token()); }
+static void pecan_read(long long &value) { value = std::stoll(pecan_token()); }
+static void pecan_read(double &value) { value = std::stod(pecan_token()); }
+static void pecan_read(bool &value) { value = pecan_token() != "0"; }
+
+static void pecan_read(std::string &value) {
+    std::size_t len = std::stoull(pecan_token());
+    value = pecan_in.substr(pecan_pos + 1, len);
+    pecan_pos += len + 1;
+}
+
+static void pecan_read(std::vector<bool> &value) {
+    value.resize(std::stoull(pecan_token()));
+    for (std::size_t i = 0; i < value.size(); i++) {
+        bool element;
+        pecan_read(element);
+        value[i] = element;
+    }
+}
+
+template <typename T>
+static void pecan_read(std::vector<T> &value) {
+    value.resize(std::stoull(pecan_token()));
+    for (auto &element : value) {
+        pecan_read(element);
+    }
+}
+
+static void pecan_write(long long value) { pecan_out += std::to_string(value) + " "; }
+static void pecan_write(int value) { pecan_write((long long)value); }
+static void pecan_write(bool value) { pecan_out += value ? "1 " : "0 "; }
+
+static void pecan_write(double value) {
+    char buffer[32];
+    std::snprintf(buffer, sizeof buffer, "%.17g ", value);
+    pecan_out += buffer;
+}
+
+static void pecan_write(const std::string &value) {
+    pecan_out += std::to_string(value.size()) + " " + value + " ";
+}
+
+static void pecan_write(const std::vector<bool> &value) {
+    pecan_out += std::to_string(value.size()) + " ";
+    for (bool element : value) {
+        pecan_write(element);
+    }
+}
+
+template <typename T>
+static void pecan_write(const std::vector<T> &value) {
+    pecan_out += std::to_string(value.size()) + " ";
+    for (const auto &element : value) {
+        pecan_write(element);
+    }
+}
+"#;
+
+fn cpp_type(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int => "int".to_string(),
+        ValueType::Long => "long long".to_string(),
+        ValueType::Double => "double".to_string(),
+        ValueType::Bool => "bool".to_string(),
+        ValueType::String => "std::string".to_string(),
+        ValueType::List(inner) => format!("std::vector<{}>", cpp_type(inner)),
+    }
+}
+
+/// The submission between the headers and the driver; `#line` keeps the
+/// line numbers of its compile errors.
+pub fn source(signature: &Signature, code: &str) -> String {
+    let mut main = String::new();
+    let mut args = Vec::new();
+    for (i, param) in signature.params.iter().enumerate() {
+        let arg = format!("pecan_arg{}", i);
+        let _ = writeln!(
+            main,
+            "    {} {arg};\n    pecan_read({arg});",
+            cpp_type(&param.value_type)
+        );
+        args.push(arg);
+    }
+    format!(
+        "{PRELUDE}#line 1 \"solution.cpp\"\n{code}\n#line 1 \"driver.cpp\"\n{HELPERS}\n\
+         int main() {{\n    \
+         pecan_in.assign(std::istreambuf_iterator<char>(std::cin), std::istreambuf_iterator<char>());\n\
+         {main}    \
+         {} pecan_result = {}({});\n    \
+         pecan_write(pecan_result);\n    \
+         std::ofstream pecan_file(\"{RESULT_FILE}\", std::ios::binary);\n    \
+         pecan_file << pecan_out;\n    \
+         return 0;\n\
+         }}\n",
+        cpp_type(&signature.returns),
+        signature.name,
+        args.join(", ")
+    )
+}
@@ -0,0 +1,155 @@
+//! Go driver. Lists are slices; `int` is Go's `int` and `long` is `int64`.
+//! The submission is compiled next to the driver in package `main`.
+
+use std::fmt::Write;
+
+use super::{RESULT_FILE, Signature, ValueType};
+
+const HELPERS: &str = r#"package main
+
+import (
+	"bytes"
+	"io"
+	"os"
+	"reflect"
+	"strconv"
+)
+
+type pecanReader struct {
+	data []byte
+	pos  int
+}
+
+func pecanSpace(b byte) bool {
+	return b == ' ' || (b >= '\t' && b <= '\r')
+}
+
+func (r *pecanReader) token() string {
+	for r.pos < len(r.data) && pecanSpace(r.data[r.pos]) {
+		r.pos++
+	}
+	start := r.pos
+	for r.pos < len(r.data) && !pecanSpace(r.data[r.pos]) {
+		r.pos++
+	}
+	return string(r.data[start:r.pos])
+}
+
+func (r *pecanReader) long() int64 {
+	value, _ := strconv.ParseInt(r.token(), 10, 64)
+	return value
+}
+
+func (r *pecanReader) double() float64 {
+	value, _ := strconv.ParseFloat(r.token(), 64)
+	return value
+}
+
+func (r *pecanReader) str() string {
+	n := int(r.long())
+	r.pos++
+	value := string(r.data[r.pos : r.pos+n])
+	r.pos += n
+	return value
+}
+
+func pecanList[T any](r *pecanReader, read func() T) []T {
+	value := make([]T, r.long())
+	for i := range value {
+		value[i] = read()
+	}
+	return value
+}
+
+func pecanWrite(out *bytes.Buffer, value reflect.Value) {
+	switch value.Kind() {
+	case reflect.Int, reflect.Int64:
+		out.WriteString(strconv.FormatInt(value.Int(), 10))
+	case reflect.Float64:
+		out.WriteString(strconv.FormatFloat(value.Float(), 'g', -1, 64))
+	case reflect.Bool:
+		if value.Bool() {
+			out.WriteString("1")
+		} else {
+			out.WriteString("0")
+		}
+	case reflect.String:
+		out.WriteString(strconv.Itoa(len(value.String())) + " " + value.String())
+	case reflect.Slice:
+		out.WriteString(strconv.Itoa(value.Len()))
+		for i := 0; i < value.Len(); i++ {
+			out.WriteByte(' ')
+			pecanWrite(out, value.Index(i))
+		}
+	}
+}
+"#;
+
+fn go_type(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int => "int".to_string(),
+        ValueType::Long => "int64".to_string(),
+        ValueType::Double => "float64".to_string(),
+        ValueType::Bool => "bool".to_string(),
+        ValueType::String => "string".to_string(),
+        ValueType::List(inner) => format!("[]{}", go_type(inner)),
+    }
+}
+
+fn read(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int => "int(r.long())".to_string(),
+        ValueType::Long => "r.long()".to_string(),
+        ValueType::Double => "r.double()".to_string(),
+        ValueType::Bool => "r.long() != 0".to_string(),
+        ValueType::String => "r.str()".to_string(),
+        ValueType::List(inner) => format!(
+            "pecanList(r, func() {} {{ return {} }})",
+            go_type(inner),
+            read(inner)
+        ),
+    }
+}
+
+pub fn driver(signature: &Signature) -> String {
+    let mut main = String::new();
+    let mut args = Vec::new();
+    for (i, param) in signature.params.iter().enumerate() {
+        let arg = format!("pecanArg{}", i);
+        let _ = writeln!(
+            main,
+            "\tvar {arg} {} = {}",
+            go_type(&param.value_type),
+            read(&param.value_type)
+        );
+        args.push(arg);
+    }
+    format!(
+        "{HELPERS}\n\
+         func main() {{\n\
+         \tdata, _ := io.ReadAll(os.Stdin)\n\
+         \tr := &pecanReader{{data: data}}\n\
+         \t_ = r\n\
+         {main}\
+         \tvar pecanResult {} = {}({})\n\
+         \tvar out bytes.Buffer\n\
+         \tpecanWrite(&out, reflect.ValueOf(pecanResult))\n\
+         \tos.WriteFile(\"{RESULT_FILE}\", out.Bytes(), 0o644)\n\
+         }}\n",
+        go_type(&signature.returns),
+        signature.name,
+        args.join(", ")
+    )
+}
+
+/// The submission as a file of package `main`, which it may leave out.
+pub fn solution(code: &str) -> String {
+    let declared = code
+        .lines()
+        .any(|line| line.trim_start().starts_with("package "));
+    if declared {
+        code.to_string()
+    } else {
+        format!("package main\n\n{}", code)
+    }
+}
@@ -0,0 +1,161 @@
+//! Java driver. The function is a method of class `Solution`, as on
+//! LeetCode; lists are arrays such as `int[]` and `String[][]`.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::{RESULT_FILE, Signature, ValueType};
+
+const HELPERS: &str = r#"import java.nio.charset.StandardCharsets;
+
+public class Main {
+    private static byte[] in;
+    private static int pos;
+    private static final java.io.ByteArrayOutputStream out = new java.io.ByteArrayOutputStream();
+
+    private static boolean isSpace(byte b) {
+        return b == ' ' || (b >= '\t' && b <= '\r');
+    }
+
+    private static String token() {
+        while (pos < in.length && isSpace(in[pos])) {
+            pos++;
+        }
+        int start = pos;
+        while (pos < in.length && !isSpace(in[pos])) {
+            pos++;
+        }
+        return new String(in, start, pos - start, StandardCharsets.UTF_8);
+    }
+
+    private static long readLong() {
+        return Long.parseLong(token());
+    }
+
+    private static double readDouble() {
+        return Double.parseDouble(token());
+    }
+
+    private static String readString() {
+        int n = (int) readLong();
+        pos++;
+        String value = new String(in, pos, n, StandardCharsets.UTF_8);
+        pos += n;
+        return value;
+    }
+
+    private static void emit(String text) {
+        byte[] bytes = text.getBytes(StandardCharsets.UTF_8);
+        out.write(bytes, 0, bytes.length);
+    }
+
+    private static void write(Object value) {
+        if (value instanceof Boolean) {
+            emit((Boolean) value ? "1 " : "0 ");
+        } else if (value instanceof String) {
+            String text = (String) value;
+            emit(text.getBytes(StandardCharsets.UTF_8).length + " " + text + " ");
+        } else if (value != null && value.getClass().isArray()) {
+            int n = java.lang.reflect.Array.getLength(value);
+            emit(n + " ");
+            for (int i = 0; i < n; i++) {
+                write(java.lang.reflect.Array.get(value, i));
+            }
+        } else {
+            emit(value + " ");
+        }
+    }
+"#;
+
+fn java_type(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int => "int".to_string(),
+        ValueType::Long => "long".to_string(),
+        ValueType::Double => "double".to_string(),
+        ValueType::Bool => "boolean".to_string(),
+        ValueType::String => "String".to_string(),
+        ValueType::List(inner) => format!("{}[]", java_type(inner)),
+    }
+}
+
+fn mangle(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int => "Int".to_string(),
+        ValueType::Long => "Long".to_string(),
+        ValueType::Double => "Double".to_string(),
+        ValueType::Bool => "Bool".to_string(),
+        ValueType::String => "String".to_string(),
+        ValueType::List(inner) => format!("List{}", mangle(inner)),
+    }
+}
+
+/// Read expression of `value_type`; arrays are read by a method of their
+/// own, collected into `lists`.
+fn read(value_type: &ValueType, lists: &mut BTreeSet<ValueType>) -> String {
+    match value_type {
+        ValueType::Int => "(int) readLong()".to_string(),
+        ValueType::Long => "readLong()".to_string(),
+        ValueType::Double => "readDouble()".to_string(),
+        ValueType::Bool => "readLong() != 0".to_string(),
+        ValueType::String => "readString()".to_string(),
+        ValueType::List(inner) => {
+            read(inner, lists);
+            lists.insert(value_type.clone());
+            format!("read{}()", mangle(value_type))
+        }
+    }
+}
+
+pub fn driver(signature: &Signature) -> String {
+    let mut lists = BTreeSet::new();
+    let mut main = String::new();
+    let mut args = Vec::new();
+    for (i, param) in signature.params.iter().enumerate() {
+        let arg = format!("pecanArg{}", i);
+        let _ = writeln!(
+            main,
+            "        {} {arg} = {};",
+            java_type(&param.value_type),
+            read(&param.value_type, &mut lists)
+        );
+        args.push(arg);
+    }
+
+    let mut readers = String::new();
+    for list in &lists {
+        let ValueType::List(inner) = list else {
+            continue;
+        };
+        // `new int[n][]` for an `int[][]`
+        let array_type = java_type(list);
+        let new_array = array_type.replacen("[]", "[n]", 1);
+        let _ = writeln!(
+            readers,
+            "\n    private static {array_type} read{}() {{\n        \
+             int n = (int) readLong();\n        \
+             {array_type} value = new {new_array};\n        \
+             for (int i = 0; i < n; i++) {{\n            \
+             value[i] = {};\n        \
+             }}\n        \
+             return value;\n    \
+             }}",
+            mangle(list),
+            read(inner, &mut BTreeSet::new())
+        );
+    }
+
+    format!(
+        "{HELPERS}{readers}\n    \
+         public static void main(String[] args) throws Exception {{\n        \
+         in = System.in.readAllBytes();\n\
+         {main}        \
+         {} pecanResult = new Solution().{}({});\n        \
+         write(pecanResult);\n        \
+         java.nio.file.Files.write(java.nio.file.Paths.get(\"{RESULT_FILE}\"), out.toByteArray());\n    \
+         }}\n\
+         }}\n",
+        java_type(&signature.returns),
+        signature.name,
+        args.join(", ")
+    )
+}
@@ -0,0 +1,107 @@
+//! Kotlin driver. The function is a top-level function; lists are `List`s.
+
+use std::fmt::Write;
+
+use super::{RESULT_FILE, Signature, ValueType};
+
+const HELPERS: &str = r#"private lateinit var pecanIn: ByteArray
+private var pecanPos = 0
+private val pecanOut = java.io.ByteArrayOutputStream()
+
+private fun pecanSpace(b: Byte): Boolean = b.toInt() == 32 || b.toInt() in 9..13
+
+private fun pecanToken(): String {
+    while (pecanPos < pecanIn.size && pecanSpace(pecanIn[pecanPos])) {
+        pecanPos++
+    }
+    val start = pecanPos
+    while (pecanPos < pecanIn.size && !pecanSpace(pecanIn[pecanPos])) {
+        pecanPos++
+    }
+    return String(pecanIn, start, pecanPos - start, Charsets.UTF_8)
+}
+
+private fun pecanLong(): Long = pecanToken().toLong()
+
+private fun pecanDouble(): Double = pecanToken().toDouble()
+
+private fun pecanString(): String {
+    val n = pecanLong().toInt()
+    pecanPos++
+    val value = String(pecanIn, pecanPos, n, Charsets.UTF_8)
+    pecanPos += n
+    return value
+}
+
+private fun <T> pecanList(read: () -> T): List<T> {
+    val n = pecanLong().toInt()
+    return List(n) { read() }
+}
+
+private fun pecanEmit(text: String) {
+    val bytes = text.toByteArray(Charsets.UTF_8)
+    pecanOut.write(bytes, 0, bytes.size)
+}
+
+private fun pecanWrite(value: Any?) {
+    when (value) {
+        is Boolean -> pecanEmit(if (value) "1 " else "0 ")
+        is String -> pecanEmit("${value.toByteArray(Charsets.UTF_8).size} $value ")
+        is List<*> -> {
+            pecanEmit("${value.size} ")
+            value.forEach { pecanWrite(it) }
+        }
+        else -> pecanEmit("$value ")
+    }
+}
+"#;
+
+fn kotlin_type(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int => "Int".to_string(),
+        ValueType::Long => "Long".to_string(),
+        ValueType::Double => "Double".to_string(),
+        ValueType::Bool => "Boolean".to_string(),
+        ValueType::String => "String".to_string(),
+        ValueType::List(inner) => format!("List<{}>", kotlin_type(inner)),
+    }
+}
+
+fn read(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int => "pecanLong().toInt()".to_string(),
+        ValueType::Long => "pecanLong()".to_string(),
+        ValueType::Double => "pecanDouble()".to_string(),
+        ValueType::Bool => "pecanLong() != 0L".to_string(),
+        ValueType::String => "pecanString()".to_string(),
+        ValueType::List(inner) => format!("pecanList {{ {} }}", read(inner)),
+    }
+}
+
+pub fn driver(signature: &Signature) -> String {
+    let mut main = String::new();
+    let mut args = Vec::new();
+    for (i, param) in signature.params.iter().enumerate() {
+        let arg = format!("pecanArg{}", i);
+        let _ = writeln!(
+            main,
+            "    val {arg}: {} = {}",
+            kotlin_type(&param.value_type),
+            read(&param.value_type)
+        );
+        args.push(arg);
+    }
+    format!(
+        "{HELPERS}\n\
+         fun main() {{\n    \
+         pecanIn = System.`in`.readBytes()\n\
+         {main}    \
+         val pecanResult: {} = {}({})\n    \
+         pecanWrite(pecanResult)\n    \
+         java.io.File(\"{RESULT_FILE}\").writeBytes(pecanOut.toByteArray())\n\
+         }}\n",
+        kotlin_type(&signature.returns),
+        signature.name,
+        args.join(", ")
+    )
+}
@@ -0,0 +1,398 @@
+//! Drivers that call one function of a submission, for judging against a
+//! function signature instead of stdin and stdout.
+//!
+//! Values travel between the server and a driver in a plain text form that
+//! every language reads without a JSON parser: tokens separated by
+//! whitespace, where
+//! - `int` and `long` are decimal integers and `double` a decimal number,
+//! - `bool` is `1` or `0`,
+//! - `string` is its length in UTF-8 bytes, one space and the bytes,
+//! - `list<T>` is its length followed by its elements.
+//!
+//! A driver reads the arguments from stdin, calls the function and writes
+//! the return value to [`RESULT_FILE`], which leaves stdout to the
+//! submission.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::{Number, Value};
+use thiserror::Error;
+
+use crate::code_execution::{Project, ProjectFile};
+use crate::toolchains::{Language, language_toolchain};
+
+mod c;
+mod cpp;
+mod go;
+mod java;
+mod kotlin;
+mod node;
+mod python;
+mod rust;
+
+/// File the driver writes the return value to.
+pub const RESULT_FILE: &str = "pecan_result.txt";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum HarnessError {
+    #[error("unknown type {0}, expected int, long, double, bool, string or list<T>")]
+    UnknownType(String),
+    #[error("{0} is not a valid identifier")]
+    InvalidName(String),
+    #[error("{0} is not supported by function judging")]
+    UnsupportedLanguage(String),
+    #[error("{language} does not support {value_type}")]
+    UnsupportedType {
+        language: String,
+        value_type: ValueType,
+    },
+    #[error("expected {0}, found {1}")]
+    Mismatch(ValueType, String),
+    #[error("malformed value: {0}")]
+    Malformed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ValueType {
+    /// 32-bit integer
+    Int,
+    /// 64-bit integer
+    Long,
+    Double,
+    Bool,
+    String,
+    List(Box<ValueType>),
+}
+
+impl FromStr for ValueType {
+    type Err = HarnessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        let inner = name
+            .strip_prefix("list")
+            .map(str::trim_start)
+            .and_then(|rest| rest.strip_prefix('<'))
+            .and_then(|rest| rest.strip_suffix('>'));
+        if let Some(inner) = inner {
+            return Ok(ValueType::List(Box::new(inner.parse()?)));
+        }
+        match name {
+            "int" => Ok(ValueType::Int),
+            "long" => Ok(ValueType::Long),
+            "double" => Ok(ValueType::Double),
+            "bool" => Ok(ValueType::Bool),
+            "string" => Ok(ValueType::String),
+            _ => Err(HarnessError::UnknownType(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Int => f.write_str("int"),
+            ValueType::Long => f.write_str("long"),
+            ValueType::Double => f.write_str("double"),
+            ValueType::Bool => f.write_str("bool"),
+            ValueType::String => f.write_str("string"),
+            ValueType::List(inner) => write!(f, "list<{}>", inner),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub value_type: ValueType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    /// function the driver calls
+    pub name: String,
+    pub params: Vec<Parameter>,
+    pub returns: ValueType,
+}
+
+/// Check that `name` can name a function or parameter in every language;
+/// the `pecan` prefix is left to the drivers.
+pub fn check_identifier(name: &str) -> Result<(), HarnessError> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("pecan")
+        && name != "main";
+    if valid {
+        Ok(())
+    } else {
+        Err(HarnessError::InvalidName(name.to_string()))
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(_) => "a boolean".to_string(),
+        Value::Number(number) => format!("the number {}", number),
+        Value::String(_) => "a string".to_string(),
+        Value::Array(_) => "a list".to_string(),
+        Value::Object(_) => "an object".to_string(),
+    }
+}
+
+/// Append the wire form of `value`, which must be of type `value_type`.
+pub fn encode(
+    value: &Value,
+    value_type: &ValueType,
+    out: &mut Vec<u8>,
+) -> Result<(), HarnessError> {
+    let mismatch = || HarnessError::Mismatch(value_type.clone(), describe(value));
+    match value_type {
+        ValueType::Int => {
+            let number = value.as_i64().and_then(|n| i32::try_from(n).ok());
+            out.extend(format!("{} ", number.ok_or_else(mismatch)?).bytes());
+        }
+        ValueType::Long => {
+            out.extend(format!("{} ", value.as_i64().ok_or_else(mismatch)?).bytes());
+        }
+        ValueType::Double => {
+            out.extend(format!("{:?} ", value.as_f64().ok_or_else(mismatch)?).bytes());
+        }
+        ValueType::Bool => {
+            out.extend_from_slice(if value.as_bool().ok_or_else(mismatch)? {
+                b"1 "
+            } else {
+                b"0 "
+            });
+        }
+        ValueType::String => {
+            let text = value.as_str().ok_or_else(mismatch)?;
+            out.extend(format!("{} ", text.len()).bytes());
+            out.extend_from_slice(text.as_bytes());
+            out.push(b' ');
+        }
+        ValueType::List(inner) => {
+            let items = value.as_array().ok_or_else(mismatch)?;
+            out.extend(format!("{} ", items.len()).bytes());
+            for item in items {
+                encode(item, inner, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads values back from the wire form a driver wrote.
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Decoder<'_> {
+    fn token(&mut self) -> Result<&str, HarnessError> {
+        while self.data.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(HarnessError::Malformed("ends early".to_string()));
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .map_err(|_| HarnessError::Malformed("a token is not UTF-8".to_string()))
+    }
+
+    fn parse<T: FromStr>(&mut self, value_type: &ValueType) -> Result<T, HarnessError> {
+        let token = self.token()?;
+        token
+            .parse()
+            .map_err(|_| HarnessError::Mismatch(value_type.clone(), format!("`{}`", token)))
+    }
+
+    fn value(&mut self, value_type: &ValueType) -> Result<Value, HarnessError> {
+        Ok(match value_type {
+            ValueType::Int => Value::from(self.parse::<i32>(value_type)?),
+            ValueType::Long => Value::from(self.parse::<i64>(value_type)?),
+            ValueType::Double => {
+                let number = self.parse::<f64>(value_type)?;
+                Value::Number(Number::from_f64(number).ok_or_else(|| {
+                    HarnessError::Mismatch(value_type.clone(), number.to_string())
+                })?)
+            }
+            ValueType::Bool => match self.token()? {
+                "1" => Value::Bool(true),
+                "0" => Value::Bool(false),
+                token => {
+                    return Err(HarnessError::Mismatch(
+                        value_type.clone(),
+                        format!("`{}`", token),
+                    ));
+                }
+            },
+            ValueType::String => {
+                let len: usize = self.parse(value_type)?;
+                // one separator, then exactly `len` bytes
+                let start = self.pos + 1;
+                let bytes = self
+                    .data
+                    .get(start..start.saturating_add(len))
+                    .ok_or_else(|| HarnessError::Malformed("a string ends early".to_string()))?;
+                let text = std::str::from_utf8(bytes)
+                    .map_err(|_| HarnessError::Malformed("a string is not UTF-8".to_string()))?;
+                self.pos = start + len;
+                Value::String(text.to_string())
+            }
+            ValueType::List(inner) => {
+                let len: usize = self.parse(value_type)?;
+                // every element takes at least two bytes
+                let mut items = Vec::with_capacity(len.min(self.data.len() / 2));
+                for _ in 0..len {
+                    items.push(self.value(inner)?);
+                }
+                Value::Array(items)
+            }
+        })
+    }
+}
+
+/// Read a value of type `value_type` from the whole of `data`.
+pub fn decode(data: &[u8], value_type: &ValueType) -> Result<Value, HarnessError> {
+    let mut decoder = Decoder { data, pos: 0 };
+    let value = decoder.value(value_type)?;
+    if !data[decoder.pos.min(data.len())..]
+        .iter()
+        .all(u8::is_ascii_whitespace)
+    {
+        return Err(HarnessError::Malformed("trailing data".to_string()));
+    }
+    Ok(value)
+}
+
+/// Project of the submission `code` together with the driver that calls its
+/// function. Scripts and single-file languages get the driver after the
+/// code, so that compile errors keep their line numbers; Go, Java and
+/// Kotlin keep the code in a file of its own.
+pub fn project(
+    language: Language,
+    signature: &Signature,
+    code: &str,
+) -> Result<Project, HarnessError> {
+    let toolchain = language_toolchain(language)
+        .ok_or_else(|| HarnessError::UnsupportedLanguage(language.as_str().to_string()))?;
+    let entry_point = toolchain.project_option.entry_point;
+    let file = |path: &str, content: String| ProjectFile {
+        path: path.to_string(),
+        content: content.into_bytes(),
+    };
+    let appended = |driver: String| vec![file(&entry_point, format!("{}\n{}", code, driver))];
+    let files = match language {
+        Language::C => vec![file(&entry_point, c::source(signature, code)?)],
+        Language::Cpp => vec![file(&entry_point, cpp::source(signature, code))],
+        Language::Go => vec![
+            file(&entry_point, go::driver(signature)),
+            file("solution.go", go::solution(code)),
+        ],
+        Language::Java => vec![
+            file(&entry_point, java::driver(signature)),
+            file("Solution.java", code.to_string()),
+        ],
+        Language::Kotlin => vec![
+            file(&entry_point, kotlin::driver(signature)),
+            file("Solution.kt", code.to_string()),
+        ],
+        Language::Node => appended(node::driver(signature, false)),
+        Language::Typescript => appended(node::driver(signature, true)),
+        Language::Python => appended(python::driver(signature)),
+        Language::Rust => appended(rust::driver(signature)),
+        Language::Unknown => {
+            return Err(HarnessError::UnsupportedLanguage("unknown".to_string()));
+        }
+    };
+    Ok(Project {
+        files,
+        entry_point: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{HarnessError, Parameter, Signature, ValueType, check_identifier, decode, encode};
+    use crate::toolchains::Language;
+
+    #[test]
+    fn types_parse_and_print() {
+        let grid: ValueType = "list< list<int> >".parse().unwrap();
+        assert_eq!(
+            grid,
+            ValueType::List(Box::new(ValueType::List(Box::new(ValueType::Int))))
+        );
+        assert_eq!(grid.to_string(), "list<list<int>>");
+        assert_eq!(
+            "map<int>".parse::<ValueType>(),
+            Err(HarnessError::UnknownType("map<int>".to_string()))
+        );
+        assert!(check_identifier("two_sum").is_ok());
+        for name in ["2sum", "main", "pecan_read", "a-b", ""] {
+            assert!(check_identifier(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn values_survive_the_wire_form() {
+        let cases = [
+            ("list<int>", json!([1, -2, 3])),
+            ("list<list<string>>", json!([["a b", ""], ["é\n"]])),
+            ("double", json!(0.1)),
+            ("list<bool>", json!([true, false])),
+            ("long", json!(9_007_199_254_740_993_i64)),
+        ];
+        for (value_type, value) in cases {
+            let value_type: ValueType = value_type.parse().unwrap();
+            let mut wire = Vec::new();
+            encode(&value, &value_type, &mut wire).unwrap();
+            assert_eq!(decode(&wire, &value_type).unwrap(), value);
+        }
+
+        let mut wire = Vec::new();
+        assert_eq!(
+            encode(&json!(3_000_000_000_i64), &ValueType::Int, &mut wire),
+            Err(HarnessError::Mismatch(
+                ValueType::Int,
+                "the number 3000000000".to_string()
+            ))
+        );
+        assert!(decode(b"3 1 2", &ValueType::List(Box::new(ValueType::Int))).is_err());
+        assert!(decode(b"5 abc", &ValueType::String).is_err());
+        assert!(decode(b"1 2", &ValueType::Int).is_err());
+    }
+
+    #[test]
+    fn c_supports_two_levels_of_lists() {
+        let signature = |returns: &str| Signature {
+            name: "solve".to_string(),
+            params: vec![Parameter {
+                name: "grid".to_string(),
+                value_type: "list<list<int>>".parse().unwrap(),
+            }],
+            returns: returns.parse().unwrap(),
+        };
+        assert!(super::project(Language::C, &signature("list<list<int>>"), "").is_ok());
+        assert!(matches!(
+            super::project(Language::C, &signature("list<list<list<int>>>"), ""),
+            Err(HarnessError::UnsupportedType { .. })
+        ));
+        assert!(super::project(Language::Cpp, &signature("list<list<list<int>>>"), "").is_ok());
+    }
+}
@@ -0,0 +1,119 @@
+//! JavaScript and TypeScript driver. The function is a top-level function;
+//! lists are arrays and every number is a `number`, so `long` values beyond
+//! 2^53 lose precision.
+
+use std::fmt::Write;
+
+use super::{RESULT_FILE, Signature, ValueType};
+
+const HELPERS: &str = r#"const pecanData = require("fs").readFileSync(0);
+let pecanPos = 0;
+
+function pecanSpace(byte) {
+    return byte === 32 || (byte >= 9 && byte <= 13);
+}
+
+function pecanToken() {
+    while (pecanPos < pecanData.length && pecanSpace(pecanData[pecanPos])) {
+        pecanPos++;
+    }
+    const start = pecanPos;
+    while (pecanPos < pecanData.length && !pecanSpace(pecanData[pecanPos])) {
+        pecanPos++;
+    }
+    return pecanData.toString("latin1", start, pecanPos);
+}
+
+function pecanString() {
+    const n = Number(pecanToken());
+    pecanPos++;
+    const value = pecanData.toString("utf8", pecanPos, pecanPos + n);
+    pecanPos += n;
+    return value;
+}
+
+function pecanList(read) {
+    const n = Number(pecanToken());
+    const value = [];
+    for (let i = 0; i < n; i++) {
+        value.push(read());
+    }
+    return value;
+}
+
+function pecanWrite(out, value) {
+    if (typeof value === "boolean") {
+        out.push(value ? "1 " : "0 ");
+    } else if (typeof value === "string") {
+        out.push(require("buffer").Buffer.byteLength(value, "utf8") + " " + value + " ");
+    } else if (Array.isArray(value)) {
+        out.push(value.length + " ");
+        for (let i = 0; i < value.length; i++) {
+            pecanWrite(out, value[i]);
+        }
+    } else {
+        out.push(String(value) + " ");
+    }
+}
+"#;
+
+fn ts_type(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int | ValueType::Long | ValueType::Double => "number".to_string(),
+        ValueType::Bool => "boolean".to_string(),
+        ValueType::String => "string".to_string(),
+        ValueType::List(inner) => format!("{}[]", ts_type(inner)),
+    }
+}
+
+fn read(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int | ValueType::Long | ValueType::Double => "Number(pecanToken())".to_string(),
+        ValueType::Bool => "pecanToken() !== \"0\"".to_string(),
+        ValueType::String => "pecanString()".to_string(),
+        ValueType::List(inner) => format!("pecanList(() => {})", read(inner)),
+    }
+}
+
+/// The driver, with type annotations that check the function's signature
+/// when `typescript` is set.
+pub fn driver(signature: &Signature, typescript: bool) -> String {
+    let annotation = |value_type: &ValueType| {
+        if typescript {
+            format!(": {}", ts_type(value_type))
+        } else {
+            String::new()
+        }
+    };
+    let mut main = String::new();
+    let mut args = Vec::new();
+    for (i, param) in signature.params.iter().enumerate() {
+        let arg = format!("pecanArg{}", i);
+        let _ = writeln!(
+            main,
+            "    const {arg}{} = {};",
+            annotation(&param.value_type),
+            read(&param.value_type)
+        );
+        args.push(arg);
+    }
+    // the toolchain has no Node.js type definitions
+    let declarations = if typescript {
+        "declare const require: any;\n"
+    } else {
+        ""
+    };
+    format!(
+        "{declarations}{HELPERS}\n\
+         {{\n\
+         {main}    \
+         const pecanResult{} = {}({});\n    \
+         const pecanOut = [];\n    \
+         pecanWrite(pecanOut, pecanResult);\n    \
+         require(\"fs\").writeFileSync(\"{RESULT_FILE}\", pecanOut.join(\"\"));\n\
+         }}\n",
+        annotation(&signature.returns),
+        signature.name,
+        args.join(", ")
+    )
+}
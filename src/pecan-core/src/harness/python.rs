@@ -0,0 +1,81 @@
+//! Python driver. The function is a top-level function; lists are `list`s.
+
+use super::{RESULT_FILE, Signature, ValueType};
+
+const HELPERS: &str = r#"
+import sys as _pecan_sys
+
+
+class _PecanReader:
+    def __init__(self, data):
+        self.data = data
+        self.pos = 0
+
+    def token(self):
+        data = self.data
+        while self.pos < len(data) and data[self.pos] in b" \t\n\r\x0b\x0c":
+            self.pos += 1
+        start = self.pos
+        while self.pos < len(data) and data[self.pos] not in b" \t\n\r\x0b\x0c":
+            self.pos += 1
+        return data[start:self.pos].decode()
+
+    def long(self):
+        return int(self.token())
+
+    def double(self):
+        return float(self.token())
+
+    def string(self):
+        n = self.long()
+        self.pos += 1
+        value = self.data[self.pos:self.pos + n].decode("utf-8")
+        self.pos += n
+        return value
+
+    def list(self, read):
+        return [read() for _ in range(self.long())]
+
+
+def _pecan_write(out, value):
+    if isinstance(value, bool):
+        out.append(b"1 " if value else b"0 ")
+    elif isinstance(value, str):
+        data = value.encode("utf-8")
+        out.append(b"%d " % len(data) + data + b" ")
+    elif isinstance(value, (list, tuple)):
+        out.append(b"%d " % len(value))
+        for item in value:
+            _pecan_write(out, item)
+    else:
+        out.append(repr(value).encode() + b" ")
+"#;
+
+fn read(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int | ValueType::Long => "_pecan_reader.long()".to_string(),
+        ValueType::Double => "_pecan_reader.double()".to_string(),
+        ValueType::Bool => "_pecan_reader.long() != 0".to_string(),
+        ValueType::String => "_pecan_reader.string()".to_string(),
+        ValueType::List(inner) => format!("_pecan_reader.list(lambda: {})", read(inner)),
+    }
+}
+
+pub fn driver(signature: &Signature) -> String {
+    let args: Vec<String> = signature
+        .params
+        .iter()
+        .map(|param| read(&param.value_type))
+        .collect();
+    format!(
+        "{HELPERS}\n\n\
+         _pecan_reader = _PecanReader(_pecan_sys.stdin.buffer.read())\n\
+         _pecan_args = [{}]\n\
+         _pecan_out = []\n\
+         _pecan_write(_pecan_out, {}(*_pecan_args))\n\
+         with open(\"{RESULT_FILE}\", \"wb\") as _pecan_file:\n    \
+         _pecan_file.write(b\"\".join(_pecan_out))\n",
+        args.join(", "),
+        signature.name
+    )
+}
@@ -0,0 +1,146 @@
+//! Rust driver. The function is a free function taking its arguments by
+//! value; lists are `Vec`s, `int` is `i32` and `long` is `i64`. The driver
+//! is written for the 2015 edition the toolchain compiles with.
+
+use std::fmt::Write;
+
+use super::{RESULT_FILE, Signature, ValueType};
+
+const HELPERS: &str = r#"
+#[allow(dead_code)]
+mod pecan {
+    use std::io::Read;
+
+    pub struct Reader {
+        data: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Reader {
+        pub fn new() -> Reader {
+            let mut data = Vec::new();
+            std::io::stdin().read_to_end(&mut data).unwrap();
+            Reader { data: data, pos: 0 }
+        }
+
+        fn token(&mut self) -> &str {
+            while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            let start = self.pos;
+            while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            std::str::from_utf8(&self.data[start..self.pos]).unwrap()
+        }
+    }
+
+    pub trait Value: Sized {
+        fn read(reader: &mut Reader) -> Self;
+        fn write(&self, out: &mut Vec<u8>);
+    }
+
+    impl Value for i32 {
+        fn read(reader: &mut Reader) -> Self {
+            reader.token().parse().unwrap()
+        }
+        fn write(&self, out: &mut Vec<u8>) {
+            out.extend(format!("{} ", self).bytes());
+        }
+    }
+
+    impl Value for i64 {
+        fn read(reader: &mut Reader) -> Self {
+            reader.token().parse().unwrap()
+        }
+        fn write(&self, out: &mut Vec<u8>) {
+            out.extend(format!("{} ", self).bytes());
+        }
+    }
+
+    impl Value for f64 {
+        fn read(reader: &mut Reader) -> Self {
+            reader.token().parse().unwrap()
+        }
+        fn write(&self, out: &mut Vec<u8>) {
+            out.extend(format!("{:?} ", self).bytes());
+        }
+    }
+
+    impl Value for bool {
+        fn read(reader: &mut Reader) -> Self {
+            reader.token() != "0"
+        }
+        fn write(&self, out: &mut Vec<u8>) {
+            out.extend_from_slice(if *self { b"1 " } else { b"0 " });
+        }
+    }
+
+    impl Value for String {
+        fn read(reader: &mut Reader) -> Self {
+            let n: usize = reader.token().parse().unwrap();
+            let start = reader.pos + 1;
+            reader.pos = start + n;
+            String::from_utf8(reader.data[start..start + n].to_vec()).unwrap()
+        }
+        fn write(&self, out: &mut Vec<u8>) {
+            out.extend(format!("{} ", self.len()).bytes());
+            out.extend_from_slice(self.as_bytes());
+            out.push(b' ');
+        }
+    }
+
+    impl<T: Value> Value for Vec<T> {
+        fn read(reader: &mut Reader) -> Self {
+            let n: usize = reader.token().parse().unwrap();
+            (0..n).map(|_| T::read(reader)).collect()
+        }
+        fn write(&self, out: &mut Vec<u8>) {
+            out.extend(format!("{} ", self.len()).bytes());
+            for item in self {
+                item.write(out);
+            }
+        }
+    }
+}
+"#;
+
+fn rust_type(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Int => "i32".to_string(),
+        ValueType::Long => "i64".to_string(),
+        ValueType::Double => "f64".to_string(),
+        ValueType::Bool => "bool".to_string(),
+        ValueType::String => "String".to_string(),
+        ValueType::List(inner) => format!("Vec<{}>", rust_type(inner)),
+    }
+}
+
+pub fn driver(signature: &Signature) -> String {
+    let mut main = String::new();
+    let mut args = Vec::new();
+    for (i, param) in signature.params.iter().enumerate() {
+        let arg = format!("pecan_arg{}", i);
+        let _ = writeln!(
+            main,
+            "    let {arg}: {} = pecan::Value::read(&mut pecan_reader);",
+            rust_type(&param.value_type)
+        );
+        args.push(arg);
+    }
+    format!(
+        "{HELPERS}\n\
+         #[allow(unused_mut, unused_variables)]\n\
+         fn main() {{\n    \
+         let mut pecan_reader = pecan::Reader::new();\n\
+         {main}    \
+         let pecan_result: {} = {}({});\n    \
+         let mut pecan_out = Vec::new();\n    \
+         pecan::Value::write(&pecan_result, &mut pecan_out);\n    \
+         std::fs::write(\"{RESULT_FILE}\", pecan_out).unwrap();\n\
+         }}\n",
+        rust_type(&signature.returns),
+        signature.name,
+        args.join(", ")
+    )
+}
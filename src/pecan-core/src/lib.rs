@@ -4,26 +4,28 @@ use crate::code_execution::AsyncCodeExecutionResult;
 use crate::errors::CoreServiceError;
 use crate::service::{Service, ServiceSpec};
 
+pub mod cache;
 pub mod code_execution;
 pub mod errors;
+pub mod harness;
+pub mod selftest;
 pub mod service;
+pub mod telemetry;
 pub mod toolchains;
 pub mod utils;
 
+pub use pecan_sandbox::artifacts::ArtifactCachePolicy;
+pub use pecan_sandbox::manager::{AutoscalePolicy, SandboxInfo};
+pub use pecan_sandbox::permits::{Priority, PriorityShares};
+pub use pecan_sandbox::sandbox::{
+    ExecutionEvent, ExecutionEvents, ExecutionInput, ExecutionPhase, OutputChunk, OutputStream,
+    SandboxStatus,
+};
+
 pub const SANDBOX_SOLUTION: &str = pecan_sandbox::tools::SANDBOX_SOLUTION;
 
 pub async fn init(
-    max_queue_size: u32,
-    max_concurrent_executions: u32,
-    webhook_buffer_size: usize,
+    spec: ServiceSpec,
 ) -> Result<(Service, Receiver<AsyncCodeExecutionResult>), CoreServiceError> {
-    let (service, rx) = Service::new(ServiceSpec {
-        enable_bg_worker_loop: true,
-        max_queue_size,
-        max_concurrent_executions,
-        webhook_buffer_size,
-    })
-    .await?;
-
-    Ok((service, rx))
+    Service::new(spec).await
 }
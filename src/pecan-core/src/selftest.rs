@@ -0,0 +1,257 @@
+//! Self-test of every registered language toolchain.
+//!
+//! Each toolchain runs a canned hello-world and an A+B program through the
+//! regular `SandboxManager` pipeline, so a missing or broken
+//! `/opt/toolchains/<language>/current` is detected before user traffic hits it.
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pecan_sandbox::manager::SandboxManager;
+use pecan_sandbox::permits::Priority;
+
+use crate::code_execution::{CodeExecutionRequest, CodeExecutionStatus, execute};
+use crate::toolchains::Language;
+
+/// CPU time limit for a single self-test run, in seconds.
+/// Also drives the compile timeout, which JVM-based compilers need.
+const SELF_TEST_TIME_LIMIT: f64 = 10.0;
+/// Memory limit for a single self-test run, in kilobytes.
+const SELF_TEST_MEMORY_LIMIT: f64 = 1024000.0;
+
+struct SelfTestCase {
+    name: &'static str,
+    code: &'static str,
+    stdin: &'static str,
+    expected_stdout: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub struct LanguageSelfTestResult {
+    pub language: Language,
+    pub passed: bool,
+    /// compile time of the slowest case in seconds
+    pub compile_time: f64,
+    /// run time of the slowest case in seconds
+    pub run_time: f64,
+    /// reason of the first failed case, if any
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+    pub results: Vec<LanguageSelfTestResult>,
+    /// UNIX epoch seconds when the self-test finished
+    pub finished_at: u64,
+}
+
+impl SelfTestReport {
+    pub fn is_available(&self, language: Language) -> bool {
+        self.results
+            .iter()
+            .find(|result| result.language == language)
+            .map(|result| result.passed)
+            .unwrap_or(false)
+    }
+
+    pub fn unavailable_languages(&self) -> Vec<Language> {
+        self.results
+            .iter()
+            .filter(|result| !result.passed)
+            .map(|result| result.language)
+            .collect()
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.results.iter().any(|result| !result.passed)
+    }
+}
+
+fn self_test_cases(language: Language) -> [SelfTestCase; 2] {
+    let (hello_world, a_plus_b) = match language {
+        Language::C => (
+            "#include <stdio.h>\nint main(void) { printf(\"Hello, World!\\n\"); return 0; }\n",
+            "#include <stdio.h>\nint main(void) { long long a, b; scanf(\"%lld %lld\", &a, &b); printf(\"%lld\\n\", a + b); return 0; }\n",
+        ),
+        Language::Cpp => (
+            "#include <iostream>\nint main() { std::cout << \"Hello, World!\" << std::endl; return 0; }\n",
+            "#include <iostream>\nint main() { long long a, b; std::cin >> a >> b; std::cout << a + b << std::endl; return 0; }\n",
+        ),
+        Language::Go => (
+            "package main\n\nimport \"fmt\"\n\nfunc main() { fmt.Println(\"Hello, World!\") }\n",
+            "package main\n\nimport \"fmt\"\n\nfunc main() { var a, b int64; fmt.Scan(&a, &b); fmt.Println(a + b) }\n",
+        ),
+        Language::Java => (
+            "public class Main { public static void main(String[] args) { System.out.println(\"Hello, World!\"); } }\n",
+            "import java.util.Scanner;\npublic class Main { public static void main(String[] args) { Scanner sc = new Scanner(System.in); long a = sc.nextLong(); long b = sc.nextLong(); System.out.println(a + b); } }\n",
+        ),
+        Language::Kotlin => (
+            "fun main() { println(\"Hello, World!\") }\n",
+            "fun main() { val (a, b) = readLine()!!.trim().split(\" \").map { it.toLong() }; println(a + b) }\n",
+        ),
+        Language::Node => (
+            "console.log(\"Hello, World!\");\n",
+            "const [a, b] = require(\"fs\").readFileSync(0, \"utf8\").trim().split(/\\s+/).map(Number);\nconsole.log(a + b);\n",
+        ),
+        Language::Python => (
+            "print(\"Hello, World!\")\n",
+            "a, b = map(int, input().split())\nprint(a + b)\n",
+        ),
+        Language::Rust => (
+            "fn main() { println!(\"Hello, World!\"); }\n",
+            "use std::io::Read;\nfn main() { let mut s = String::new(); std::io::stdin().read_to_string(&mut s).unwrap(); let v: Vec<i64> = s.split_whitespace().map(|x| x.parse().unwrap()).collect(); println!(\"{}\", v[0] + v[1]); }\n",
+        ),
+        Language::Typescript => (
+            "console.log(\"Hello, World!\");\n",
+            "declare var require: any;\nconst [a, b] = require(\"fs\").readFileSync(0, \"utf8\").trim().split(/\\s+/).map(Number);\nconsole.log(a + b);\n",
+        ),
+        Language::Unknown => ("", ""),
+    };
+
+    [
+        SelfTestCase {
+            name: "hello-world",
+            code: hello_world,
+            stdin: "",
+            expected_stdout: "Hello, World!\n",
+        },
+        SelfTestCase {
+            name: "a-plus-b",
+            code: a_plus_b,
+            stdin: "1 2\n",
+            expected_stdout: "3\n",
+        },
+    ]
+}
+
+/// Run the canned programs of `language` and report whether the toolchain works.
+pub async fn self_test_language(
+    sandbox_manager: &Arc<SandboxManager>,
+    language: Language,
+) -> LanguageSelfTestResult {
+    let mut result = LanguageSelfTestResult {
+        language,
+        passed: true,
+        compile_time: 0.0,
+        run_time: 0.0,
+        message: None,
+    };
+
+    for case in self_test_cases(language) {
+        let failure = match execute(
+            sandbox_manager,
+            CodeExecutionRequest {
+                language,
+                code: case.code.to_string(),
+                project: None,
+                input: case.stdin.into(),
+                timeout: SELF_TEST_TIME_LIMIT,
+                memory_limit: SELF_TEST_MEMORY_LIMIT,
+                priority: Priority::Normal,
+                queue_timeout: Some(SELF_TEST_TIME_LIMIT),
+                output_files: Vec::new(),
+                args: Vec::new(),
+                input_files: Vec::new(),
+                bypass_artifact_cache: true,
+            },
+        )
+        .await
+        {
+            Ok(res) => {
+                result.compile_time = result.compile_time.max(res.compile_time);
+                result.run_time = result.run_time.max(res.time);
+                match res.status {
+                    CodeExecutionStatus::Success
+                        if res.stdout == case.expected_stdout.as_bytes() =>
+                    {
+                        None
+                    }
+                    CodeExecutionStatus::Success => Some(format!(
+                        "unexpected output {:?}, expected {:?}",
+                        res.stdout, case.expected_stdout
+                    )),
+                    CodeExecutionStatus::CompileError => Some(format!(
+                        "compile error: {}",
+                        String::from_utf8_lossy(&res.stderr).trim()
+                    )),
+                    CodeExecutionStatus::RuntimeError => Some(format!(
+                        "runtime error: {}",
+                        String::from_utf8_lossy(&res.stderr).trim()
+                    )),
+                    CodeExecutionStatus::TimeLimitExceeded => {
+                        Some("time limit exceeded".to_string())
+                    }
+                    CodeExecutionStatus::MemoryLimitExceeded => {
+                        Some("memory limit exceeded".to_string())
+                    }
+                    CodeExecutionStatus::InternalError => Some("internal error".to_string()),
+                    CodeExecutionStatus::Aborted => Some("aborted".to_string()),
+                }
+            }
+            Err(e) => Some(e.to_string()),
+        };
+
+        if let Some(message) = failure {
+            result.passed = false;
+            result.message = Some(format!("{}: {}", case.name, message));
+            break;
+        }
+    }
+
+    result
+}
+
+/// Run the self-test for every supported language, one language at a time.
+pub async fn self_test_all(sandbox_manager: &Arc<SandboxManager>) -> SelfTestReport {
+    let mut results = Vec::with_capacity(Language::SUPPORTED.len());
+    for language in Language::SUPPORTED {
+        results.push(self_test_language(sandbox_manager, language).await);
+    }
+
+    SelfTestReport {
+        results,
+        finished_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LanguageSelfTestResult, SelfTestReport, self_test_cases};
+    use crate::toolchains::Language;
+
+    fn result(language: Language, passed: bool) -> LanguageSelfTestResult {
+        LanguageSelfTestResult {
+            language,
+            passed,
+            compile_time: 0.0,
+            run_time: 0.0,
+            message: None,
+        }
+    }
+
+    #[test]
+    fn every_supported_language_has_canned_programs() {
+        for language in Language::SUPPORTED {
+            for case in self_test_cases(language) {
+                assert!(!case.code.is_empty(), "{:?} {}", language, case.name);
+            }
+        }
+    }
+
+    #[test]
+    fn report_marks_failed_languages_unavailable() {
+        let report = SelfTestReport {
+            results: vec![result(Language::C, true), result(Language::Kotlin, false)],
+            finished_at: 0,
+        };
+
+        assert!(report.is_degraded());
+        assert!(report.is_available(Language::C));
+        assert!(!report.is_available(Language::Kotlin));
+        assert!(!report.is_available(Language::Rust));
+        assert_eq!(report.unavailable_languages(), vec![Language::Kotlin]);
+    }
+}
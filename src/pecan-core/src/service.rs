@@ -1,18 +1,32 @@
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use pecan_sandbox::manager::SandboxManager;
+use pecan_sandbox::artifacts::ArtifactCachePolicy;
+use pecan_sandbox::errors::SandboxManagerError;
+use pecan_sandbox::manager::{AutoscalePolicy, SandboxInfo, SandboxManager};
+use pecan_sandbox::permits::{Priority, PriorityShares};
+use pecan_sandbox::sandbox::ExecutionEvents;
 use tokio::sync::mpsc::{self, Receiver, Sender};
-use tokio::task::JoinHandle;
+use tokio::task::{JoinHandle, JoinSet};
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use uuid::Uuid;
 
+use crate::cache::{ResultCache, ResultCachePolicy, cache_key, is_cacheable};
 use crate::code_execution::{
     AsyncCodeExecutionResult, CodeExecutionRequest, CodeExecutionRequestLazy, CodeExecutionResult,
-    execute,
+    CodeExecutionStatus, InteractiveInput, execute, execute_with_events,
 };
 use crate::errors::{CoreExecutionError, CoreServiceError};
-use crate::utils::queue::Queue;
+use crate::selftest::{SelfTestReport, self_test_all};
+use crate::telemetry::RESULT_CACHE_LOOKUPS_TOTAL;
+use crate::toolchains::Language;
+use crate::utils::queue::{Queue, TryPushError};
+
+/// Share of async queue pops per priority class, in `Priority::ALL` order.
+const QUEUE_WEIGHTS: [u32; 3] = [4, 2, 1];
 
 pub struct ServiceLoop {
     manager_handle: JoinHandle<()>,
@@ -20,7 +34,7 @@ pub struct ServiceLoop {
 }
 
 pub struct Service {
-    /// for lazy execution (enqueue only — uses try_push to avoid blocking)
+    /// for lazy execution, one lane per priority class (enqueue only — uses try_push to avoid blocking)
     task_queue: Arc<Queue<CodeExecutionRequestLazy>>,
     /// sender half for pushing completed results to the webhook handler
     task_sender: Sender<AsyncCodeExecutionResult>,
@@ -28,6 +42,14 @@ pub struct Service {
     sandbox_manager: Arc<SandboxManager>,
     /// sandbox manager loop
     service_loop: Option<ServiceLoop>,
+    /// result of the latest toolchain self-test, `None` until the first run finishes
+    self_test_report: RwLock<Option<SelfTestReport>>,
+    /// reject new submissions while already accepted work finishes
+    draining: AtomicBool,
+    /// number of tasks `run_task_loop` executes at the same time
+    async_workers: usize,
+    /// `None` when result caching is disabled
+    result_cache: Option<ResultCache>,
 }
 
 pub struct ServiceSpec {
@@ -35,6 +57,16 @@ pub struct ServiceSpec {
     pub max_queue_size: u32,
     pub max_concurrent_executions: u32,
     pub webhook_buffer_size: usize,
+    /// concurrent async task workers; executions are still bounded by sandbox permits
+    pub async_workers: u32,
+    /// share of the sandbox pool reserved to each priority class
+    pub priority_shares: PriorityShares,
+    /// resize the sandbox pool at runtime, `None` keeps it fixed
+    pub autoscale: Option<AutoscalePolicy>,
+    /// reuse results of identical executions, `None` disables the cache
+    pub result_cache: Option<ResultCachePolicy>,
+    /// reuse compiled programs of identical sources, `None` disables the store
+    pub artifact_cache: Option<ArtifactCachePolicy>,
 }
 
 impl Service {
@@ -46,13 +78,26 @@ impl Service {
             max_queue_size,
             max_concurrent_executions,
             webhook_buffer_size,
+            async_workers,
+            priority_shares,
+            autoscale,
+            result_cache,
+            artifact_cache,
         } = spec;
 
-        let task_queue = Arc::new(Queue::bounded(max_queue_size as usize));
+        let task_queue = Arc::new(Queue::weighted(max_queue_size as usize, &QUEUE_WEIGHTS));
 
-        let sandbox_manager = SandboxManager::new(max_concurrent_executions as usize)
-            .await
-            .map_err(|e| CoreServiceError::InternalError(e.to_string()))?;
+        let sandbox_manager =
+            SandboxManager::with_autoscale(max_concurrent_executions as usize, autoscale)
+                .await
+                .map_err(|e| CoreServiceError::InternalError(e.to_string()))?;
+        sandbox_manager.set_priority_shares(priority_shares);
+        if let Some(policy) = artifact_cache {
+            sandbox_manager
+                .enable_artifact_cache(policy)
+                .await
+                .map_err(|e| CoreServiceError::InternalError(e.to_string()))?;
+        }
 
         let ct = CancellationToken::new();
         let ct_m_loop = ct.child_token();
@@ -69,6 +114,15 @@ impl Service {
             None
         };
 
+        let result_cache = match result_cache {
+            Some(policy) => Some(
+                ResultCache::open(policy)
+                    .await
+                    .map_err(|e| CoreServiceError::InternalError(e.to_string()))?,
+            ),
+            None => None,
+        };
+
         let (tx, rx) = mpsc::channel::<AsyncCodeExecutionResult>(webhook_buffer_size);
 
         Ok((
@@ -77,6 +131,10 @@ impl Service {
                 task_sender: tx,
                 sandbox_manager,
                 service_loop,
+                self_test_report: RwLock::new(None),
+                draining: AtomicBool::new(false),
+                async_workers: async_workers.max(1) as usize,
+                result_cache,
             },
             rx,
         ))
@@ -98,74 +156,288 @@ impl Service {
         self.sandbox_manager.error_sandboxes_count().await
     }
 
+    /// Number of async tasks waiting in the task queue.
+    pub fn get_queue_depth(&self) -> usize {
+        self.task_queue.len()
+    }
+
+    /// Number of async tasks of `priority` waiting in the task queue.
+    pub fn get_queue_depth_of(&self, priority: Priority) -> usize {
+        self.task_queue.lane_len(priority.index())
+    }
+
+    pub fn list_sandboxes(&self) -> Vec<SandboxInfo> {
+        self.sandbox_manager.list_sandboxes()
+    }
+
+    pub fn recycle_sandbox(&self, id: Uuid) -> Result<(), CoreServiceError> {
+        self.sandbox_manager
+            .recycle_sandbox(id)
+            .map_err(|e| match e {
+                SandboxManagerError::SandboxNotFound(id) => {
+                    CoreServiceError::SandboxNotFound(id.to_string())
+                }
+                SandboxManagerError::SandboxBusy(id) => {
+                    CoreServiceError::SandboxBusy(id.to_string())
+                }
+                e => CoreServiceError::InternalError(e.to_string()),
+            })
+    }
+
+    /// Resize the sandbox pool toward `size` and return the resulting size.
+    pub async fn resize_pool(&self, size: usize) -> Result<usize, CoreServiceError> {
+        self.sandbox_manager
+            .resize(size)
+            .await
+            .map_err(|e| CoreServiceError::InternalError(e.to_string()))
+    }
+
+    /// While draining, `execute` and `execute_async` reject new work; queued
+    /// tasks and running executions still complete.
+    pub fn set_draining(&self, draining: bool) {
+        self.draining.store(draining, Ordering::Release);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    /// Reject new work while draining; work admitted earlier goes through
+    /// `execute_admitted`.
+    pub fn admit(&self) -> Result<(), CoreExecutionError> {
+        if self.is_draining() {
+            return Err(CoreExecutionError::ServiceBusy(
+                "Service is draining".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Run the self-test for every toolchain and keep the report for later lookups.
+    pub async fn run_self_test(&self) -> SelfTestReport {
+        let report = self_test_all(&self.sandbox_manager).await;
+        if let Ok(mut latest) = self.self_test_report.write() {
+            *latest = Some(report.clone());
+        }
+        report
+    }
+
+    pub fn self_test_report(&self) -> Option<SelfTestReport> {
+        self.self_test_report
+            .read()
+            .ok()
+            .and_then(|report| report.clone())
+    }
+
     pub async fn execute(
         &self,
         request: CodeExecutionRequest,
     ) -> Result<CodeExecutionResult, CoreExecutionError> {
-        let result = execute(&self.sandbox_manager, request).await?;
+        self.admit()?;
+        self.execute_admitted(request).await
+    }
+
+    /// `execute` without the drain check, for work accepted before a drain started.
+    pub async fn execute_admitted(
+        &self,
+        request: CodeExecutionRequest,
+    ) -> Result<CodeExecutionResult, CoreExecutionError> {
+        let Some((cache, key)) = self
+            .result_cache
+            .as_ref()
+            .and_then(|cache| Some((cache, cache_key(&request)?)))
+        else {
+            return execute(&self.sandbox_manager, request).await;
+        };
+
+        let language = request.language.as_str();
+        if let Some(mut result) = cache.get(&key).await {
+            metrics::counter!(RESULT_CACHE_LOOKUPS_TOTAL, "language" => language, "result" => "hit")
+                .increment(1);
+            tracing::debug!("served from the result cache");
+            result.cached = true;
+            return Ok(result);
+        }
+        metrics::counter!(RESULT_CACHE_LOOKUPS_TOTAL, "language" => language, "result" => "miss")
+            .increment(1);
 
+        let (timeout, memory_limit) = (request.timeout, request.memory_limit);
+        let result = execute(&self.sandbox_manager, request).await?;
+        if is_cacheable(&result, timeout, memory_limit) {
+            cache.put(key, &result).await;
+        }
         Ok(result)
     }
 
+    /// `execute_admitted` that streams progress and output to `events`. The
+    /// result cache is bypassed so that the output is always produced live.
+    pub async fn execute_streaming(
+        &self,
+        request: CodeExecutionRequest,
+        events: &ExecutionEvents,
+    ) -> Result<CodeExecutionResult, CoreExecutionError> {
+        execute_with_events(&self.sandbox_manager, request, Some(events), None).await
+    }
+
+    /// `execute_streaming` whose program reads its standard input from
+    /// `interactive` while it runs.
+    pub async fn execute_interactive(
+        &self,
+        request: CodeExecutionRequest,
+        events: &ExecutionEvents,
+        interactive: InteractiveInput,
+    ) -> Result<CodeExecutionResult, CoreExecutionError> {
+        execute_with_events(
+            &self.sandbox_manager,
+            request,
+            Some(events),
+            Some(interactive),
+        )
+        .await
+    }
+
     pub async fn execute_async(
         &self,
         request: CodeExecutionRequestLazy,
     ) -> Result<(), CoreExecutionError> {
+        self.admit()?;
+        if let Language::Unknown = request.req.language {
+            return Err(CoreExecutionError::NotSupportedLanguage(
+                "Unknown language".to_string(),
+            ));
+        }
+
+        let lane = request.req.priority.index();
         self.task_queue
-            .try_push(request)
-            .map_err(|_| CoreExecutionError::InternalError("task queue full or closed".to_string()))
+            .try_push_to(lane, request)
+            .map_err(|e| match e {
+                TryPushError::Full(_) => {
+                    CoreExecutionError::ServiceBusy("Task queue is full".to_string())
+                }
+                TryPushError::Closed(_) => {
+                    CoreExecutionError::ServiceBusy("Service is shutting down".to_string())
+                }
+                TryPushError::Poisoned(_) => {
+                    CoreExecutionError::InternalError("Task queue is poisoned".to_string())
+                }
+            })
     }
 
-    async fn process_one_task(&self) {
-        let task = match self.task_queue.try_pop() {
-            Ok(task) => task,
-            Err(_) => return,
-        };
-
-        let result = match self
-            .execute(CodeExecutionRequest {
-                language: task.req.language,
+    async fn process_task(&self, task: CodeExecutionRequestLazy) {
+        let language = task.req.language;
+        let span = tracing::info_span!(
+            parent: &task.span,
+            "task",
+            job_id = %task.request_id,
+            queue_wait_ms = task.enqueued_at.elapsed().as_millis() as u64,
+        );
+        // accepted before any drain started, so bypass the drain check of `Service::execute`
+        let result = self
+            .execute_admitted(CodeExecutionRequest {
+                language,
                 code: task.req.code,
+                project: task.req.project,
                 input: task.req.input,
                 timeout: task.req.timeout,
                 memory_limit: task.req.memory_limit,
+                priority: task.req.priority,
+                queue_timeout: task.req.queue_timeout,
+                output_files: task.req.output_files,
+                args: task.req.args,
+                input_files: task.req.input_files,
+                bypass_artifact_cache: task.req.bypass_artifact_cache,
             })
+            .instrument(span.clone())
             .await
-        {
-            Ok(res) => Some(res),
-            Err(_) => None,
-        };
+            .unwrap_or_else(|e| {
+                span.in_scope(|| tracing::warn!(error = %e, "task execution failed"));
+                not_run(CodeExecutionStatus::InternalError, e.to_string())
+            });
 
         let _ = self
             .task_sender
             .send(AsyncCodeExecutionResult {
                 request_id: task.request_id,
+                origin_request_id: task.origin_request_id,
+                span,
+                language,
                 webhook_url: task.webhook_url,
                 send_failed_count: task.send_failed_count,
                 desired_stdout: task.desired_stdout,
+                judged_file: task.judged_file,
                 result,
             })
             .await;
     }
 
-    pub async fn run_task_loop(&self, cancel: CancellationToken) {
-        loop {
-            tokio::select! {
-                _ = cancel.cancelled() => {
-                    break;
-                }
-                _ = sleep(Duration::from_millis(500)) => {
-                    self.process_one_task().await;
+    /// Run async tasks as soon as they are queued, up to `async_workers` at a
+    /// time. A task that was already taken runs to completion after `cancel`;
+    /// the loop also ends once the queue is closed and empty.
+    pub async fn run_task_loop(self: Arc<Self>, cancel: CancellationToken) {
+        let mut workers = JoinSet::new();
+        for _ in 0..self.async_workers {
+            let service = Arc::clone(&self);
+            let cancel = cancel.clone();
+            workers.spawn(async move {
+                loop {
+                    let task = tokio::select! {
+                        _ = cancel.cancelled() => break,
+                        task = service.task_queue.pop() => match task {
+                            Some(task) => task,
+                            None => break,
+                        },
+                    };
+                    service.process_task(task).await;
                 }
+            });
+        }
+        while workers.join_next().await.is_some() {}
+    }
+
+    /// Close the task queue and report every task still waiting in it as
+    /// aborted. Returns the number of aborted tasks.
+    pub async fn abort_queued(&self) -> usize {
+        self.task_queue.close();
+
+        let mut aborted = 0;
+        while let Ok(task) = self.task_queue.try_pop() {
+            let _ = self
+                .task_sender
+                .send(AsyncCodeExecutionResult {
+                    request_id: task.request_id,
+                    origin_request_id: task.origin_request_id,
+                    span: task.span,
+                    language: task.req.language,
+                    webhook_url: task.webhook_url,
+                    send_failed_count: task.send_failed_count,
+                    desired_stdout: task.desired_stdout,
+                    judged_file: task.judged_file,
+                    result: not_run(CodeExecutionStatus::Aborted, "Service is shutting down"),
+                })
+                .await;
+            aborted += 1;
+        }
+        aborted
+    }
+
+    /// Wait until no sandbox is running or `deadline` passes. Returns whether
+    /// the pool went idle in time.
+    pub async fn wait_until_idle(&self, deadline: Instant) -> bool {
+        loop {
+            if self.get_running_sandboxes_count().await == 0 {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
             }
+            sleep(Duration::from_millis(100)).await;
         }
     }
 
     pub async fn shutdown(&self) -> Result<(), CoreServiceError> {
         self.task_queue.close();
 
-        let _ = self
-            .sandbox_manager
+        self.sandbox_manager
             .teardown()
             .await
             .map_err(|e| CoreServiceError::InternalError(e.to_string()))?;
@@ -177,3 +449,152 @@ impl Service {
         Ok(())
     }
 }
+
+/// Result of a task that never produced one, with `reason` as its stderr.
+fn not_run(status: CodeExecutionStatus, reason: impl Into<Vec<u8>>) -> CodeExecutionResult {
+    CodeExecutionResult {
+        status,
+        stdout: Vec::new(),
+        stderr: reason.into(),
+        time: 0.0,
+        memory: 0.0,
+        compile_time: 0.0,
+        output_files: Vec::new(),
+        exit_code: None,
+        cached: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use pecan_sandbox::permits::{Priority, PriorityShares};
+    use pecan_sandbox::sandbox::SandboxExecutionOptions;
+    use uuid::Uuid;
+
+    use super::{Service, ServiceSpec};
+    use crate::code_execution::{
+        CodeExecutionRequest, CodeExecutionRequestLazy, CodeExecutionStatus,
+    };
+    use crate::errors::CoreExecutionError;
+    use crate::toolchains::Language;
+
+    fn spec(sandboxes: u32) -> ServiceSpec {
+        ServiceSpec {
+            enable_bg_worker_loop: false,
+            max_queue_size: 8,
+            max_concurrent_executions: sandboxes,
+            webhook_buffer_size: 8,
+            async_workers: 1,
+            priority_shares: PriorityShares::default(),
+            autoscale: None,
+            result_cache: None,
+            artifact_cache: None,
+        }
+    }
+
+    fn task(request_id: Uuid) -> CodeExecutionRequestLazy {
+        CodeExecutionRequestLazy {
+            request_id,
+            origin_request_id: None,
+            span: tracing::Span::none(),
+            enqueued_at: Instant::now(),
+            webhook_url: "http://localhost/webhook".to_string(),
+            send_failed_count: 0,
+            desired_stdout: Vec::new(),
+            judged_file: None,
+            req: CodeExecutionRequest {
+                language: Language::Python,
+                code: "print(1)".to_string(),
+                project: None,
+                input: Vec::new(),
+                timeout: 1.0,
+                memory_limit: 262144.0,
+                priority: Priority::Normal,
+                queue_timeout: Some(1.0),
+                output_files: Vec::new(),
+                args: Vec::new(),
+                input_files: Vec::new(),
+                bypass_artifact_cache: false,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn tasks_that_cannot_run_are_reported_as_internal_errors() {
+        let (service, mut results) = Service::new(spec(0)).await.expect("service init");
+        let id = Uuid::new_v4();
+        let mut waiting = task(id);
+        waiting.req.queue_timeout = Some(0.1);
+
+        service.process_task(waiting).await;
+        let failed = results.recv().await.expect("failed result");
+        assert_eq!(failed.request_id, id);
+        assert_eq!(failed.result.status, CodeExecutionStatus::InternalError);
+        assert!(!failed.result.stderr.is_empty());
+    }
+
+    #[tokio::test]
+    async fn queued_tasks_are_reported_as_aborted() {
+        let (service, mut results) = Service::new(spec(0)).await.expect("service init");
+        let ids = [Uuid::new_v4(), Uuid::new_v4()];
+        for id in ids {
+            service.execute_async(task(id)).await.expect("enqueue");
+        }
+
+        assert_eq!(service.abort_queued().await, 2);
+        for id in ids {
+            let aborted = results.recv().await.expect("aborted result");
+            assert_eq!(aborted.request_id, id);
+            assert_eq!(aborted.result.status, CodeExecutionStatus::Aborted);
+        }
+        assert!(matches!(
+            service.execute_async(task(Uuid::new_v4())).await,
+            Err(CoreExecutionError::ServiceBusy(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn waiting_for_idle_times_out_while_a_sandbox_runs() {
+        let (service, _results) = Service::new(spec(1)).await.expect("service init");
+        let manager = Arc::clone(&service.sandbox_manager);
+        let running = tokio::spawn(async move {
+            manager
+                .execute_via_manager(&SandboxExecutionOptions {
+                    additional_file_options: None,
+                    compile_options: None,
+                    additional_directory_options: None,
+                    binary_path: "/bin/sh".into(),
+                    args: vec!["-c".to_string(), "sleep 1".to_string()],
+                    stdin: Vec::new(),
+                    time_limit: 5.0,
+                    wall_time_limit: 10.0,
+                    memory_limit: 262144.0,
+                    compile_timeout: 5.0,
+                    queue_timeout: Some(5.0),
+                    priority: Priority::Normal,
+                    output_files: Vec::new(),
+                    input_files: Vec::new(),
+                    bypass_artifact_cache: false,
+                })
+                .await
+        });
+        while service.get_running_sandboxes_count().await == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let started = Instant::now();
+        assert!(
+            !service
+                .wait_until_idle(Instant::now() + Duration::from_millis(200))
+                .await
+        );
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        running.await.unwrap().expect("execute");
+        assert!(service.wait_until_idle(Instant::now()).await);
+        service.shutdown().await.expect("shutdown");
+    }
+}
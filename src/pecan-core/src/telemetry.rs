@@ -0,0 +1,164 @@
+//! Names of the per-language execution metrics.
+//!
+//! Values are emitted through the `metrics` facade and are no-ops until the
+//! embedding application installs a recorder.
+
+use pecan_sandbox::permits::Priority;
+use pecan_sandbox::sandbox::{SandboxExecutionResult, SandboxExecutionStatus};
+
+use crate::toolchains::Language;
+
+/// compile phase duration in seconds, labelled by `language`
+pub const EXECUTION_COMPILE_SECONDS: &str = "pecan_execution_compile_seconds";
+/// CPU time of the executed program in seconds, labelled by `language`
+pub const EXECUTION_RUN_SECONDS: &str = "pecan_execution_run_seconds";
+/// peak memory of the executed program in kilobytes, labelled by `language`
+pub const EXECUTION_MEMORY_KILOBYTES: &str = "pecan_execution_memory_kilobytes";
+/// time spent before a sandbox was claimed, labelled by `language` and `stage`
+/// (`semaphore` or `idle_queue`)
+pub const EXECUTION_WAIT_SECONDS: &str = "pecan_execution_wait_seconds";
+/// result cache lookups, labelled by `language` and `result` (`hit` or `miss`)
+pub const RESULT_CACHE_LOOKUPS_TOTAL: &str = "pecan_result_cache_lookups_total";
+
+/// Describe the metrics of this crate and of `pecan-sandbox`.
+pub fn describe_metrics() {
+    pecan_sandbox::telemetry::describe_metrics();
+    metrics::describe_histogram!(
+        EXECUTION_COMPILE_SECONDS,
+        metrics::Unit::Seconds,
+        "Compile phase duration"
+    );
+    metrics::describe_histogram!(
+        EXECUTION_RUN_SECONDS,
+        metrics::Unit::Seconds,
+        "CPU time of executed programs"
+    );
+    metrics::describe_histogram!(
+        EXECUTION_MEMORY_KILOBYTES,
+        metrics::Unit::Kibibytes,
+        "Peak memory of executed programs"
+    );
+    metrics::describe_histogram!(
+        EXECUTION_WAIT_SECONDS,
+        metrics::Unit::Seconds,
+        "Time spent waiting for an execution permit or an idle sandbox"
+    );
+    metrics::describe_counter!(
+        RESULT_CACHE_LOOKUPS_TOTAL,
+        "Result cache lookups by outcome"
+    );
+}
+
+/// Record the waits, compile time, run time and memory of one execution.
+pub fn record_execution(
+    language: Language,
+    priority: Priority,
+    compiled: bool,
+    result: &SandboxExecutionResult,
+) {
+    let language = language.as_str();
+    let priority = priority.as_str();
+    metrics::histogram!(
+        EXECUTION_WAIT_SECONDS,
+        "language" => language,
+        "stage" => "semaphore",
+        "priority" => priority
+    )
+    .record(result.semaphore_wait);
+    metrics::histogram!(
+        EXECUTION_WAIT_SECONDS,
+        "language" => language,
+        "stage" => "idle_queue",
+        "priority" => priority
+    )
+    .record(result.idle_queue_wait);
+    if compiled {
+        metrics::histogram!(EXECUTION_COMPILE_SECONDS, "language" => language)
+            .record(result.compile_time);
+    }
+    if result.status != SandboxExecutionStatus::CompileError {
+        metrics::histogram!(EXECUTION_RUN_SECONDS, "language" => language).record(result.time);
+        metrics::histogram!(EXECUTION_MEMORY_KILOBYTES, "language" => language)
+            .record(result.memory);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::Key;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use pecan_sandbox::permits::Priority;
+    use pecan_sandbox::sandbox::{SandboxExecutionResult, SandboxExecutionStatus};
+
+    use super::{
+        EXECUTION_COMPILE_SECONDS, EXECUTION_MEMORY_KILOBYTES, EXECUTION_RUN_SECONDS,
+        EXECUTION_WAIT_SECONDS, record_execution,
+    };
+    use crate::toolchains::Language;
+
+    fn result(status: SandboxExecutionStatus) -> SandboxExecutionResult {
+        SandboxExecutionResult {
+            status,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            time: 0.25,
+            memory: 2048.0,
+            compile_time: 1.5,
+            semaphore_wait: 0.0,
+            idle_queue_wait: 0.0,
+            output_files: Vec::new(),
+            exit_code: Some(0),
+        }
+    }
+
+    #[test]
+    fn executions_are_recorded_per_language() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            record_execution(
+                Language::C,
+                Priority::Normal,
+                true,
+                &result(SandboxExecutionStatus::Success),
+            );
+            // a compile error has no run to measure
+            record_execution(
+                Language::Go,
+                Priority::Bulk,
+                true,
+                &result(SandboxExecutionStatus::CompileError),
+            );
+        });
+
+        let histograms: Vec<(Key, Vec<f64>)> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter_map(|(key, _, _, value)| match value {
+                DebugValue::Histogram(values) => {
+                    Some((key.key().clone(), values.into_iter().map(|v| v.0).collect()))
+                }
+                _ => None,
+            })
+            .collect();
+        let recorded = |name: &'static str, language: &'static str| {
+            histograms
+                .iter()
+                .filter(|(key, _)| {
+                    key.name() == name
+                        && key
+                            .labels()
+                            .any(|l| l.key() == "language" && l.value() == language)
+                })
+                .flat_map(|(_, values)| values.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(recorded(EXECUTION_RUN_SECONDS, "c"), [0.25]);
+        assert_eq!(recorded(EXECUTION_MEMORY_KILOBYTES, "c"), [2048.0]);
+        assert_eq!(recorded(EXECUTION_COMPILE_SECONDS, "go"), [1.5]);
+        assert_eq!(recorded(EXECUTION_WAIT_SECONDS, "go").len(), 2);
+        assert!(recorded(EXECUTION_RUN_SECONDS, "go").is_empty());
+    }
+}
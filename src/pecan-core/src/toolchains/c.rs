@@ -6,7 +6,9 @@
 
 use std::path::PathBuf;
 
-use crate::toolchains::{CompileOption, LanguageToolchain, RuntimeOption};
+use crate::toolchains::{
+    CompileOption, LanguageToolchain, ProjectOption, RuntimeOption, same_path,
+};
 
 pub const C_LANGUAGE: &str = "c";
 pub const C_VERSION: &str = "11";
@@ -28,6 +30,7 @@ pub fn language_toolchain_c() -> LanguageToolchain {
                 C_BINARY_FILE_NAME.to_string(),
                 C_SOURCE_FILE_NAME.to_string(),
             ],
+            outputs: vec![C_BINARY_FILE_NAME.to_string()],
         }),
         runtime_option: RuntimeOption {
             binary_path: PathBuf::from(C_BINARY_FILE_NAME),
@@ -35,5 +38,11 @@ pub fn language_toolchain_c() -> LanguageToolchain {
             env: None,
             args: vec![],
         },
+        project_option: ProjectOption {
+            entry_point: C_SOURCE_FILE_NAME.to_string(),
+            sources: vec![".c".to_string()],
+            run_target: same_path,
+            single_directory: false,
+        },
     }
 }
@@ -6,7 +6,9 @@
 
 use std::path::PathBuf;
 
-use crate::toolchains::{CompileOption, LanguageToolchain, RuntimeOption};
+use crate::toolchains::{
+    CompileOption, LanguageToolchain, ProjectOption, RuntimeOption, same_path,
+};
 
 pub const CPP_LANGUAGE: &str = "cpp";
 pub const CPP_VERSION: &str = "11";
@@ -28,6 +30,7 @@ pub fn language_toolchain_cpp() -> LanguageToolchain {
                 CPP_BINARY_FILE_NAME.to_string(),
                 CPP_SOURCE_FILE_NAME.to_string(),
             ],
+            outputs: vec![CPP_BINARY_FILE_NAME.to_string()],
         }),
         runtime_option: RuntimeOption {
             binary_path: PathBuf::from(CPP_BINARY_FILE_NAME),
@@ -35,5 +38,11 @@ pub fn language_toolchain_cpp() -> LanguageToolchain {
             env: None,
             args: vec![],
         },
+        project_option: ProjectOption {
+            entry_point: CPP_SOURCE_FILE_NAME.to_string(),
+            sources: vec![".cpp".to_string(), ".cc".to_string(), ".cxx".to_string()],
+            run_target: same_path,
+            single_directory: false,
+        },
     }
 }
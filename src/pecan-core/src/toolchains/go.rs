@@ -4,7 +4,9 @@
 use std::path::PathBuf;
 
 use crate::define_language_toolchain_dir_default;
-use crate::toolchains::{CompileOption, LanguageToolchain, RuntimeOption};
+use crate::toolchains::{
+    CompileOption, LanguageToolchain, ProjectOption, RuntimeOption, same_path,
+};
 
 pub const GO_LANGUAGE: &str = "go";
 pub const GO_VERSION: &str = "1.23.3";
@@ -28,6 +30,7 @@ pub fn language_toolchain_go() -> LanguageToolchain {
                 GO_BINARY_FILE_NAME.to_string(),
                 GO_SOURCE_FILE_NAME.to_string(),
             ],
+            outputs: vec![GO_BINARY_FILE_NAME.to_string()],
         }),
         runtime_option: RuntimeOption {
             binary_path: PathBuf::from(GO_BINARY_FILE_NAME),
@@ -35,5 +38,11 @@ pub fn language_toolchain_go() -> LanguageToolchain {
             env: None,
             args: vec![],
         },
+        project_option: ProjectOption {
+            entry_point: GO_SOURCE_FILE_NAME.to_string(),
+            sources: vec![".go".to_string()],
+            run_target: same_path,
+            single_directory: true,
+        },
     }
 }
@@ -3,7 +3,9 @@
 
 use std::path::PathBuf;
 
-use crate::toolchains::{CompileOption, DirMountOption, LanguageToolchain, RuntimeOption};
+use crate::toolchains::{
+    CompileOption, DirMountOption, LanguageToolchain, ProjectOption, RuntimeOption, java_class_name,
+};
 use crate::{define_language_toolchain_dir_default, define_mount_point_default};
 
 pub const JAVA_LANGUAGE: &str = "java";
@@ -25,6 +27,7 @@ pub fn language_toolchain_java() -> LanguageToolchain {
             compiler_path: PathBuf::from(JAVA_DIR).join(JAVAC_BIN),
             env: None,
             args: vec![JAVA_SOURCE_FILE_NAME.to_string()],
+            outputs: vec!["*.class".to_string()],
         }),
         runtime_option: RuntimeOption {
             binary_path: PathBuf::from(JAVA_MOUNT_POINT).join(JAVA_BIN),
@@ -44,5 +47,11 @@ pub fn language_toolchain_java() -> LanguageToolchain {
                 JAVA_CLASS_NAME.to_string(),
             ],
         },
+        project_option: ProjectOption {
+            entry_point: JAVA_SOURCE_FILE_NAME.to_string(),
+            sources: vec![".java".to_string()],
+            run_target: java_class_name,
+            single_directory: false,
+        },
     }
 }
@@ -6,7 +6,9 @@ use std::path::PathBuf;
 
 use crate::define_language_toolchain_dir_default;
 use crate::toolchains::java::{JAVA_BIN, JAVA_DIR, JAVA_MOUNT_POINT};
-use crate::toolchains::{CompileOption, DirMountOption, LanguageToolchain, RuntimeOption};
+use crate::toolchains::{
+    CompileOption, DirMountOption, LanguageToolchain, ProjectOption, RuntimeOption, same_path,
+};
 
 pub const KOTLIN_LANGUAGE: &str = "kotlin";
 pub const KOTLIN_VERSION: &str = "2.0.21";
@@ -33,6 +35,7 @@ pub fn language_toolchain_kotlin() -> LanguageToolchain {
                 "-d".to_string(),
                 KOTLIN_JAR_FILE_NAME.to_string(),
             ],
+            outputs: vec![KOTLIN_JAR_FILE_NAME.to_string()],
         }),
         runtime_option: RuntimeOption {
             binary_path: PathBuf::from(JAVA_MOUNT_POINT).join(JAVA_BIN),
@@ -53,5 +56,11 @@ pub fn language_toolchain_kotlin() -> LanguageToolchain {
                 KOTLIN_JAR_FILE_NAME.to_string(),
             ],
         },
+        project_option: ProjectOption {
+            entry_point: KOTLIN_SOURCE_FILE_NAME.to_string(),
+            sources: vec![".kt".to_string()],
+            run_target: same_path,
+            single_directory: false,
+        },
     }
 }
@@ -14,7 +14,7 @@ mod typescript;
 
 pub mod sandbox_options;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Language {
     C,
     Cpp,
@@ -45,6 +45,52 @@ impl From<&str> for Language {
     }
 }
 
+impl Language {
+    /// Every language with a registered toolchain, in listing order.
+    pub const SUPPORTED: [Language; 9] = [
+        Language::C,
+        Language::Cpp,
+        Language::Go,
+        Language::Java,
+        Language::Kotlin,
+        Language::Node,
+        Language::Python,
+        Language::Rust,
+        Language::Typescript,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::C => c::C_LANGUAGE,
+            Language::Cpp => cpp::CPP_LANGUAGE,
+            Language::Go => go::GO_LANGUAGE,
+            Language::Java => java::JAVA_LANGUAGE,
+            Language::Kotlin => kotlin::KOTLIN_LANGUAGE,
+            Language::Node => node::NODE_LANGUAGE,
+            Language::Python => python::PYTHON_LANGUAGE,
+            Language::Rust => rust::RUST_LANGUAGE,
+            Language::Typescript => typescript::TYPESCRIPT_LANGUAGE,
+            Language::Unknown => "unknown",
+        }
+    }
+}
+
+/// Returns the registered toolchain for `language`, or `None` for `Language::Unknown`.
+pub fn language_toolchain(language: Language) -> Option<LanguageToolchain> {
+    match language {
+        Language::C => Some(c::language_toolchain_c()),
+        Language::Cpp => Some(cpp::language_toolchain_cpp()),
+        Language::Go => Some(go::language_toolchain_go()),
+        Language::Java => Some(java::language_toolchain_java()),
+        Language::Kotlin => Some(kotlin::language_toolchain_kotlin()),
+        Language::Node => Some(node::language_toolchain_node()),
+        Language::Python => Some(python::language_toolchain_python()),
+        Language::Rust => Some(rust::language_toolchain_rust()),
+        Language::Typescript => Some(typescript::language_toolchain_typescript()),
+        Language::Unknown => None,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DirMountOption {
     pub source_path: PathBuf,
@@ -56,6 +102,8 @@ pub struct CompileOption {
     pub compiler_path: PathBuf,
     pub env: Option<HashMap<String, String>>,
     pub args: Vec<String>,
+    /// files the compiler produces for the run; `*.ext` matches by suffix
+    pub outputs: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -73,11 +121,90 @@ pub struct LanguageToolchain {
     pub version: String,
     pub compile_option: Option<CompileOption>,
     pub runtime_option: RuntimeOption,
+    pub project_option: ProjectOption,
+}
+
+impl LanguageToolchain {
+    /// Host files and directories the toolchain runs from: the compiler, the
+    /// mounted runtime directories and the runtime binary inside them.
+    pub fn host_paths(&self) -> Vec<PathBuf> {
+        let mounts = self.runtime_option.dir_mount_options.iter().flatten();
+        let mut paths: Vec<PathBuf> = self
+            .compile_option
+            .iter()
+            .map(|compile_option| compile_option.compiler_path.clone())
+            .chain(mounts.clone().map(|mount| mount.source_path.clone()))
+            .collect();
+        // a compiled program runs from the working directory instead
+        let binary_path = &self.runtime_option.binary_path;
+        if let Some(binary) = mounts
+            .filter_map(|mount| {
+                let relative = binary_path.strip_prefix(&mount.target_path).ok()?;
+                Some(mount.source_path.join(relative))
+            })
+            .next()
+        {
+            paths.push(binary);
+        } else if binary_path.is_absolute() {
+            paths.push(binary_path.clone());
+        }
+        paths
+    }
+}
+
+/// How a multi-file project maps onto the single-file compile and run commands.
+#[derive(Debug, Clone)]
+pub struct ProjectOption {
+    /// entry point used when a project does not name one
+    pub entry_point: String,
+    /// extensions of the files handed to the compiler next to the entry point;
+    /// empty when the compiler follows imports on its own
+    pub sources: Vec<String>,
+    /// maps the entry point to the runtime argument that replaces the default one
+    pub run_target: fn(&str) -> String,
+    /// the sources must sit in the entry point's directory, as the compiler
+    /// builds one package from files of a single directory
+    pub single_directory: bool,
+}
+
+/// Runs the entry point as given.
+pub fn same_path(entry_point: &str) -> String {
+    entry_point.to_string()
+}
+
+/// `app/Main.java` runs as the class `app.Main`.
+pub fn java_class_name(entry_point: &str) -> String {
+    entry_point
+        .strip_suffix(".java")
+        .unwrap_or(entry_point)
+        .replace('/', ".")
+}
+
+/// `src/main.ts` runs as the emitted `src/main.js`.
+pub fn compiled_script(entry_point: &str) -> String {
+    format!(
+        "{}.js",
+        entry_point.strip_suffix(".ts").unwrap_or(entry_point)
+    )
+}
+
+#[macro_export]
+macro_rules! define_language_toolchain_dir_default {
+    ($name:ident, $language:expr) => {
+        pub const $name: &str = concat!("/opt/toolchains/", $language, "/current");
+    };
+}
+
+#[macro_export]
+macro_rules! define_mount_point_default {
+    ($name:ident, $language:expr) => {
+        pub const $name: &str = concat!("/opt/", $language);
+    };
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Language;
+    use super::{Language, language_toolchain};
 
     #[test]
     fn language_from_str_maps_known_values() {
@@ -92,23 +219,36 @@ mod tests {
         assert!(matches!(Language::from("typescript"), Language::Typescript));
     }
 
+    #[test]
+    fn supported_languages_roundtrip_through_identifier() {
+        for language in Language::SUPPORTED {
+            assert_eq!(Language::from(language.as_str()), language);
+            let toolchain = language_toolchain(language).expect("registered toolchain");
+            assert_eq!(toolchain.identifier, language.as_str());
+        }
+        assert!(language_toolchain(Language::Unknown).is_none());
+    }
+
+    #[test]
+    fn host_paths_resolve_the_runtime_through_its_mount() {
+        let java = language_toolchain(Language::Java).unwrap();
+        assert_eq!(
+            java.host_paths(),
+            [
+                "/opt/toolchains/java/current/bin/javac",
+                "/opt/toolchains/java/current",
+                "/opt/toolchains/java/current/bin/java",
+            ]
+            .map(std::path::PathBuf::from)
+        );
+
+        let c = language_toolchain(Language::C).unwrap();
+        assert_eq!(c.host_paths(), [std::path::PathBuf::from("/usr/bin/gcc")]);
+    }
+
     #[test]
     fn language_from_str_defaults_to_unknown() {
         assert!(matches!(Language::from("elixir"), Language::Unknown));
         assert!(matches!(Language::from(""), Language::Unknown));
     }
 }
-
-#[macro_export]
-macro_rules! define_language_toolchain_dir_default {
-    ($name:ident, $language:expr) => {
-        pub const $name: &str = concat!("/opt/toolchains/", $language, "/current");
-    };
-}
-
-#[macro_export]
-macro_rules! define_mount_point_default {
-    ($name:ident, $language:expr) => {
-        pub const $name: &str = concat!("/opt/", $language);
-    };
-}
@@ -3,7 +3,9 @@
 
 use std::path::PathBuf;
 
-use crate::toolchains::{DirMountOption, LanguageToolchain, RuntimeOption};
+use crate::toolchains::{
+    DirMountOption, LanguageToolchain, ProjectOption, RuntimeOption, same_path,
+};
 use crate::{define_language_toolchain_dir_default, define_mount_point_default};
 
 pub const NODE_LANGUAGE: &str = "node";
@@ -29,5 +31,11 @@ pub fn language_toolchain_node() -> LanguageToolchain {
             env: None,
             args: vec![NODE_SOURCE_FILE_NAME.to_string()],
         },
+        project_option: ProjectOption {
+            entry_point: NODE_SOURCE_FILE_NAME.to_string(),
+            sources: vec![],
+            run_target: same_path,
+            single_directory: false,
+        },
     }
 }
@@ -4,7 +4,9 @@
 use std::path::PathBuf;
 
 use crate::define_language_toolchain_dir_default;
-use crate::toolchains::{CompileOption, LanguageToolchain, RuntimeOption};
+use crate::toolchains::{
+    CompileOption, LanguageToolchain, ProjectOption, RuntimeOption, same_path,
+};
 
 pub const RUST_LANGUAGE: &str = "rust";
 pub const RUST_VERSION: &str = "1.81.0";
@@ -27,6 +29,7 @@ pub fn language_toolchain_rust() -> LanguageToolchain {
                 RUST_BINARY_FILE_NAME.to_string(),
                 RUST_SOURCE_FILE_NAME.to_string(),
             ],
+            outputs: vec![RUST_BINARY_FILE_NAME.to_string()],
         }),
         runtime_option: RuntimeOption {
             binary_path: PathBuf::from(RUST_BINARY_FILE_NAME),
@@ -34,5 +37,11 @@ pub fn language_toolchain_rust() -> LanguageToolchain {
             env: None,
             args: vec![],
         },
+        project_option: ProjectOption {
+            entry_point: RUST_SOURCE_FILE_NAME.to_string(),
+            sources: vec![],
+            run_target: same_path,
+            single_directory: false,
+        },
     }
 }
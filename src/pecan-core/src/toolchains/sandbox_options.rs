@@ -1,107 +1,196 @@
+use pecan_sandbox::permits::Priority;
 use pecan_sandbox::sandbox::{
     CompileOptions, SandboxAdditionalDirectoryOptions, SandboxAdditionalFileOptions,
     SandboxExecutionOptions,
 };
 
+use crate::code_execution::Project;
 use crate::errors::CoreExecutionError;
-use crate::toolchains::Language;
-use crate::toolchains::c::{C_SOURCE_FILE_NAME, language_toolchain_c};
-use crate::toolchains::cpp::{CPP_SOURCE_FILE_NAME, language_toolchain_cpp};
-use crate::toolchains::go::{GO_SOURCE_FILE_NAME, language_toolchain_go};
-use crate::toolchains::java::{JAVA_SOURCE_FILE_NAME, language_toolchain_java};
-use crate::toolchains::kotlin::{KOTLIN_SOURCE_FILE_NAME, language_toolchain_kotlin};
-use crate::toolchains::node::{NODE_SOURCE_FILE_NAME, language_toolchain_node};
-use crate::toolchains::python::{PYTHON_SOURCE_FILE_NAME, language_toolchain_python};
-use crate::toolchains::rust::{RUST_SOURCE_FILE_NAME, language_toolchain_rust};
-use crate::toolchains::typescript::{TYPESCRIPT_SOURCE_FILE_NAME, language_toolchain_typescript};
+use crate::toolchains::c::C_SOURCE_FILE_NAME;
+use crate::toolchains::cpp::CPP_SOURCE_FILE_NAME;
+use crate::toolchains::go::GO_SOURCE_FILE_NAME;
+use crate::toolchains::java::JAVA_SOURCE_FILE_NAME;
+use crate::toolchains::kotlin::KOTLIN_SOURCE_FILE_NAME;
+use crate::toolchains::node::NODE_SOURCE_FILE_NAME;
+use crate::toolchains::python::PYTHON_SOURCE_FILE_NAME;
+use crate::toolchains::rust::RUST_SOURCE_FILE_NAME;
+use crate::toolchains::typescript::TYPESCRIPT_SOURCE_FILE_NAME;
+use crate::toolchains::{Language, LanguageToolchain, language_toolchain};
 
 pub fn build_sandbox_execution_option(
     language: Language,
     code: String,
-    stdin: String,
+    project: Option<Project>,
+    stdin: Vec<u8>,
     timeout: f64,
     memory_limit: f64,
+    priority: Priority,
 ) -> Result<SandboxExecutionOptions, CoreExecutionError> {
-    let language_toolchain = match language {
-        Language::C => language_toolchain_c(),
-        Language::Cpp => language_toolchain_cpp(),
-        Language::Go => language_toolchain_go(),
-        Language::Java => language_toolchain_java(),
-        Language::Kotlin => language_toolchain_kotlin(),
-        Language::Node => language_toolchain_node(),
-        Language::Python => language_toolchain_python(),
-        Language::Rust => language_toolchain_rust(),
-        Language::Typescript => language_toolchain_typescript(),
-        Language::Unknown => {
-            return Err(CoreExecutionError::NotSupportedLanguage(
-                "Unknown language".to_string(),
-            ));
-        }
-    };
+    let language_toolchain = language_toolchain(language)
+        .ok_or_else(|| CoreExecutionError::NotSupportedLanguage("Unknown language".to_string()))?;
+
+    if let Some(project) = project {
+        return Ok(project_execution_option(
+            language_toolchain,
+            project,
+            stdin,
+            timeout,
+            memory_limit,
+            priority,
+        ));
+    }
 
     let additional_file_options = match language {
         Language::C => Some(vec![SandboxAdditionalFileOptions {
             file_name: C_SOURCE_FILE_NAME.to_string(),
-            file_content: code,
+            file_content: code.into_bytes(),
         }]),
         Language::Cpp => Some(vec![SandboxAdditionalFileOptions {
             file_name: CPP_SOURCE_FILE_NAME.to_string(),
-            file_content: code,
+            file_content: code.into_bytes(),
         }]),
         Language::Go => Some(vec![SandboxAdditionalFileOptions {
             file_name: GO_SOURCE_FILE_NAME.to_string(),
-            file_content: code,
+            file_content: code.into_bytes(),
         }]),
         Language::Java => Some(vec![SandboxAdditionalFileOptions {
             file_name: JAVA_SOURCE_FILE_NAME.to_string(),
-            file_content: code,
+            file_content: code.into_bytes(),
         }]),
         Language::Kotlin => Some(vec![SandboxAdditionalFileOptions {
             file_name: KOTLIN_SOURCE_FILE_NAME.to_string(),
-            file_content: code,
+            file_content: code.into_bytes(),
         }]),
         Language::Node => Some(vec![SandboxAdditionalFileOptions {
             file_name: NODE_SOURCE_FILE_NAME.to_string(),
-            file_content: code,
+            file_content: code.into_bytes(),
         }]),
         Language::Python => Some(vec![SandboxAdditionalFileOptions {
             file_name: PYTHON_SOURCE_FILE_NAME.to_string(),
-            file_content: code,
+            file_content: code.into_bytes(),
         }]),
         Language::Rust => Some(vec![SandboxAdditionalFileOptions {
             file_name: RUST_SOURCE_FILE_NAME.to_string(),
-            file_content: code,
+            file_content: code.into_bytes(),
         }]),
         Language::Typescript => Some(vec![SandboxAdditionalFileOptions {
             file_name: TYPESCRIPT_SOURCE_FILE_NAME.to_string(),
-            file_content: code,
+            file_content: code.into_bytes(),
         }]),
         Language::Unknown => None,
     };
 
-    let compile_options = match language_toolchain.compile_option {
-        Some(compile_option) => Some(CompileOptions {
+    Ok(execution_option(
+        language_toolchain,
+        additional_file_options,
+        stdin,
+        timeout,
+        memory_limit,
+        priority,
+    ))
+}
+
+/// Options for a multi-file project: its files are materialized as given and
+/// the toolchain's default entry point is swapped for the project's one.
+fn project_execution_option(
+    mut language_toolchain: LanguageToolchain,
+    project: Project,
+    stdin: Vec<u8>,
+    timeout: f64,
+    memory_limit: f64,
+    priority: Priority,
+) -> SandboxExecutionOptions {
+    let project_option = &language_toolchain.project_option;
+    let entry_point = project
+        .entry_point
+        .unwrap_or_else(|| project_option.entry_point.clone());
+
+    if let Some(compile_option) = language_toolchain.compile_option.as_mut() {
+        let sources = project.files.iter().map(|file| &file.path).filter(|path| {
+            **path != entry_point
+                && project_option
+                    .sources
+                    .iter()
+                    .any(|extension| path.ends_with(extension.as_str()))
+        });
+        let mut source_args = vec![entry_point.clone()];
+        source_args.extend(sources.cloned());
+        compile_option.args = replace_arg(
+            std::mem::take(&mut compile_option.args),
+            &project_option.entry_point,
+            source_args,
+        );
+    }
+    let run_target = project_option.run_target;
+    language_toolchain.runtime_option.args = replace_arg(
+        std::mem::take(&mut language_toolchain.runtime_option.args),
+        &run_target(&project_option.entry_point),
+        vec![run_target(&entry_point)],
+    );
+
+    let additional_file_options = project
+        .files
+        .into_iter()
+        .map(|file| SandboxAdditionalFileOptions {
+            file_name: file.path,
+            file_content: file.content,
+        })
+        .collect();
+
+    execution_option(
+        language_toolchain,
+        Some(additional_file_options),
+        stdin,
+        timeout,
+        memory_limit,
+        priority,
+    )
+}
+
+/// `args` with every occurrence of `from` replaced by `to`.
+fn replace_arg(args: Vec<String>, from: &str, to: Vec<String>) -> Vec<String> {
+    args.into_iter()
+        .flat_map(|arg| if arg == from { to.clone() } else { vec![arg] })
+        .collect()
+}
+
+fn execution_option(
+    language_toolchain: LanguageToolchain,
+    additional_file_options: Option<Vec<SandboxAdditionalFileOptions>>,
+    stdin: Vec<u8>,
+    timeout: f64,
+    memory_limit: f64,
+    priority: Priority,
+) -> SandboxExecutionOptions {
+    let toolchain = format!(
+        "{} {}",
+        language_toolchain.identifier, language_toolchain.version
+    );
+    let compile_options = language_toolchain
+        .compile_option
+        .map(|compile_option| CompileOptions {
             compiler_path: compile_option.compiler_path,
             env: compile_option.env,
             args: compile_option.args,
-        }),
-        None => None,
-    };
+            toolchain,
+            outputs: compile_option.outputs,
+        });
 
-    let additional_directory_options = match language_toolchain.runtime_option.dir_mount_options {
-        Some(dir_mount_options) => Some(
-            dir_mount_options
-                .into_iter()
-                .map(|dir_mount_option| SandboxAdditionalDirectoryOptions {
-                    directory_path: dir_mount_option.source_path,
-                    mount_point: dir_mount_option.target_path,
-                })
-                .collect::<Vec<SandboxAdditionalDirectoryOptions>>(),
-        ),
-        None => None,
-    };
+    let additional_directory_options =
+        language_toolchain
+            .runtime_option
+            .dir_mount_options
+            .map(|dir_mount_options| {
+                dir_mount_options
+                    .into_iter()
+                    .map(|dir_mount_option| SandboxAdditionalDirectoryOptions {
+                        directory_path: dir_mount_option.source_path,
+                        mount_point: dir_mount_option.target_path,
+                    })
+                    .collect::<Vec<SandboxAdditionalDirectoryOptions>>()
+            });
 
-    Ok(SandboxExecutionOptions {
+    SandboxExecutionOptions {
         additional_file_options,
         compile_options,
         additional_directory_options,
@@ -110,7 +199,93 @@ pub fn build_sandbox_execution_option(
         stdin,
         time_limit: timeout,
         wall_time_limit: (timeout + 1.0) * 3.0,
-        memory_limit: memory_limit,
+        memory_limit,
         compile_timeout: (timeout * 3.0).max(10.0),
-    })
+        queue_timeout: Some(timeout),
+        priority,
+        output_files: Vec::new(),
+        input_files: Vec::new(),
+        bypass_artifact_cache: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pecan_sandbox::permits::Priority;
+
+    use super::build_sandbox_execution_option;
+    use crate::code_execution::{Project, ProjectFile};
+    use crate::toolchains::Language;
+
+    fn project(paths: &[&str], entry_point: Option<&str>) -> Project {
+        Project {
+            files: paths
+                .iter()
+                .map(|path| ProjectFile {
+                    path: path.to_string(),
+                    content: Vec::new(),
+                })
+                .collect(),
+            entry_point: entry_point.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn project_sources_are_compiled_together() {
+        let options = build_sandbox_execution_option(
+            Language::C,
+            String::new(),
+            Some(project(&["main.c", "lib/util.c", "lib/util.h"], None)),
+            Vec::new(),
+            1.0,
+            65536.0,
+            Priority::Normal,
+        )
+        .unwrap();
+
+        let compile_options = options.compile_options.unwrap();
+        assert_eq!(compile_options.args, ["-o", "main", "main.c", "lib/util.c"]);
+        let files: Vec<_> = options
+            .additional_file_options
+            .unwrap()
+            .into_iter()
+            .map(|file| file.file_name)
+            .collect();
+        assert_eq!(files, ["main.c", "lib/util.c", "lib/util.h"]);
+    }
+
+    #[test]
+    fn project_entry_point_replaces_the_default_one() {
+        let options = build_sandbox_execution_option(
+            Language::Java,
+            String::new(),
+            Some(project(
+                &["app/Main.java", "app/Util.java"],
+                Some("app/Main.java"),
+            )),
+            Vec::new(),
+            1.0,
+            65536.0,
+            Priority::Normal,
+        )
+        .unwrap();
+
+        assert_eq!(
+            options.compile_options.unwrap().args,
+            ["app/Main.java", "app/Util.java"]
+        );
+        assert_eq!(options.args.last().unwrap(), "app.Main");
+
+        let options = build_sandbox_execution_option(
+            Language::Python,
+            String::new(),
+            Some(project(&["pkg/__init__.py", "run.py"], Some("run.py"))),
+            Vec::new(),
+            1.0,
+            65536.0,
+            Priority::Normal,
+        )
+        .unwrap();
+        assert_eq!(options.args.last().unwrap(), "run.py");
+    }
 }
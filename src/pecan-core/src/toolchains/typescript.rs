@@ -5,7 +5,9 @@ use std::path::PathBuf;
 
 use crate::define_language_toolchain_dir_default;
 use crate::toolchains::node::{NODE_BIN, NODE_DIR, NODE_MOUNT_POINT};
-use crate::toolchains::{CompileOption, DirMountOption, LanguageToolchain, RuntimeOption};
+use crate::toolchains::{
+    CompileOption, DirMountOption, LanguageToolchain, ProjectOption, RuntimeOption, compiled_script,
+};
 
 pub const TYPESCRIPT_LANGUAGE: &str = "typescript";
 pub const TYPESCRIPT_VERSION: &str = "5.7.3";
@@ -24,6 +26,8 @@ pub fn language_toolchain_typescript() -> LanguageToolchain {
             compiler_path: PathBuf::from(TYPESCRIPT_DIR).join(TYPESCRIPT_BIN),
             env: None,
             args: vec![TYPESCRIPT_SOURCE_FILE_NAME.to_string()],
+            // tsc also emits the modules the entry point imports
+            outputs: vec!["*.js".to_string()],
         }),
         runtime_option: RuntimeOption {
             binary_path: PathBuf::from(NODE_MOUNT_POINT).join(NODE_BIN),
@@ -34,5 +38,11 @@ pub fn language_toolchain_typescript() -> LanguageToolchain {
             env: None,
             args: vec![TYPESCRIPT_JS_FILE_NAME.to_string()],
         },
+        project_option: ProjectOption {
+            entry_point: TYPESCRIPT_SOURCE_FILE_NAME.to_string(),
+            sources: vec![],
+            run_target: compiled_script,
+            single_directory: false,
+        },
     }
 }
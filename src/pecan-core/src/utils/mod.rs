@@ -1 +1,2 @@
 pub mod queue;
+pub mod serde_base64;
@@ -1,71 +1,129 @@
 use std::collections::VecDeque;
-use std::sync::{Condvar, Mutex};
+use std::pin::pin;
+use std::sync::Mutex;
 
+use tokio::sync::Notify;
+
+/// Bounded MPMC queue whose `push` and `pop` wait asynchronously instead of
+/// blocking the thread. The lock is only held for the buffer operation itself.
+///
+/// Values are pushed into one of several lanes, each with its own capacity.
+/// `pop` serves the non-empty lanes by smooth weighted round-robin, so a lane
+/// of weight 4 gets four values out for every one of a lane of weight 1.
 pub struct Queue<T> {
     inner: Mutex<Inner<T>>,
-    not_empty: Condvar,
-    not_full: Condvar,
+    not_empty: Notify,
+    not_full: Notify,
 }
 
-struct Inner<T> {
+struct Lane<T> {
     buf: VecDeque<T>,
+    weight: i64,
+    credit: i64,
+}
+
+struct Inner<T> {
+    lanes: Vec<Lane<T>>,
     capacity: usize,
     closed: bool,
 }
 
+impl<T> Inner<T> {
+    fn pop_weighted(&mut self) -> Option<T> {
+        let mut active = 0;
+        for lane in &mut self.lanes {
+            if lane.buf.is_empty() {
+                // an idle lane does not bank credit for later bursts
+                lane.credit = 0;
+            } else {
+                lane.credit += lane.weight;
+                active += lane.weight;
+            }
+        }
+        let lane = self
+            .lanes
+            .iter_mut()
+            .filter(|lane| !lane.buf.is_empty())
+            // the first lane wins ties
+            .rev()
+            .max_by_key(|lane| lane.credit)?;
+        lane.credit -= active;
+        lane.buf.pop_front()
+    }
+}
+
 impl<T> Queue<T> {
     pub fn bounded(capacity: usize) -> Self {
+        Self::weighted(capacity, &[1])
+    }
+
+    /// One lane per weight, each holding up to `capacity` values.
+    pub fn weighted(capacity: usize, weights: &[u32]) -> Self {
         assert!(capacity > 0);
+        assert!(!weights.is_empty());
         Self {
             inner: Mutex::new(Inner {
-                buf: VecDeque::with_capacity(capacity),
+                lanes: weights
+                    .iter()
+                    .map(|&weight| Lane {
+                        buf: VecDeque::new(),
+                        weight: i64::from(weight.max(1)),
+                        credit: 0,
+                    })
+                    .collect(),
                 capacity,
                 closed: false,
             }),
-            not_empty: Condvar::new(),
-            not_full: Condvar::new(),
+            not_empty: Notify::new(),
+            not_full: Notify::new(),
         }
     }
 
-    pub fn push(&self, value: T) -> Result<(), T> {
-        let mut inner = match self.inner.lock() {
-            Ok(inner) => inner,
-            Err(_) => return Err(value),
-        };
-        while inner.buf.len() == inner.capacity && !inner.closed {
-            inner = match self.not_full.wait(inner) {
-                Ok(inner) => inner,
-                Err(_) => return Err(value),
-            };
-        }
-        if inner.closed {
-            return Err(value);
-        }
-        inner.buf.push_back(value);
-        self.not_empty.notify_one();
-        Ok(())
+    /// Wait for free space and push `value`; gives it back if the queue is closed.
+    pub async fn push(&self, value: T) -> Result<(), T> {
+        self.push_to(0, value).await
     }
 
-    pub fn pop(&self) -> Option<T> {
-        let mut inner = match self.inner.lock() {
-            Ok(inner) => inner,
-            Err(_) => return None,
-        };
-        while inner.buf.is_empty() && !inner.closed {
-            inner = match self.not_empty.wait(inner) {
-                Ok(inner) => inner,
-                Err(_) => return None,
-            };
+    /// `push` into `lane`.
+    pub async fn push_to(&self, lane: usize, value: T) -> Result<(), T> {
+        let mut value = value;
+        loop {
+            // registered before checking the buffer so that a wakeup is never lost
+            let mut notified = pin!(self.not_full.notified());
+            notified.as_mut().enable();
+
+            match self.try_push_to(lane, value) {
+                Ok(()) => return Ok(()),
+                Err(TryPushError::Full(v)) => value = v,
+                Err(TryPushError::Closed(v)) | Err(TryPushError::Poisoned(v)) => return Err(v),
+            }
+
+            notified.await;
         }
-        if let Some(v) = inner.buf.pop_front() {
-            self.not_full.notify_one();
-            Some(v)
-        } else {
-            None
+    }
+
+    /// Wait for the next value; `None` once the queue is closed and empty.
+    pub async fn pop(&self) -> Option<T> {
+        loop {
+            let mut notified = pin!(self.not_empty.notified());
+            notified.as_mut().enable();
+
+            match self.try_pop() {
+                Ok(v) => return Some(v),
+                Err(TryPopError::Empty) => {}
+                Err(TryPopError::Closed) | Err(TryPopError::Poisoned) => return None,
+            }
+
+            notified.await;
         }
     }
 
     pub fn try_push(&self, value: T) -> Result<(), TryPushError<T>> {
+        self.try_push_to(0, value)
+    }
+
+    /// `try_push` into `lane`; lanes past the last one go to the last one.
+    pub fn try_push_to(&self, lane: usize, value: T) -> Result<(), TryPushError<T>> {
         let mut inner = match self.inner.lock() {
             Ok(inner) => inner,
             Err(_) => return Err(TryPushError::Poisoned(value)),
@@ -73,10 +131,14 @@ impl<T> Queue<T> {
         if inner.closed {
             return Err(TryPushError::Closed(value));
         }
-        if inner.buf.len() == inner.capacity {
+        let capacity = inner.capacity;
+        let last = inner.lanes.len() - 1;
+        let buf = &mut inner.lanes[lane.min(last)].buf;
+        if buf.len() == capacity {
             return Err(TryPushError::Full(value));
         }
-        inner.buf.push_back(value);
+        buf.push_back(value);
+        drop(inner);
         self.not_empty.notify_one();
         Ok(())
     }
@@ -86,8 +148,15 @@ impl<T> Queue<T> {
             Ok(inner) => inner,
             Err(_) => return Err(TryPopError::Poisoned),
         };
-        if let Some(v) = inner.buf.pop_front() {
-            self.not_full.notify_one();
+        if let Some(v) = inner.pop_weighted() {
+            let single_lane = inner.lanes.len() == 1;
+            drop(inner);
+            if single_lane {
+                self.not_full.notify_one();
+            } else {
+                // the one waiter woken might be pushing into another, still full, lane
+                self.not_full.notify_waiters();
+            }
             Ok(v)
         } else if inner.closed {
             Err(TryPopError::Closed)
@@ -96,14 +165,15 @@ impl<T> Queue<T> {
         }
     }
 
+    /// Reject further pushes and wake every waiter. Values already queued can
+    /// still be popped.
     pub fn close(&self) {
-        let mut inner = match self.inner.lock() {
-            Ok(inner) => inner,
+        match self.inner.lock() {
+            Ok(mut inner) => inner.closed = true,
             Err(_) => return,
-        };
-        inner.closed = true;
-        self.not_empty.notify_all();
-        self.not_full.notify_all();
+        }
+        self.not_empty.notify_waiters();
+        self.not_full.notify_waiters();
     }
 
     pub fn is_closed(&self) -> bool {
@@ -115,7 +185,15 @@ impl<T> Queue<T> {
 
     pub fn len(&self) -> usize {
         match self.inner.lock() {
-            Ok(inner) => inner.buf.len(),
+            Ok(inner) => inner.lanes.iter().map(|lane| lane.buf.len()).sum(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Values waiting in `lane`.
+    pub fn lane_len(&self, lane: usize) -> usize {
+        match self.inner.lock() {
+            Ok(inner) => inner.lanes.get(lane).map_or(0, |lane| lane.buf.len()),
             Err(_) => 0,
         }
     }
@@ -139,6 +217,9 @@ pub enum TryPopError {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
     use super::{Queue, TryPopError, TryPushError};
 
     #[test]
@@ -156,15 +237,76 @@ mod tests {
         assert!(matches!(queue.try_pop(), Err(TryPopError::Empty)));
     }
 
-    #[test]
-    fn close_prevents_future_pushes() {
+    #[tokio::test]
+    async fn close_prevents_future_pushes() {
         let queue = Queue::bounded(1);
 
         queue.close();
 
         assert!(queue.is_closed());
         assert!(matches!(queue.try_push(1), Err(TryPushError::Closed(1))));
-        assert!(matches!(queue.push(2), Err(2)));
+        assert!(matches!(queue.push(2).await, Err(2)));
         assert!(matches!(queue.try_pop(), Err(TryPopError::Closed)));
     }
+
+    #[tokio::test]
+    async fn pop_waits_for_push() {
+        let queue = Arc::new(Queue::bounded(1));
+
+        let popper = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move { queue.pop().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(queue.try_push(7).is_ok());
+
+        assert_eq!(popper.await.unwrap(), Some(7));
+    }
+
+    #[tokio::test]
+    async fn push_waits_for_space_and_close_wakes_pop() {
+        let queue = Arc::new(Queue::bounded(1));
+        assert!(queue.try_push(1).is_ok());
+
+        let pusher = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move { queue.push(2).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!pusher.is_finished());
+        assert_eq!(queue.pop().await, Some(1));
+        assert!(pusher.await.unwrap().is_ok());
+        assert_eq!(queue.pop().await, Some(2));
+
+        let popper = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move { queue.pop().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        queue.close();
+        assert_eq!(popper.await.unwrap(), None);
+    }
+
+    #[test]
+    fn weighted_lanes_share_pops_by_weight() {
+        let queue = Queue::weighted(8, &[3, 1]);
+        for i in 0..8 {
+            assert!(queue.try_push_to(0, ('a', i)).is_ok());
+            assert!(queue.try_push_to(1, ('b', i)).is_ok());
+        }
+        assert!(matches!(
+            queue.try_push_to(1, ('b', 8)),
+            Err(TryPushError::Full(_))
+        ));
+        assert_eq!(queue.lane_len(1), 8);
+
+        let lanes: String = (0..8).map(|_| queue.try_pop().ok().unwrap().0).collect();
+        assert_eq!(lanes, "aaba".repeat(2));
+
+        // once the heavier lane is drained the other one gets every pop
+        while queue.lane_len(0) > 0 {
+            assert!(queue.try_pop().is_ok());
+        }
+        assert!(matches!(queue.try_pop(), Ok(('b', _))));
+    }
 }
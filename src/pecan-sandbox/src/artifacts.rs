@@ -0,0 +1,517 @@
+//! Content-addressed store of compiled programs.
+//!
+//! Compiler outputs are kept in one directory per key, where the key covers
+//! the toolchain and its installed files, the compile profile (compiler,
+//! arguments, environment) and the source files. A later execution with the same key copies them into its
+//! working directory instead of compiling again.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::fingerprint::path_fingerprint;
+use crate::sandbox::SandboxExecutionOptions;
+use crate::tools::common::create_file_below;
+
+#[derive(Debug, Clone)]
+pub struct ArtifactCachePolicy {
+    pub dir: PathBuf,
+    /// bound on the total size of the stored artifacts, in bytes
+    pub max_bytes: u64,
+}
+
+pub type ArtifactKey = [u8; 32];
+
+/// Key of the program compiled by `options`, `None` for interpreted languages.
+pub fn artifact_key(options: &SandboxExecutionOptions) -> Option<ArtifactKey> {
+    let compile_options = options.compile_options.as_ref()?;
+
+    let mut hasher = Sha256::new();
+    let mut update = |part: &[u8]| {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    };
+    update(compile_options.toolchain.as_bytes());
+    update(compile_options.compiler_path.as_os_str().as_encoded_bytes());
+    update(&path_fingerprint(&compile_options.compiler_path));
+    for arg in &compile_options.args {
+        update(arg.as_bytes());
+    }
+    let mut env: Vec<_> = compile_options.env.iter().flatten().collect();
+    env.sort();
+    for (name, value) in env {
+        update(name.as_bytes());
+        update(value.as_bytes());
+        // such as the `JAVA_HOME` a compiler runs on
+        let value = Path::new(value);
+        if value.is_absolute() {
+            update(&path_fingerprint(value));
+        }
+    }
+    for file in options.additional_file_options.iter().flatten() {
+        update(file.file_name.as_bytes());
+        update(&Sha256::digest(&file.file_content));
+    }
+    Some(hasher.finalize().into())
+}
+
+/// Regular files below `dir`, as `/`-separated paths relative to it.
+async fn files_below(dir: &Path) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut pending = vec![String::new()];
+    while let Some(prefix) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(dir.join(&prefix)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let path = if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Whether `file` below `dir` is a regular file reached through real
+/// directories only, so that nothing is read through a link.
+async fn is_file_below(dir: &Path, file: &str) -> io::Result<bool> {
+    let mut path = dir.to_path_buf();
+    let mut components = Path::new(file).components().peekable();
+    while let Some(component) = components.next() {
+        let Component::Normal(name) = component else {
+            return Ok(false);
+        };
+        path.push(name);
+        let metadata = match tokio::fs::symlink_metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let expected = if components.peek().is_some() {
+            metadata.is_dir()
+        } else {
+            metadata.is_file()
+        };
+        if !expected {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Regular files in `dir` named by `outputs`; an entry like `*.class`
+/// matches by suffix, in subdirectories too. Links are never matched.
+pub async fn find_outputs(dir: &Path, outputs: &[String]) -> io::Result<Vec<String>> {
+    let mut found = Vec::new();
+    let suffixes: Vec<&str> = outputs.iter().filter_map(|o| o.strip_prefix('*')).collect();
+    for output in outputs.iter().filter(|o| !o.starts_with('*')) {
+        if is_file_below(dir, output).await? {
+            found.push(output.clone());
+        }
+    }
+    if !suffixes.is_empty() {
+        for path in files_below(dir).await? {
+            if suffixes.iter().any(|s| path.ends_with(s)) {
+                found.push(path);
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn hex(key: &ArtifactKey) -> String {
+    key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(name: &str) -> Option<ArtifactKey> {
+    if name.len() != 64 {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(name.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(key)
+}
+
+async fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for path in files_below(dir).await? {
+        size += tokio::fs::metadata(dir.join(path)).await?.len();
+    }
+    Ok(size)
+}
+
+/// Copy `files` below `from` to the same paths below `to`. The target may
+/// be a box, where links left by a program are replaced, not written through.
+async fn copy_files(from: &Path, to: &Path, files: &[String]) -> io::Result<u64> {
+    let mut size = 0;
+    for file in files {
+        let mut source = tokio::fs::File::open(from.join(file)).await?;
+        let mut target = create_file_below(to, file).await?;
+        size += tokio::io::copy(&mut source, &mut target).await?;
+        target.flush().await?;
+        // as `fs::copy` would, so binaries stay executable
+        let permissions = source.metadata().await?.permissions();
+        target.set_permissions(permissions).await?;
+    }
+    Ok(size)
+}
+
+struct Entry {
+    size: u64,
+    /// position in `Index::lru`
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Index {
+    entries: HashMap<ArtifactKey, Entry>,
+    /// least recently used first
+    lru: BTreeMap<u64, ArtifactKey>,
+    bytes: u64,
+    tick: u64,
+}
+
+impl Index {
+    fn touch(&mut self, key: &ArtifactKey) -> bool {
+        self.tick += 1;
+        let tick = self.tick;
+        let Some(entry) = self.entries.get_mut(key) else {
+            return false;
+        };
+        self.lru.remove(&entry.last_used);
+        self.lru.insert(tick, *key);
+        entry.last_used = tick;
+        true
+    }
+
+    fn insert(&mut self, key: ArtifactKey, size: u64) {
+        self.remove(&key);
+        self.tick += 1;
+        self.lru.insert(self.tick, key);
+        self.bytes += size;
+        self.entries.insert(
+            key,
+            Entry {
+                size,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &ArtifactKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.last_used);
+            self.bytes -= entry.size;
+        }
+    }
+
+    /// Drop least recently used entries until at most `max_bytes` remain.
+    fn evict(&mut self, max_bytes: u64) -> Vec<ArtifactKey> {
+        let mut evicted = Vec::new();
+        while self.bytes > max_bytes {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.bytes -= entry.size;
+            }
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+pub struct ArtifactStore {
+    policy: ArtifactCachePolicy,
+    index: Mutex<Index>,
+}
+
+impl ArtifactStore {
+    /// Open the store, picking up the artifacts already in its directory.
+    pub async fn open(policy: ArtifactCachePolicy) -> io::Result<Self> {
+        tokio::fs::create_dir_all(&policy.dir).await?;
+
+        let mut index = Index::default();
+        let mut entries = tokio::fs::read_dir(&policy.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            match parse_hex(&name) {
+                Some(key) => index.insert(key, dir_size(&entry.path()).await?),
+                // left behind by an interrupted `save`
+                None if name.contains(".partial-") => {
+                    let _ = tokio::fs::remove_dir_all(entry.path()).await;
+                }
+                None => {}
+            }
+        }
+
+        let store = Self {
+            policy,
+            index: Mutex::new(index),
+        };
+        let evicted = store.lock().evict(store.policy.max_bytes);
+        store.remove_dirs(&evicted).await;
+        Ok(store)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Index> {
+        // the index stays consistent across a panic, so keep using it
+        self.index.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn path(&self, key: &ArtifactKey) -> PathBuf {
+        self.policy.dir.join(hex(key))
+    }
+
+    async fn remove_dirs(&self, keys: &[ArtifactKey]) {
+        for key in keys {
+            let _ = tokio::fs::remove_dir_all(self.path(key)).await;
+        }
+    }
+
+    /// Number of stored artifacts and their total size in bytes.
+    pub fn usage(&self) -> (usize, u64) {
+        let index = self.lock();
+        (index.entries.len(), index.bytes)
+    }
+
+    /// Copy the artifact stored under `key` into `dir`. Returns whether it was
+    /// found; on `false` the caller compiles as usual.
+    pub async fn restore(&self, key: &ArtifactKey, dir: &Path) -> bool {
+        if !self.lock().touch(key) {
+            return false;
+        }
+        let copied = async {
+            let stored = self.path(key);
+            copy_files(&stored, dir, &files_below(&stored).await?).await
+        }
+        .await;
+
+        if let Err(e) = copied {
+            // evicted meanwhile or damaged on disk, forget it
+            tracing::warn!(error = %e, "failed to restore compiled artifact");
+            self.lock().remove(key);
+            self.remove_dirs(&[*key]).await;
+            return false;
+        }
+        true
+    }
+
+    /// Store the `outputs` a successful compile left in `dir` under `key`.
+    pub async fn save(&self, key: ArtifactKey, dir: &Path, outputs: &[String]) {
+        let target = self.path(&key);
+        // copy aside and rename, so `restore` never sees a partial artifact
+        let partial = self
+            .policy
+            .dir
+            .join(format!("{}.partial-{}", hex(&key), Uuid::new_v4()));
+        let saved = async {
+            let files = find_outputs(dir, outputs).await?;
+            if files.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "compiler produced none of the expected outputs",
+                ));
+            }
+            tokio::fs::create_dir(&partial).await?;
+            let size = copy_files(dir, &partial, &files).await?;
+            tokio::fs::rename(&partial, &target).await?;
+            io::Result::Ok(size)
+        }
+        .await;
+
+        let size = match saved {
+            Ok(size) => size,
+            Err(e) => {
+                // a concurrent compile of the same source may have won the rename
+                let _ = tokio::fs::remove_dir_all(&partial).await;
+                tracing::debug!(error = %e, "compiled artifact not stored");
+                return;
+            }
+        };
+
+        let evicted = {
+            let mut index = self.lock();
+            index.insert(key, size);
+            index.evict(self.policy.max_bytes)
+        };
+        self.remove_dirs(&evicted).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use super::{ArtifactCachePolicy, ArtifactStore, artifact_key, find_outputs};
+    use crate::permits::Priority;
+    use crate::sandbox::{CompileOptions, SandboxAdditionalFileOptions, SandboxExecutionOptions};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pecan-{}-{}", name, Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn keys_change_when_the_compiler_is_replaced_in_place() {
+        let toolchain = temp_dir("toolchain");
+        let compiler = toolchain.join("cc");
+        std::fs::write(&compiler, "1.0").unwrap();
+        let options = SandboxExecutionOptions {
+            additional_file_options: Some(vec![SandboxAdditionalFileOptions {
+                file_name: "main.c".to_string(),
+                file_content: b"int main() {}".to_vec(),
+            }]),
+            compile_options: Some(CompileOptions {
+                compiler_path: compiler.clone(),
+                env: None,
+                args: vec!["main.c".to_string()],
+                toolchain: "c 1".to_string(),
+                outputs: vec!["main".to_string()],
+            }),
+            additional_directory_options: None,
+            binary_path: "main".into(),
+            args: Vec::new(),
+            stdin: Vec::new(),
+            time_limit: 1.0,
+            wall_time_limit: 6.0,
+            memory_limit: 65536.0,
+            compile_timeout: 10.0,
+            queue_timeout: Some(1.0),
+            priority: Priority::Normal,
+            output_files: Vec::new(),
+            input_files: Vec::new(),
+            bypass_artifact_cache: false,
+        };
+
+        let installed = artifact_key(&options).unwrap();
+        assert_eq!(artifact_key(&options).unwrap(), installed);
+        std::fs::write(&compiler, "1.0.1").unwrap();
+        assert_ne!(artifact_key(&options).unwrap(), installed);
+        std::fs::remove_dir_all(toolchain).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stored_artifacts_are_restored_and_evicted_by_size() {
+        let store_dir = temp_dir("artifacts");
+        let work = temp_dir("work");
+        std::fs::write(work.join("main"), [0u8; 64]).unwrap();
+        std::fs::write(work.join("main.c"), "int main() {}").unwrap();
+
+        let store = ArtifactStore::open(ArtifactCachePolicy {
+            dir: store_dir.clone(),
+            max_bytes: 100,
+        })
+        .await
+        .unwrap();
+        let outputs = vec!["main".to_string()];
+        store.save([1; 32], &work, &outputs).await;
+        assert_eq!(store.usage(), (1, 64));
+
+        let restored = temp_dir("restored");
+        assert!(store.restore(&[1; 32], &restored).await);
+        assert_eq!(std::fs::read(restored.join("main")).unwrap(), [0u8; 64]);
+        assert!(!restored.join("main.c").exists());
+        assert!(!store.restore(&[2; 32], &restored).await);
+
+        store.save([2; 32], &work, &outputs).await;
+        assert_eq!(store.usage(), (1, 64));
+        assert!(!store.restore(&[1; 32], &restored).await);
+
+        // reopening picks up what is on disk
+        drop(store);
+        let store = ArtifactStore::open(ArtifactCachePolicy {
+            dir: store_dir.clone(),
+            max_bytes: 100,
+        })
+        .await
+        .unwrap();
+        assert!(store.restore(&[2; 32], &restored).await);
+
+        for dir in [store_dir, work, restored] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn links_in_the_box_are_neither_cached_nor_written_through() {
+        let store_dir = temp_dir("artifacts");
+        let work = temp_dir("work");
+        let outside = temp_dir("outside");
+        std::fs::write(outside.join("secret"), "kept").unwrap();
+        std::os::unix::fs::symlink(outside.join("secret"), work.join("main")).unwrap();
+        std::os::unix::fs::symlink(&outside, work.join("bin")).unwrap();
+
+        let store = ArtifactStore::open(ArtifactCachePolicy {
+            dir: store_dir.clone(),
+            max_bytes: 100,
+        })
+        .await
+        .unwrap();
+        let outputs = vec!["main".to_string(), "bin/secret".to_string()];
+        assert!(find_outputs(&work, &outputs).await.unwrap().is_empty());
+        store.save([1; 32], &work, &outputs).await;
+        assert_eq!(store.usage(), (0, 0));
+
+        // a later run finds the link still in the box when restoring
+        std::fs::remove_file(work.join("main")).unwrap();
+        std::fs::write(work.join("main"), [0u8; 8]).unwrap();
+        store.save([2; 32], &work, &["main".to_string()]).await;
+        std::fs::remove_file(work.join("main")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret"), work.join("main")).unwrap();
+        assert!(store.restore(&[2; 32], &work).await);
+        assert_eq!(std::fs::read(work.join("main")).unwrap(), [0u8; 8]);
+        assert!(
+            !std::fs::symlink_metadata(work.join("main"))
+                .unwrap()
+                .is_symlink()
+        );
+        assert_eq!(std::fs::read(outside.join("secret")).unwrap(), b"kept");
+
+        for dir in [store_dir, work, outside] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn suffix_outputs_match_every_generated_file() {
+        let work = temp_dir("classes");
+        std::fs::create_dir_all(work.join("app/util")).unwrap();
+        for file in [
+            "Main.class",
+            "Main$Inner.class",
+            "Main.java",
+            "app/util/Strings.class",
+        ] {
+            std::fs::write(work.join(file), "").unwrap();
+        }
+
+        let mut found = find_outputs(&work, &["*.class".to_string()]).await.unwrap();
+        found.sort();
+        assert_eq!(
+            found,
+            ["Main$Inner.class", "Main.class", "app/util/Strings.class"]
+        );
+        let _ = std::fs::remove_dir_all(work);
+    }
+}
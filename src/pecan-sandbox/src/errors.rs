@@ -35,4 +35,8 @@ pub enum SandboxManagerError {
     SemaphoreAcquireTimeout,
     #[error("Timed out waiting for an idle sandbox from the queue")]
     IdleQueueTimeout,
+    #[error("Sandbox not found: {0}")]
+    SandboxNotFound(Uuid),
+    #[error("Sandbox is running: {0}")]
+    SandboxBusy(Uuid),
 }
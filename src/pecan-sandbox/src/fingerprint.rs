@@ -0,0 +1,66 @@
+//! Fingerprints of toolchain files on the host.
+//!
+//! Toolchains are upgraded in place under the same paths while their version
+//! strings are fixed in the code, so keys of compiled programs and cached
+//! results also cover what those paths resolve to and when they last changed.
+
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Canonical path, size and modification time of `path`, following links
+/// such as a `current` symlink; empty when `path` does not exist. Read on
+/// every call, so that an upgrade is noticed without a restart.
+pub fn path_fingerprint(path: &Path) -> Vec<u8> {
+    let Ok(resolved) = std::fs::canonicalize(path) else {
+        return Vec::new();
+    };
+    let Ok(metadata) = std::fs::metadata(&resolved) else {
+        return Vec::new();
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+
+    let mut fingerprint = resolved.into_os_string().into_encoded_bytes();
+    fingerprint.extend(metadata.len().to_le_bytes());
+    fingerprint.extend(modified.as_nanos().to_le_bytes());
+    fingerprint
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::path_fingerprint;
+
+    #[test]
+    fn fingerprints_follow_upgrades_behind_the_same_path() {
+        let dir = std::env::temp_dir().join(format!("pecan-toolchain-{}", Uuid::new_v4()));
+        for version in ["1.0", "1.1"] {
+            std::fs::create_dir_all(dir.join(version)).unwrap();
+            std::fs::write(dir.join(version).join("cc"), version).unwrap();
+        }
+        let current = dir.join("current");
+        std::os::unix::fs::symlink(dir.join("1.0"), &current).unwrap();
+        let compiler = current.join("cc");
+
+        let installed = path_fingerprint(&compiler);
+        assert!(!installed.is_empty());
+        assert_eq!(path_fingerprint(&compiler), installed);
+
+        // the link is switched to a new release
+        std::fs::remove_file(&current).unwrap();
+        std::os::unix::fs::symlink(dir.join("1.1"), &current).unwrap();
+        let switched = path_fingerprint(&compiler);
+        assert_ne!(switched, installed);
+
+        // the release is overwritten in place
+        std::fs::write(dir.join("1.1").join("cc"), "1.1.1").unwrap();
+        assert_ne!(path_fingerprint(&compiler), switched);
+
+        assert!(path_fingerprint(&dir.join("missing")).is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
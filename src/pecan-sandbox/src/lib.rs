@@ -7,14 +7,19 @@ use futures::stream::FuturesUnordered;
 use tokio_util::sync::CancellationToken;
 
 use crate::manager::SandboxManager;
+use crate::permits::Priority;
 use crate::sandbox::{
     CompileOptions, SandboxAdditionalDirectoryOptions, SandboxAdditionalFileOptions,
     SandboxExecutionOptions,
 };
 
+pub mod artifacts;
 pub mod errors;
+pub mod fingerprint;
 pub mod manager;
+pub mod permits;
 pub mod sandbox;
+pub mod telemetry;
 pub mod tools;
 
 pub async fn test_sandbox_manager() {
@@ -50,6 +55,8 @@ pub async fn test_sandbox_manager() {
                 "-d".to_string(),
                 "Main.jar".to_string(),
             ],
+            toolchain: "kotlin 2.0.21".to_string(),
+            outputs: vec!["Main.jar".to_string()],
         }),
         additional_file_options: Some(vec![SandboxAdditionalFileOptions {
             file_name: "Main.kt".to_string(),
@@ -61,7 +68,7 @@ pub async fn test_sandbox_manager() {
                         }
                         println(sum)
                     }"#
-            .to_string(),
+            .into(),
         }]),
         additional_directory_options: Some(vec![SandboxAdditionalDirectoryOptions {
             directory_path: PathBuf::from("/opt/toolchains/java/current"),
@@ -79,11 +86,16 @@ pub async fn test_sandbox_manager() {
             "-jar".to_string(),
             "Main.jar".to_string(),
         ],
-        stdin: "5\n1\n2\n3\n4\n1011".to_string(),
+        stdin: b"5\n1\n2\n3\n4\n1011".to_vec(),
         time_limit: 10.0,
         wall_time_limit: 33.0,
         memory_limit: 2048000.0,
         compile_timeout: 30.0,
+        queue_timeout: Some(10.0),
+        priority: Priority::Normal,
+        output_files: Vec::new(),
+        input_files: Vec::new(),
+        bypass_artifact_cache: false,
     });
 
     let mut futs = FuturesUnordered::new();
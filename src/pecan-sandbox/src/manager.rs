@@ -1,22 +1,35 @@
 //! Sandbox manager holds all initialized sandboxes, tracks their status,
 //! and stores actual tool information based on build configuration
 
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, OnceLock};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use dashmap::DashMap;
+use futures::StreamExt;
+use futures::stream;
 use tokio::process::Command;
-use tokio::sync::{Mutex, Semaphore, mpsc};
+use tokio::sync::{Mutex, mpsc};
 use tokio::time::{sleep, timeout};
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 use uuid::Uuid;
 
+use crate::artifacts::{ArtifactCachePolicy, ArtifactStore, artifact_key};
 use crate::errors::SandboxManagerError;
+use crate::permits::{PriorityPermits, PriorityShares};
 use crate::sandbox::{
-    Sandbox, SandboxExecutionOptions, SandboxExecutionResult, SandboxExecutionStatus, SandboxStatus,
+    ExecutionEvent, ExecutionEvents, ExecutionInput, ExecutionPhase, Sandbox,
+    SandboxExecutionOptions, SandboxExecutionResult, SandboxExecutionStatus, SandboxOutputFile,
+    SandboxStatus,
 };
-use crate::tools::common::ISandboxTool;
+use crate::telemetry::{
+    ARTIFACT_CACHE_LOOKUPS_TOTAL, SANDBOX_CREATE_FAILURES_TOTAL, SANDBOX_DESTROY_FAILURES_TOTAL,
+    SANDBOX_REAPED_TOTAL,
+};
+use crate::tools::common::{ISandboxTool, wait_with_output};
 use crate::tools::{SandboxTool, build_tool};
 
 pub static MAX_PREWARMED_SANDBOXES: OnceLock<usize> = OnceLock::new();
@@ -24,6 +37,112 @@ pub static MAX_PREWARMED_SANDBOXES: OnceLock<usize> = OnceLock::new();
 /// Acts as a safety net behind `SandboxGuard`'s RAII cleanup.
 pub static MAX_RUNNING_SANDBOX_SECS: OnceLock<u64> = OnceLock::new();
 
+/// Sandboxes created concurrently when the pool is prewarmed or grown.
+const CREATE_CONCURRENCY: usize = 16;
+/// Minimum time between two autoscaler decisions.
+const AUTOSCALE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bounds and triggers of the pool autoscaler.
+#[derive(Debug, Clone)]
+pub struct AutoscalePolicy {
+    /// the pool never shrinks below this size
+    pub min_sandboxes: usize,
+    /// the pool never grows above this size, nor above `MAX_PREWARMED_SANDBOXES`
+    pub max_sandboxes: usize,
+    /// sandboxes added or removed per decision
+    pub step: usize,
+    /// grow when an execution waited at least this long for a sandbox
+    pub scale_up_wait: Duration,
+    /// grow when at least this many executions are waiting for a sandbox
+    pub scale_up_waiting: usize,
+    /// shrink idle sandboxes after this long without pressure
+    pub scale_down_after: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScaleAction {
+    Grow(usize),
+    Shrink(usize),
+    Hold,
+}
+
+/// Point-in-time view of one sandbox, for operators.
+#[derive(Debug, Clone)]
+pub struct SandboxInfo {
+    pub id: Uuid,
+    pub status: SandboxStatus,
+    /// UNIX epoch seconds when the current run started
+    pub running_since: Option<u64>,
+    /// backend box identifier, e.g. the isolate `--box-id`
+    pub box_id: String,
+    /// backend working directory of the box
+    pub path: PathBuf,
+}
+
+/// Signals sampled by the autoscaler since its previous decision.
+struct PoolPressure {
+    total: usize,
+    idle: usize,
+    waiting: usize,
+    max_wait: Duration,
+    /// time since the pool last saw pressure or was resized
+    calm_for: Duration,
+}
+
+impl AutoscalePolicy {
+    fn is_under_pressure(&self, pressure: &PoolPressure) -> bool {
+        pressure.waiting >= self.scale_up_waiting.max(1) || pressure.max_wait >= self.scale_up_wait
+    }
+
+    fn decide(&self, pressure: &PoolPressure) -> ScaleAction {
+        let max = self.max_sandboxes.max(self.min_sandboxes);
+        let step = self.step.max(1);
+
+        if pressure.total < self.min_sandboxes {
+            return ScaleAction::Grow(self.min_sandboxes - pressure.total);
+        }
+        if self.is_under_pressure(pressure) {
+            return match max.saturating_sub(pressure.total).min(step) {
+                0 => ScaleAction::Hold,
+                num => ScaleAction::Grow(num),
+            };
+        }
+        if pressure.calm_for >= self.scale_down_after {
+            let removable = pressure
+                .total
+                .saturating_sub(self.min_sandboxes)
+                .min(pressure.idle)
+                .min(step);
+            if removable > 0 {
+                return ScaleAction::Shrink(removable);
+            }
+        }
+        ScaleAction::Hold
+    }
+}
+
+struct AutoscaleState {
+    evaluated_at: Instant,
+    /// last time the pool saw pressure or was resized
+    active_at: Instant,
+}
+
+/// Counts an execution as waiting for a sandbox until dropped.
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl<'a> WaitingGuard<'a> {
+    fn new(waiting: &'a AtomicUsize) -> Self {
+        waiting.fetch_add(1, Ordering::AcqRel);
+        Self(waiting)
+    }
+}
+
+impl<'a> Drop for WaitingGuard<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 /// initialize manager config based on deployed environment
 fn init_manager_config() -> Result<(), SandboxManagerError> {
     MAX_PREWARMED_SANDBOXES
@@ -103,27 +222,68 @@ fn now_secs() -> u64 {
         .unwrap_or(0)
 }
 
+/// Remove a file, link or directory tree, doing nothing when it is missing.
+async fn remove_entry(path: &Path) -> std::io::Result<()> {
+    let removed = match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.is_dir() => tokio::fs::remove_dir_all(path).await,
+        Ok(_) => tokio::fs::remove_file(path).await,
+        Err(e) => Err(e),
+    };
+    match removed {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        removed => removed,
+    }
+}
+
 pub struct SandboxManager {
     pub tool: SandboxTool,
     sandboxes: DashMap<Uuid, Arc<Sandbox>>,
     idle_tx: mpsc::UnboundedSender<Uuid>,
     idle_rx: Mutex<mpsc::UnboundedReceiver<Uuid>>,
-    permits: Arc<Semaphore>,
+    permits: Arc<PriorityPermits>,
+    autoscale: Option<AutoscalePolicy>,
+    autoscale_state: std::sync::Mutex<AutoscaleState>,
+    /// executions currently waiting for a permit or an idle sandbox
+    waiting: AtomicUsize,
+    /// longest claim wait in microseconds since the last autoscaler decision
+    max_wait_micros: AtomicU64,
+    /// compiled programs reused across executions, unset when disabled
+    artifacts: OnceLock<ArtifactStore>,
 }
 
 pub async fn create_sandbox(tool: &SandboxTool) -> Result<Arc<Sandbox>, SandboxManagerError> {
-    let inner = tool
-        .build_inner()
-        .await
-        .map_err(|e| SandboxManagerError::SandboxCreationFailed(e.to_string()))?;
+    let inner = tool.build_inner().await.map_err(|e| {
+        metrics::counter!(SANDBOX_CREATE_FAILURES_TOTAL).increment(1);
+        SandboxManagerError::SandboxCreationFailed(e.to_string())
+    })?;
 
     let sb = Arc::new(Sandbox::new(inner));
 
     Ok(sb)
 }
 
+/// Create `num` sandboxes, at most `CREATE_CONCURRENCY` at a time.
+async fn create_sandboxes(
+    tool: &SandboxTool,
+    num: usize,
+) -> Vec<Result<Arc<Sandbox>, SandboxManagerError>> {
+    stream::iter(0..num)
+        .map(|_| create_sandbox(tool))
+        .buffer_unordered(CREATE_CONCURRENCY)
+        .collect()
+        .await
+}
+
 impl SandboxManager {
     pub async fn new(prewarm: usize) -> Result<Arc<Self>, SandboxManagerError> {
+        Self::with_autoscale(prewarm, None).await
+    }
+
+    /// Create a manager whose pool is resized by `run_loop` according to `autoscale`.
+    pub async fn with_autoscale(
+        prewarm: usize,
+        autoscale: Option<AutoscalePolicy>,
+    ) -> Result<Arc<Self>, SandboxManagerError> {
         let _ = init_manager_config(); // ignore error
 
         let (tx, rx) = mpsc::unbounded_channel::<Uuid>();
@@ -132,10 +292,8 @@ impl SandboxManager {
             SandboxManagerError::ToolInitializationFailed(format!("Failed to build tool: {}", e))
         })?;
 
-        for _ in 0..prewarm {
-            let sb = create_sandbox(&tool)
-                .await
-                .map_err(|e| SandboxManagerError::SandboxCreationFailed(e.to_string()))?;
+        for sb in create_sandboxes(&tool, prewarm).await {
+            let sb = sb?;
             map.insert(sb.id, Arc::clone(&sb));
             tx.send(sb.id)
                 .map_err(|e| SandboxManagerError::QueueFull(e.to_string()))?;
@@ -146,7 +304,15 @@ impl SandboxManager {
             sandboxes: map,
             idle_tx: tx,
             idle_rx: Mutex::new(rx),
-            permits: Arc::new(Semaphore::new(prewarm)),
+            permits: Arc::new(PriorityPermits::new(prewarm, PriorityShares::default())),
+            autoscale,
+            autoscale_state: std::sync::Mutex::new(AutoscaleState {
+                evaluated_at: Instant::now(),
+                active_at: Instant::now(),
+            }),
+            waiting: AtomicUsize::new(0),
+            max_wait_micros: AtomicU64::new(0),
+            artifacts: OnceLock::new(),
         });
 
         Ok(mgr)
@@ -165,18 +331,45 @@ impl SandboxManager {
         &self,
         options: &SandboxExecutionOptions,
     ) -> Result<SandboxExecutionResult, SandboxManagerError> {
-        let _permit = timeout(
-            Duration::from_secs_f64(options.time_limit),
-            self.permits.acquire(),
-        )
-        .await
-        .map_err(|_| SandboxManagerError::SemaphoreAcquireTimeout)?
-        .map_err(|e| {
-            SandboxManagerError::SemaphoreClosed(format!("Semaphore acquisition failed: {}", e))
-        })?;
+        self.execute_with_events(options, None, None).await
+    }
+
+    /// `execute_via_manager` that reports phase changes and output to `events`
+    /// while the execution runs, and feeds `input` to the program as its
+    /// standard input when given. Dropping the returned future kills the
+    /// program; its sandbox is then recycled.
+    #[tracing::instrument(name = "sandbox", skip_all, fields(sandbox_id = tracing::field::Empty))]
+    pub async fn execute_with_events(
+        &self,
+        options: &SandboxExecutionOptions,
+        events: Option<&ExecutionEvents>,
+        input: Option<ExecutionInput>,
+    ) -> Result<SandboxExecutionResult, SandboxManagerError> {
+        let report = |phase| async move {
+            if let Some(events) = events {
+                let _ = events.send(ExecutionEvent::Phase(phase)).await;
+            }
+        };
+        report(ExecutionPhase::Queued).await;
+
+        let waiting = WaitingGuard::new(&self.waiting);
+        let wait_started = Instant::now();
+        // a deadline too far to represent never elapses
+        let queue_timeout = options
+            .queue_timeout
+            .map_or(Duration::MAX, Duration::from_secs_f64);
+        let _permit = timeout(queue_timeout, self.permits.acquire(options.priority))
+            .instrument(tracing::info_span!(
+                "claim_permit",
+                priority = options.priority.as_str()
+            ))
+            .await
+            .map_err(|_| SandboxManagerError::SemaphoreAcquireTimeout)?;
 
-        let claim_deadline = Duration::from_secs_f64(options.time_limit);
-        let sb = timeout(claim_deadline, async {
+        let semaphore_wait = wait_started.elapsed().as_secs_f64();
+
+        let wait_started = Instant::now();
+        let sb = timeout(queue_timeout, async {
             loop {
                 let sb_id = {
                     let mut rx = self.idle_rx.lock().await;
@@ -187,7 +380,8 @@ impl SandboxManager {
 
                 match self.sandboxes.get(&sb_id) {
                     Some(sb) => {
-                        if sb.status() == SandboxStatus::Idle {
+                        // atomic so that a concurrent `recycle_sandbox` cannot retire it mid-claim
+                        if sb.try_claim() {
                             return Ok::<Arc<Sandbox>, SandboxManagerError>(Arc::clone(&sb));
                         } else {
                             continue;
@@ -197,8 +391,16 @@ impl SandboxManager {
                 }
             }
         })
+        .instrument(tracing::info_span!("claim_sandbox"))
         .await
         .map_err(|_| SandboxManagerError::IdleQueueTimeout)??;
+        let idle_queue_wait = wait_started.elapsed().as_secs_f64();
+        drop(waiting);
+        self.max_wait_micros.fetch_max(
+            ((semaphore_wait + idle_queue_wait) * 1_000_000.0) as u64,
+            Ordering::AcqRel,
+        );
+        tracing::Span::current().record("sandbox_id", tracing::field::display(sb.id));
 
         let guard = SandboxGuard::arm(sb, &self.idle_tx);
         let sb = Arc::clone(guard.sandbox());
@@ -220,74 +422,138 @@ impl SandboxManager {
             }
         }
 
+        let mut compile_time = 0.0;
         if let Some(compile_options) = &options.compile_options {
-            let compile_cmd = match Command::new(&compile_options.compiler_path)
-                .args(&compile_options.args)
-                .envs(compile_options.env.iter().flatten())
-                .current_dir(sb.inner.get_path())
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .kill_on_drop(true)
-                .spawn()
-            {
-                Ok(child) => child,
-                Err(e) => {
-                    guard.complete_error();
-                    return Err(SandboxManagerError::CommandExecutionFailed(e.to_string()));
+            let artifact = self
+                .artifacts
+                .get()
+                .filter(|_| !options.bypass_artifact_cache)
+                .and_then(|store| Some((store, artifact_key(options)?)));
+            let restored = match &artifact {
+                Some((store, key)) => {
+                    let restored = store
+                        .restore(key, sb.inner.get_path())
+                        .instrument(tracing::info_span!("restore_artifact"))
+                        .await;
+                    metrics::counter!(
+                        ARTIFACT_CACHE_LOOKUPS_TOTAL,
+                        "result" => if restored { "hit" } else { "miss" }
+                    )
+                    .increment(1);
+                    restored
                 }
+                None => false,
             };
 
-            let compile_result = match timeout(
-                Duration::from_secs_f64(options.compile_timeout),
-                compile_cmd.wait_with_output(),
-            )
-            .await
-            {
-                Ok(Ok(output)) => output,
-                Ok(Err(e)) => {
-                    guard.complete_error();
-                    return Err(SandboxManagerError::CommandExecutionFailed(e.to_string()));
-                }
-                Err(_) => {
-                    guard.complete_error();
-                    return Err(SandboxManagerError::CompileTimeout);
-                }
-            };
+            if !restored {
+                report(ExecutionPhase::Compiling).await;
+                let compile_started = Instant::now();
+                let compile_cmd = match Command::new(&compile_options.compiler_path)
+                    .args(&compile_options.args)
+                    .envs(compile_options.env.iter().flatten())
+                    .current_dir(sb.inner.get_path())
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                {
+                    Ok(child) => child,
+                    Err(e) => {
+                        guard.complete_error();
+                        return Err(SandboxManagerError::CommandExecutionFailed(e.to_string()));
+                    }
+                };
 
-            if !compile_result.status.success() {
-                if let Err(e) = guard.complete_idle() {
-                    eprintln!(
-                        "Warning: Failed to return sandbox after compile error: {}",
-                        e
-                    );
-                }
+                let compile_result = match timeout(
+                    Duration::from_secs_f64(options.compile_timeout),
+                    wait_with_output(compile_cmd, events, None, ExecutionEvent::CompileOutput),
+                )
+                .instrument(tracing::info_span!("compile"))
+                .await
+                {
+                    Ok(Ok(output)) => output,
+                    Ok(Err(e)) => {
+                        guard.complete_error();
+                        return Err(SandboxManagerError::CommandExecutionFailed(e.to_string()));
+                    }
+                    Err(_) => {
+                        guard.complete_error();
+                        return Err(SandboxManagerError::CompileTimeout);
+                    }
+                };
+                compile_time = compile_started.elapsed().as_secs_f64();
+
+                if !compile_result.status.success() {
+                    match self.clear_working_dir(&sb).await {
+                        Ok(()) => {
+                            if let Err(e) = guard.complete_idle() {
+                                tracing::warn!(error = %e, "failed to return sandbox after compile error");
+                            }
+                        }
+                        // recycled rather than reused with files of this submission
+                        Err(_) => guard.complete_error(),
+                    }
 
-                drop(_permit);
+                    drop(_permit);
 
-                return Ok(SandboxExecutionResult {
-                    status: SandboxExecutionStatus::CompileError,
-                    stdout: String::from_utf8_lossy(&compile_result.stdout).to_string(),
-                    stderr: String::from_utf8_lossy(&compile_result.stderr).to_string(),
-                    time: 0.0,
-                    memory: 0.0,
-                });
+                    return Ok(SandboxExecutionResult {
+                        status: SandboxExecutionStatus::CompileError,
+                        stdout: compile_result.stdout,
+                        stderr: compile_result.stderr,
+                        time: 0.0,
+                        memory: 0.0,
+                        compile_time,
+                        semaphore_wait,
+                        idle_queue_wait,
+                        output_files: Vec::new(),
+                        exit_code: None,
+                    });
+                }
+
+                if let Some((store, key)) = artifact {
+                    store
+                        .save(key, sb.inner.get_path(), &compile_options.outputs)
+                        .await;
+                }
             }
         }
 
-        let result = self.tool.execute(&sb.inner, options).await;
+        for input_file in &options.input_files {
+            if let Err(e) = self
+                .tool
+                .add_file_wd(&sb.inner, &input_file.file_name, &input_file.file_content)
+                .await
+            {
+                guard.complete_error();
+                return Err(SandboxManagerError::FileOperationFailed(e.to_string()));
+            }
+        }
 
-        if let Some(additional_file_options) = &options.additional_file_options {
-            for additional_file_option in additional_file_options {
-                if let Err(e) = self
-                    .tool
-                    .remove_file_wd(&sb.inner, &additional_file_option.file_name)
-                    .await
-                {
-                    guard.complete_error();
-                    return Err(SandboxManagerError::FileOperationFailed(e.to_string()));
-                }
+        report(ExecutionPhase::Running).await;
+        let result = self
+            .tool
+            .execute(&sb.inner, options, events, input)
+            .instrument(tracing::info_span!("run"))
+            .await
+            .map(|mut result| {
+                result.compile_time = compile_time;
+                result.semaphore_wait = semaphore_wait;
+                result.idle_queue_wait = idle_queue_wait;
+                result
+            });
+
+        let result = match result {
+            Ok(mut result) => {
+                result.output_files = self.collect_output_files(&sb, options).await;
+                Ok(result)
             }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = self.clear_working_dir(&sb).await {
+            guard.complete_error();
+            return Err(e);
         }
 
         match &result {
@@ -308,6 +574,123 @@ impl SandboxManager {
         result.map_err(|e| SandboxManagerError::ExecutionFailed(e.to_string()))
     }
 
+    /// Read the output files the program was asked to write. A file that
+    /// cannot be read safely is reported as not written.
+    async fn collect_output_files(
+        &self,
+        sb: &Sandbox,
+        options: &SandboxExecutionOptions,
+    ) -> Vec<SandboxOutputFile> {
+        let mut files = Vec::with_capacity(options.output_files.len());
+        for file in &options.output_files {
+            // one byte past the cap tells whether the file was cut short
+            let content = match self
+                .tool
+                .read_file_wd(&sb.inner, &file.file_name, file.max_size + 1)
+                .await
+            {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::warn!(file = %file.file_name, error = %e, "failed to collect output file");
+                    None
+                }
+            };
+            let truncated = content
+                .as_ref()
+                .is_some_and(|content| content.len() as u64 > file.max_size);
+            files.push(SandboxOutputFile {
+                file_name: file.file_name.clone(),
+                content: content.map(|mut content| {
+                    content.truncate(file.max_size as usize);
+                    content
+                }),
+                truncated,
+            });
+        }
+        files
+    }
+
+    /// Empty the working directory of `sb` before it is reused: the
+    /// submitted files, the compiler outputs, the input and output files
+    /// and whatever else the program left, links included. Entries are
+    /// looked at without following links, so a link planted by one run can
+    /// neither be removed through nor redirect a file of the next run.
+    async fn clear_working_dir(&self, sb: &Sandbox) -> Result<(), SandboxManagerError> {
+        let failed = |e: std::io::Error| SandboxManagerError::FileOperationFailed(e.to_string());
+        let dir = sb.inner.get_path();
+        let mut entries = tokio::fs::read_dir(dir).await.map_err(failed)?;
+        while let Some(entry) = entries.next_entry().await.map_err(failed)? {
+            remove_entry(&entry.path()).await.map_err(failed)?;
+        }
+        Ok(())
+    }
+
+    /// Reuse compiled programs across executions with identical sources.
+    pub async fn enable_artifact_cache(
+        &self,
+        policy: ArtifactCachePolicy,
+    ) -> Result<(), SandboxManagerError> {
+        let store = ArtifactStore::open(policy)
+            .await
+            .map_err(|e| SandboxManagerError::FileOperationFailed(e.to_string()))?;
+        self.artifacts.set(store).map_err(|_| {
+            SandboxManagerError::InternalError("artifact cache already enabled".to_string())
+        })
+    }
+
+    /// Change the share of the pool reserved to each priority class.
+    pub fn set_priority_shares(&self, shares: PriorityShares) {
+        self.permits.set_shares(shares);
+    }
+
+    pub fn list_sandboxes(&self) -> Vec<SandboxInfo> {
+        let mut sandboxes: Vec<SandboxInfo> = self
+            .sandboxes
+            .iter()
+            .map(|entry| {
+                let sb = entry.value();
+                SandboxInfo {
+                    id: sb.id,
+                    status: sb.status(),
+                    running_since: sb.running_since(),
+                    box_id: sb.inner.get_box_id().to_string(),
+                    path: sb.inner.get_path().to_path_buf(),
+                }
+            })
+            .collect();
+        sandboxes.sort_by(|a, b| (a.box_id.len(), &a.box_id).cmp(&(b.box_id.len(), &b.box_id)));
+        sandboxes
+    }
+
+    /// Mark an idle or failed sandbox for replacement by the recovery loop.
+    /// Running sandboxes are left alone so the execution can finish.
+    pub fn recycle_sandbox(&self, id: Uuid) -> Result<(), SandboxManagerError> {
+        let sb = self
+            .sandboxes
+            .get(&id)
+            .map(|entry| Arc::clone(entry.value()))
+            .ok_or(SandboxManagerError::SandboxNotFound(id))?;
+
+        if sb.try_retire() || sb.status() == SandboxStatus::Error {
+            tracing::info!(sandbox_id = %id, "sandbox marked for recycling");
+            Ok(())
+        } else {
+            Err(SandboxManagerError::SandboxBusy(id))
+        }
+    }
+
+    /// Grow or shrink the pool toward `size` sandboxes and return the resulting size.
+    /// Growth is capped by `MAX_PREWARMED_SANDBOXES`; only idle sandboxes are removed.
+    pub async fn resize(&self, size: usize) -> Result<usize, SandboxManagerError> {
+        let current = self.available_sandboxes_count().await;
+        if size > current {
+            self.add_new_prewarmed_sandbox(size - current).await?;
+        } else if size < current {
+            self.remove_idle_sandbox(current - size).await?;
+        }
+        Ok(self.available_sandboxes_count().await)
+    }
+
     pub async fn available_sandboxes_count(&self) -> usize {
         self.sandboxes.len()
     }
@@ -335,61 +718,84 @@ impl SandboxManager {
 
     pub async fn add_new_prewarmed_sandbox(&self, num: usize) -> Result<(), SandboxManagerError> {
         let target_num = num.min(
-            MAX_PREWARMED_SANDBOXES.get_or_init(|| 1000) - self.available_sandboxes_count().await,
+            MAX_PREWARMED_SANDBOXES
+                .get_or_init(|| 1000)
+                .saturating_sub(self.available_sandboxes_count().await),
         );
 
-        self.replenish_destroyed(target_num).await?;
-        self.permits.add_permits(target_num);
-
-        Ok(())
+        // one permit per sandbox actually created, even when some fail
+        self.enqueue_new_sandboxes(target_num, || self.permits.add_permits(1))
+            .await
     }
 
     /// Create `num` fresh sandboxes and enqueue them as idle, **without** touching
     /// the semaphore permit count. Used by the recovery loop when replacing a
     /// sandbox whose permit was already released by the caller's RAII drop.
     async fn replenish_destroyed(&self, num: usize) -> Result<(), SandboxManagerError> {
-        for _ in 0..num {
-            let sb = create_sandbox(&self.tool)
-                .await
-                .map_err(|e| SandboxManagerError::SandboxCreationFailed(e.to_string()))?;
+        self.enqueue_new_sandboxes(num, || {}).await
+    }
+
+    /// Create `num` sandboxes and enqueue the ones that could be created,
+    /// calling `on_enqueued` for each. Returns the first creation error.
+    async fn enqueue_new_sandboxes(
+        &self,
+        num: usize,
+        on_enqueued: impl Fn(),
+    ) -> Result<(), SandboxManagerError> {
+        let mut result = Ok(());
+        for sb in create_sandboxes(&self.tool, num).await {
+            let sb = match sb {
+                Ok(sb) => sb,
+                Err(e) => {
+                    result = result.and(Err(e));
+                    continue;
+                }
+            };
             self.sandboxes.insert(sb.id, Arc::clone(&sb));
             self.idle_tx
                 .send(sb.id)
                 .map_err(|e| SandboxManagerError::QueueFull(e.to_string()))?;
+            on_enqueued();
         }
-        Ok(())
+        result
     }
 
     pub async fn destroy_sandbox(&self, id: Uuid) -> Result<(), SandboxManagerError> {
         if let Some((_, sb)) = self.sandboxes.remove(&id) {
-            self.tool
-                .destroy_inner(&sb.inner)
-                .await
-                .map_err(|e| SandboxManagerError::SandboxDestructionFailed(e.to_string()))?;
+            self.tool.destroy_inner(&sb.inner).await.map_err(|e| {
+                metrics::counter!(SANDBOX_DESTROY_FAILURES_TOTAL).increment(1);
+                SandboxManagerError::SandboxDestructionFailed(e.to_string())
+            })?;
         }
         Ok(())
     }
 
+    /// Destroy up to `num` idle sandboxes, forgetting one permit for each.
+    /// Sandboxes that are running or about to be recycled are left alone.
     pub async fn remove_idle_sandbox(&self, num: usize) -> Result<(), SandboxManagerError> {
-        let target_num = num.min(self.idle_sandboxes_count().await);
-
-        for _ in 0..target_num {
-            let sb_id = {
+        for _ in 0..num {
+            let sb = {
                 let mut rx = self.idle_rx.lock().await;
-                rx.recv()
-                    .await
-                    .ok_or(SandboxManagerError::NoSandboxAvailable)?
+                loop {
+                    // never waits: the idle count may be stale by now
+                    let Ok(sb_id) = rx.try_recv() else {
+                        return Ok(());
+                    };
+                    let Some(sb) = self.sandboxes.get(&sb_id) else {
+                        continue;
+                    };
+                    // claimed so that no execution picks it up meanwhile
+                    if sb.try_claim() {
+                        break Arc::clone(&sb);
+                    }
+                }
             };
-
-            if self.sandboxes.contains_key(&sb_id) {
-                self.destroy_sandbox(sb_id).await?;
-            } else {
-                continue;
-            }
+            // forgotten before the first await, so that cancelling cannot
+            // leave a permit behind for a sandbox that is gone
+            self.permits.forget_permits(1);
+            self.destroy_sandbox(sb.id).await?;
         }
 
-        self.permits.forget_permits(target_num);
-
         Ok(())
     }
 
@@ -415,7 +821,9 @@ impl SandboxManager {
                     .map(|s| s > threshold)
                     .unwrap_or(false)
             {
+                tracing::warn!(sandbox_id = %sb.id, "reaping sandbox stuck in running state");
                 sb.set_error();
+                metrics::counter!(SANDBOX_REAPED_TOTAL, "reason" => "stuck").increment(1);
             }
         }
 
@@ -426,17 +834,78 @@ impl SandboxManager {
             .filter(|entry| entry.value().status() == SandboxStatus::Error)
             .map(|entry| *entry.key())
             .collect();
+        metrics::counter!(SANDBOX_REAPED_TOTAL, "reason" => "error")
+            .increment(error_sandbox_ids.len() as u64);
 
         for id in &error_sandbox_ids {
             if let Err(e) = self.destroy_sandbox(*id).await {
-                eprintln!("Warning: failed to destroy error sandbox {}: {}", id, e);
+                tracing::warn!(sandbox_id = %id, error = %e, "failed to destroy error sandbox");
             }
         }
 
         // Replenish without touching the semaphore: the permit for each error
         // sandbox was already released by the caller's RAII drop.
         if let Err(e) = self.replenish_destroyed(error_sandbox_ids.len()).await {
-            eprintln!("Warning: failed to replenish destroyed sandboxes: {}", e);
+            tracing::warn!(error = %e, "failed to replenish destroyed sandboxes");
+        }
+
+        self.autoscale().await;
+    }
+
+    /// Grow the pool under pressure and shrink idle sandboxes after a cool-down.
+    async fn autoscale(&self) {
+        let Some(policy) = &self.autoscale else {
+            return;
+        };
+
+        let calm_for = {
+            let Ok(mut state) = self.autoscale_state.lock() else {
+                return;
+            };
+            if state.evaluated_at.elapsed() < AUTOSCALE_INTERVAL {
+                return;
+            }
+            state.evaluated_at = Instant::now();
+            state.active_at.elapsed()
+        };
+
+        let pressure = PoolPressure {
+            total: self.available_sandboxes_count().await,
+            idle: self.idle_sandboxes_count().await,
+            waiting: self.waiting.load(Ordering::Acquire),
+            max_wait: Duration::from_micros(self.max_wait_micros.swap(0, Ordering::AcqRel)),
+            calm_for,
+        };
+
+        let action = policy.decide(&pressure);
+        let result = match action {
+            ScaleAction::Grow(num) => self.add_new_prewarmed_sandbox(num).await,
+            ScaleAction::Shrink(num) => self.remove_idle_sandbox(num).await,
+            ScaleAction::Hold => {
+                // saturated at max_sandboxes: postpone shrinking until the pressure is gone
+                if policy.is_under_pressure(&pressure) {
+                    self.mark_autoscale_active();
+                }
+                return;
+            }
+        };
+
+        self.mark_autoscale_active();
+        match result {
+            Ok(()) => tracing::info!(
+                ?action,
+                waiting = pressure.waiting,
+                max_wait_ms = pressure.max_wait.as_millis() as u64,
+                sandboxes = self.sandboxes.len(),
+                "sandbox pool resized"
+            ),
+            Err(e) => tracing::warn!(?action, error = %e, "failed to resize sandbox pool"),
+        }
+    }
+
+    fn mark_autoscale_active(&self) {
+        if let Ok(mut state) = self.autoscale_state.lock() {
+            state.active_at = Instant::now();
         }
     }
 
@@ -458,11 +927,107 @@ impl SandboxManager {
 mod tests {
     use std::sync::Arc;
     use std::sync::atomic::Ordering;
+    use std::time::Duration;
 
     use tokio::sync::mpsc;
+    use tokio::time::sleep;
+    use uuid::Uuid;
+
+    use super::{AutoscalePolicy, PoolPressure, SandboxGuard, SandboxManager, ScaleAction};
+    use crate::artifacts::ArtifactCachePolicy;
+    use crate::errors::SandboxManagerError;
+    use crate::permits::Priority;
+    use crate::sandbox::{
+        CompileOptions, SandboxAdditionalFileOptions, SandboxExecutionOptions,
+        SandboxExecutionStatus, SandboxStatus,
+    };
+
+    /// Options running `script` with `sh` in the box, next to `files`.
+    fn shell_options(script: &str, files: &[&str]) -> SandboxExecutionOptions {
+        let file = |file_name: &&str| SandboxAdditionalFileOptions {
+            file_name: file_name.to_string(),
+            file_content: b"submitted".to_vec(),
+        };
+        SandboxExecutionOptions {
+            additional_file_options: Some(files.iter().map(file).collect()),
+            compile_options: None,
+            additional_directory_options: None,
+            binary_path: "/bin/sh".into(),
+            args: vec!["-c".to_string(), script.to_string()],
+            stdin: Vec::new(),
+            time_limit: 5.0,
+            wall_time_limit: 10.0,
+            memory_limit: 262144.0,
+            compile_timeout: 5.0,
+            queue_timeout: Some(5.0),
+            priority: Priority::Normal,
+            output_files: Vec::new(),
+            input_files: Vec::new(),
+            bypass_artifact_cache: false,
+        }
+    }
+
+    fn outside_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("pecan-outside-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("passwd"), b"kept").unwrap();
+        dir
+    }
+
+    fn policy() -> AutoscalePolicy {
+        AutoscalePolicy {
+            min_sandboxes: 2,
+            max_sandboxes: 8,
+            step: 4,
+            scale_up_wait: Duration::from_millis(100),
+            scale_up_waiting: 1,
+            scale_down_after: Duration::from_secs(60),
+        }
+    }
+
+    fn pressure(total: usize, idle: usize, waiting: usize, max_wait_ms: u64) -> PoolPressure {
+        PoolPressure {
+            total,
+            idle,
+            waiting,
+            max_wait: Duration::from_millis(max_wait_ms),
+            calm_for: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn autoscale_grows_under_pressure_up_to_max() {
+        let policy = policy();
+
+        assert_eq!(policy.decide(&pressure(0, 0, 0, 0)), ScaleAction::Grow(2));
+        assert_eq!(policy.decide(&pressure(2, 0, 3, 0)), ScaleAction::Grow(4));
+        assert_eq!(policy.decide(&pressure(2, 0, 0, 250)), ScaleAction::Grow(4));
+        assert_eq!(policy.decide(&pressure(6, 0, 1, 0)), ScaleAction::Grow(2));
+        assert_eq!(policy.decide(&pressure(8, 0, 5, 900)), ScaleAction::Hold);
+        assert_eq!(policy.decide(&pressure(4, 4, 0, 10)), ScaleAction::Hold);
+    }
 
-    use super::{SandboxGuard, SandboxManager};
-    use crate::sandbox::SandboxStatus;
+    #[test]
+    fn autoscale_shrinks_idle_sandboxes_after_cool_down() {
+        let policy = policy();
+        let mut calm = pressure(8, 3, 0, 0);
+
+        calm.calm_for = Duration::from_secs(30);
+        assert_eq!(policy.decide(&calm), ScaleAction::Hold);
+
+        calm.calm_for = Duration::from_secs(61);
+        assert_eq!(policy.decide(&calm), ScaleAction::Shrink(3));
+
+        calm.idle = 8;
+        assert_eq!(policy.decide(&calm), ScaleAction::Shrink(4));
+
+        calm.total = 3;
+        calm.idle = 3;
+        assert_eq!(policy.decide(&calm), ScaleAction::Shrink(1));
+
+        calm.total = 2;
+        assert_eq!(policy.decide(&calm), ScaleAction::Hold);
+    }
 
     #[tokio::test]
     async fn manager_starts_empty_with_zero_prewarm() {
@@ -606,4 +1171,200 @@ mod tests {
         assert_eq!(manager.error_sandboxes_count().await, 0);
         assert!(!manager.sandboxes.contains_key(&id));
     }
+
+    #[tokio::test]
+    async fn recycle_retires_idle_sandboxes_only() {
+        let manager = SandboxManager::new(2).await.expect("manager init");
+        let ids = manager.list_ids();
+
+        manager.recycle_sandbox(ids[0]).expect("recycle idle");
+        assert_eq!(
+            manager.sandboxes.get(&ids[0]).unwrap().status(),
+            SandboxStatus::Error
+        );
+
+        manager.sandboxes.get(&ids[1]).unwrap().set_running();
+        assert!(matches!(
+            manager.recycle_sandbox(ids[1]),
+            Err(SandboxManagerError::SandboxBusy(_))
+        ));
+        assert!(matches!(
+            manager.recycle_sandbox(Uuid::new_v4()),
+            Err(SandboxManagerError::SandboxNotFound(_))
+        ));
+
+        manager._loop().await;
+        assert_eq!(manager.available_sandboxes_count().await, 2);
+        assert!(!manager.sandboxes.contains_key(&ids[0]));
+    }
+
+    #[tokio::test]
+    async fn resize_grows_and_shrinks_pool() {
+        let manager = SandboxManager::new(1).await.expect("manager init");
+
+        assert_eq!(manager.resize(3).await.expect("grow"), 3);
+        assert_eq!(manager.permits.available_permits(), 3);
+        assert_eq!(manager.list_sandboxes().len(), 3);
+
+        assert_eq!(manager.resize(1).await.expect("shrink"), 1);
+        assert_eq!(manager.permits.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn shrinking_leaves_running_sandboxes_and_their_permits_alone() {
+        let manager = SandboxManager::new(3).await.expect("manager init");
+        let running: Vec<_> = (0..2)
+            .map(|_| {
+                let manager = Arc::clone(&manager);
+                tokio::spawn(async move {
+                    manager
+                        .execute_via_manager(&shell_options("sleep 1", &[]))
+                        .await
+                })
+            })
+            .collect();
+        while manager.running_sandboxes_count().await < 2 {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        // only the idle one can go
+        assert_eq!(manager.resize(0).await.expect("shrink"), 2);
+        assert_eq!(manager.permits.available_permits(), 0);
+        for result in running {
+            let result = result.await.unwrap().expect("execute");
+            assert_eq!(result.status, SandboxExecutionStatus::Success);
+        }
+        assert_eq!(manager.permits.available_permits(), 2);
+
+        // the queued id of a recycled sandbox is neither removed nor counted
+        let recycled = manager.list_sandboxes()[0].id;
+        manager.recycle_sandbox(recycled).expect("recycle");
+        manager.remove_idle_sandbox(2).await.expect("shrink");
+        assert_eq!(manager.list_ids(), vec![recycled]);
+        assert_eq!(manager.permits.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn queued_executions_wait_past_their_time_limit_unless_bounded() {
+        let manager = SandboxManager::new(1).await.expect("manager init");
+        let running = {
+            let manager = Arc::clone(&manager);
+            tokio::spawn(async move {
+                manager
+                    .execute_via_manager(&shell_options("sleep 1", &[]))
+                    .await
+            })
+        };
+        while manager.running_sandboxes_count().await == 0 {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        let mut bounded = shell_options("true", &[]);
+        bounded.time_limit = 0.2;
+        bounded.queue_timeout = Some(0.2);
+        assert!(matches!(
+            manager.execute_via_manager(&bounded).await,
+            Err(SandboxManagerError::SemaphoreAcquireTimeout)
+        ));
+
+        bounded.queue_timeout = None;
+        let result = manager
+            .execute_via_manager(&bounded)
+            .await
+            .expect("execute");
+        assert_eq!(result.status, SandboxExecutionStatus::Success);
+        running.await.unwrap().expect("execute");
+    }
+
+    #[tokio::test]
+    async fn bypassing_the_artifact_cache_always_compiles() {
+        let manager = SandboxManager::new(1).await.expect("manager init");
+        let store = outside_dir();
+        manager
+            .enable_artifact_cache(ArtifactCachePolicy {
+                dir: store.join("artifacts"),
+                max_bytes: 1 << 20,
+            })
+            .await
+            .expect("artifact cache");
+        let compiles = store.join("compiles");
+        let mut options = shell_options("cat out", &["main.c"]);
+        options.compile_options = Some(CompileOptions {
+            compiler_path: "/bin/sh".into(),
+            env: None,
+            args: vec![
+                "-c".to_string(),
+                format!("echo >> {} && echo built > out", compiles.display()),
+            ],
+            toolchain: "sh".to_string(),
+            outputs: vec!["out".to_string()],
+        });
+        let compiled = || std::fs::read_to_string(&compiles).unwrap().lines().count();
+
+        for bypass in [false, false, true] {
+            options.bypass_artifact_cache = bypass;
+            let result = manager
+                .execute_via_manager(&options)
+                .await
+                .expect("execute");
+            assert_eq!(result.stdout, b"built\n");
+        }
+        // the second run restored the program, the third compiled it again
+        assert_eq!(compiled(), 2);
+        std::fs::remove_dir_all(store).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cleanup_does_not_follow_a_swapped_project_directory() {
+        let manager = SandboxManager::new(1).await.expect("manager init");
+        let outside = outside_dir();
+        let script = format!("rm -rf etc && ln -s {} etc", outside.display());
+
+        let result = manager
+            .execute_via_manager(&shell_options(&script, &["main.c", "etc/passwd"]))
+            .await
+            .expect("execute");
+        assert_eq!(result.status, SandboxExecutionStatus::Success);
+
+        assert_eq!(std::fs::read(outside.join("passwd")).unwrap(), b"kept");
+        let sb = manager.list_sandboxes().remove(0);
+        assert_eq!(sb.status, SandboxStatus::Idle);
+        assert!(std::fs::symlink_metadata(sb.path.join("etc")).is_err());
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+
+    #[tokio::test]
+    async fn links_planted_by_one_run_do_not_redirect_the_next() {
+        let manager = SandboxManager::new(1).await.expect("manager init");
+        let outside = outside_dir();
+        let script = format!(
+            "ln -s {0}/passwd answer.txt && ln -s {0} evil",
+            outside.display()
+        );
+
+        let planted = manager
+            .execute_via_manager(&shell_options(&script, &["main.c"]))
+            .await
+            .expect("execute");
+        assert_eq!(planted.status, SandboxExecutionStatus::Success);
+        let sb = manager.list_sandboxes().remove(0);
+        assert_eq!(std::fs::read_dir(&sb.path).unwrap().count(), 0);
+
+        let mut options = shell_options("cat answer.txt evil/passwd", &["main.c"]);
+        options.input_files = ["answer.txt", "evil/passwd"]
+            .iter()
+            .map(|file_name| SandboxAdditionalFileOptions {
+                file_name: file_name.to_string(),
+                file_content: b"expected".to_vec(),
+            })
+            .collect();
+        let result = manager
+            .execute_via_manager(&options)
+            .await
+            .expect("execute");
+        assert_eq!(result.status, SandboxExecutionStatus::Success);
+        assert_eq!(result.stdout, b"expectedexpected");
+        assert_eq!(std::fs::read(outside.join("passwd")).unwrap(), b"kept");
+        std::fs::remove_dir_all(outside).unwrap();
+    }
 }
@@ -0,0 +1,323 @@
+//! Execution permits handed out by priority class.
+//!
+//! Every class is guaranteed a reserved share of the pool while classes
+//! compete; permits beyond those shares go to the most urgent waiting class.
+//! Permits never sit idle while anyone waits, so with a single class in use the
+//! pool behaves like a plain FIFO semaphore.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// live contest traffic
+    Realtime,
+    #[default]
+    Normal,
+    /// rejudges and other batch work
+    Bulk,
+}
+
+impl Priority {
+    /// Most urgent first.
+    pub const ALL: [Priority; 3] = [Priority::Realtime, Priority::Normal, Priority::Bulk];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Realtime => "realtime",
+            Priority::Normal => "normal",
+            Priority::Bulk => "bulk",
+        }
+    }
+
+    /// Position in `Priority::ALL`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Fraction of the pool guaranteed to each class while classes compete.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriorityShares {
+    pub realtime: f64,
+    pub normal: f64,
+    pub bulk: f64,
+}
+
+impl Default for PriorityShares {
+    fn default() -> Self {
+        Self {
+            realtime: 0.2,
+            normal: 0.2,
+            bulk: 0.1,
+        }
+    }
+}
+
+impl PriorityShares {
+    fn get(&self, priority: Priority) -> f64 {
+        match priority {
+            Priority::Realtime => self.realtime,
+            Priority::Normal => self.normal,
+            Priority::Bulk => self.bulk,
+        }
+    }
+}
+
+struct Waiter {
+    id: u64,
+    tx: oneshot::Sender<()>,
+}
+
+struct State {
+    total: usize,
+    in_use: [usize; 3],
+    waiters: [VecDeque<Waiter>; 3],
+    shares: PriorityShares,
+    next_id: u64,
+}
+
+impl State {
+    fn free(&self) -> usize {
+        self.total.saturating_sub(self.in_use.iter().sum())
+    }
+
+    /// Permits guaranteed to `priority`; a non-zero share is worth at least one.
+    fn reserved(&self, priority: Priority) -> usize {
+        let share = self.shares.get(priority);
+        if share <= 0.0 {
+            0
+        } else {
+            ((self.total as f64 * share) as usize).max(1)
+        }
+    }
+
+    /// Classes below their reserved share go first, then strict priority.
+    fn next_class(&self) -> Option<usize> {
+        let waiting = |p: &Priority| !self.waiters[p.index()].is_empty();
+        Priority::ALL
+            .iter()
+            .filter(|p| waiting(p))
+            .find(|p| self.in_use[p.index()] < self.reserved(**p))
+            .or_else(|| Priority::ALL.iter().find(|p| waiting(p)))
+            .map(|p| p.index())
+    }
+
+    fn dispatch(&mut self) {
+        while self.free() > 0 {
+            let Some(class) = self.next_class() else {
+                break;
+            };
+            let Some(waiter) = self.waiters[class].pop_front() else {
+                break;
+            };
+            self.in_use[class] += 1;
+            if waiter.tx.send(()).is_err() {
+                self.in_use[class] -= 1;
+            }
+        }
+    }
+}
+
+pub struct PriorityPermits {
+    state: Mutex<State>,
+}
+
+/// Held for the duration of one execution; released on drop.
+pub struct PriorityPermit<'a> {
+    permits: &'a PriorityPermits,
+    priority: Priority,
+}
+
+impl Drop for PriorityPermit<'_> {
+    fn drop(&mut self) {
+        self.permits.release(self.priority);
+    }
+}
+
+/// Withdraws an unfinished acquisition, or gives back a permit that was
+/// granted after the caller stopped waiting (e.g. on timeout).
+struct PendingAcquire<'a> {
+    permits: &'a PriorityPermits,
+    priority: Priority,
+    id: u64,
+    rx: oneshot::Receiver<()>,
+    done: bool,
+}
+
+impl Drop for PendingAcquire<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut state = self.permits.lock();
+        let waiters = &mut state.waiters[self.priority.index()];
+        if let Some(pos) = waiters.iter().position(|w| w.id == self.id) {
+            waiters.remove(pos);
+        } else if self.rx.try_recv().is_ok() {
+            state.in_use[self.priority.index()] -= 1;
+            state.dispatch();
+        }
+    }
+}
+
+impl PriorityPermits {
+    pub fn new(permits: usize, shares: PriorityShares) -> Self {
+        Self {
+            state: Mutex::new(State {
+                total: permits,
+                in_use: [0; 3],
+                waiters: Default::default(),
+                shares,
+                next_id: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        // the state stays consistent across a panic, so keep using it
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub async fn acquire(&self, priority: Priority) -> PriorityPermit<'_> {
+        let (tx, rx) = oneshot::channel();
+        let id = {
+            let mut state = self.lock();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters[priority.index()].push_back(Waiter { id, tx });
+            state.dispatch();
+            id
+        };
+
+        let mut pending = PendingAcquire {
+            permits: self,
+            priority,
+            id,
+            rx,
+            done: false,
+        };
+        // the sender only goes away through `send`, so this resolves once granted
+        let _ = (&mut pending.rx).await;
+        pending.done = true;
+
+        PriorityPermit {
+            permits: self,
+            priority,
+        }
+    }
+
+    fn release(&self, priority: Priority) {
+        let mut state = self.lock();
+        state.in_use[priority.index()] -= 1;
+        state.dispatch();
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.lock().free()
+    }
+
+    /// Permits currently held by `priority`.
+    pub fn in_use(&self, priority: Priority) -> usize {
+        self.lock().in_use[priority.index()]
+    }
+
+    pub fn add_permits(&self, num: usize) {
+        let mut state = self.lock();
+        state.total += num;
+        state.dispatch();
+    }
+
+    pub fn forget_permits(&self, num: usize) {
+        let mut state = self.lock();
+        state.total = state.total.saturating_sub(num);
+    }
+
+    pub fn set_shares(&self, shares: PriorityShares) {
+        let mut state = self.lock();
+        state.shares = shares;
+        state.dispatch();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::time::{sleep, timeout};
+
+    use super::{Priority, PriorityPermits, PriorityShares};
+
+    fn shares(realtime: f64, normal: f64, bulk: f64) -> PriorityShares {
+        PriorityShares {
+            realtime,
+            normal,
+            bulk,
+        }
+    }
+
+    /// Start an acquisition that holds its permit until the returned sender fires.
+    fn hold(
+        permits: &Arc<PriorityPermits>,
+        priority: Priority,
+    ) -> tokio::sync::oneshot::Sender<()> {
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let permits = Arc::clone(permits);
+        tokio::spawn(async move {
+            let _permit = permits.acquire(priority).await;
+            let _ = rx.await;
+        });
+        tx
+    }
+
+    #[tokio::test]
+    async fn higher_priority_waiters_are_served_first() {
+        let permits = Arc::new(PriorityPermits::new(2, shares(0.0, 0.0, 0.0)));
+        let first = hold(&permits, Priority::Bulk);
+        let _second = hold(&permits, Priority::Bulk);
+        sleep(Duration::from_millis(10)).await;
+
+        let _bulk = hold(&permits, Priority::Bulk);
+        let _realtime = hold(&permits, Priority::Realtime);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(permits.available_permits(), 0);
+
+        drop(first);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(permits.in_use(Priority::Realtime), 1);
+        assert_eq!(permits.in_use(Priority::Bulk), 1);
+    }
+
+    #[tokio::test]
+    async fn reserved_share_keeps_low_priority_from_starving() {
+        let permits = Arc::new(PriorityPermits::new(4, shares(0.0, 0.0, 0.25)));
+        let held: Vec<_> = (0..4).map(|_| hold(&permits, Priority::Realtime)).collect();
+        sleep(Duration::from_millis(10)).await;
+
+        let _realtime = hold(&permits, Priority::Realtime);
+        let _bulk = hold(&permits, Priority::Bulk);
+        sleep(Duration::from_millis(10)).await;
+
+        drop(held);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(permits.in_use(Priority::Bulk), 1);
+        assert_eq!(permits.in_use(Priority::Realtime), 1);
+    }
+
+    #[tokio::test]
+    async fn cancelled_acquire_does_not_leak_permits() {
+        let permits = PriorityPermits::new(1, PriorityShares::default());
+        let held = permits.acquire(Priority::Normal).await;
+
+        let waited = timeout(Duration::from_millis(10), permits.acquire(Priority::Normal)).await;
+        assert!(waited.is_err());
+
+        drop(held);
+        assert_eq!(permits.available_permits(), 1);
+        let _again = permits.acquire(Priority::Bulk).await;
+        assert_eq!(permits.available_permits(), 0);
+    }
+}
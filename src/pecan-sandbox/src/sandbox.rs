@@ -7,11 +7,13 @@
 
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
+use crate::permits::Priority;
 use crate::tools::SandboxInner;
 
 fn now_secs() -> u64 {
@@ -41,6 +43,45 @@ pub enum SandboxExecutionStatus {
     MemoryLimitExceeded,
 }
 
+/// Stage an execution has reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionPhase {
+    /// waiting for an execution permit or an idle sandbox
+    Queued,
+    Compiling,
+    Running,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Output as it is produced, split at arbitrary points but never inside a
+/// UTF-8 character, so that text output can be decoded chunk by chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputChunk {
+    pub stream: OutputStream,
+    pub data: Vec<u8>,
+}
+
+/// Progress reported while an execution runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionEvent {
+    Phase(ExecutionPhase),
+    CompileOutput(OutputChunk),
+    Output(OutputChunk),
+}
+
+/// Receives the events of one execution. Sending waits for room, so a slow
+/// reader slows the program down rather than buffering its output.
+pub type ExecutionEvents = mpsc::Sender<ExecutionEvent>;
+
+/// Standard input of an interactive execution, written to the program as it
+/// arrives. The program sees the end of its input once every sender is gone.
+pub type ExecutionInput = mpsc::UnboundedReceiver<Vec<u8>>;
+
 /// The sandbox is identified by a single UUID.
 pub struct Sandbox {
     pub id: Uuid,
@@ -85,6 +126,44 @@ impl Sandbox {
         self.status.store(STATUS_ERROR, Ordering::Release);
     }
 
+    /// Atomically move an idle sandbox to `Running`. Returns false in any other state.
+    pub fn try_claim(&self) -> bool {
+        let claimed = self
+            .status
+            .compare_exchange(
+                STATUS_IDLE,
+                STATUS_RUNNING,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok();
+        if claimed {
+            self.running_since.store(now_secs(), Ordering::Release);
+        }
+        claimed
+    }
+
+    /// Atomically move an idle sandbox to `Error` so that the recovery loop recycles it.
+    /// Returns false in any other state.
+    pub fn try_retire(&self) -> bool {
+        self.status
+            .compare_exchange(
+                STATUS_IDLE,
+                STATUS_ERROR,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+
+    /// UNIX epoch seconds when the sandbox transitioned to `Running`, `None` when not running.
+    pub fn running_since(&self) -> Option<u64> {
+        match self.running_since.load(Ordering::Acquire) {
+            0 => None,
+            started => Some(started),
+        }
+    }
+
     /// Returns how long this sandbox has been in `Running`, or `None` if not running.
     pub fn running_for_secs(&self, now: u64) -> Option<u64> {
         let started = self.running_since.load(Ordering::Acquire);
@@ -99,8 +178,19 @@ impl Sandbox {
 /// Files to be added to the sandbox working directory before the execution.
 #[derive(Debug, Clone)]
 pub struct SandboxAdditionalFileOptions {
+    /// path relative to the working directory, `/`-separated
     pub file_name: String,
-    pub file_content: String,
+    pub file_content: Vec<u8>,
+}
+
+/// File the program is expected to write to the working directory, collected
+/// once it has run.
+#[derive(Debug, Clone)]
+pub struct SandboxOutputFileOptions {
+    /// path relative to the working directory, `/`-separated
+    pub file_name: String,
+    /// bytes read at most; the rest of a larger file is dropped
+    pub max_size: u64,
 }
 
 /// Options for compiling the program before the execution.
@@ -112,6 +202,10 @@ pub struct CompileOptions {
     pub env: Option<HashMap<String, String>>,
     /// arguments to pass to the compiler
     pub args: Vec<String>,
+    /// toolchain name and version, part of the compiled artifact key
+    pub toolchain: String,
+    /// files the compiler produces for the run; `*.ext` matches by suffix
+    pub outputs: Vec<String>,
 }
 
 /// Options for mounting additional directories
@@ -131,8 +225,8 @@ pub struct SandboxExecutionOptions {
     pub binary_path: PathBuf,
     /// arguments to pass to the binary
     pub args: Vec<String>,
-    /// standard input to the binary
-    pub stdin: String,
+    /// standard input to the binary, unless an `ExecutionInput` is given
+    pub stdin: Vec<u8>,
     /// time limit in seconds (CPU time)
     pub time_limit: f64,
     /// wall-clock time limit in seconds for the executed binary inside the sandbox
@@ -141,14 +235,48 @@ pub struct SandboxExecutionOptions {
     pub memory_limit: f64,
     /// timeout for the compile phase in seconds
     pub compile_timeout: f64,
+    /// seconds to wait for an execution permit, and again for an idle
+    /// sandbox; `None` waits as long as it takes
+    pub queue_timeout: Option<f64>,
+    /// scheduling class used to claim a sandbox
+    pub priority: Priority,
+    /// files collected from the working directory after the run
+    pub output_files: Vec<SandboxOutputFileOptions>,
+    /// files added once the program is compiled, such as data it reads; unlike
+    /// `additional_file_options` they are not part of the compiled program
+    pub input_files: Vec<SandboxAdditionalFileOptions>,
+    /// always run the compiler, neither restoring nor storing the program in
+    /// the artifact cache
+    pub bypass_artifact_cache: bool,
 }
 
 /// Result of the sandbox execution.
 #[derive(Debug, Clone)]
 pub struct SandboxExecutionResult {
     pub status: SandboxExecutionStatus,
-    pub stdout: String,
-    pub stderr: String,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
     pub time: f64,
     pub memory: f64,
+    /// wall-clock seconds spent in the compile phase, zero for interpreted languages
+    pub compile_time: f64,
+    /// seconds spent waiting for an execution permit
+    pub semaphore_wait: f64,
+    /// seconds spent waiting for an idle sandbox once a permit was held
+    pub idle_queue_wait: f64,
+    /// the requested output files in order, empty when the program did not run
+    pub output_files: Vec<SandboxOutputFile>,
+    /// exit status of a program that exited on its own, `None` when it was
+    /// killed or did not run
+    pub exit_code: Option<i32>,
+}
+
+/// Output file collected after the run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxOutputFile {
+    pub file_name: String,
+    /// `None` when the program did not write the file
+    pub content: Option<Vec<u8>>,
+    /// the file was larger than `max_size` and only its start was read
+    pub truncated: bool,
 }
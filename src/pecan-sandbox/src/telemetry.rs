@@ -0,0 +1,82 @@
+//! Names of the metrics recorded by the sandbox manager.
+//!
+//! Values are emitted through the `metrics` facade and are no-ops until the
+//! embedding application installs a recorder.
+
+/// sandboxes reaped by the recovery loop, labelled by `reason` (`stuck` or `error`)
+pub const SANDBOX_REAPED_TOTAL: &str = "pecan_sandbox_reaped_total";
+/// sandboxes that could not be created
+pub const SANDBOX_CREATE_FAILURES_TOTAL: &str = "pecan_sandbox_create_failures_total";
+/// sandboxes that could not be destroyed
+pub const SANDBOX_DESTROY_FAILURES_TOTAL: &str = "pecan_sandbox_destroy_failures_total";
+/// compiled artifact lookups, labelled by `result` (`hit` or `miss`)
+pub const ARTIFACT_CACHE_LOOKUPS_TOTAL: &str = "pecan_artifact_cache_lookups_total";
+
+pub fn describe_metrics() {
+    metrics::describe_counter!(
+        SANDBOX_REAPED_TOTAL,
+        "Sandboxes reaped by the recovery loop"
+    );
+    metrics::describe_counter!(SANDBOX_CREATE_FAILURES_TOTAL, "Sandbox creation failures");
+    metrics::describe_counter!(
+        SANDBOX_DESTROY_FAILURES_TOTAL,
+        "Sandbox destruction failures"
+    );
+    metrics::describe_counter!(
+        ARTIFACT_CACHE_LOOKUPS_TOTAL,
+        "Compiled artifact lookups by outcome"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use tokio::time::{sleep, timeout};
+    use tokio_util::sync::CancellationToken;
+
+    use super::SANDBOX_REAPED_TOTAL;
+    use crate::manager::SandboxManager;
+
+    #[tokio::test]
+    async fn recycled_sandboxes_are_counted_as_reaped() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        // the test runtime polls everything on this thread
+        let _recorder = metrics::set_default_local_recorder(&recorder);
+
+        let manager = SandboxManager::new(2).await.expect("manager init");
+        let recycled = manager.list_ids()[0];
+        manager.recycle_sandbox(recycled).expect("recycle");
+        let replaced = async {
+            while manager.list_ids().contains(&recycled) || manager.list_ids().len() < 2 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        };
+        timeout(Duration::from_secs(10), async {
+            tokio::select! {
+                _ = manager.run_loop(CancellationToken::new()) => {}
+                _ = replaced => {}
+            }
+        })
+        .await
+        .expect("recycled sandbox replaced");
+
+        let reaped: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, _, _, _)| {
+                key.key().name() == SANDBOX_REAPED_TOTAL
+                    && key
+                        .key()
+                        .labels()
+                        .any(|l| l.key() == "reason" && l.value() == "error")
+            })
+            .map(|(_, _, _, value)| value)
+            .collect();
+        assert_eq!(reaped, [DebugValue::Counter(1)]);
+        manager.teardown().await.expect("teardown");
+    }
+}
@@ -1,4 +1,14 @@
-use crate::sandbox::{SandboxExecutionOptions, SandboxExecutionResult};
+use std::io;
+use std::path::{Component, Path};
+use std::process::Output;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin};
+
+use crate::sandbox::{
+    ExecutionEvent, ExecutionEvents, ExecutionInput, OutputChunk, OutputStream,
+    SandboxExecutionOptions, SandboxExecutionResult,
+};
 use crate::tools::SandboxInner;
 use crate::tools::errors::SandboxToolError;
 
@@ -8,24 +18,30 @@ pub trait ISandboxTool: Send + Sync {
 
     async fn destroy_inner(&self, inner: &SandboxInner) -> Result<(), SandboxToolError>;
 
+    /// Run the program; its output is also streamed to `events` when given,
+    /// and its standard input is read from `input` instead of `options.stdin`.
     async fn execute(
         &self,
         inner: &SandboxInner,
         options: &SandboxExecutionOptions,
+        events: Option<&ExecutionEvents>,
+        input: Option<ExecutionInput>,
     ) -> Result<SandboxExecutionResult, SandboxToolError>;
 
     async fn add_file_wd(
         &self,
         inner: &SandboxInner,
         file_name: &str,
-        file_content: &str,
+        file_content: &[u8],
     ) -> Result<(), SandboxToolError>;
 
+    /// Read at most `limit` bytes of a file, `None` when it does not exist.
     async fn read_file_wd(
         &self,
         inner: &SandboxInner,
         file_name: &str,
-    ) -> Result<String, SandboxToolError>;
+        limit: u64,
+    ) -> Result<Option<Vec<u8>>, SandboxToolError>;
 
     async fn remove_file_wd(
         &self,
@@ -33,3 +49,314 @@ pub trait ISandboxTool: Send + Sync {
         file_name: &str,
     ) -> Result<(), SandboxToolError>;
 }
+
+/// Read at most `limit` bytes of `file_name` below `dir`, `None` when it does
+/// not exist. The program may have replaced the file, so links leading out of
+/// `dir` and anything but a regular file are refused.
+pub async fn read_file_below(
+    dir: &Path,
+    file_name: &str,
+    limit: u64,
+) -> Result<Option<Vec<u8>>, SandboxToolError> {
+    let failed = |e: io::Error| SandboxToolError::FileOperationFailed(e.to_string());
+    let path = match tokio::fs::canonicalize(dir.join(file_name)).await {
+        Ok(path) => path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(failed(e)),
+    };
+    let dir = tokio::fs::canonicalize(dir).await.map_err(failed)?;
+    if !path.starts_with(&dir) {
+        return Err(SandboxToolError::FileOperationFailed(format!(
+            "{} leads out of the working directory",
+            file_name
+        )));
+    }
+    // checked before opening, since opening a FIFO would block
+    if !tokio::fs::metadata(&path).await.map_err(failed)?.is_file() {
+        return Err(SandboxToolError::FileOperationFailed(format!(
+            "{} is not a regular file",
+            file_name
+        )));
+    }
+
+    let file = tokio::fs::File::open(&path).await.map_err(failed)?;
+    let mut content = Vec::new();
+    file.take(limit)
+        .read_to_end(&mut content)
+        .await
+        .map_err(failed)?;
+    Ok(Some(content))
+}
+
+/// Create `file_name` below `dir` for writing, creating the directories on
+/// the way. Boxes are reused, so an earlier program may have left links
+/// behind: every directory on the way must be a real directory, and an
+/// existing entry at `file_name` is replaced instead of written through.
+pub async fn create_file_below(dir: &Path, file_name: &str) -> io::Result<tokio::fs::File> {
+    let refused = |reason: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} {}", file_name, reason),
+        )
+    };
+    let mut names = Vec::new();
+    for component in Path::new(file_name).components() {
+        match component {
+            Component::Normal(name) => names.push(name),
+            Component::CurDir => {}
+            _ => return Err(refused("leads out of the working directory")),
+        }
+    }
+    let Some((name, dirs)) = names.split_last() else {
+        return Err(refused("is not a file name"));
+    };
+
+    let mut path = dir.to_path_buf();
+    for dir in dirs {
+        path.push(dir);
+        // a link is not a directory to `symlink_metadata`
+        match tokio::fs::symlink_metadata(&path).await {
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(_) => return Err(refused("has a parent that is not a directory")),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => tokio::fs::create_dir(&path).await?,
+            Err(e) => return Err(e),
+        }
+    }
+    path.push(name);
+    match tokio::fs::symlink_metadata(&path).await {
+        Ok(metadata) if metadata.is_dir() => return Err(refused("is a directory")),
+        Ok(_) => tokio::fs::remove_file(&path).await?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    // `create_new` neither follows nor replaces an entry created meanwhile
+    tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .await
+}
+
+/// Write `content` to `file_name` below `dir`, see [`create_file_below`].
+pub async fn write_file_below(
+    dir: &Path,
+    file_name: &str,
+    content: &[u8],
+) -> Result<(), SandboxToolError> {
+    let failed = |e: io::Error| SandboxToolError::FileOperationFailed(e.to_string());
+    let mut file = create_file_below(dir, file_name).await.map_err(failed)?;
+    file.write_all(content).await.map_err(failed)?;
+    file.flush().await.map_err(failed)
+}
+
+/// Wait for `child` while reading its output. Each chunk is also sent to
+/// `events`, wrapped by `wrap`, as soon as it is read. `input` is written to
+/// the child's stdin as it arrives; without it stdin is closed right away.
+pub async fn wait_with_output(
+    mut child: Child,
+    events: Option<&ExecutionEvents>,
+    input: Option<ExecutionInput>,
+    wrap: fn(OutputChunk) -> ExecutionEvent,
+) -> io::Result<Output> {
+    let stdin = child.stdin.take();
+    let stdout = read_output(child.stdout.take(), OutputStream::Stdout, events, wrap);
+    let stderr = read_output(child.stderr.take(), OutputStream::Stderr, events, wrap);
+    let output = async { tokio::try_join!(stdout, stderr, child.wait()) };
+    tokio::pin!(output);
+
+    // the program may exit while input is still expected, so feeding only
+    // runs until the output is complete
+    let (stdout, stderr, status) = match (stdin, input) {
+        (Some(stdin), Some(input)) => {
+            tokio::select! {
+                output = &mut output => output?,
+                _ = write_input(stdin, input) => output.await?,
+            }
+        }
+        // close stdin like `Child::wait_with_output`, so readers of it see EOF
+        _ => output.await?,
+    };
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+async fn write_input(mut stdin: ChildStdin, mut input: ExecutionInput) {
+    while let Some(data) = input.recv().await {
+        // the program closed its stdin or exited; the rest of the input is unread
+        if stdin.write_all(&data).await.is_err() || stdin.flush().await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn read_output<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    stream: OutputStream,
+    events: Option<&ExecutionEvents>,
+    wrap: fn(OutputChunk) -> ExecutionEvent,
+) -> io::Result<Vec<u8>> {
+    let Some(mut reader) = reader else {
+        return Ok(Vec::new());
+    };
+
+    let mut output = Vec::new();
+    let mut sent = 0;
+    let mut buf = [0u8; 8192];
+    loop {
+        let read = reader.read(&mut buf).await?;
+        output.extend_from_slice(&buf[..read]);
+
+        let Some(events) = events else {
+            if read == 0 {
+                break;
+            }
+            continue;
+        };
+        let unsent = &output[sent..];
+        // hold back a character cut in half until the rest arrives
+        let len = match std::str::from_utf8(unsent) {
+            Err(e) if read > 0 && e.error_len().is_none() => e.valid_up_to(),
+            _ => unsent.len(),
+        };
+        if len > 0 {
+            let data = unsent[..len].to_vec();
+            sent += len;
+            // the reader went away; keep draining so the program is not blocked
+            let _ = events.send(wrap(OutputChunk { stream, data })).await;
+        }
+        if read == 0 {
+            break;
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Stdio;
+
+    use tokio::process::Command;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    use super::{read_file_below, wait_with_output, write_file_below};
+    use crate::sandbox::{ExecutionEvent, OutputChunk, OutputStream};
+
+    #[tokio::test]
+    async fn output_is_streamed_without_splitting_characters() {
+        // "é" is written one byte at a time
+        let child = Command::new("sh")
+            .args([
+                "-c",
+                r"printf '\303'; sleep 0.1; printf '\251!'; printf err >&2",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let (sender, mut receiver) = mpsc::channel(16);
+
+        let output = wait_with_output(child, Some(&sender), None, ExecutionEvent::Output)
+            .await
+            .unwrap();
+        drop(sender);
+        assert_eq!(output.stdout, "é!".as_bytes());
+
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        while let Some(event) = receiver.recv().await {
+            let ExecutionEvent::Output(OutputChunk { stream, data }) = event else {
+                panic!("unexpected event {:?}", event);
+            };
+            let data = String::from_utf8(data).expect("chunk of whole characters");
+            match stream {
+                OutputStream::Stdout => stdout.push_str(&data),
+                OutputStream::Stderr => stderr.push_str(&data),
+            }
+        }
+        assert_eq!((stdout.as_str(), stderr.as_str()), ("é!", "err"));
+    }
+
+    #[tokio::test]
+    async fn input_is_fed_while_the_program_runs() {
+        let spawn = |script: &str| {
+            Command::new("sh")
+                .args(["-c", script])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap()
+        };
+
+        let (input, receiver) = mpsc::unbounded_channel();
+        input.send(b"Alice\n".to_vec()).unwrap();
+        drop(input);
+        let output = wait_with_output(
+            spawn("read name; echo hello $name"),
+            None,
+            Some(receiver),
+            ExecutionEvent::Output,
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.stdout, b"hello Alice\n");
+
+        // a program that exits without reading does not wait for more input
+        let (_input, receiver) = mpsc::unbounded_channel();
+        let output = wait_with_output(
+            spawn("echo done"),
+            None,
+            Some(receiver),
+            ExecutionEvent::Output,
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.stdout, b"done\n");
+    }
+
+    #[tokio::test]
+    async fn files_are_read_only_below_the_directory() {
+        let dir = std::env::temp_dir().join(format!("pecan-read-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        std::fs::write(dir.join("out/result.txt"), b"0123456789").unwrap();
+        std::os::unix::fs::symlink("/etc/hostname", dir.join("leak.txt")).unwrap();
+
+        let read = |name: &'static str, limit| read_file_below(&dir, name, limit);
+        assert_eq!(
+            read("out/result.txt", 4).await.unwrap(),
+            Some(b"0123".to_vec())
+        );
+        assert_eq!(read("missing.txt", 4).await.unwrap(), None);
+        assert!(read("leak.txt", 4).await.is_err());
+        assert!(read("out", 4).await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn files_are_not_written_through_links() {
+        let dir = std::env::temp_dir().join(format!("pecan-write-{}", Uuid::new_v4()));
+        let outside = std::env::temp_dir().join(format!("pecan-outside-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("secret"), b"kept").unwrap();
+        // links an earlier program left in the box
+        std::os::unix::fs::symlink(&outside, dir.join("evil")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret"), dir.join("input.txt")).unwrap();
+
+        write_file_below(&dir, "src/lib/a.txt", b"a").await.unwrap();
+        assert_eq!(std::fs::read(dir.join("src/lib/a.txt")).unwrap(), b"a");
+        assert!(write_file_below(&dir, "evil/secret", b"x").await.is_err());
+        assert!(write_file_below(&dir, "../secret", b"x").await.is_err());
+        write_file_below(&dir, "input.txt", b"data").await.unwrap();
+        assert!(!dir.join("input.txt").is_symlink());
+        assert_eq!(std::fs::read(outside.join("secret")).unwrap(), b"kept");
+
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+}
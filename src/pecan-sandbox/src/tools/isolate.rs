@@ -8,14 +8,19 @@ use std::str::FromStr;
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicI32, Ordering};
 
-use tokio::fs::{read, remove_file, write};
+use tokio::fs::remove_file;
 use tokio::process::Command;
 
-use crate::sandbox::{SandboxExecutionOptions, SandboxExecutionResult, SandboxExecutionStatus};
-use crate::tools::common::ISandboxTool;
+use crate::sandbox::{
+    ExecutionEvent, ExecutionEvents, ExecutionInput, SandboxExecutionOptions,
+    SandboxExecutionResult, SandboxExecutionStatus,
+};
+use crate::tools::common::{ISandboxTool, read_file_below, wait_with_output, write_file_below};
 use crate::tools::errors::SandboxToolError;
 
 const PROGRAM_NAME: &str = "isolate";
+/// bytes of the meta file read at most, far more than the few keys it holds
+const META_FILE_LIMIT: u64 = 64 * 1024;
 
 pub struct SandboxToolIsolate {
     box_id_counter: AtomicI32,
@@ -98,6 +103,12 @@ impl SandboxToolIsolate {
     }
 }
 
+impl Default for SandboxToolIsolate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ISandboxTool for SandboxToolIsolate {
     async fn build_inner(&self) -> Result<IsolateInner, SandboxToolError> {
         let box_id = self
@@ -123,14 +134,19 @@ impl ISandboxTool for SandboxToolIsolate {
         &self,
         inner: &IsolateInner,
         options: &SandboxExecutionOptions,
+        events: Option<&ExecutionEvents>,
+        input: Option<ExecutionInput>,
     ) -> Result<SandboxExecutionResult, SandboxToolError> {
-        let stdin_file_name = "stdin.txt";
+        // interactive input reaches the program through isolate's own stdin
+        let stdin_file_name = input.is_none().then_some("stdin.txt");
 
         let meta_file_name = "meta.txt";
         let meta_file_path = inner.path.join(meta_file_name);
 
-        self.add_file_wd(inner, stdin_file_name, options.stdin.as_str())
-            .await?;
+        if let Some(stdin_file_name) = stdin_file_name {
+            self.add_file_wd(inner, stdin_file_name, &options.stdin)
+                .await?;
+        }
 
         let mut base_cmd = Command::new(PROGRAM_NAME);
         if cfg!(sandbox_isolate_cg) {
@@ -156,7 +172,7 @@ impl ISandboxTool for SandboxToolIsolate {
             .arg(format!("--processes={}", 128))
             .arg(format!("--time={}", options.time_limit))
             .arg(format!("--wall-time={}", options.wall_time_limit))
-            .arg(format!("--stdin={}", stdin_file_name))
+            .args(stdin_file_name.map(|name| format!("--stdin={}", name)))
             .arg(format!("--meta={}", meta_file_path.to_string_lossy()))
             .arg("--run")
             .arg("--")
@@ -173,14 +189,21 @@ impl ISandboxTool for SandboxToolIsolate {
             .spawn()
             .map_err(|e| SandboxToolError::UnknownError(e.to_string()))?;
 
-        let res = match base_cmd_child.wait_with_output().await {
-            Ok(output) => output,
-            Err(e) => {
-                return Err(SandboxToolError::UnknownError(e.to_string()));
-            }
-        };
-
-        let meta_content = self.read_file_wd(inner, meta_file_name).await?;
+        let res =
+            match wait_with_output(base_cmd_child, events, input, ExecutionEvent::Output).await {
+                Ok(output) => output,
+                Err(e) => {
+                    return Err(SandboxToolError::UnknownError(e.to_string()));
+                }
+            };
+
+        let meta_content = self
+            .read_file_wd(inner, meta_file_name, META_FILE_LIMIT)
+            .await?
+            .ok_or_else(|| {
+                SandboxToolError::FileOperationFailed(format!("{} is missing", meta_file_name))
+            })?;
+        let meta_content = String::from_utf8_lossy(&meta_content);
 
         let meta_time: f64 = parse_meta_file(&meta_content, "time", 0.0);
         let meta_cg_mem: u32 = parse_meta_file(&meta_content, "cg-mem", 0);
@@ -189,7 +212,9 @@ impl ISandboxTool for SandboxToolIsolate {
         let meta_status: String = parse_meta_file(&meta_content, "status", "OK".to_string());
 
         self.remove_file_wd(inner, meta_file_name).await?;
-        self.remove_file_wd(inner, stdin_file_name).await?;
+        if let Some(stdin_file_name) = stdin_file_name {
+            self.remove_file_wd(inner, stdin_file_name).await?;
+        }
 
         let mut is_internal_error = false;
 
@@ -212,18 +237,28 @@ impl ISandboxTool for SandboxToolIsolate {
             return Err(SandboxToolError::UnknownError("Internal error".to_string()));
         }
 
-        let stdout = String::from_utf8_lossy(&res.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&res.stderr).to_string();
+        // isolate reports an exit code only when the program exited on its own
+        let meta_exitcode: i32 = parse_meta_file(&meta_content, "exitcode", -1);
+        let exit_code = match status {
+            SandboxExecutionStatus::Success => Some(0),
+            SandboxExecutionStatus::RuntimeError if meta_exitcode >= 0 => Some(meta_exitcode),
+            _ => None,
+        };
 
         Ok(SandboxExecutionResult {
             status,
-            stdout,
-            stderr,
+            stdout: res.stdout,
+            stderr: res.stderr,
             time: meta_time,
             memory: match cfg!(sandbox_isolate_cg) {
                 true => meta_cg_mem as f64,
                 false => meta_mem as f64,
             },
+            compile_time: 0.0,
+            semaphore_wait: 0.0,
+            idle_queue_wait: 0.0,
+            output_files: Vec::new(),
+            exit_code,
         })
     }
 
@@ -231,25 +266,18 @@ impl ISandboxTool for SandboxToolIsolate {
         &self,
         inner: &IsolateInner,
         file_name: &str,
-        file_content: &str,
+        file_content: &[u8],
     ) -> Result<(), SandboxToolError> {
-        let path = inner.path.join(file_name);
-        write(path, file_content)
-            .await
-            .map_err(|e| SandboxToolError::UnknownError(e.to_string()))?;
-        Ok(())
+        write_file_below(&inner.path, file_name, file_content).await
     }
 
     async fn read_file_wd(
         &self,
         inner: &IsolateInner,
         file_name: &str,
-    ) -> Result<String, SandboxToolError> {
-        let path = inner.path.join(file_name);
-        let content = read(path)
-            .await
-            .map_err(|e| SandboxToolError::FileOperationFailed(e.to_string()))?;
-        Ok(String::from_utf8_lossy(&content).to_string())
+        limit: u64,
+    ) -> Result<Option<Vec<u8>>, SandboxToolError> {
+        read_file_below(&inner.path, file_name, limit).await
     }
 
     async fn remove_file_wd(
@@ -290,8 +318,7 @@ fn parse_meta_file<S: FromStr>(content: &str, key: &str, default: S) -> S {
         .lines()
         .find(|line| line.starts_with(key))
         .and_then(|line| line.split(':').nth(1))
-        .map(|value| value.trim().parse::<S>().ok())
-        .flatten()
+        .and_then(|value| value.trim().parse::<S>().ok())
         .unwrap_or(default)
 }
 
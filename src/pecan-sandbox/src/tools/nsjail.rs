@@ -8,12 +8,15 @@ use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Mutex;
 
-use tokio::fs::{read, remove_file, write};
+use tokio::fs::remove_file;
 use tokio::process::Command;
 use uuid::Uuid;
 
-use crate::sandbox::{SandboxExecutionOptions, SandboxExecutionResult, SandboxExecutionStatus};
-use crate::tools::common::ISandboxTool;
+use crate::sandbox::{
+    ExecutionEvent, ExecutionEvents, ExecutionInput, SandboxExecutionOptions,
+    SandboxExecutionResult, SandboxExecutionStatus,
+};
+use crate::tools::common::{ISandboxTool, read_file_below, wait_with_output, write_file_below};
 use crate::tools::errors::SandboxToolError;
 
 const PROGRAM_NAME: &str = "nsjail";
@@ -79,10 +82,12 @@ impl ISandboxTool for SandboxToolNsjail {
         &self,
         inner: &NsjailInner,
         options: &SandboxExecutionOptions,
+        events: Option<&ExecutionEvents>,
+        input: Option<ExecutionInput>,
     ) -> Result<SandboxExecutionResult, SandboxToolError> {
         let stdin_file_name = "stdin.txt";
 
-        self.add_file_wd(inner, stdin_file_name, options.stdin.as_str())
+        self.add_file_wd(inner, stdin_file_name, &options.stdin)
             .await?;
 
         let mut base_cmd = Command::new(PROGRAM_NAME);
@@ -121,26 +126,32 @@ impl ISandboxTool for SandboxToolNsjail {
             .spawn()
             .map_err(|e| SandboxToolError::UnknownError(e.to_string()))?;
 
-        let res = match base_cmd_child.wait_with_output().await {
-            Ok(output) => output,
-            Err(e) => {
-                return Err(SandboxToolError::UnknownError(e.to_string()));
-            }
-        };
-
-        let stdout = String::from_utf8_lossy(&res.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&res.stderr).to_string();
+        let res =
+            match wait_with_output(base_cmd_child, events, input, ExecutionEvent::Output).await {
+                Ok(output) => output,
+                Err(e) => {
+                    return Err(SandboxToolError::UnknownError(e.to_string()));
+                }
+            };
 
         // parse stderr, stdout here and retrieve status
-        let status = parse_nsjail_output(&stdout, &stderr);
+        let status = parse_nsjail_output(
+            &String::from_utf8_lossy(&res.stdout),
+            &String::from_utf8_lossy(&res.stderr),
+        );
 
         Ok(SandboxExecutionResult {
             status,
-            stdout,
-            stderr,
+            stdout: res.stdout,
+            stderr: res.stderr,
             // just return default value because nsjail does not output consumed time and memory
             time: 0.into(),
             memory: 0.into(),
+            compile_time: 0.0,
+            semaphore_wait: 0.0,
+            idle_queue_wait: 0.0,
+            output_files: Vec::new(),
+            exit_code: nsjail_exit_code(&String::from_utf8_lossy(&res.stderr)),
         })
     }
 
@@ -148,26 +159,18 @@ impl ISandboxTool for SandboxToolNsjail {
         &self,
         inner: &NsjailInner,
         file_name: &str,
-        file_content: &str,
+        file_content: &[u8],
     ) -> Result<(), SandboxToolError> {
-        let path = inner.path.join(file_name);
-        write(path, file_content)
-            .await
-            .map_err(|e| SandboxToolError::UnknownError(e.to_string()))?;
-        Ok(())
+        write_file_below(&inner.path, file_name, file_content).await
     }
 
     async fn read_file_wd(
         &self,
         inner: &super::SandboxInner,
         file_name: &str,
-    ) -> Result<String, SandboxToolError> {
-        let path = inner.path.join(file_name);
-        let content = read(path)
-            .await
-            .map_err(|e| SandboxToolError::FileOperationFailed(e.to_string()))?;
-
-        Ok(String::from_utf8_lossy(&content).to_string())
+        limit: u64,
+    ) -> Result<Option<Vec<u8>>, SandboxToolError> {
+        read_file_below(&inner.path, file_name, limit).await
     }
 
     async fn remove_file_wd(
@@ -203,6 +206,13 @@ impl NsjailInner {
     }
 }
 
+/// Exit status from nsjail's `exited with status: N` log line.
+fn nsjail_exit_code(stderr: &str) -> Option<i32> {
+    let (_, rest) = stderr.split_once("exited with status: ")?;
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
 fn parse_nsjail_output(stdout: &str, stderr: &str) -> SandboxExecutionStatus {
     if stdout != "" && stderr.contains("exited with status: 0") {
         return SandboxExecutionStatus::Success;